@@ -1,6 +1,9 @@
 bits 16
 org 0x7C00
 
+KERNEL_SECTORS equ 768 ; 384 KiB, must match the padding in build-run.sh
+CHUNK_SECTORS equ 64   ; 32 KiB per BIOS read
+
 _start:
     ; save boot drive number
     mov [boot_drive], dl
@@ -17,20 +20,28 @@ _start:
     mov si, msg
     call print_string
 
-    ; Load kernel using LBA
-    mov word [dap_segment], 0x0000
-    mov word [dap_offset], 0x8000
-    mov word [dap_sectors], 100 ; Load 100 sectors (50KB)
-    mov dword [dap_lba_low], 1 ; Start from LBA 1 (sector after bootloader)
+    ; Load kernel using LBA, KERNEL_SECTORS sectors from LBA 1 (sector after bootloader) to 0x8000.
+    ; BIOSes limit the size of one read, so it is done in chunks of CHUNK_SECTORS,
+    ; moving the segment forward after each chunk.
+    mov word [dap_segment], 0x0800
+    mov word [dap_offset], 0x0000
+    mov word [dap_sectors], CHUNK_SECTORS
+    mov dword [dap_lba_low], 1
     mov dword [dap_lba_high], 0
 
+    mov cx, KERNEL_SECTORS / CHUNK_SECTORS
+.load_chunk:
     call disk_load_lba
+    add word [dap_segment], CHUNK_SECTORS * 512 / 16
+    add dword [dap_lba_low], CHUNK_SECTORS
+    loop .load_chunk
 
     ; Print sucess message
     mov si, msg_loaded
     call print_string
 
-    ; jmp to loaded kernel
+    ; jmp to loaded kernel, passing it the boot drive in dl
+    mov dl, [boot_drive]
     jmp 0x0000:0x8000
 
     ; infinite loop
@@ -88,6 +99,20 @@ boot_drive: db 0
 _loop:
     jmp _loop
 
-times 510-($-$$) db 0
+; ===============================================
+; MBR partition table (4 entries of 16 bytes)
+; Partition 1: data partition after the kernel and the initrd,
+; from LBA 8192 (4 MiB) for 131072 sectors (64 MiB)
+; ===============================================
+times 446-($-$$) db 0
+partition_table:
+    db 0x00                     ; Status: not bootable
+    db 0xFE, 0xFF, 0xFF         ; CHS start (unused, LBA only)
+    db 0x0C                     ; Type: FAT32 (LBA)
+    db 0xFE, 0xFF, 0xFF         ; CHS end (unused, LBA only)
+    dd 8192                     ; First LBA
+    dd 131072                   ; Number of sectors
+    times 3 * 16 db 0           ; Partitions 2 to 4: unused
+
 db 0x55
 db 0xAA
\ No newline at end of file
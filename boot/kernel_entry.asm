@@ -7,10 +7,20 @@ extern _bss_size
 section .text
 bits 16
 
+INITRD_LBA equ 769          ; Right after the 768 kernel sectors
+INITRD_SECTORS equ 4096     ; 2 MiB, must match the padding in build-run.sh
+INITRD_ADDRESS equ 0x400000 ; Must match INITRD_ADDRESS in main.rs
+INITRD_CHUNK equ 64         ; Sectors per BIOS read (32 KiB)
+BOUNCE_SEGMENT equ 0x7000   ; 0x70000, free until the page tables are built
+
 _start:
+    mov [boot_drive], dl ; boot.asm passes the boot drive
+
     mov si, msg_kernel_started
     call print_string
 
+    call load_initrd
+
     mov si, msg_switching_pm
     call print_string
 
@@ -46,6 +56,94 @@ align 8
 %include "boot/gdt.asm"
 %include "boot/print.asm"
 
+; ===============================================
+; Load the initrd (INITRD_SECTORS from INITRD_LBA) to INITRD_ADDRESS.
+; Real mode can only address the first MiB, so each chunk is read into
+; a bounce buffer, then copied above 1 MiB with the BIOS block move.
+; ===============================================
+load_initrd:
+    mov dword [initrd_lba], INITRD_LBA
+    mov dword [initrd_dest], INITRD_ADDRESS
+    mov cx, INITRD_SECTORS / INITRD_CHUNK
+
+.chunk:
+    push cx
+
+    ; 1. Read the chunk into the bounce buffer
+    mov word [initrd_dap_sectors], INITRD_CHUNK
+    mov word [initrd_dap_offset], 0
+    mov word [initrd_dap_segment], BOUNCE_SEGMENT
+    mov eax, [initrd_lba]
+    mov [initrd_dap_lba], eax
+    mov ah, 0x42 ; Extended Read Function
+    mov dl, [boot_drive]
+    mov si, initrd_dap
+    int 0x13
+    jc .error
+
+    ; 2. Copy it to its final place (int 0x15, ah=0x87: ES:SI = GDT, CX = words)
+    mov eax, [initrd_dest]
+    mov [move_dest_low], ax
+    shr eax, 16
+    mov [move_dest_mid], al
+    mov [move_dest_high], ah
+    mov ah, 0x87
+    mov cx, INITRD_CHUNK * 512 / 2
+    mov si, move_gdt
+    int 0x15
+    jc .error
+
+    add dword [initrd_lba], INITRD_CHUNK
+    add dword [initrd_dest], INITRD_CHUNK * 512
+    pop cx
+    loop .chunk
+    ret
+
+.error:
+    pop cx
+    mov si, msg_initrd_error
+    call print_string
+    ret
+
+align 4
+initrd_dap:
+    db 0x10             ; Size of DAP (16 bytes)
+    db 0                ; Always 0
+initrd_dap_sectors:
+    dw 0                ; Number of sectors to read
+initrd_dap_offset:
+    dw 0                ; Memory offset
+initrd_dap_segment:
+    dw 0                ; Memory segment
+initrd_dap_lba:
+    dq 0                ; LBA
+initrd_lba: dd 0
+initrd_dest: dd 0
+boot_drive: db 0
+
+; Descriptor table for the BIOS block move
+align 8
+move_gdt:
+    times 16 db 0       ; Null descriptor + GDT descriptor (filled by the BIOS)
+    ; Source: the bounce buffer at 0x70000
+    dw 0xFFFF           ; Limit
+    dw 0x0000           ; Base (bits 0-15)
+    db 0x07             ; Base (bits 16-23)
+    db 0x93             ; Access: present, ring 0, data, writable
+    db 0x00             ; Flags + Limit (bits 16-19)
+    db 0x00             ; Base (bits 24-31)
+    ; Destination: filled in by load_initrd
+    dw 0xFFFF           ; Limit
+move_dest_low:
+    dw 0                ; Base (bits 0-15)
+move_dest_mid:
+    db 0                ; Base (bits 16-23)
+    db 0x93             ; Access: present, ring 0, data, writable
+    db 0x00             ; Flags + Limit (bits 16-19)
+move_dest_high:
+    db 0                ; Base (bits 24-31)
+    times 16 db 0       ; BIOS code and stack segments (filled by the BIOS)
+
 bits 32
 init_pm:
     ; Set up segment registers for protected mode
@@ -125,8 +223,9 @@ init_lm:
     mov fs, ax
     mov gs, ax
 
-    ; stack
-    mov rsp, 0x7FF0
+    ; stack: 256 KiB at 0x1C0000-0x200000, above the process stacks and away from the
+    ; page tables (0x80000-0x86000), must match STACK_START and STACK_END in procfs
+    mov rsp, 0x200000
     and rsp, -16
     xor rbp, rbp
 
@@ -174,5 +273,6 @@ print_string_lm:
 ; Messages
 msg_kernel_started: db "Kernel started in 16-bit mode", 0x0D, 0x0A, 0
 msg_switching_pm: db "Switching to 32-bit protected mode...", 0x0D, 0x0A, 0
+msg_initrd_error: db "Failed to load initrd!", 0x0D, 0x0A, 0
 msg_pm_success: db "32-bit Protected Mode Active!", 0
 msg_lm_success: db "64-bit Long Mode Active!", 0
\ No newline at end of file
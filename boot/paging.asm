@@ -1,6 +1,6 @@
 setup_page_tables:
-    ; We'll use 0x12000 for page tables (well above our kernel at 0x1000)
-    mov edi, 0x12000
+    ; We'll use 0x80000 for page tables (above the 384 KiB kernel loaded at 0x8000)
+    mov edi, 0x80000
     mov cr3, edi
 
     xor eax, eax
@@ -12,7 +12,7 @@ setup_page_tables:
 
     ; --- Level 4 (PML4) ---
     ; Map first entry to PDPT
-    mov dword [edi], 0x13003      ; Point to PDPT at 0x13000 | Present | Writable
+    mov dword [edi], 0x81003      ; Point to PDPT at 0x81000 | Present | Writable
 
     ; --- Level 3 (PDPT) ---
     ; We need to map 4 entries (4GB total) to cover typical VBE Framebuffer locations
@@ -21,7 +21,7 @@ setup_page_tables:
     ; PDPT[2] -> PD2 (2-3GB)
     ; PDPT[3] -> PD3 (3-4GB)
 
-    mov eax, 0x14003 ; First PD at 0x14000
+    mov eax, 0x82003 ; First PD at 0x82000
     mov dword [edi + 0x1000], eax
 
     add eax, 0x1000
@@ -37,7 +37,7 @@ setup_page_tables:
     ; We need to fill 4 Page Directories (2048 entries total)
     ; Each entry maps 2MB. 2048 * 2MB = 4GB.
 
-    mov edi, 0x14000 ; Start of first PD
+    mov edi, 0x82000 ; Start of first PD
     mov eax, 0x83 ; Start at physical address 0 | Huge | Present | Writable
     mov ecx, 2048 ; 512 entries * 4 directories
 
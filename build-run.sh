@@ -1,16 +1,61 @@
+# `sh build-run.sh test` builds the kernel with its #[test_case] tests instead and runs them
+# in QEMU without a window, the exit code is 0 when they all pass
+if [ "$1" = "test" ]; then
+    TEST_FLAGS="--test -Z panic-abort-tests"
+fi
+
 nasm -f bin boot/boot.asm -o out/boot.bin
 nasm -f elf64 boot/kernel_entry.asm -o out/kernel_entry.o
 
-cargo rustc --release -- --emit obj=out/kernel.o
+# Like `cargo build-kernel` (.cargo/config.toml), but emitting the object file to link
+cargo rustc --release -Z json-target-spec -Z build-std=core,compiler_builtins -Z build-std-features=compiler-builtins-mem \
+    -- $TEST_FLAGS --emit obj=out/kernel.o
 
 ld.lld -T linker.ld -o out/kernel.bin \
     out/kernel_entry.o out/kernel.o \
     --oformat binary
 
-# Pad kernel to exactly 100 sectors (50 KiB)
-truncate -s $((100 * 512)) out/kernel.bin
+# Pad kernel to exactly 768 sectors (384 KiB), must match KERNEL_SECTORS in boot/boot.asm
+truncate -s $((768 * 512)) out/kernel.bin
+
+# Data partition (64 MiB, FAT32), must match the partition table in boot/boot.asm
+# It is only created once, so files copied into it with mtools are kept between runs
+if [ ! -f out/data.img ]; then
+    truncate -s 64M out/data.img
+    mkfs.fat -F 32 -n JACKCATOS out/data.img
+fi
+
+# User programs (user/src/bin), installed in /bin of the initrd
+(cd user && cargo build --release) || exit 1
+rm -rf out/initrd
+cp -r initrd out/initrd
+mkdir -p out/initrd/bin
+for program in user/src/bin/*.rs; do
+    cp "user/target/x86_64-jackcatos-user/release/$(basename "$program" .rs)" out/initrd/bin/
+done
+
+# Initial ramdisk: the initrd/ directory plus the programs as a newc cpio archive, padded to
+# 4096 sectors (2 MiB), must match INITRD_SECTORS in boot/kernel_entry.asm
+(cd out/initrd && find . | cpio -o -H newc --quiet) > out/initrd.cpio
+if [ "$(stat -c %s out/initrd.cpio)" -gt $((4096 * 512)) ]; then
+    echo "initrd is bigger than 2 MiB"
+    exit 1
+fi
+truncate -s $((4096 * 512)) out/initrd.cpio
+
+# Create disk image: boot sector + kernel + initrd, padded up to LBA 8192, then the data partition
+cat out/boot.bin out/kernel.bin out/initrd.cpio > out/os-image.bin
+truncate -s $((8192 * 512)) out/os-image.bin
+cat out/data.img >> out/os-image.bin
 
-# Create disk image
-cat out/boot.bin out/kernel.bin > out/os-image.bin
+if [ "$1" = "test" ]; then
+    # The test runner writes 0x10 (all passed) or 0x11 to the isa-debug-exit port, QEMU
+    # then exits with (value << 1) | 1. A hang or a triple fault fails too.
+    timeout 120 qemu-system-x86_64 -drive format=raw,file=out/os-image.bin -serial stdio \
+        -display none -no-reboot -device isa-debug-exit,iobase=0xf4,iosize=0x04
+    [ $? -eq 33 ]
+    exit
+fi
 
-qemu-system-x86_64 -drive format=raw,file=out/os-image.bin
+# COM1 (/dev/ttyS0) is connected to this terminal
+qemu-system-x86_64 -drive format=raw,file=out/os-image.bin -serial stdio
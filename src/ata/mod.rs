@@ -0,0 +1,320 @@
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::block::{check_range, BlockDevice, BlockError, SECTOR_SIZE};
+use crate::io::{inb, insw, outb, outsw};
+
+// Legacy (ISA compatible) port bases of the two IDE channels
+const PRIMARY_IO: u16 = 0x1F0;
+const PRIMARY_CONTROL: u16 = 0x3F6;
+const SECONDARY_IO: u16 = 0x170;
+const SECONDARY_CONTROL: u16 = 0x376;
+
+// Offsets from the I/O base
+const REG_DATA: u16 = 0;
+const REG_SECTOR_COUNT: u16 = 2;
+const REG_LBA_LOW: u16 = 3;
+const REG_LBA_MID: u16 = 4;
+const REG_LBA_HIGH: u16 = 5;
+const REG_DRIVE: u16 = 6;
+const REG_STATUS: u16 = 7;
+const REG_COMMAND: u16 = 7;
+
+const STATUS_ERR: u8 = 0x01;
+const STATUS_DRQ: u8 = 0x08;
+const STATUS_DF: u8 = 0x20;
+const STATUS_BSY: u8 = 0x80;
+
+const CMD_READ_PIO: u8 = 0x20;
+const CMD_READ_PIO_EXT: u8 = 0x24;
+const CMD_WRITE_PIO: u8 = 0x30;
+const CMD_WRITE_PIO_EXT: u8 = 0x34;
+const CMD_CACHE_FLUSH: u8 = 0xE7;
+const CMD_CACHE_FLUSH_EXT: u8 = 0xEA;
+const CMD_IDENTIFY: u8 = 0xEC;
+
+// Highest sector reachable with a 28-bit LBA
+const LBA28_LIMIT: u64 = 1 << 28;
+// Sectors transferred per command (the 8-bit count register treats 0 as 256)
+const MAX_SECTORS_PER_COMMAND: usize = 255;
+// Spins before giving up on an IRQ and polling the status register instead
+const IRQ_TIMEOUT: usize = 1_000_000;
+const POLL_TIMEOUT: usize = 10_000_000;
+
+static PRIMARY_IRQ: AtomicBool = AtomicBool::new(false);
+static SECONDARY_IRQ: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy)]
+pub struct AtaChannel {
+    io_base: u16,
+    control_base: u16,
+    irq: &'static AtomicBool,
+}
+
+pub const PRIMARY: AtaChannel = AtaChannel {
+    io_base: PRIMARY_IO,
+    control_base: PRIMARY_CONTROL,
+    irq: &PRIMARY_IRQ,
+};
+
+pub const SECONDARY: AtaChannel = AtaChannel {
+    io_base: SECONDARY_IO,
+    control_base: SECONDARY_CONTROL,
+    irq: &SECONDARY_IRQ,
+};
+
+impl AtaChannel {
+    unsafe fn status(&self) -> u8 {
+        // Reading the status register also acknowledges the drive interrupt
+        unsafe { inb(self.io_base + REG_STATUS) }
+    }
+
+    unsafe fn alt_status(&self) -> u8 {
+        unsafe { inb(self.control_base) }
+    }
+
+    /// Reading the alternate status 4 times gives the drive the 400ns it needs after a select
+    unsafe fn delay_400ns(&self) {
+        for _ in 0..4 {
+            unsafe { self.alt_status(); }
+        }
+    }
+
+    unsafe fn select(&self, slave: bool, lba_high_nibble: u8, use_lba: bool) {
+        let lba_bit = if use_lba { 0x40 } else { 0 };
+        let value = 0xA0 | lba_bit | ((slave as u8) << 4) | (lba_high_nibble & 0x0F);
+        unsafe {
+            outb(self.io_base + REG_DRIVE, value);
+            self.delay_400ns();
+        }
+    }
+
+    unsafe fn wait_not_busy(&self) -> Result<u8, BlockError> {
+        for _ in 0..POLL_TIMEOUT {
+            let status = unsafe { self.alt_status() };
+            if status & STATUS_BSY == 0 {
+                return Ok(status);
+            }
+            spin_loop();
+        }
+        Err(BlockError::Timeout)
+    }
+
+    /// Wait until the drive asks for (or offers) the next data block
+    unsafe fn wait_drq(&self) -> Result<(), BlockError> {
+        for _ in 0..POLL_TIMEOUT {
+            let status = unsafe { self.alt_status() };
+            if status & (STATUS_ERR | STATUS_DF) != 0 {
+                return Err(BlockError::Io);
+            }
+            if status & STATUS_BSY == 0 && status & STATUS_DRQ != 0 {
+                return Ok(());
+            }
+            spin_loop();
+        }
+        Err(BlockError::Timeout)
+    }
+
+    /// Wait for the IRQ raised at the end of a block. If interrupts are disabled
+    /// (or the IRQ is masked) we fall back to polling once the timeout runs out.
+    unsafe fn wait_irq(&self) -> Result<(), BlockError> {
+        for _ in 0..IRQ_TIMEOUT {
+            if self.irq.swap(false, Ordering::AcqRel) {
+                break;
+            }
+            spin_loop();
+        }
+        let status = unsafe {
+            self.wait_not_busy()?;
+            self.status()
+        };
+        if status & (STATUS_ERR | STATUS_DF) != 0 {
+            return Err(BlockError::Io);
+        }
+        Ok(())
+    }
+
+    unsafe fn setup_lba(&self, slave: bool, lba: u64, count: usize, lba48: bool) {
+        let io = self.io_base;
+        unsafe {
+            if lba48 {
+                self.select(slave, 0, true);
+                // High bytes first, then low bytes, through the same registers
+                outb(io + REG_SECTOR_COUNT, (count >> 8) as u8);
+                outb(io + REG_LBA_LOW, (lba >> 24) as u8);
+                outb(io + REG_LBA_MID, (lba >> 32) as u8);
+                outb(io + REG_LBA_HIGH, (lba >> 40) as u8);
+            } else {
+                self.select(slave, (lba >> 24) as u8, true);
+            }
+            outb(io + REG_SECTOR_COUNT, count as u8);
+            outb(io + REG_LBA_LOW, lba as u8);
+            outb(io + REG_LBA_MID, (lba >> 8) as u8);
+            outb(io + REG_LBA_HIGH, (lba >> 16) as u8);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AtaDrive {
+    channel: AtaChannel,
+    slave: bool,
+    lba48: bool,
+    sectors: u64,
+}
+
+impl AtaDrive {
+    /// Send IDENTIFY to a drive. Returns None when nothing (or an ATAPI device) answers.
+    pub fn identify(channel: AtaChannel, slave: bool) -> Option<AtaDrive> {
+        let io = channel.io_base;
+        let mut raw = [0u8; SECTOR_SIZE];
+        unsafe {
+            // Floating bus: no controller on this channel
+            if inb(io + REG_STATUS) == 0xFF {
+                return None;
+            }
+            channel.select(slave, 0, false);
+            outb(io + REG_SECTOR_COUNT, 0);
+            outb(io + REG_LBA_LOW, 0);
+            outb(io + REG_LBA_MID, 0);
+            outb(io + REG_LBA_HIGH, 0);
+            outb(io + REG_COMMAND, CMD_IDENTIFY);
+
+            if inb(io + REG_STATUS) == 0 {
+                return None;
+            }
+            channel.wait_not_busy().ok()?;
+
+            // ATAPI and SATA devices set these signature bytes instead of answering
+            if inb(io + REG_LBA_MID) != 0 || inb(io + REG_LBA_HIGH) != 0 {
+                return None;
+            }
+            channel.wait_drq().ok()?;
+            insw(io + REG_DATA, &mut raw);
+            // Drop the IRQ raised by IDENTIFY
+            channel.irq.store(false, Ordering::Release);
+        }
+
+        let data: [u16; SECTOR_SIZE / 2] = core::array::from_fn(|i| u16::from_le_bytes([raw[i * 2], raw[i * 2 + 1]]));
+        let lba48 = data[83] & (1 << 10) != 0;
+        let sectors = if lba48 {
+            (data[100] as u64)
+                | (data[101] as u64) << 16
+                | (data[102] as u64) << 32
+                | (data[103] as u64) << 48
+        } else {
+            (data[60] as u64) | (data[61] as u64) << 16
+        };
+        Some(AtaDrive { channel, slave, lba48, sectors })
+    }
+
+    /// Use 48-bit commands only when the request does not fit in 28 bits
+    fn needs_lba48(&self, lba: u64, count: usize) -> bool {
+        self.lba48 && lba + count as u64 >= LBA28_LIMIT
+    }
+
+    unsafe fn read_chunk(&self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError> {
+        let count = buffer.len() / SECTOR_SIZE;
+        let lba48 = self.needs_lba48(lba, count);
+        let channel = &self.channel;
+        unsafe {
+            channel.wait_not_busy()?;
+            channel.irq.store(false, Ordering::Release);
+            channel.setup_lba(self.slave, lba, count, lba48);
+            outb(channel.io_base + REG_COMMAND, if lba48 { CMD_READ_PIO_EXT } else { CMD_READ_PIO });
+
+            for sector in buffer.chunks_exact_mut(SECTOR_SIZE) {
+                // The drive raises an IRQ each time a sector is ready in its buffer
+                channel.wait_irq()?;
+                channel.wait_drq()?;
+                insw(channel.io_base + REG_DATA, sector);
+            }
+        }
+        Ok(())
+    }
+
+    unsafe fn write_chunk(&self, lba: u64, buffer: &[u8]) -> Result<(), BlockError> {
+        let count = buffer.len() / SECTOR_SIZE;
+        let lba48 = self.needs_lba48(lba, count);
+        let channel = &self.channel;
+        unsafe {
+            channel.wait_not_busy()?;
+            channel.irq.store(false, Ordering::Release);
+            channel.setup_lba(self.slave, lba, count, lba48);
+            outb(channel.io_base + REG_COMMAND, if lba48 { CMD_WRITE_PIO_EXT } else { CMD_WRITE_PIO });
+
+            for sector in buffer.chunks_exact(SECTOR_SIZE) {
+                // No IRQ for the first block, the drive just sets DRQ
+                channel.wait_drq()?;
+                outsw(channel.io_base + REG_DATA, sector);
+                channel.wait_irq()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Ask the drive to commit its write cache to the media
+    pub fn flush_cache(&self) -> Result<(), BlockError> {
+        let channel = &self.channel;
+        unsafe {
+            channel.wait_not_busy()?;
+            channel.irq.store(false, Ordering::Release);
+            channel.select(self.slave, 0, true);
+            outb(channel.io_base + REG_COMMAND, if self.lba48 { CMD_CACHE_FLUSH_EXT } else { CMD_CACHE_FLUSH });
+            channel.wait_irq()
+        }
+    }
+}
+
+impl BlockDevice for AtaDrive {
+    fn read_sectors(&self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError> {
+        check_range(self, lba, buffer.len())?;
+        let mut lba = lba;
+        for chunk in buffer.chunks_mut(MAX_SECTORS_PER_COMMAND * SECTOR_SIZE) {
+            unsafe { self.read_chunk(lba, chunk)?; }
+            lba += (chunk.len() / SECTOR_SIZE) as u64;
+        }
+        Ok(())
+    }
+
+    fn write_sectors(&self, lba: u64, buffer: &[u8]) -> Result<(), BlockError> {
+        check_range(self, lba, buffer.len())?;
+        let mut lba = lba;
+        for chunk in buffer.chunks(MAX_SECTORS_PER_COMMAND * SECTOR_SIZE) {
+            unsafe { self.write_chunk(lba, chunk)?; }
+            lba += (chunk.len() / SECTOR_SIZE) as u64;
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), BlockError> {
+        self.flush_cache()
+    }
+
+    fn capacity(&self) -> u64 {
+        self.sectors
+    }
+}
+
+/// Drives found by init_ata: primary master, primary slave, secondary master, secondary slave
+static mut DRIVES: [Option<AtaDrive>; 4] = [None; 4];
+
+/// Probe the four legacy IDE positions. Must run after the IDT and PIC are set up,
+/// so the IRQ14/IRQ15 handlers are in place.
+pub fn init_ata() {
+    let positions = [(PRIMARY, false), (PRIMARY, true), (SECONDARY, false), (SECONDARY, true)];
+    for (i, (channel, slave)) in positions.into_iter().enumerate() {
+        let drive = AtaDrive::identify(channel, slave);
+        unsafe { DRIVES[i] = drive; }
+    }
+}
+
+/// Get a drive detected by init_ata (0 = primary master ... 3 = secondary slave)
+pub fn get_drive(index: usize) -> Option<&'static AtaDrive> {
+    unsafe { (*core::ptr::addr_of!(DRIVES)).get(index)?.as_ref() }
+}
+
+/// Called from the IRQ14 / IRQ15 handlers
+pub fn handle_irq(channel: &AtaChannel) {
+    unsafe { channel.status(); }
+    channel.irq.store(true, Ordering::Release);
+}
@@ -0,0 +1,268 @@
+use core::cell::{Cell, RefCell};
+use crate::block::{check_range, BlockDevice, BlockError, SECTOR_SIZE};
+
+/// Number of sectors kept in memory by one cache (128 KiB)
+pub const CACHE_SECTORS: usize = 256;
+/// Sectors read in one go when a read misses the cache
+pub const READAHEAD_SECTORS: usize = 8;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct CacheEntry {
+    lba: u64,
+    /// Value of the cache clock the last time the entry was touched (0 = never used)
+    last_used: u64,
+    valid: bool,
+    dirty: bool,
+}
+
+/// Memory used by a cache. It is too big for the kernel stack or .bss,
+/// so it is carved out of a fixed memory region (see `CacheStorage::at`).
+#[repr(C)]
+pub struct CacheStorage {
+    entries: [CacheEntry; CACHE_SECTORS],
+    buffers: [[u8; SECTOR_SIZE]; CACHE_SECTORS],
+    readahead: [u8; READAHEAD_SECTORS * SECTOR_SIZE],
+}
+
+impl CacheStorage {
+    /// Zero the memory at `address` and use it as cache storage
+    pub unsafe fn at(address: usize) -> &'static mut CacheStorage {
+        let storage = address as *mut CacheStorage;
+        unsafe {
+            core::ptr::write_bytes(storage, 0, 1);
+            &mut *storage
+        }
+    }
+}
+
+/// Write-back sector cache with LRU eviction and readahead, wrapping any block device.
+/// It is a block device itself, so filesystems can sit on top of it transparently.
+pub struct BlockCache<'a> {
+    device: &'a dyn BlockDevice,
+    storage: RefCell<&'a mut CacheStorage>,
+    clock: Cell<u64>,
+}
+
+impl<'a> BlockCache<'a> {
+    pub fn new(device: &'a dyn BlockDevice, storage: &'a mut CacheStorage) -> Self {
+        assert!(device.sector_size() == SECTOR_SIZE, "block cache only handles 512 byte sectors");
+        for entry in storage.entries.iter_mut() {
+            *entry = CacheEntry { lba: 0, last_used: 0, valid: false, dirty: false };
+        }
+        BlockCache {
+            device,
+            storage: RefCell::new(storage),
+            clock: Cell::new(0),
+        }
+    }
+
+    fn tick(&self) -> u64 {
+        let now = self.clock.get() + 1;
+        self.clock.set(now);
+        now
+    }
+
+    fn lookup(storage: &CacheStorage, lba: u64) -> Option<usize> {
+        storage.entries.iter().position(|entry| entry.valid && entry.lba == lba)
+    }
+
+    /// Find a free slot, or evict the least recently used one (writing it back if needed)
+    fn evict(&self, storage: &mut CacheStorage) -> Result<usize, BlockError> {
+        let slot = match storage.entries.iter().position(|entry| !entry.valid) {
+            Some(slot) => slot,
+            None => {
+                let (slot, _) = storage.entries.iter()
+                    .enumerate()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .ok_or(BlockError::Io)?;
+                slot
+            }
+        };
+
+        let entry = storage.entries[slot];
+        if entry.valid && entry.dirty {
+            self.device.write_sectors(entry.lba, &storage.buffers[slot])?;
+        }
+        storage.entries[slot].valid = false;
+        storage.entries[slot].dirty = false;
+        Ok(slot)
+    }
+
+    fn insert(&self, storage: &mut CacheStorage, lba: u64, data: &[u8], dirty: bool) -> Result<usize, BlockError> {
+        let slot = self.evict(storage)?;
+        storage.buffers[slot].copy_from_slice(data);
+        storage.entries[slot] = CacheEntry { lba, last_used: self.tick(), valid: true, dirty };
+        Ok(slot)
+    }
+
+    /// Read a run of uncached sectors starting at `lba` in one request, and cache all of them
+    fn fill(&self, storage: &mut CacheStorage, lba: u64) -> Result<usize, BlockError> {
+        let capacity = self.device.capacity();
+        let mut count = 1;
+        while count < READAHEAD_SECTORS
+            && lba + (count as u64) < capacity
+            && Self::lookup(storage, lba + count as u64).is_none()
+        {
+            count += 1;
+        }
+
+        let bytes = count * SECTOR_SIZE;
+        self.device.read_sectors(lba, &mut storage.readahead[..bytes])?;
+
+        let mut first_slot = 0;
+        for i in 0..count {
+            let mut sector = [0u8; SECTOR_SIZE];
+            sector.copy_from_slice(&storage.readahead[i * SECTOR_SIZE..(i + 1) * SECTOR_SIZE]);
+            let slot = self.insert(storage, lba + i as u64, &sector, false)?;
+            if i == 0 {
+                first_slot = slot;
+            }
+        }
+        Ok(first_slot)
+    }
+}
+
+impl BlockDevice for BlockCache<'_> {
+    fn read_sectors(&self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError> {
+        check_range(self, lba, buffer.len())?;
+        let mut storage = self.storage.borrow_mut();
+        for (i, sector) in buffer.chunks_exact_mut(SECTOR_SIZE).enumerate() {
+            let lba = lba + i as u64;
+            let slot = match Self::lookup(&storage, lba) {
+                Some(slot) => slot,
+                None => self.fill(&mut storage, lba)?,
+            };
+            storage.entries[slot].last_used = self.tick();
+            sector.copy_from_slice(&storage.buffers[slot]);
+        }
+        Ok(())
+    }
+
+    fn write_sectors(&self, lba: u64, buffer: &[u8]) -> Result<(), BlockError> {
+        check_range(self, lba, buffer.len())?;
+        let mut storage = self.storage.borrow_mut();
+        for (i, sector) in buffer.chunks_exact(SECTOR_SIZE).enumerate() {
+            let lba = lba + i as u64;
+            match Self::lookup(&storage, lba) {
+                Some(slot) => {
+                    storage.buffers[slot].copy_from_slice(sector);
+                    storage.entries[slot].dirty = true;
+                    storage.entries[slot].last_used = self.tick();
+                }
+                None => {
+                    self.insert(&mut storage, lba, sector, true)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), BlockError> {
+        let mut storage = self.storage.borrow_mut();
+        let storage = &mut **storage;
+        for (entry, buffer) in storage.entries.iter_mut().zip(storage.buffers.iter()) {
+            if entry.valid && entry.dirty {
+                self.device.write_sectors(entry.lba, buffer)?;
+                entry.dirty = false;
+            }
+        }
+        self.device.flush()
+    }
+
+    fn capacity(&self) -> u64 {
+        self.device.capacity()
+    }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryDisk;
+
+    fn storage() -> Box<CacheStorage> {
+        // All zeroes is an empty cache
+        unsafe { Box::new_zeroed().assume_init() }
+    }
+
+    /// A disk whose sector i is filled with the byte i
+    fn numbered_disk(sectors: usize) -> MemoryDisk {
+        MemoryDisk::new((0..sectors).flat_map(|i| [i as u8; SECTOR_SIZE]).collect())
+    }
+
+    #[test]
+    fn reads_ahead_and_hits() {
+        let disk = numbered_disk(20);
+        let mut storage = storage();
+        let cache = BlockCache::new(&disk, &mut storage);
+        let mut sector = [0u8; SECTOR_SIZE];
+        cache.read_sectors(3, &mut sector).unwrap();
+        assert_eq!(sector, [3; SECTOR_SIZE]);
+        assert_eq!(disk.reads.get(), 1);
+
+        // Sectors 3 to 10 came in the same request
+        let mut sectors = [0u8; READAHEAD_SECTORS * SECTOR_SIZE];
+        cache.read_sectors(3, &mut sectors).unwrap();
+        assert_eq!(disk.reads.get(), 1);
+        assert!(sectors.chunks(SECTOR_SIZE).enumerate().all(|(i, sector)| sector.iter().all(|&byte| byte == 3 + i as u8)));
+
+        // Readahead stops at the end of the disk
+        cache.read_sectors(18, &mut [0; 2 * SECTOR_SIZE]).unwrap();
+        cache.read_sectors(19, &mut sector).unwrap();
+        assert_eq!((disk.reads.get(), sector[0]), (2, 19));
+    }
+
+    #[test]
+    fn writes_back_on_flush() {
+        let disk = numbered_disk(20);
+        let mut storage = storage();
+        let cache = BlockCache::new(&disk, &mut storage);
+        cache.write_sectors(5, &[0xAA; 2 * SECTOR_SIZE]).unwrap();
+        assert_eq!(disk.writes.get(), 0);
+
+        let mut sector = [0u8; SECTOR_SIZE];
+        cache.read_sectors(6, &mut sector).unwrap();
+        assert_eq!((sector[0], disk.reads.get()), (0xAA, 0));
+        assert_eq!(disk.bytes()[5 * SECTOR_SIZE], 5);
+
+        cache.flush().unwrap();
+        assert_eq!(disk.writes.get(), 2);
+        assert!(disk.bytes()[5 * SECTOR_SIZE..7 * SECTOR_SIZE].iter().all(|&byte| byte == 0xAA));
+        // Clean now, a second flush writes nothing
+        cache.flush().unwrap();
+        assert_eq!(disk.writes.get(), 2);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let disk = numbered_disk(CACHE_SECTORS + 10);
+        let mut storage = storage();
+        let cache = BlockCache::new(&disk, &mut storage);
+        for lba in 0..CACHE_SECTORS as u64 {
+            cache.write_sectors(lba, &[0xEE; SECTOR_SIZE]).unwrap();
+        }
+        // Touch sector 0 so that sector 1 is the oldest
+        cache.read_sectors(0, &mut [0; SECTOR_SIZE]).unwrap();
+        cache.write_sectors(CACHE_SECTORS as u64, &[0xEE; SECTOR_SIZE]).unwrap();
+        assert_eq!(disk.writes.get(), 1);
+        assert_eq!(disk.bytes()[SECTOR_SIZE], 0xEE);
+        assert_eq!(disk.bytes()[0], 0);
+
+        let mut sector = [0u8; SECTOR_SIZE];
+        cache.read_sectors(1, &mut sector).unwrap();
+        assert_eq!((sector[0], disk.reads.get()), (0xEE, 1));
+    }
+
+    #[test]
+    fn range_errors() {
+        let disk = numbered_disk(8);
+        let mut storage = storage();
+        let cache = BlockCache::new(&disk, &mut storage);
+        assert_eq!(cache.capacity(), 8);
+        assert_eq!(cache.read_sectors(8, &mut [0; SECTOR_SIZE]), Err(BlockError::OutOfRange));
+        assert_eq!(cache.read_sectors(u64::MAX, &mut [0; SECTOR_SIZE]), Err(BlockError::OutOfRange));
+        assert_eq!(cache.write_sectors(7, &[0; 2 * SECTOR_SIZE]), Err(BlockError::OutOfRange));
+        assert_eq!(cache.read_sectors(0, &mut [0; 10]), Err(BlockError::BadBuffer));
+        assert_eq!(disk.reads.get() + disk.writes.get(), 0);
+    }
+}
@@ -0,0 +1,50 @@
+pub mod cache;
+
+/// Size of a hardware sector, in bytes
+pub const SECTOR_SIZE: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockError {
+    /// The requested sectors are past the end of the device
+    OutOfRange,
+    /// The buffer length is not a multiple of the sector size
+    BadBuffer,
+    /// The device reported an error while transferring
+    Io,
+    /// The device never became ready
+    Timeout,
+}
+
+/// A device addressed in fixed-size sectors (disks, partitions, caches, ...).
+/// Storage drivers implement it, filesystems and the partition parser only use it.
+pub trait BlockDevice {
+    /// Read `buffer.len() / sector_size()` sectors starting at `lba`
+    fn read_sectors(&self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError>;
+
+    /// Write `buffer.len() / sector_size()` sectors starting at `lba`.
+    /// The data may stay in a volatile cache until `flush` is called.
+    fn write_sectors(&self, lba: u64, buffer: &[u8]) -> Result<(), BlockError>;
+
+    /// Commit every pending write to the underlying media
+    fn flush(&self) -> Result<(), BlockError>;
+
+    /// Number of sectors on the device
+    fn capacity(&self) -> u64;
+
+    fn sector_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+}
+
+/// Check that `buffer_len` bytes starting at `lba` fit on `device`
+pub fn check_range(device: &dyn BlockDevice, lba: u64, buffer_len: usize) -> Result<(), BlockError> {
+    let sector_size = device.sector_size();
+    if !buffer_len.is_multiple_of(sector_size) {
+        return Err(BlockError::BadBuffer);
+    }
+    let count = (buffer_len / sector_size) as u64;
+    match lba.checked_add(count) {
+        Some(end) if end <= device.capacity() => Ok(()),
+        _ => Err(BlockError::OutOfRange),
+    }
+}
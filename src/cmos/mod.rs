@@ -0,0 +1,94 @@
+use crate::io::{inb, outb};
+
+const CMOS_ADDRESS: u16 = 0x70;
+const CMOS_DATA: u16 = 0x71;
+
+// Extended memory between 1 MiB and 64 MiB, in KiB
+const REG_EXTENDED_LOW: u8 = 0x17;
+const REG_EXTENDED_HIGH: u8 = 0x18;
+// Memory above 16 MiB in 64 KiB blocks (set by QEMU and Bochs BIOSes)
+const REG_HIGH_LOW: u8 = 0x34;
+const REG_HIGH_HIGH: u8 = 0x35;
+
+pub fn read_register(register: u8) -> u8 {
+    unsafe {
+        // Bit 7 set keeps NMIs disabled
+        outb(CMOS_ADDRESS, 0x80 | register);
+        inb(CMOS_DATA)
+    }
+}
+
+/// RAM size in bytes as reported by the BIOS in the CMOS (below 4 GiB only)
+pub fn memory_size() -> usize {
+    let word = |low, high| read_register(low) as usize | (read_register(high) as usize) << 8;
+    let high_blocks = word(REG_HIGH_LOW, REG_HIGH_HIGH);
+    if high_blocks != 0 {
+        (16 << 20) + high_blocks * (64 << 10)
+    } else {
+        (1 << 20) + word(REG_EXTENDED_LOW, REG_EXTENDED_HIGH) * 1024
+    }
+}
+
+// Real time clock registers
+const REG_SECONDS: u8 = 0x00;
+const REG_MINUTES: u8 = 0x02;
+const REG_HOURS: u8 = 0x04;
+const REG_DAY: u8 = 0x07;
+const REG_MONTH: u8 = 0x08;
+const REG_YEAR: u8 = 0x09;
+const REG_STATUS_A: u8 = 0x0A;
+const REG_STATUS_B: u8 = 0x0B;
+
+const UPDATE_IN_PROGRESS: u8 = 0x80;
+const HOURS_24: u8 = 0x02;
+const BINARY_MODE: u8 = 0x04;
+const HOUR_PM: u8 = 0x80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+fn read_rtc_raw() -> [u8; 6] {
+    while read_register(REG_STATUS_A) & UPDATE_IN_PROGRESS != 0 {
+        core::hint::spin_loop();
+    }
+    [REG_SECONDS, REG_MINUTES, REG_HOURS, REG_DAY, REG_MONTH, REG_YEAR].map(read_register)
+}
+
+/// Current date and time of the real time clock (usually UTC under QEMU)
+pub fn read_rtc() -> DateTime {
+    // Read until two reads agree, so an update in the middle cannot mix two dates
+    let mut raw = read_rtc_raw();
+    loop {
+        let again = read_rtc_raw();
+        if again == raw {
+            break;
+        }
+        raw = again;
+    }
+
+    let status = read_register(REG_STATUS_B);
+    let decode = |value: u8| if status & BINARY_MODE != 0 { value } else { (value & 0x0F) + (value >> 4) * 10 };
+    let [second, minute, hour, day, month, year] = raw;
+    let mut hours = decode(hour & !HOUR_PM);
+    if status & HOURS_24 == 0 && hour & HOUR_PM != 0 {
+        hours = (hours % 12) + 12;
+    } else if status & HOURS_24 == 0 && hours == 12 {
+        hours = 0;
+    }
+    DateTime {
+        // The century register is not reliable, assume 20xx
+        year: 2000 + decode(year) as u16,
+        month: decode(month),
+        day: decode(day),
+        hour: hours,
+        minute: decode(minute),
+        second: decode(second),
+    }
+}
@@ -1,6 +1,185 @@
-#[derive(Clone, Copy)]
-pub struct Color{
+/// An sRGB color with straight (not premultiplied) alpha, 255 is opaque
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
     pub red: u8,
     pub green: u8,
-    pub blue: u8
-}
\ No newline at end of file
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::rgb(0x00, 0x00, 0x00);
+    pub const WHITE: Color = Color::rgb(0xFF, 0xFF, 0xFF);
+    pub const GRAY: Color = Color::rgb(0x80, 0x80, 0x80);
+    pub const RED: Color = Color::rgb(0xFF, 0x00, 0x00);
+    pub const GREEN: Color = Color::rgb(0x00, 0xFF, 0x00);
+    pub const BLUE: Color = Color::rgb(0x00, 0x00, 0xFF);
+    pub const YELLOW: Color = Color::rgb(0xFF, 0xFF, 0x00);
+    pub const CYAN: Color = Color::rgb(0x00, 0xFF, 0xFF);
+    pub const MAGENTA: Color = Color::rgb(0xFF, 0x00, 0xFF);
+    pub const TRANSPARENT: Color = Color::rgba(0x00, 0x00, 0x00, 0x00);
+
+    /// Opaque color
+    pub const fn rgb(red: u8, green: u8, blue: u8) -> Self {
+        Color { red, green, blue, alpha: 0xFF }
+    }
+
+    pub const fn rgba(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
+        Color { red, green, blue, alpha }
+    }
+
+    /// Opaque color from 0xRRGGBB
+    pub const fn from_rgb_u32(value: u32) -> Self {
+        Color::rgb((value >> 16) as u8, (value >> 8) as u8, value as u8)
+    }
+
+    /// Color from 0xAARRGGBB
+    pub const fn from_argb_u32(value: u32) -> Self {
+        Color::rgba((value >> 16) as u8, (value >> 8) as u8, value as u8, (value >> 24) as u8)
+    }
+
+    /// Parse "#RGB", "#RRGGBB" or "#RRGGBBAA" (the '#' is optional, like in CSS)
+    pub fn from_hex(text: &str) -> Option<Self> {
+        let digits = text.strip_prefix('#').unwrap_or(text);
+        if !digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return None;
+        }
+        let value = u32::from_str_radix(digits, 16).ok()?;
+        match digits.len() {
+            3 => {
+                // Each digit is doubled: #F80 is #FF8800
+                let channel = |shift: u32| ((value >> shift) & 0xF) as u8 * 0x11;
+                Some(Color::rgb(channel(8), channel(4), channel(0)))
+            }
+            6 => Some(Color::from_rgb_u32(value)),
+            8 => Some(Color::from_argb_u32(value.rotate_right(8))),
+            _ => None,
+        }
+    }
+
+    /// Opaque color from a hue in degrees (wraps around at 360), a saturation and a value
+    pub fn from_hsv(hue: u16, saturation: u8, value: u8) -> Self {
+        let hue = (hue % 360) as u32;
+        let (saturation, value) = (saturation as u32, value as u32);
+        // Position in the 60 degree sector, 0 to 255
+        let fraction = (hue % 60) * 255 / 60;
+        let p = div_255(value * (255 - saturation)) as u8;
+        let q = div_255(value * (255 - div_255(saturation * fraction))) as u8;
+        let t = div_255(value * (255 - div_255(saturation * (255 - fraction)))) as u8;
+        let v = value as u8;
+        match hue / 60 {
+            0 => Color::rgb(v, t, p),
+            1 => Color::rgb(q, v, p),
+            2 => Color::rgb(p, v, t),
+            3 => Color::rgb(p, q, v),
+            4 => Color::rgb(t, p, v),
+            _ => Color::rgb(v, p, q),
+        }
+    }
+
+    /// The same color with another alpha
+    pub const fn with_alpha(self, alpha: u8) -> Self {
+        Color { alpha, ..self }
+    }
+
+    pub const fn is_opaque(self) -> bool {
+        self.alpha == 0xFF
+    }
+
+    /// From `self` (t = 0) to `other` (t = 255), every channel including alpha
+    pub fn lerp(self, other: Color, t: u8) -> Color {
+        let mix = |from: u8, to: u8| {
+            let (from, to, t) = (from as u32, to as u32, t as u32);
+            div_255(from * (255 - t) + to * t) as u8
+        };
+        Color {
+            red: mix(self.red, other.red),
+            green: mix(self.green, other.green),
+            blue: mix(self.blue, other.blue),
+            alpha: mix(self.alpha, other.alpha),
+        }
+    }
+
+    /// Porter-Duff "over": `self` drawn on top of `below`
+    pub fn over(self, below: Color) -> Color {
+        match (self.alpha, below.alpha) {
+            (0xFF, _) | (_, 0) => return self,
+            (0, _) => return below,
+            _ => {}
+        }
+        let (alpha, below_alpha) = (self.alpha as u32, below.alpha as u32);
+        // How much of `below` shows through, 0 to 255 * 255
+        let below_weight = below_alpha * (255 - alpha);
+        let total = alpha * 255 + below_weight;
+        let mix = |top: u8, bottom: u8| ((top as u32 * alpha * 255 + bottom as u32 * below_weight + total / 2) / total) as u8;
+        Color {
+            red: mix(self.red, below.red),
+            green: mix(self.green, below.green),
+            blue: mix(self.blue, below.blue),
+            alpha: div_255(total) as u8,
+        }
+    }
+}
+
+/// x / 255, rounded, for x up to 255 * 255
+const fn div_255(x: u32) -> u32 {
+    (x + 128 + ((x + 128) >> 8)) >> 8
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex() {
+        assert_eq!(Color::from_hex("#F80"), Some(Color::rgb(0xFF, 0x88, 0x00)));
+        assert_eq!(Color::from_hex("1e90ff"), Some(Color::rgb(0x1E, 0x90, 0xFF)));
+        assert_eq!(Color::from_hex("#11223344"), Some(Color::rgba(0x11, 0x22, 0x33, 0x44)));
+        assert_eq!(Color::from_hex("#12345"), None);
+        assert_eq!(Color::from_hex("#+12"), None);
+        assert_eq!(Color::from_hex(""), None);
+    }
+
+    #[test]
+    fn from_u32() {
+        let color = Color::rgba(0x12, 0x34, 0x56, 0x78);
+        assert_eq!(Color::from_argb_u32(0x78123456), color);
+        assert_eq!(Color::from_rgb_u32(0x123456), color.with_alpha(0xFF));
+    }
+
+    #[test]
+    fn hsv() {
+        assert_eq!(Color::from_hsv(0, 255, 255), Color::RED);
+        assert_eq!(Color::from_hsv(120, 255, 255), Color::GREEN);
+        assert_eq!(Color::from_hsv(240, 255, 255), Color::BLUE);
+        assert_eq!(Color::from_hsv(60, 255, 255), Color::YELLOW);
+        assert_eq!(Color::from_hsv(360 + 180, 255, 255), Color::CYAN);
+        assert_eq!(Color::from_hsv(200, 0, 0x80), Color::GRAY);
+    }
+
+    #[test]
+    fn lerp() {
+        assert_eq!(Color::BLACK.lerp(Color::WHITE, 0), Color::BLACK);
+        assert_eq!(Color::BLACK.lerp(Color::WHITE, 255), Color::WHITE);
+        assert_eq!(Color::BLACK.lerp(Color::WHITE, 128), Color::rgb(0x80, 0x80, 0x80));
+    }
+
+    #[test]
+    fn over() {
+        assert_eq!(Color::RED.over(Color::BLUE), Color::RED);
+        assert_eq!(Color::TRANSPARENT.over(Color::BLUE), Color::BLUE);
+        assert_eq!(Color::RED.with_alpha(0x80).over(Color::TRANSPARENT), Color::RED.with_alpha(0x80));
+        assert_eq!(Color::WHITE.with_alpha(0x80).over(Color::BLACK), Color::rgb(0x80, 0x80, 0x80));
+        // Two translucent layers let less through than either
+        let blended = Color::RED.with_alpha(0x80).over(Color::BLUE.with_alpha(0x80));
+        assert_eq!(blended.alpha, 0xC0);
+        assert!(blended.red > blended.blue);
+    }
+
+    #[test]
+    fn divide_by_255() {
+        for x in 0..=255 * 255 {
+            assert_eq!(div_255(x), (x + 127) / 255, "{}", x);
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use core::fmt::{self, Write};
+use crate::color::Color;
+use crate::font::{self, font};
+use crate::graphics::canvas::Canvas;
+use crate::serial::COM1;
+use crate::vbe::back_buffer::BackBuffer;
+use crate::vbe::screen;
+
+/// Largest text grid kept in memory (enough for 1280x1024 with an 8x16 font)
+const MAX_COLUMNS: usize = 160;
+const MAX_ROWS: usize = 64;
+
+pub const BACKGROUND: Color = Color::rgb(0x00, 0x11, 0x33);
+pub const FOREGROUND: Color = Color::rgb(0xDD, 0xDD, 0xDD);
+
+/// Text terminal drawn on the VBE framebuffer with the current font, mirrored to the
+/// first serial port. Takes UTF-8 and understands '\n', '\r' and '\x08' (cursor one
+/// column left, like a VT100).
+pub struct Console {
+    screen: &'static mut BackBuffer,
+    columns: usize,
+    rows: usize,
+    column: usize,
+    row: usize,
+    foreground: Color,
+    /// Glyphs on screen, to redraw the cell under the cursor
+    cells: [[u16; MAX_COLUMNS]; MAX_ROWS],
+    /// Code point of the UTF-8 sequence being received so far
+    partial: u32,
+    /// Continuation bytes it still needs
+    pending: u8,
+}
+
+impl Console {
+    fn new(screen: &'static mut BackBuffer) -> Self {
+        let mut console = Console {
+            screen,
+            columns: 0,
+            rows: 0,
+            column: 0,
+            row: 0,
+            foreground: FOREGROUND,
+            cells: [[0; MAX_COLUMNS]; MAX_ROWS],
+            partial: 0,
+            pending: 0,
+        };
+        console.resize();
+        console
+    }
+
+    /// Text grid of the screen with the current font
+    fn resize(&mut self) {
+        self.columns = (self.screen.width() / font().width()).clamp(1, MAX_COLUMNS);
+        self.rows = (self.screen.height() / font().height()).clamp(1, MAX_ROWS);
+    }
+
+    /// Start over with the new font, the text on screen is lost
+    pub fn font_changed(&mut self) {
+        self.resize();
+        self.clear();
+    }
+
+    pub fn set_foreground(&mut self, color: Color) {
+        self.foreground = color;
+    }
+
+    /// Erase the screen (and the serial terminal) and put the cursor at the top left
+    pub fn clear(&mut self) {
+        self.screen.clear_background(BACKGROUND);
+        self.cells = [[blank(); MAX_COLUMNS]; MAX_ROWS];
+        self.column = 0;
+        self.row = 0;
+        for byte in b"\x1b[2J\x1b[H" {
+            COM1.write_byte(*byte);
+        }
+        self.draw_cursor();
+        self.screen.flush();
+    }
+
+    fn draw_cell(&mut self, column: usize, row: usize, foreground: Color, background: Color) {
+        let (width, height) = (font().width(), font().height());
+        let glyph = self.cells[row][column] as usize;
+        font::draw_glyph(self.screen, font(), column * width, row * height, glyph, foreground, Some(background));
+    }
+
+    fn draw_cursor(&mut self) {
+        self.draw_cell(self.column, self.row, BACKGROUND, self.foreground);
+    }
+
+    fn hide_cursor(&mut self) {
+        self.draw_cell(self.column, self.row, self.foreground, BACKGROUND);
+    }
+
+    /// Move everything one text row up and clear the last row
+    fn scroll(&mut self) {
+        self.screen.scroll_up(font().height(), BACKGROUND);
+        self.cells.copy_within(1..self.rows, 0);
+        self.cells[self.rows - 1] = [blank(); MAX_COLUMNS];
+    }
+
+    fn new_line(&mut self) {
+        self.column = 0;
+        if self.row + 1 == self.rows {
+            self.scroll();
+        } else {
+            self.row += 1;
+        }
+    }
+
+    /// Write all of `bytes`, then show them in one flush
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.put_byte(byte);
+        }
+        self.screen.flush();
+    }
+
+    /// Draw `byte` in the back buffer
+    fn put_byte(&mut self, byte: u8) {
+        self.hide_cursor();
+        match byte {
+            b'\n' => {
+                self.new_line();
+                COM1.write_byte(b'\r');
+            }
+            b'\r' => self.column = 0,
+            // Going left from the first column continues at the end of the previous row,
+            // so line editing works across wrapped lines
+            0x08 if self.column == 0 && self.row > 0 => {
+                self.row -= 1;
+                self.column = self.columns - 1;
+            }
+            0x08 => self.column = self.column.saturating_sub(1),
+            _ => {
+                if let Some(char) = self.decode_utf8(byte) {
+                    self.cells[self.row][self.column] = font().glyph_for(char) as u16;
+                    self.draw_cell(self.column, self.row, self.foreground, BACKGROUND);
+                    self.column += 1;
+                    if self.column == self.columns {
+                        self.new_line();
+                    }
+                }
+            }
+        }
+        COM1.write_byte(byte);
+        self.draw_cursor();
+    }
+
+    /// Add a byte of UTF-8, returns the character it completes. A sequence cut short
+    /// is dropped, bytes that can't start one are U+FFFD.
+    fn decode_utf8(&mut self, byte: u8) -> Option<char> {
+        if byte & 0xC0 == 0x80 && self.pending > 0 {
+            self.partial = self.partial << 6 | (byte & 0x3F) as u32;
+            self.pending -= 1;
+            if self.pending > 0 {
+                return None;
+            }
+            return Some(char::from_u32(self.partial).unwrap_or(char::REPLACEMENT_CHARACTER));
+        }
+        let (pending, partial) = match byte {
+            0x00..=0x7F => return Some(byte as char),
+            0xC2..=0xDF => (1, byte & 0x1F),
+            0xE0..=0xEF => (2, byte & 0x0F),
+            0xF0..=0xF4 => (3, byte & 0x07),
+            _ => {
+                self.pending = 0;
+                return Some(char::REPLACEMENT_CHARACTER);
+            }
+        };
+        self.pending = pending;
+        self.partial = partial as u32;
+        None
+    }
+}
+
+/// Glyph of an empty cell
+fn blank() -> u16 {
+    font().glyph_for(' ') as u16
+}
+
+impl Write for Console {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.write_bytes(text.as_bytes());
+        Ok(())
+    }
+}
+
+static mut CONSOLE: Option<Console> = None;
+
+/// Set up the console on the screen's back buffer and clear it
+pub fn init_console() {
+    let Some(screen) = screen() else {
+        return;
+    };
+    unsafe {
+        CONSOLE = Some(Console::new(screen));
+    }
+    if let Some(console) = console() {
+        console.clear();
+    }
+}
+
+pub fn console() -> Option<&'static mut Console> {
+    unsafe { (*core::ptr::addr_of_mut!(CONSOLE)).as_mut() }
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    if let Some(console) = console() {
+        let _ = console.write_fmt(args);
+    }
+}
+
+/// Like std's print!, to the console
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => ($crate::console::_print(format_args!($($arg)*)));
+}
+
+/// Like std's println!, to the console
+#[macro_export]
+macro_rules! println {
+    () => ($crate::print!("\n"));
+    ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
+}
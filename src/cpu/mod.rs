@@ -0,0 +1,22 @@
+use core::arch::asm;
+
+/// Extended Feature Enable Register (LME, SCE, NXE)
+pub const IA32_EFER: u32 = 0xC000_0080;
+/// Segments loaded by syscall / sysret
+pub const IA32_STAR: u32 = 0xC000_0081;
+/// Entry point of syscall
+pub const IA32_LSTAR: u32 = 0xC000_0082;
+/// RFLAGS bits cleared by syscall
+pub const IA32_FMASK: u32 = 0xC000_0084;
+
+/// Read a model specific register
+pub unsafe fn rdmsr(msr: u32) -> u64 {
+    let (low, high): (u32, u32);
+    unsafe { asm!("rdmsr", in("ecx") msr, out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags)); }
+    (high as u64) << 32 | low as u64
+}
+
+/// Write a model specific register
+pub unsafe fn wrmsr(msr: u32, value: u64) {
+    unsafe { asm!("wrmsr", in("ecx") msr, in("eax") value as u32, in("edx") (value >> 32) as u32, options(nostack, preserves_flags)); }
+}
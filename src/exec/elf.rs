@@ -0,0 +1,257 @@
+use crate::exec::ExecError;
+use crate::fs::vfs::Vnode;
+use crate::fs::{read_u16, read_u32, read_u64};
+use crate::memory::paging::{map_user_page, user_page_flags, USER_END, USER_START};
+use crate::memory::FRAME_SIZE;
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+const CLASS_64: u8 = 2;
+const LITTLE_ENDIAN: u8 = 1;
+const TYPE_EXECUTABLE: u16 = 2;
+const MACHINE_X86_64: u16 = 0x3E;
+
+const HEADER_SIZE: usize = 64;
+pub const PROGRAM_HEADER_SIZE: usize = 56;
+/// Program headers one executable may have
+const MAX_PROGRAM_HEADERS: usize = 16;
+
+// Program header types and flags
+const PT_LOAD: u32 = 1;
+const PT_INTERP: u32 = 3;
+const PT_PHDR: u32 = 6;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+
+#[derive(Debug, Clone, Copy)]
+struct ProgramHeader {
+    kind: u32,
+    flags: u32,
+    offset: u64,
+    address: u64,
+    file_size: u64,
+    memory_size: u64,
+}
+
+impl ProgramHeader {
+    fn parse(bytes: &[u8]) -> Self {
+        ProgramHeader {
+            kind: read_u32(bytes, 0),
+            flags: read_u32(bytes, 4),
+            offset: read_u64(bytes, 8),
+            address: read_u64(bytes, 16),
+            file_size: read_u64(bytes, 32),
+            memory_size: read_u64(bytes, 40),
+        }
+    }
+
+    fn contains(&self, address: u64) -> bool {
+        (self.address..self.address + self.memory_size).contains(&address)
+    }
+}
+
+/// Where a program was loaded, for its entry and auxiliary vector
+pub struct LoadedImage {
+    pub entry: u64,
+    /// User address of the program headers, 0 when no segment contains them
+    pub program_headers: u64,
+    pub program_header_count: usize,
+}
+
+/// Read exactly `buffer.len()` bytes at `offset`, a shorter file is malformed
+fn read_exact(file: Vnode, offset: u64, buffer: &mut [u8]) -> Result<(), ExecError> {
+    let mut done = 0;
+    while done < buffer.len() {
+        let count = file.fs.read(file.inode, offset + done as u64, &mut buffer[done..])?;
+        if count == 0 {
+            return Err(ExecError::Malformed);
+        }
+        done += count;
+    }
+    Ok(())
+}
+
+/// Check that `file` is a static x86_64 executable and map its PT_LOAD segments
+/// into the user part of `pml4`, with the permissions of each segment
+pub fn load(file: Vnode, pml4: u64) -> Result<LoadedImage, ExecError> {
+    let mut header = [0u8; HEADER_SIZE];
+    read_exact(file, 0, &mut header).map_err(|_| ExecError::NotElf)?;
+    if &header[..4] != ELF_MAGIC {
+        return Err(ExecError::NotElf);
+    }
+    // Shared objects would need relocations and an interpreter
+    if header[4] != CLASS_64 || header[5] != LITTLE_ENDIAN || read_u16(&header, 16) != TYPE_EXECUTABLE
+        || read_u16(&header, 18) != MACHINE_X86_64
+    {
+        return Err(ExecError::Unsupported);
+    }
+    let entry = read_u64(&header, 24);
+    let header_offset = read_u64(&header, 32);
+    let header_count = read_u16(&header, 56) as usize;
+    if read_u16(&header, 54) as usize != PROGRAM_HEADER_SIZE || header_count > MAX_PROGRAM_HEADERS {
+        return Err(ExecError::Malformed);
+    }
+
+    let file_size = file.fs.stat(file.inode)?.size;
+    let mut table = [0u8; PROGRAM_HEADER_SIZE * MAX_PROGRAM_HEADERS];
+    let table = &mut table[..PROGRAM_HEADER_SIZE * header_count];
+    read_exact(file, header_offset, table)?;
+
+    let mut image = LoadedImage { entry, program_headers: 0, program_header_count: header_count };
+    let mut entry_found = false;
+    for bytes in table.chunks(PROGRAM_HEADER_SIZE) {
+        let segment = ProgramHeader::parse(bytes);
+        match segment.kind {
+            PT_INTERP => return Err(ExecError::Unsupported),
+            PT_PHDR => image.program_headers = segment.address,
+            PT_LOAD if segment.memory_size > 0 => {
+                load_segment(file, file_size, &segment, pml4)?;
+                entry_found |= segment.flags & PF_X != 0 && segment.contains(entry);
+                let headers_end = header_offset + table.len() as u64;
+                if image.program_headers == 0 && segment.offset <= header_offset
+                    && headers_end <= segment.offset + segment.file_size
+                {
+                    image.program_headers = segment.address + (header_offset - segment.offset);
+                }
+            }
+            _ => {}
+        }
+    }
+    if !entry_found {
+        return Err(ExecError::Malformed);
+    }
+    Ok(image)
+}
+
+fn load_segment(file: Vnode, file_size: u64, segment: &ProgramHeader, pml4: u64) -> Result<(), ExecError> {
+    let end = segment.address.checked_add(segment.memory_size).ok_or(ExecError::Malformed)?;
+    let file_end = segment.offset.checked_add(segment.file_size).ok_or(ExecError::Malformed)?;
+    if segment.address < USER_START || end > USER_END || segment.file_size > segment.memory_size
+        || file_end > file_size || segment.address % FRAME_SIZE != segment.offset % FRAME_SIZE
+    {
+        return Err(ExecError::Malformed);
+    }
+
+    let flags = user_page_flags(segment.flags & PF_W != 0, segment.flags & PF_X != 0);
+    let data_end = segment.address + segment.file_size;
+    let mut page = segment.address & !(FRAME_SIZE - 1);
+    while page < end {
+        let frame = map_user_page(pml4, page, flags)?;
+        // The part of the file in this page, the rest stays zero (bss)
+        let start = page.max(segment.address);
+        let stop = (page + FRAME_SIZE).min(data_end);
+        if start < stop {
+            let destination = unsafe {
+                core::slice::from_raw_parts_mut((frame + (start - page)) as *mut u8, (stop - start) as usize)
+            };
+            read_exact(file, segment.offset + (start - segment.address), destination)?;
+        }
+        page += FRAME_SIZE;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+    use crate::fs::ramfs::{RamFs, RamStorage};
+    use crate::fs::{write_u16, write_u32, FileSystem, FileType};
+
+    /// Only the checks made before any page is mapped can run on the host,
+    /// so every segment here is rejected or empty
+    fn load_bytes(bytes: &[u8]) -> Result<LoadedImage, ExecError> {
+        let storage: &'static mut RamStorage = Box::leak(unsafe { Box::new_zeroed().assume_init() });
+        let fs: &'static RamFs = Box::leak(Box::new(RamFs::new(storage)));
+        let inode = fs.create(fs.root(), "program", FileType::Regular).unwrap();
+        fs.write(inode, 0, bytes).unwrap();
+        load(Vnode { fs, inode }, 0)
+    }
+
+    fn header(program_headers: &[ProgramHeader]) -> Vec<u8> {
+        let mut bytes = vec![0u8; HEADER_SIZE];
+        bytes[..4].copy_from_slice(ELF_MAGIC);
+        bytes[4] = CLASS_64;
+        bytes[5] = LITTLE_ENDIAN;
+        write_u16(&mut bytes, 16, TYPE_EXECUTABLE);
+        write_u16(&mut bytes, 18, MACHINE_X86_64);
+        bytes[24..32].copy_from_slice(&(USER_START + 0x1000).to_le_bytes());
+        bytes[32..40].copy_from_slice(&(HEADER_SIZE as u64).to_le_bytes());
+        write_u16(&mut bytes, 54, PROGRAM_HEADER_SIZE as u16);
+        write_u16(&mut bytes, 56, program_headers.len() as u16);
+        for segment in program_headers {
+            let mut raw = [0u8; PROGRAM_HEADER_SIZE];
+            write_u32(&mut raw, 0, segment.kind);
+            write_u32(&mut raw, 4, segment.flags);
+            raw[8..16].copy_from_slice(&segment.offset.to_le_bytes());
+            raw[16..24].copy_from_slice(&segment.address.to_le_bytes());
+            raw[32..40].copy_from_slice(&segment.file_size.to_le_bytes());
+            raw[40..48].copy_from_slice(&segment.memory_size.to_le_bytes());
+            bytes.extend_from_slice(&raw);
+        }
+        bytes
+    }
+
+    fn text(offset: u64, address: u64, file_size: u64, memory_size: u64) -> ProgramHeader {
+        ProgramHeader { kind: PT_LOAD, flags: PF_X, offset, address, file_size, memory_size }
+    }
+
+    #[test]
+    fn not_elf() {
+        assert_eq!(load_bytes(b"#!/bin/sh\n").err(), Some(ExecError::NotElf));
+        let mut bytes = header(&[]);
+        bytes[0] = b'E';
+        assert_eq!(load_bytes(&bytes).err(), Some(ExecError::NotElf));
+        assert_eq!(load_bytes(&header(&[])[..HEADER_SIZE - 1]).err(), Some(ExecError::NotElf));
+    }
+
+    #[test]
+    fn unsupported() {
+        for (offset, value) in [(4, 1), (5, 2), (16, 3), (18, 3)] {
+            let mut bytes = header(&[]);
+            bytes[offset] = value;
+            assert_eq!(load_bytes(&bytes).err(), Some(ExecError::Unsupported), "byte {}", offset);
+        }
+        let interpreter = ProgramHeader { kind: PT_INTERP, ..text(0, 0, 0, 0) };
+        assert_eq!(load_bytes(&header(&[interpreter])).err(), Some(ExecError::Unsupported));
+    }
+
+    #[test]
+    fn malformed_headers() {
+        let mut bytes = header(&[]);
+        write_u16(&mut bytes, 54, 32);
+        assert_eq!(load_bytes(&bytes).err(), Some(ExecError::Malformed));
+        let mut bytes = header(&[]);
+        write_u16(&mut bytes, 56, MAX_PROGRAM_HEADERS as u16 + 1);
+        assert_eq!(load_bytes(&bytes).err(), Some(ExecError::Malformed));
+
+        // The table is cut short
+        let bytes = header(&[text(0, USER_START, 0, 0)]);
+        assert_eq!(load_bytes(&bytes[..bytes.len() - 1]).err(), Some(ExecError::Malformed));
+        // Nothing is loaded, so nothing contains the entry point
+        let empty = header(&[text(0, USER_START, 0, 0), ProgramHeader { kind: PT_PHDR, ..text(0, 0, 0, 0) }]);
+        assert_eq!(load_bytes(&empty).err(), Some(ExecError::Malformed));
+    }
+
+    #[test]
+    fn malformed_segments() {
+        let segments = [
+            // Below the user part of the address space
+            text(0, USER_START - 0x1000, 0x100, 0x100),
+            // Past its end
+            text(0, USER_END - 0x1000, 0x100, 0x2000),
+            // The last page of the lower half, which sysretq can't return to
+            text(0, USER_END, 0x100, 0x1000),
+            // Wraps around
+            text(0, USER_START, 0x100, u64::MAX),
+            // More file than memory
+            text(0, USER_START, 0x200, 0x100),
+            // Past the end of the file
+            text(0, USER_START, 0x1000, 0x1000),
+            text(u64::MAX, USER_START, 0x10, 0x10),
+            // Address and offset not congruent modulo the page size
+            text(0x10, USER_START, 0x10, 0x10),
+        ];
+        for segment in segments {
+            assert_eq!(load_bytes(&header(&[segment])).err(), Some(ExecError::Malformed), "{:?}", segment);
+        }
+    }
+}
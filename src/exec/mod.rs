@@ -0,0 +1,132 @@
+use crate::exec::elf::{load, LoadedImage, PROGRAM_HEADER_SIZE};
+use crate::fs::vfs::resolve;
+use crate::fs::{FileType, FsError};
+use crate::memory::address_space::AddressSpace;
+use crate::memory::paging::{map_user_page, user_page_flags, write_user, USER_END};
+use crate::memory::{MemoryError, FRAME_SIZE};
+
+mod elf;
+
+/// Largest argv and envp
+pub const MAX_ARGS: usize = 16;
+/// The stack ends one page below the end of the user half, which stays unmapped
+const USER_STACK_TOP: u64 = USER_END - FRAME_SIZE;
+const USER_STACK_SIZE: u64 = 64 * 1024;
+/// Bytes of the stack the argument and environment strings may use
+const MAX_STRINGS_SIZE: usize = 16 * 1024;
+
+// Auxiliary vector entries (System V ABI)
+const AT_NULL: u64 = 0;
+const AT_PHDR: u64 = 3;
+const AT_PHENT: u64 = 4;
+const AT_PHNUM: u64 = 5;
+const AT_PAGESZ: u64 = 6;
+const AT_ENTRY: u64 = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecError {
+    /// Not an ELF file
+    NotElf,
+    /// An ELF file, but not a static x86_64 executable
+    Unsupported,
+    /// Inconsistent headers or segments
+    Malformed,
+    /// More than MAX_ARGS arguments, or strings too long for the stack
+    ArgumentsTooLong,
+    Fs(FsError),
+    Memory(MemoryError),
+}
+
+impl From<FsError> for ExecError {
+    fn from(error: FsError) -> Self {
+        ExecError::Fs(error)
+    }
+}
+
+impl From<MemoryError> for ExecError {
+    fn from(error: MemoryError) -> Self {
+        ExecError::Memory(error)
+    }
+}
+
+/// A program loaded in its own address space, ready to enter
+pub struct Program {
+    pub space: AddressSpace,
+    pub entry: u64,
+    pub stack: u64,
+}
+
+/// Load the static ELF executable at `path` in a new address space, with `args`
+/// and `env` on its stack. `args[0]` is the program name by convention.
+pub fn load_program(path: &str, args: &[&str], env: &[&str]) -> Result<Program, ExecError> {
+    let file = resolve(path)?;
+    if file.fs.stat(file.inode)?.file_type != FileType::Regular {
+        return Err(ExecError::Fs(FsError::IsADirectory));
+    }
+    // Built while the caller's page tables are active, through the identity mapping.
+    // Dropping it on an error frees everything mapped so far.
+    let space = AddressSpace::new()?;
+    let image = load(file, space.pml4())?;
+    let stack = build_stack(space.pml4(), &image, args, env)?;
+    Ok(Program { space, entry: image.entry, stack })
+}
+
+/// Map the user stack and put on it what the System V ABI expects at the entry point:
+/// argc, argv, NULL, envp, NULL, the auxiliary vector, then the strings.
+/// Returns the initial stack pointer.
+fn build_stack(pml4: u64, image: &LoadedImage, args: &[&str], env: &[&str]) -> Result<u64, ExecError> {
+    let strings_size: usize = args.iter().chain(env).map(|string| string.len() + 1).sum();
+    if args.len() > MAX_ARGS || env.len() > MAX_ARGS || strings_size > MAX_STRINGS_SIZE {
+        return Err(ExecError::ArgumentsTooLong);
+    }
+
+    let flags = user_page_flags(true, false);
+    let mut page = USER_STACK_TOP - USER_STACK_SIZE;
+    while page < USER_STACK_TOP {
+        map_user_page(pml4, page, flags)?;
+        page += FRAME_SIZE;
+    }
+
+    // argc, argv + NULL, envp + NULL, 6 auxiliary vector pairs
+    let mut words = [0u64; 1 + (MAX_ARGS + 1) * 2 + 12];
+    let mut count = 0;
+    let mut push = |word: u64| {
+        words[count] = word;
+        count += 1;
+    };
+
+    let mut top = USER_STACK_TOP;
+    let mut push_string = |string: &str| -> Result<u64, ExecError> {
+        top -= string.len() as u64 + 1;
+        write_user(pml4, top, string.as_bytes())?;
+        write_user(pml4, top + string.len() as u64, &[0])?;
+        Ok(top)
+    };
+    push(args.len() as u64);
+    for arg in args {
+        push(push_string(arg)?);
+    }
+    push(0);
+    for variable in env {
+        push(push_string(variable)?);
+    }
+    push(0);
+    for (key, value) in [
+        (AT_PHDR, image.program_headers),
+        (AT_PHENT, PROGRAM_HEADER_SIZE as u64),
+        (AT_PHNUM, image.program_header_count as u64),
+        (AT_PAGESZ, FRAME_SIZE),
+        (AT_ENTRY, image.entry),
+        (AT_NULL, 0),
+    ] {
+        push(key);
+        push(value);
+    }
+
+    // rsp must be 16-byte aligned and point at argc
+    let stack = (top - count as u64 * 8) & !15;
+    for (i, word) in words[..count].iter().enumerate() {
+        write_user(pml4, stack + i as u64 * 8, &word.to_le_bytes())?;
+    }
+    Ok(stack)
+}
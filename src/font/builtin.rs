@@ -0,0 +1,107 @@
+// 8x16 bitmap glyphs for printable ASCII (0x20 to 0x7E), rendered from
+// DejaVu Sans Mono Bold (Bitstream Vera license). One byte per row, the most
+// significant bit is the leftmost pixel.
+
+pub const FONT_WIDTH: usize = 8;
+pub const FONT_HEIGHT: usize = 16;
+pub const FIRST_CHAR: u8 = 0x20;
+pub const LAST_CHAR: u8 = 0x7E;
+
+pub const FONT: [[u8; FONT_HEIGHT]; (LAST_CHAR - FIRST_CHAR + 1) as usize] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // space
+    [0x00, 0x00, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // !
+    [0x00, 0x00, 0x24, 0x66, 0x66, 0x24, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // "
+    [0x00, 0x00, 0x02, 0x12, 0x16, 0x7F, 0x34, 0x24, 0xFE, 0xFE, 0x68, 0x48, 0x00, 0x00, 0x00, 0x00], // #
+    [0x00, 0x00, 0x08, 0x18, 0x7E, 0x68, 0x78, 0x3C, 0x1E, 0x0E, 0x7E, 0x7C, 0x08, 0x08, 0x00, 0x00], // $
+    [0x00, 0x00, 0x00, 0x70, 0x90, 0xD0, 0x66, 0x18, 0x4E, 0x09, 0x0B, 0x06, 0x00, 0x00, 0x00, 0x00], // %
+    [0x00, 0x00, 0x3C, 0x3C, 0x60, 0x30, 0x70, 0x7B, 0xCF, 0xCE, 0x6E, 0x7F, 0x00, 0x00, 0x00, 0x00], // &
+    [0x00, 0x00, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '
+    [0x00, 0x00, 0x0C, 0x08, 0x18, 0x18, 0x10, 0x30, 0x30, 0x10, 0x18, 0x18, 0x08, 0x0C, 0x00, 0x00], // (
+    [0x00, 0x00, 0x30, 0x10, 0x18, 0x18, 0x08, 0x0C, 0x0C, 0x08, 0x18, 0x18, 0x10, 0x30, 0x00, 0x00], // )
+    [0x00, 0x00, 0x10, 0x5A, 0x7C, 0x3C, 0x7E, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // *
+    [0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x18, 0xFF, 0x7E, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00], // +
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x10, 0x10, 0x00, 0x00], // ,
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3C, 0x3C, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // .
+    [0x00, 0x00, 0x02, 0x06, 0x04, 0x0C, 0x08, 0x18, 0x10, 0x30, 0x20, 0x20, 0x60, 0x00, 0x00, 0x00], // /
+    [0x00, 0x00, 0x18, 0x3C, 0x66, 0x66, 0x66, 0x7E, 0x66, 0x66, 0x7E, 0x3C, 0x00, 0x00, 0x00, 0x00], // 0
+    [0x00, 0x00, 0x18, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3E, 0x7E, 0x00, 0x00, 0x00, 0x00], // 1
+    [0x00, 0x00, 0x78, 0x7E, 0x06, 0x06, 0x0C, 0x1C, 0x38, 0x30, 0x7E, 0x7E, 0x00, 0x00, 0x00, 0x00], // 2
+    [0x00, 0x00, 0x78, 0x7E, 0x06, 0x06, 0x3C, 0x1C, 0x06, 0x06, 0x7E, 0x7C, 0x00, 0x00, 0x00, 0x00], // 3
+    [0x00, 0x00, 0x0C, 0x0C, 0x1C, 0x3C, 0x2C, 0x6C, 0x7E, 0x7E, 0x0C, 0x0C, 0x00, 0x00, 0x00, 0x00], // 4
+    [0x00, 0x00, 0x3C, 0x7E, 0x60, 0x60, 0x7C, 0x0E, 0x06, 0x06, 0x4E, 0x7C, 0x00, 0x00, 0x00, 0x00], // 5
+    [0x00, 0x00, 0x1C, 0x3E, 0x60, 0x60, 0x7E, 0x66, 0x66, 0x66, 0x76, 0x3C, 0x00, 0x00, 0x00, 0x00], // 6
+    [0x00, 0x00, 0x7E, 0x7E, 0x06, 0x0C, 0x0C, 0x0C, 0x18, 0x18, 0x30, 0x30, 0x00, 0x00, 0x00, 0x00], // 7
+    [0x00, 0x00, 0x3C, 0x7E, 0x66, 0x66, 0x3C, 0x3C, 0x66, 0x66, 0x66, 0x3C, 0x00, 0x00, 0x00, 0x00], // 8
+    [0x00, 0x00, 0x38, 0x7C, 0x66, 0x66, 0x66, 0x7E, 0x3E, 0x06, 0x0C, 0x7C, 0x00, 0x00, 0x00, 0x00], // 9
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x18, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // :
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x18, 0x00, 0x00, 0x18, 0x18, 0x18, 0x10, 0x00, 0x00], // ;
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x06, 0x3C, 0x60, 0x70, 0x1E, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00], // <
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7E, 0x00, 0x7E, 0x7E, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // =
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x60, 0x3C, 0x06, 0x0E, 0x78, 0x60, 0x00, 0x00, 0x00, 0x00, 0x00], // >
+    [0x00, 0x00, 0x3C, 0x7E, 0x06, 0x06, 0x0C, 0x18, 0x18, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // ?
+    [0x00, 0x00, 0x00, 0x3C, 0x66, 0x42, 0xDF, 0x93, 0xB3, 0x93, 0xDF, 0x40, 0x62, 0x1E, 0x00, 0x00], // @
+    [0x00, 0x00, 0x18, 0x3C, 0x3C, 0x3C, 0x24, 0x66, 0x7E, 0x7E, 0x66, 0xC3, 0x00, 0x00, 0x00, 0x00], // A
+    [0x00, 0x00, 0x7C, 0x7E, 0x66, 0x66, 0x7C, 0x7E, 0x66, 0x63, 0x7E, 0x7C, 0x00, 0x00, 0x00, 0x00], // B
+    [0x00, 0x00, 0x1E, 0x3E, 0x70, 0x60, 0x60, 0x60, 0x60, 0x60, 0x3E, 0x1E, 0x00, 0x00, 0x00, 0x00], // C
+    [0x00, 0x00, 0x78, 0x7C, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x7E, 0x78, 0x00, 0x00, 0x00, 0x00], // D
+    [0x00, 0x00, 0x7E, 0x7E, 0x60, 0x60, 0x7E, 0x7E, 0x60, 0x60, 0x7E, 0x7E, 0x00, 0x00, 0x00, 0x00], // E
+    [0x00, 0x00, 0x7E, 0x7E, 0x60, 0x60, 0x7E, 0x7E, 0x60, 0x60, 0x60, 0x60, 0x00, 0x00, 0x00, 0x00], // F
+    [0x00, 0x00, 0x1C, 0x3E, 0x60, 0x60, 0x60, 0x6E, 0x66, 0x62, 0x3E, 0x3E, 0x00, 0x00, 0x00, 0x00], // G
+    [0x00, 0x00, 0x42, 0x66, 0x66, 0x66, 0x7E, 0x7E, 0x66, 0x66, 0x66, 0x66, 0x00, 0x00, 0x00, 0x00], // H
+    [0x00, 0x00, 0x7E, 0x7E, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x7E, 0x7E, 0x00, 0x00, 0x00, 0x00], // I
+    [0x00, 0x00, 0x1C, 0x3E, 0x06, 0x06, 0x06, 0x06, 0x06, 0x06, 0x7C, 0x7C, 0x00, 0x00, 0x00, 0x00], // J
+    [0x00, 0x00, 0x42, 0x66, 0x6C, 0x78, 0x78, 0x7C, 0x6C, 0x6E, 0x66, 0x63, 0x00, 0x00, 0x00, 0x00], // K
+    [0x00, 0x00, 0x20, 0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x7E, 0x7F, 0x00, 0x00, 0x00, 0x00], // L
+    [0x00, 0x00, 0x66, 0xE7, 0xE7, 0xFF, 0xFF, 0xDB, 0xC3, 0xC3, 0xC3, 0xC3, 0x00, 0x00, 0x00, 0x00], // M
+    [0x00, 0x00, 0x62, 0x66, 0x76, 0x76, 0x76, 0x7E, 0x6E, 0x6E, 0x66, 0x66, 0x00, 0x00, 0x00, 0x00], // N
+    [0x00, 0x00, 0x3C, 0x7E, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x7E, 0x3C, 0x00, 0x00, 0x00, 0x00], // O
+    [0x00, 0x00, 0x78, 0x7E, 0x66, 0x66, 0x66, 0x7E, 0x60, 0x60, 0x60, 0x60, 0x00, 0x00, 0x00, 0x00], // P
+    [0x00, 0x00, 0x3C, 0x7E, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x7E, 0x3C, 0x06, 0x04, 0x00, 0x00], // Q
+    [0x00, 0x00, 0x78, 0x7E, 0x66, 0x66, 0x6E, 0x7C, 0x6C, 0x66, 0x66, 0x63, 0x00, 0x00, 0x00, 0x00], // R
+    [0x00, 0x00, 0x3C, 0x7E, 0x60, 0x60, 0x78, 0x1E, 0x06, 0x06, 0x6E, 0x7C, 0x00, 0x00, 0x00, 0x00], // S
+    [0x00, 0x00, 0x7E, 0x7E, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // T
+    [0x00, 0x00, 0x42, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x7E, 0x3C, 0x00, 0x00, 0x00, 0x00], // U
+    [0x00, 0x00, 0x42, 0x66, 0x66, 0x66, 0x66, 0x24, 0x3C, 0x3C, 0x3C, 0x18, 0x00, 0x00, 0x00, 0x00], // V
+    [0x00, 0x00, 0x81, 0xC3, 0xC3, 0xDB, 0x5B, 0x5A, 0x7E, 0x66, 0x66, 0x66, 0x00, 0x00, 0x00, 0x00], // W
+    [0x00, 0x00, 0x42, 0x66, 0x24, 0x3C, 0x18, 0x18, 0x3C, 0x3C, 0x66, 0xC3, 0x00, 0x00, 0x00, 0x00], // X
+    [0x00, 0x00, 0x42, 0x66, 0x66, 0x3C, 0x3C, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // Y
+    [0x00, 0x00, 0x7E, 0x7E, 0x06, 0x0C, 0x1C, 0x18, 0x30, 0x70, 0x7E, 0x7F, 0x00, 0x00, 0x00, 0x00], // Z
+    [0x00, 0x00, 0x1C, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1C, 0x1C, 0x00, 0x00], // [
+    [0x00, 0x00, 0x40, 0x60, 0x20, 0x30, 0x10, 0x18, 0x08, 0x0C, 0x04, 0x04, 0x06, 0x00, 0x00, 0x00], // \
+    [0x00, 0x00, 0x38, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x38, 0x38, 0x00, 0x00], // ]
+    [0x00, 0x00, 0x18, 0x3C, 0x66, 0x42, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ^
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x00], // _
+    [0x00, 0x20, 0x30, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // `
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7E, 0x06, 0x3E, 0x7E, 0x66, 0x66, 0x7E, 0x00, 0x00, 0x00, 0x00], // a
+    [0x00, 0x00, 0x60, 0x60, 0x60, 0x7E, 0x66, 0x66, 0x66, 0x66, 0x76, 0x7C, 0x00, 0x00, 0x00, 0x00], // b
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3E, 0x70, 0x60, 0x60, 0x60, 0x32, 0x3E, 0x00, 0x00, 0x00, 0x00], // c
+    [0x00, 0x00, 0x06, 0x06, 0x06, 0x7E, 0x66, 0x66, 0x66, 0x66, 0x6E, 0x3E, 0x00, 0x00, 0x00, 0x00], // d
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3E, 0x66, 0x7E, 0x7F, 0x60, 0x72, 0x3E, 0x00, 0x00, 0x00, 0x00], // e
+    [0x00, 0x00, 0x1E, 0x18, 0x18, 0x7E, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // f
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7E, 0x66, 0x66, 0x66, 0x66, 0x7E, 0x3E, 0x06, 0x7E, 0x38, 0x00], // g
+    [0x00, 0x00, 0x60, 0x60, 0x60, 0x7E, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x00, 0x00, 0x00, 0x00], // h
+    [0x00, 0x18, 0x18, 0x00, 0x00, 0x78, 0x18, 0x18, 0x18, 0x18, 0x7E, 0x7F, 0x00, 0x00, 0x00, 0x00], // i
+    [0x00, 0x08, 0x0C, 0x00, 0x00, 0x3C, 0x1C, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x08, 0x78, 0x70, 0x00], // j
+    [0x00, 0x00, 0x60, 0x60, 0x60, 0x66, 0x6C, 0x78, 0x7C, 0x6C, 0x66, 0x66, 0x00, 0x00, 0x00, 0x00], // k
+    [0x00, 0x00, 0x70, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x10, 0x1E, 0x1E, 0x00, 0x00, 0x00, 0x00], // l
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xFE, 0xDA, 0xDB, 0xDB, 0xDB, 0xDB, 0xDB, 0x00, 0x00, 0x00, 0x00], // m
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7E, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x00, 0x00, 0x00, 0x00], // n
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3C, 0x66, 0x66, 0x66, 0x66, 0x66, 0x3C, 0x00, 0x00, 0x00, 0x00], // o
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7E, 0x66, 0x66, 0x66, 0x66, 0x76, 0x7C, 0x60, 0x60, 0x60, 0x00], // p
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7E, 0x66, 0x66, 0x66, 0x66, 0x6E, 0x3E, 0x06, 0x06, 0x06, 0x00], // q
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3F, 0x38, 0x30, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00, 0x00, 0x00], // r
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3C, 0x60, 0x70, 0x3C, 0x06, 0x46, 0x7C, 0x00, 0x00, 0x00, 0x00], // s
+    [0x00, 0x00, 0x00, 0x18, 0x38, 0x7E, 0x38, 0x18, 0x18, 0x18, 0x1E, 0x1E, 0x00, 0x00, 0x00, 0x00], // t
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x66, 0x66, 0x66, 0x66, 0x66, 0x7E, 0x3E, 0x00, 0x00, 0x00, 0x00], // u
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x66, 0x66, 0x66, 0x24, 0x3C, 0x3C, 0x18, 0x00, 0x00, 0x00, 0x00], // v
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xC3, 0xC3, 0xDB, 0x5A, 0x7E, 0x66, 0x66, 0x00, 0x00, 0x00, 0x00], // w
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x66, 0x3C, 0x18, 0x18, 0x3C, 0x66, 0x66, 0x00, 0x00, 0x00, 0x00], // x
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x66, 0x66, 0x66, 0x3C, 0x3C, 0x18, 0x18, 0x18, 0x70, 0x60, 0x00], // y
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7E, 0x0E, 0x0C, 0x18, 0x30, 0x70, 0x7E, 0x00, 0x00, 0x00, 0x00], // z
+    [0x00, 0x00, 0x0E, 0x18, 0x18, 0x18, 0x18, 0x70, 0x70, 0x18, 0x18, 0x18, 0x18, 0x0E, 0x00, 0x00], // {
+    [0x00, 0x00, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00], // |
+    [0x00, 0x00, 0x70, 0x18, 0x18, 0x18, 0x18, 0x0E, 0x0E, 0x18, 0x18, 0x18, 0x18, 0x70, 0x00, 0x00], // }
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7E, 0x0E, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ~
+];
+
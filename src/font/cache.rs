@@ -0,0 +1,221 @@
+use crate::font::raster::{ceil, floor, Rasterizer, Vector};
+use crate::font::truetype::TrueTypeFont;
+
+/// Slots of the hash table, a quarter stays free so that lookups are short
+const SLOTS: usize = 512;
+const MAX_ENTRIES: usize = SLOTS * 3 / 4;
+
+#[derive(Clone, Copy)]
+struct Entry {
+    glyph: u16,
+    /// Pixels per em, in 1/64
+    size: u32,
+    left: i16,
+    top: i16,
+    width: u16,
+    height: u16,
+    /// Where its coverage is in the pool
+    offset: u32,
+}
+
+/// The coverage of a rendered glyph, placed relative to the pen on the baseline
+pub struct GlyphMask<'a> {
+    /// Columns from the pen to the first one of the mask
+    pub left: i32,
+    /// Rows from the first one of the mask to the baseline
+    pub top: i32,
+    pub width: usize,
+    pub height: usize,
+    /// 0 to 255, row after row
+    pub coverage: &'a [u8],
+}
+
+/// Glyphs rendered at the sizes they were asked for, kept until the pool is full,
+/// then it all starts over
+pub struct GlyphCache<'a> {
+    /// Work area of the rasterizer, also the limit on the size of a glyph
+    cells: &'a mut [f32],
+    pool: &'a mut [u8],
+    used: usize,
+    slots: [Option<Entry>; SLOTS],
+    count: usize,
+}
+
+impl<'a> GlyphCache<'a> {
+    pub const fn new(cells: &'a mut [f32], pool: &'a mut [u8]) -> Self {
+        GlyphCache { cells, pool, used: 0, slots: [None; SLOTS], count: 0 }
+    }
+
+    /// False for a cache made with empty memory, which renders nothing
+    pub fn has_memory(&self) -> bool {
+        !self.cells.is_empty() && !self.pool.is_empty()
+    }
+
+    /// Render in `cells` and keep the glyphs in `pool` from now on, forgetting the others
+    pub fn set_memory(&mut self, cells: &'a mut [f32], pool: &'a mut [u8]) {
+        self.cells = cells;
+        self.pool = pool;
+        self.clear();
+    }
+
+    /// Forget every glyph, e.g. for another font
+    pub fn clear(&mut self) {
+        self.slots.fill(None);
+        self.count = 0;
+        self.used = 0;
+    }
+
+    /// `glyph` of `font` at `pixels_per_em`, rendered now or earlier. None when it is too
+    /// big for the cache. The sizes are rounded to 1/64 pixel.
+    pub fn glyph(&mut self, font: &TrueTypeFont, glyph: u16, pixels_per_em: f32) -> Option<GlyphMask<'_>> {
+        let size = (pixels_per_em.max(0.0) * 64.0 + 0.5) as u32;
+        let slot = match self.find(glyph, size) {
+            Ok(slot) => slot,
+            Err(slot) => self.render(font, glyph, size, slot)?,
+        };
+        let entry = self.slots[slot]?;
+        let (offset, area) = (entry.offset as usize, entry.width as usize * entry.height as usize);
+        Some(GlyphMask {
+            left: entry.left as i32,
+            top: entry.top as i32,
+            width: entry.width as usize,
+            height: entry.height as usize,
+            coverage: &self.pool[offset..offset + area],
+        })
+    }
+
+    /// The slot of the glyph, or the free one it goes in
+    fn find(&self, glyph: u16, size: u32) -> Result<usize, usize> {
+        let key = (size as u64) << 16 | glyph as u64;
+        let mut slot = (key.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32) as usize % SLOTS;
+        loop {
+            match self.slots[slot] {
+                None => return Err(slot),
+                Some(entry) if entry.glyph == glyph && entry.size == size => return Ok(slot),
+                Some(_) => slot = (slot + 1) % SLOTS,
+            }
+        }
+    }
+
+    fn render(&mut self, font: &TrueTypeFont, glyph: u16, size: u32, slot: usize) -> Option<usize> {
+        let scale = size as f32 / 64.0 / font.units_per_em() as f32;
+        // Whole pixels around the outline
+        let (left, top, right, bottom) = match font.bounding_box(glyph) {
+            Some(bounds) => (
+                floor(bounds.x_min as f32 * scale) as i32,
+                ceil(bounds.y_max as f32 * scale) as i32,
+                ceil(bounds.x_max as f32 * scale) as i32,
+                floor(bounds.y_min as f32 * scale) as i32,
+            ),
+            None => (0, 0, 0, 0),
+        };
+        let (width, height) = (right.saturating_sub(left).max(0) as usize, top.saturating_sub(bottom).max(0) as usize);
+        let area = width * height;
+        let fits = |value: i32| i16::try_from(value).is_ok();
+        if area + 2 > self.cells.len() || area > self.pool.len() || width.max(height) > u16::MAX as usize || !fits(left) || !fits(top) {
+            return None;
+        }
+
+        let mut slot = slot;
+        if self.used + area > self.pool.len() || self.count == MAX_ENTRIES {
+            self.clear();
+            slot = self.find(glyph, size).err()?;
+        }
+        let offset = self.used;
+        if area > 0 {
+            let origin = Vector::new(-left as f32, top as f32);
+            let mut rasterizer = Rasterizer::new(self.cells, width, height, scale, origin)?;
+            // A broken glyph still shows what could be read of it
+            font.outline(glyph, &mut rasterizer);
+            rasterizer.coverage(&mut self.pool[offset..offset + area]);
+        }
+        self.used += area;
+        self.count += 1;
+        self.slots[slot] = Some(Entry {
+            glyph,
+            size,
+            left: left as i16,
+            top: top as i16,
+            width: width as u16,
+            height: height as u16,
+            offset: offset as u32,
+        });
+        Some(slot)
+    }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+    use crate::testing::truetype_font;
+
+    #[test]
+    fn renders_once() {
+        let data = truetype_font(false, false);
+        let font = TrueTypeFont::parse(&data).unwrap();
+        let (mut cells, mut pool) = (vec![0.0; 1000], vec![0; 1000]);
+        let mut cache = GlyphCache::new(&mut cells, &mut pool);
+        // 'I' at 10 pixels per em: 2 x 7 pixels, from 1 pixel right of the pen
+        let mask = cache.glyph(&font, 1, 10.0).unwrap();
+        assert_eq!((mask.left, mask.top, mask.width, mask.height), (1, 7, 2, 7));
+        assert!(mask.coverage.iter().all(|&coverage| coverage == 255));
+        let first = mask.coverage.as_ptr();
+        assert_eq!(cache.glyph(&font, 1, 10.0).unwrap().coverage.as_ptr(), first);
+        assert_eq!(cache.used, 14);
+        // Another size is another glyph
+        let mask = cache.glyph(&font, 1, 15.0).unwrap();
+        assert_eq!((mask.left, mask.top, mask.width, mask.height), (1, 11, 4, 11));
+        // From 1.5 to 4.5, and the top at 10.5
+        assert_eq!(&mask.coverage[..8], [64, 128, 128, 64, 128, 255, 255, 128]);
+        assert_eq!(cache.count, 2);
+        // No outline, nothing to keep but the entry
+        let mask = cache.glyph(&font, 4, 10.0).unwrap();
+        assert_eq!((mask.width, mask.height), (0, 0));
+        assert_eq!(cache.count, 3);
+    }
+
+    #[test]
+    fn starts_over_when_full() {
+        let data = truetype_font(false, false);
+        let font = TrueTypeFont::parse(&data).unwrap();
+        let (mut cells, mut pool) = (vec![0.0; 1000], vec![0; 30]);
+        let mut cache = GlyphCache::new(&mut cells, &mut pool);
+        assert!(cache.glyph(&font, 1, 10.0).is_some());
+        // 14 + 24 > 30
+        let mask = cache.glyph(&font, 1, 11.0).unwrap();
+        assert_eq!((mask.width, mask.height), (3, 8));
+        assert_eq!((cache.count, cache.used), (1, 24));
+        // Bigger than the whole pool, or than the rasterizer's cells
+        assert!(cache.glyph(&font, 1, 30.0).is_none());
+        let (mut cells, mut pool) = (vec![0.0; 10], vec![0; 1000]);
+        assert!(GlyphCache::new(&mut cells, &mut pool).glyph(&font, 1, 10.0).is_none());
+    }
+
+    #[test]
+    fn memory_set_later() {
+        let data = truetype_font(false, false);
+        let font = TrueTypeFont::parse(&data).unwrap();
+        let mut cache = GlyphCache::new(&mut [], &mut []);
+        assert!(!cache.has_memory());
+        assert!(cache.glyph(&font, 1, 10.0).is_none());
+        let (mut cells, mut pool) = (vec![0.0; 1000], vec![0; 1000]);
+        cache.set_memory(&mut cells, &mut pool);
+        assert!(cache.has_memory());
+        assert_eq!(cache.glyph(&font, 1, 10.0).unwrap().coverage.len(), 14);
+        assert_eq!((cache.count, cache.used), (1, 14));
+    }
+
+    #[test]
+    fn many_glyphs() {
+        let data = truetype_font(false, false);
+        let font = TrueTypeFont::parse(&data).unwrap();
+        let (mut cells, mut pool) = (vec![0.0; 1000], vec![0; 100_000]);
+        let mut cache = GlyphCache::new(&mut cells, &mut pool);
+        // More than the table holds: it starts over, every one is still found or rendered
+        for size in 0..SLOTS {
+            let mask = cache.glyph(&font, 1, 5.0 + size as f32 / 64.0).unwrap();
+            assert!(mask.width >= 1 && mask.height >= 3);
+        }
+        assert!(cache.count <= MAX_ENTRIES);
+    }
+}
@@ -0,0 +1,345 @@
+//! Bitmap fonts for the console and `Canvas::draw_text`: the built-in 8x16 one for
+//! ASCII, or a PC Screen Font (.psf) loaded from a file, with its Unicode table.
+//!
+//! And scalable text: a TrueType font drawn anti-aliased at any size, its glyphs
+//! rendered once in the glyph cache.
+
+use crate::color::Color;
+use crate::console::console;
+use crate::font::cache::GlyphCache;
+use crate::font::psf::{PsfError, PsfFont};
+use crate::font::truetype::{TrueTypeError, TrueTypeFont};
+use crate::fs::vfs::resolve;
+use crate::fs::{FileType, FsError};
+use crate::graphics::canvas::Canvas;
+use crate::graphics::plot;
+use crate::println;
+use crate::{FONT_ADDRESS, FONT_MAX_SIZE, GLYPH_CACHE_ADDRESS, GLYPH_CACHE_SIZE, TRUETYPE_ADDRESS, TRUETYPE_MAX_SIZE};
+
+mod builtin;
+pub mod cache;
+pub mod psf;
+pub mod raster;
+pub mod truetype;
+
+/// Font loaded at boot when the file exists
+pub const DEFAULT_FONT_PATH: &str = "/etc/console.psf";
+/// Scalable font loaded at boot when the file exists
+pub const DEFAULT_TRUETYPE_PATH: &str = "/etc/sans.ttf";
+/// Points are 1/72 inch, the screen is taken as the usual 96 pixels per inch
+const SCREEN_DPI: f32 = 96.0;
+/// Work area of the rasterizer at the start of the glyph cache memory: glyphs up to 512x512
+const RASTER_CELLS: usize = 512 * 512 + 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontError {
+    Fs(FsError),
+    Psf(PsfError),
+    TrueType(TrueTypeError),
+    /// Bigger than the memory for it
+    TooBig,
+}
+
+impl From<FsError> for FontError {
+    fn from(error: FsError) -> Self {
+        FontError::Fs(error)
+    }
+}
+
+impl From<PsfError> for FontError {
+    fn from(error: PsfError) -> Self {
+        FontError::Psf(error)
+    }
+}
+
+impl From<TrueTypeError> for FontError {
+    fn from(error: TrueTypeError) -> Self {
+        FontError::TrueType(error)
+    }
+}
+
+static BUILTIN: PsfFont<'static> =
+    PsfFont::from_glyphs(builtin::FONT.as_flattened(), builtin::FONT_WIDTH, builtin::FONT_HEIGHT, builtin::FIRST_CHAR as char);
+
+/// Parsed from the file copied in one of the two FONT_MAX_SIZE slots at FONT_ADDRESS
+static mut LOADED: Option<PsfFont<'static>> = None;
+/// The slot LOADED uses
+static mut LOADED_SLOT: usize = 0;
+/// Parsed from the file copied in one of the two TRUETYPE_MAX_SIZE slots at TRUETYPE_ADDRESS
+static mut TRUETYPE: Option<TrueTypeFont<'static>> = None;
+static mut TRUETYPE_SLOT: usize = 0;
+/// Over GLYPH_CACHE_ADDRESS from its first use. Only its table is in the static.
+static mut GLYPH_CACHE: GlyphCache<'static> = GlyphCache::new(&mut [], &mut []);
+
+/// The font text is drawn with: the loaded one, or the built-in one
+pub fn font() -> &'static PsfFont<'static> {
+    unsafe { (*core::ptr::addr_of!(LOADED)).as_ref() }.unwrap_or(&BUILTIN)
+}
+
+/// Load the .psf file at `path` and use it from now on. On an error the font doesn't change.
+pub fn load_font(path: &str) -> Result<(), FontError> {
+    // In the slot the current font doesn't use
+    let slot = unsafe { 1 - LOADED_SLOT };
+    let data = read_font_file(path, FONT_ADDRESS + slot * FONT_MAX_SIZE, FONT_MAX_SIZE)?;
+    let font = PsfFont::parse(data)?;
+    unsafe {
+        LOADED = Some(font);
+        LOADED_SLOT = slot;
+    }
+    Ok(())
+}
+
+/// The scalable font, when one has been loaded
+pub fn truetype() -> Option<&'static TrueTypeFont<'static>> {
+    unsafe { (*core::ptr::addr_of!(TRUETYPE)).as_ref() }
+}
+
+/// Load the .ttf file at `path` as the scalable font. On an error the font doesn't change.
+pub fn load_truetype(path: &str) -> Result<(), FontError> {
+    let slot = unsafe { 1 - TRUETYPE_SLOT };
+    let data = read_font_file(path, TRUETYPE_ADDRESS + slot * TRUETYPE_MAX_SIZE, TRUETYPE_MAX_SIZE)?;
+    let font = TrueTypeFont::parse(data)?;
+    glyph_cache().clear();
+    unsafe {
+        TRUETYPE = Some(font);
+        TRUETYPE_SLOT = slot;
+    }
+    Ok(())
+}
+
+/// The glyphs of the scalable font rendered so far
+pub fn glyph_cache() -> &'static mut GlyphCache<'static> {
+    // Set up in place: a GlyphCache is too big to build on the stack
+    let cache = unsafe { &mut *core::ptr::addr_of_mut!(GLYPH_CACHE) };
+    if !cache.has_memory() {
+        let cells = unsafe { core::slice::from_raw_parts_mut(GLYPH_CACHE_ADDRESS as *mut f32, RASTER_CELLS) };
+        let pool_address = GLYPH_CACHE_ADDRESS + RASTER_CELLS * size_of::<f32>();
+        let pool_size = GLYPH_CACHE_ADDRESS + GLYPH_CACHE_SIZE - pool_address;
+        let pool = unsafe { core::slice::from_raw_parts_mut(pool_address as *mut u8, pool_size) };
+        cache.set_memory(cells, pool);
+    }
+    cache
+}
+
+/// Copy the regular file at `path` to `address`, `max_size` bytes at most
+fn read_font_file(path: &str, address: usize, max_size: usize) -> Result<&'static [u8], FontError> {
+    let file = resolve(path)?;
+    let stat = file.fs.stat(file.inode)?;
+    if stat.file_type != FileType::Regular {
+        return Err(FontError::Fs(FsError::IsADirectory));
+    }
+    let size = stat.size as usize;
+    if size > max_size {
+        return Err(FontError::TooBig);
+    }
+    let data = unsafe { core::slice::from_raw_parts_mut(address as *mut u8, size) };
+    let mut done = 0;
+    while done < size {
+        match file.fs.read(file.inode, done as u64, &mut data[done..])? {
+            0 => break,
+            count => done += count,
+        }
+    }
+    Ok(&data[..done])
+}
+
+/// Switch the console to DEFAULT_FONT_PATH and load DEFAULT_TRUETYPE_PATH, when they exist
+pub fn init_font() {
+    if resolve(DEFAULT_FONT_PATH).is_ok() {
+        match load_font(DEFAULT_FONT_PATH) {
+            Ok(()) => {
+                if let Some(console) = console() {
+                    console.font_changed();
+                }
+            }
+            Err(error) => println!("{}: {:?}", DEFAULT_FONT_PATH, error),
+        }
+    }
+    let loaded = resolve(DEFAULT_TRUETYPE_PATH).is_ok().then(|| load_truetype(DEFAULT_TRUETYPE_PATH));
+    if let Some(Err(error)) = loaded {
+        println!("{}: {:?}", DEFAULT_TRUETYPE_PATH, error);
+    }
+}
+
+/// Draw glyph `index` of `font` with its top left corner at (x, y). Its other pixels
+/// are painted `background`, or left as they are when it is None.
+pub fn draw_glyph<C: Canvas + ?Sized>(
+    canvas: &mut C,
+    font: &PsfFont,
+    x: usize,
+    y: usize,
+    index: usize,
+    foreground: Color,
+    background: Option<Color>,
+) {
+    for dy in 0..font.height() {
+        for dx in 0..font.width() {
+            let color = if font.is_set(index, dx, dy) { Some(foreground) } else { background };
+            if let Some(color) = color {
+                canvas.draw_pixel(x.saturating_add(dx), y.saturating_add(dy), color);
+            }
+        }
+    }
+}
+
+/// Draw `text` on one line from (x, y), one glyph per character. Returns the x after it.
+pub fn draw_text<C: Canvas + ?Sized>(canvas: &mut C, font: &PsfFont, x: usize, y: usize, text: &str, color: Color) -> usize {
+    let mut x = x;
+    for char in text.chars() {
+        if x >= canvas.clip().right() {
+            break;
+        }
+        draw_glyph(canvas, font, x, y, font.glyph_for(char), color, None);
+        x = x.saturating_add(font.width());
+    }
+    x
+}
+
+/// Pixels per em of text `points` high
+pub fn points_to_pixels(points: f32) -> f32 {
+    points * SCREEN_DPI / 72.0
+}
+
+/// Call `f(glyph, pen x)` for the glyphs of `text`, kerned. Returns the x after the last one.
+fn layout(font: &TrueTypeFont, text: &str, pixels_per_em: f32, x: f32, mut f: impl FnMut(u16, f32)) -> f32 {
+    let scale = pixels_per_em / font.units_per_em() as f32;
+    let mut pen = x;
+    let mut previous = None;
+    for char in text.chars() {
+        let glyph = font.glyph_index(char);
+        if let Some(previous) = previous {
+            pen += font.kerning(previous, glyph) as f32 * scale;
+        }
+        f(glyph, pen);
+        pen += font.advance_width(glyph) as f32 * scale;
+        previous = Some(glyph);
+    }
+    pen
+}
+
+/// Draw `text` on one line with `font`, `points` high, its baseline at y from x.
+/// The edges are blended with what's below. Returns the x after it.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_scaled_text<C: Canvas + ?Sized>(
+    canvas: &mut C,
+    font: &TrueTypeFont,
+    cache: &mut GlyphCache,
+    x: i32,
+    baseline: i32,
+    text: &str,
+    points: f32,
+    color: Color,
+) -> i32 {
+    let pixels_per_em = points_to_pixels(points);
+    let end = layout(font, text, pixels_per_em, x as f32, |glyph, pen| {
+        let Some(mask) = cache.glyph(font, glyph, pixels_per_em) else {
+            return;
+        };
+        // Whole pixels, so that the cached glyphs fit
+        let (left, top) = ((pen + 0.5) as i32 + mask.left, baseline - mask.top);
+        for row in 0..mask.height {
+            for column in 0..mask.width {
+                let coverage = mask.coverage[row * mask.width + column];
+                if coverage != 0 {
+                    let alpha = (color.alpha as u32 * coverage as u32 / 255) as u8;
+                    plot(canvas, left + column as i32, top + row as i32, color.with_alpha(alpha));
+                }
+            }
+        }
+    });
+    (end + 0.5) as i32
+}
+
+/// How wide `text` is with `font` at `points`, in pixels
+pub fn scaled_text_width(font: &TrueTypeFont, text: &str, points: f32) -> i32 {
+    (layout(font, text, points_to_pixels(points), 0.0, |_, _| {}) + 0.5) as i32
+}
+
+/// Height of a line of text with `font` at `points`, in pixels
+pub fn scaled_line_height(font: &TrueTypeFont, points: f32) -> i32 {
+    let height = font.ascender() as i32 - font.descender() as i32 + font.line_gap() as i32;
+    (height as f32 * points_to_pixels(points) / font.units_per_em() as f32 + 0.5) as i32
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+    use crate::font::truetype::TrueTypeFont;
+    use crate::graphics::bitmap::Bitmap;
+    use crate::testing::{assert_golden, truetype_font, SoftwareFramebuffer};
+
+    #[test]
+    fn builtin_glyphs() {
+        let font = font();
+        let mut framebuffer = SoftwareFramebuffer::new(6 * font.width(), font.height(), 16);
+        let canvas = framebuffer.canvas();
+        for (i, char) in "Hi, 0\x7F".chars().enumerate() {
+            draw_glyph(canvas, font, i * font.width(), 0, font.glyph_for(char), Color::WHITE, Some(Color::BLUE));
+        }
+        canvas.flush();
+        assert_golden("console_glyphs", &framebuffer.screen_text(&[(Color::BLUE, '.'), (Color::WHITE, '#')]));
+    }
+
+    #[test]
+    fn builtin_is_ascii() {
+        let font = font();
+        assert_eq!((font.width(), font.height()), (8, 16));
+        assert!(!font.has_unicode_table());
+        assert_eq!(font.glyph_index(' '), Some(0));
+        assert_eq!(font.glyph_index('A'), Some('A' as usize - ' ' as usize));
+        assert_eq!(font.glyph_index('\n'), None);
+        assert_eq!(font.glyph_index('é'), None);
+        assert_eq!(font.glyph_for('é'), font.glyph_for('?'));
+    }
+
+    #[test]
+    fn scaled_text() {
+        let data = truetype_font(false, false);
+        let font = TrueTypeFont::parse(&data).unwrap();
+        let (mut cells, mut pool) = (vec![0.0; 10_000], vec![0; 10_000]);
+        let mut cache = GlyphCache::new(&mut cells, &mut pool);
+        let mut pixels = vec![Color::BLACK; 104 * 24];
+        let mut bitmap = Bitmap::new(&mut pixels, 104, 24).unwrap();
+        // 24 pixels per em. 'I' 'O' are kerned, 'J' is the missing character box.
+        let end = draw_scaled_text(&mut bitmap, &font, &mut cache, 1, 19, "HIO éJ", 18.0, Color::WHITE);
+        assert_eq!(end, scaled_text_width(&font, "HIO éJ", 18.0) + 1);
+        // Coverage from ' ' to '@'
+        const SHADES: &[u8] = b" .:-=+*#%@";
+        let mut text = String::new();
+        for row in pixels.chunks(104) {
+            text.extend(row.iter().map(|pixel| SHADES[pixel.red as usize * (SHADES.len() - 1) / 255] as char));
+            text.push('\n');
+        }
+        assert_golden("scaled_text", &text);
+    }
+
+    #[test]
+    fn scaled_sizes() {
+        let data = truetype_font(false, false);
+        let font = TrueTypeFont::parse(&data).unwrap();
+        // 75 points are 100 pixels per em, 0.1 pixel per unit
+        assert_eq!(points_to_pixels(75.0), 100.0);
+        assert_eq!(scaled_text_width(&font, "I", 75.0), 40);
+        assert_eq!(scaled_text_width(&font, "IO", 75.0), 40 + 70 - 10);
+        assert_eq!(scaled_text_width(&font, "OI", 75.0), 70 + 40 - 5);
+        assert_eq!(scaled_text_width(&font, "", 75.0), 0);
+        assert_eq!(scaled_line_height(&font, 75.0), 100);
+    }
+
+    #[test]
+    fn translucent_scaled_text() {
+        let data = truetype_font(false, false);
+        let font = TrueTypeFont::parse(&data).unwrap();
+        let (mut cells, mut pool) = (vec![0.0; 1000], vec![0; 1000]);
+        let mut cache = GlyphCache::new(&mut cells, &mut pool);
+        let mut pixels = vec![Color::BLACK; 8 * 8];
+        let mut bitmap = Bitmap::new(&mut pixels, 8, 8).unwrap();
+        // 'I' at 10 pixels per em covers (1, 0) to (3, 7) fully, half as opaque as the color
+        draw_scaled_text(&mut bitmap, &font, &mut cache, 0, 7, "I", 7.5, Color::WHITE.with_alpha(0x80));
+        assert_eq!(bitmap.pixel(1, 0), Some(Color::BLACK.lerp(Color::WHITE, 0x80)));
+        assert_eq!(bitmap.pixel(0, 0), Some(Color::BLACK));
+        assert_eq!(bitmap.pixel(1, 7), Some(Color::BLACK));
+        // Off the bitmap on the left, clipped
+        assert_eq!(draw_scaled_text(&mut bitmap, &font, &mut cache, -20, 7, "HH", 7.5, Color::WHITE), 0);
+    }
+}
@@ -0,0 +1,344 @@
+use crate::fs::{read_u16, read_u32};
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_HEADER_SIZE: usize = 4;
+/// 512 glyphs instead of 256
+const PSF1_MODE_512: u8 = 0x01;
+/// The glyphs are followed by a Unicode table (HASTAB, or HASSEQ which implies it)
+const PSF1_MODE_TABLE: u8 = 0x06;
+const PSF1_SEPARATOR: u16 = 0xFFFF;
+const PSF1_START_SEQUENCE: u16 = 0xFFFE;
+
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xB5, 0x4A, 0x86];
+const PSF2_HEADER_SIZE: usize = 32;
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+const PSF2_SEPARATOR: u8 = 0xFF;
+const PSF2_START_SEQUENCE: u8 = 0xFE;
+
+/// Characters whose glyph is looked up once when the font is parsed, the others
+/// search the Unicode table every time
+const CACHED_CHARS: usize = 256;
+const NO_GLYPH: u16 = u16::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PsfError {
+    /// No PSF1 or PSF2 magic number
+    NotPsf,
+    /// Sizes in the header that don't match the file
+    Malformed,
+}
+
+/// Which glyph a character has
+#[derive(Clone, Copy)]
+enum Mapping<'a> {
+    /// Glyph i is the character `first + i`
+    Range(u32),
+    /// PSF1 table: u16 code points, sequences after 0xFFFE, 0xFFFF after each glyph
+    Psf1(&'a [u8]),
+    /// PSF2 table: UTF-8, sequences after 0xFE, 0xFF after each glyph
+    Psf2(&'a [u8]),
+}
+
+/// A PC Screen Font, version 1 (8 pixels wide) or 2 (any size), as the Linux console uses.
+/// Glyphs are rows of whole bytes, the most significant bit is the leftmost pixel.
+/// Sequences of the Unicode table (a letter and combining accents) are ignored.
+pub struct PsfFont<'a> {
+    glyphs: &'a [u8],
+    glyph_count: usize,
+    bytes_per_glyph: usize,
+    width: usize,
+    height: usize,
+    mapping: Mapping<'a>,
+    /// Glyph of the first CACHED_CHARS characters, NO_GLYPH when there is none
+    cached: [u16; CACHED_CHARS],
+    /// Glyph for the characters the font doesn't have
+    replacement: usize,
+}
+
+impl<'a> PsfFont<'a> {
+    /// A font of `data`, the whole content of a .psf file (not compressed)
+    pub fn parse(data: &'a [u8]) -> Result<Self, PsfError> {
+        let (glyphs_offset, glyph_count, bytes_per_glyph, width, height, has_table) = if data.starts_with(&PSF2_MAGIC) {
+            if data.len() < PSF2_HEADER_SIZE {
+                return Err(PsfError::Malformed);
+            }
+            let width = read_u32(data, 28) as usize;
+            let height = read_u32(data, 24) as usize;
+            let bytes_per_glyph = read_u32(data, 20) as usize;
+            if bytes_per_glyph < width.div_ceil(8) * height {
+                return Err(PsfError::Malformed);
+            }
+            let flags = read_u32(data, 12);
+            (read_u32(data, 8) as usize, read_u32(data, 16) as usize, bytes_per_glyph, width, height, flags & PSF2_HAS_UNICODE_TABLE != 0)
+        } else if data.starts_with(&PSF1_MAGIC) {
+            if data.len() < PSF1_HEADER_SIZE {
+                return Err(PsfError::Malformed);
+            }
+            let (mode, height) = (data[2], data[3] as usize);
+            let glyph_count = if mode & PSF1_MODE_512 != 0 { 512 } else { 256 };
+            (PSF1_HEADER_SIZE, glyph_count, height, 8, height, mode & PSF1_MODE_TABLE != 0)
+        } else {
+            return Err(PsfError::NotPsf);
+        };
+
+        if width == 0 || height == 0 || glyph_count == 0 || glyph_count > NO_GLYPH as usize {
+            return Err(PsfError::Malformed);
+        }
+        let glyphs_end = glyph_count.checked_mul(bytes_per_glyph).and_then(|size| size.checked_add(glyphs_offset));
+        let Some(glyphs) = glyphs_end.and_then(|end| data.get(glyphs_offset..end)) else {
+            return Err(PsfError::Malformed);
+        };
+        let table = &data[glyphs_offset + glyphs.len()..];
+        let mapping = match (has_table, data[0] == PSF2_MAGIC[0]) {
+            // Usually code page 437, which is ASCII for the first 128
+            (false, _) => Mapping::Range(0),
+            (true, true) => Mapping::Psf2(table),
+            (true, false) => Mapping::Psf1(table),
+        };
+
+        let mut cached = [NO_GLYPH; CACHED_CHARS];
+        for_each_char(mapping, glyph_count, |code, glyph| {
+            if let Some(slot) = cached.get_mut(code as usize)
+                && *slot == NO_GLYPH
+            {
+                *slot = glyph as u16;
+            }
+            false
+        });
+        let mut font = PsfFont { glyphs, glyph_count, bytes_per_glyph, width, height, mapping, cached, replacement: 0 };
+        font.replacement = font.glyph_index(char::REPLACEMENT_CHARACTER).or(font.glyph_index('?')).unwrap_or(0);
+        Ok(font)
+    }
+
+    /// A font without a file: glyphs of `width` x `height` pixels for the characters from
+    /// `first` on, one after the other in `glyphs`
+    pub const fn from_glyphs(glyphs: &'a [u8], width: usize, height: usize, first: char) -> Self {
+        let bytes_per_glyph = width.div_ceil(8) * height;
+        let glyph_count = glyphs.len() / bytes_per_glyph;
+        let question_mark = ('?' as usize).wrapping_sub(first as usize);
+        PsfFont {
+            glyphs,
+            glyph_count,
+            bytes_per_glyph,
+            width,
+            height,
+            mapping: Mapping::Range(first as u32),
+            cached: [NO_GLYPH; CACHED_CHARS],
+            replacement: if question_mark < glyph_count { question_mark } else { 0 },
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn glyph_count(&self) -> usize {
+        self.glyph_count
+    }
+
+    pub fn has_unicode_table(&self) -> bool {
+        !matches!(self.mapping, Mapping::Range(_))
+    }
+
+    /// The glyph of `char`, None when the font doesn't have one
+    pub fn glyph_index(&self, char: char) -> Option<usize> {
+        let code = char as u32;
+        match self.mapping {
+            Mapping::Range(first) => code.checked_sub(first).map(|index| index as usize).filter(|&index| index < self.glyph_count),
+            _ if (code as usize) < CACHED_CHARS => match self.cached[code as usize] {
+                NO_GLYPH => None,
+                glyph => Some(glyph as usize),
+            },
+            mapping => {
+                let mut found = None;
+                for_each_char(mapping, self.glyph_count, |other, glyph| {
+                    if other == code {
+                        found = Some(glyph);
+                    }
+                    found.is_some()
+                });
+                found
+            }
+        }
+    }
+
+    /// The glyph of `char`, or the one of U+FFFD or '?' when the font doesn't have it
+    pub fn glyph_for(&self, char: char) -> usize {
+        self.glyph_index(char).unwrap_or(self.replacement)
+    }
+
+    /// Whether the pixel (x, y) of glyph `index` is set
+    pub fn is_set(&self, index: usize, x: usize, y: usize) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+        let row = y * self.width.div_ceil(8);
+        self.glyphs
+            .get(index * self.bytes_per_glyph + row + x / 8)
+            .is_some_and(|bits| bits & (0x80 >> (x % 8)) != 0)
+    }
+}
+
+/// Call `f(code point, glyph)` for every single character of the Unicode table (not the
+/// sequences), until it returns true
+fn for_each_char(mapping: Mapping, glyph_count: usize, mut f: impl FnMut(u32, usize) -> bool) {
+    match mapping {
+        Mapping::Range(_) => {}
+        Mapping::Psf1(table) => {
+            let (mut glyph, mut in_sequence) = (0, false);
+            for offset in (0..table.len() & !1).step_by(2) {
+                match read_u16(table, offset) {
+                    PSF1_SEPARATOR => {
+                        glyph += 1;
+                        in_sequence = false;
+                        if glyph == glyph_count {
+                            return;
+                        }
+                    }
+                    PSF1_START_SEQUENCE => in_sequence = true,
+                    code if !in_sequence && f(code as u32, glyph) => return,
+                    _ => {}
+                }
+            }
+        }
+        Mapping::Psf2(table) => {
+            for (glyph, entry) in table.split(|&byte| byte == PSF2_SEPARATOR).take(glyph_count).enumerate() {
+                let singles = entry.split(|&byte| byte == PSF2_START_SEQUENCE).next().unwrap_or(&[]);
+                // A broken entry only loses its glyph
+                let Ok(text) = core::str::from_utf8(singles) else {
+                    continue;
+                };
+                for char in text.chars() {
+                    if f(char as u32, glyph) {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+
+    /// 256 glyphs of 8x2 pixels, glyph i is the rows `i` and `!i`
+    fn psf1(table: Option<&[u16]>) -> Vec<u8> {
+        let mode = if table.is_some() { 0x02 } else { 0x00 };
+        let mut data = vec![0x36, 0x04, mode, 2];
+        for glyph in 0..=255u8 {
+            data.extend([glyph, !glyph]);
+        }
+        for code in table.unwrap_or(&[]) {
+            data.extend(code.to_le_bytes());
+        }
+        data
+    }
+
+    /// Glyphs of 10x3 pixels with only their last column set, and `table` when it isn't empty
+    fn psf2(glyph_count: u32, table: &[u8]) -> Vec<u8> {
+        let flags = if table.is_empty() { 0 } else { PSF2_HAS_UNICODE_TABLE };
+        let mut data = PSF2_MAGIC.to_vec();
+        for field in [0, 32, flags, glyph_count, 6, 3, 10] {
+            data.extend(field.to_le_bytes());
+        }
+        for _ in 0..glyph_count * 3 {
+            data.extend([0x00, 0x40]);
+        }
+        data.extend(table);
+        data
+    }
+
+    #[test]
+    fn psf1_without_table() {
+        let data = psf1(None);
+        let font = PsfFont::parse(&data).unwrap();
+        assert_eq!((font.width(), font.height(), font.glyph_count()), (8, 2, 256));
+        assert!(!font.has_unicode_table());
+        assert_eq!(font.glyph_index('A'), Some(0x41));
+        assert_eq!(font.glyph_index('€'), None);
+        assert_eq!(font.glyph_for('€'), 0x3F);
+        // Glyph 0x81 is 10000001 over 01111110
+        assert!(font.is_set(0x81, 0, 0) && font.is_set(0x81, 7, 0) && !font.is_set(0x81, 1, 0));
+        assert!(!font.is_set(0x81, 0, 1) && font.is_set(0x81, 1, 1));
+        assert!(!font.is_set(0x81, 8, 0) && !font.is_set(0x81, 0, 2) && !font.is_set(256, 0, 0));
+    }
+
+    #[test]
+    fn psf1_unicode_table() {
+        let mut table = Vec::new();
+        for glyph in 0..256u16 {
+            match glyph {
+                // 'é' and '€' on the same glyph, and 'e' + U+0301 as a sequence
+                0x82 => table.extend([0xE9, 0x20AC, PSF1_START_SEQUENCE, 0x65, 0x0301]),
+                // No character for this one
+                0x41 => {}
+                _ => table.push(glyph),
+            }
+            table.push(PSF1_SEPARATOR);
+        }
+        let data = psf1(Some(&table));
+        let font = PsfFont::parse(&data).unwrap();
+        assert!(font.has_unicode_table());
+        assert_eq!(font.glyph_index('é'), Some(0x82));
+        assert_eq!(font.glyph_index('€'), Some(0x82));
+        assert_eq!(font.glyph_index('e'), Some(0x65));
+        assert_eq!(font.glyph_index('A'), None);
+        assert_eq!(font.glyph_index('\u{0301}'), None);
+        assert_eq!(font.glyph_for('A'), 0x3F);
+    }
+
+    #[test]
+    fn psf2_unicode_table() {
+        let table = "?\u{FF}A\u{FF}é€\u{FE}e\u{301}\u{FF}\u{FFFD}\u{FF}";
+        // The separators are bytes, not characters
+        let table: Vec<u8> = table.chars().flat_map(|char| match char {
+            '\u{FF}' => vec![PSF2_SEPARATOR],
+            '\u{FE}' => vec![PSF2_START_SEQUENCE],
+            char => char.to_string().into_bytes(),
+        }).collect();
+        let data = psf2(4, &table);
+        let font = PsfFont::parse(&data).unwrap();
+        assert_eq!((font.width(), font.height(), font.glyph_count()), (10, 3, 4));
+        assert_eq!(font.glyph_index('?'), Some(0));
+        assert_eq!(font.glyph_index('A'), Some(1));
+        assert_eq!(font.glyph_index('é'), Some(2));
+        assert_eq!(font.glyph_index('€'), Some(2));
+        assert_eq!(font.glyph_index('e'), None);
+        // U+FFFD before '?'
+        assert_eq!(font.glyph_for('Z'), 3);
+        assert!(font.is_set(3, 9, 2) && !font.is_set(3, 8, 2) && !font.is_set(3, 10, 2));
+    }
+
+    #[test]
+    fn psf2_without_table() {
+        let data = psf2(2, &[]);
+        let font = PsfFont::parse(&data).unwrap();
+        assert!(!font.has_unicode_table());
+        assert_eq!(font.glyph_index('\u{1}'), Some(1));
+        assert_eq!(font.glyph_index('\u{2}'), None);
+        // Neither U+FFFD nor '?'
+        assert_eq!(font.glyph_for('\u{2}'), 0);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(PsfFont::parse(&[]).err(), Some(PsfError::NotPsf));
+        assert_eq!(PsfFont::parse(b"not a font").err(), Some(PsfError::NotPsf));
+        assert_eq!(PsfFont::parse(&PSF1_MAGIC).err(), Some(PsfError::Malformed));
+        let data = psf1(None);
+        assert_eq!(PsfFont::parse(&data[..data.len() - 1]).err(), Some(PsfError::Malformed));
+        let mut data = psf2(2, &[]);
+        assert_eq!(PsfFont::parse(&data[..PSF2_HEADER_SIZE]).err(), Some(PsfError::Malformed));
+        // Glyphs smaller than their rows
+        data[20] = 5;
+        assert_eq!(PsfFont::parse(&data).err(), Some(PsfError::Malformed));
+        // No glyph at all
+        let data = psf2(0, &[]);
+        assert_eq!(PsfFont::parse(&data).err(), Some(PsfError::Malformed));
+    }
+}
@@ -0,0 +1,277 @@
+use core::ops::{Add, Mul, Sub};
+use crate::font::truetype::OutlineSink;
+
+/// Curves are cut in at most this many lines
+const MAX_QUAD_SEGMENTS: u32 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vector {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Vector {
+    pub const fn new(x: f32, y: f32) -> Self {
+        Vector { x, y }
+    }
+
+    pub fn lerp(self, other: Vector, t: f32) -> Vector {
+        self + (other - self) * t
+    }
+
+    pub fn midpoint(self, other: Vector) -> Vector {
+        self.lerp(other, 0.5)
+    }
+}
+
+impl Add for Vector {
+    type Output = Vector;
+
+    fn add(self, other: Vector) -> Vector {
+        Vector::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Vector {
+    type Output = Vector;
+
+    fn sub(self, other: Vector) -> Vector {
+        Vector::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<f32> for Vector {
+    type Output = Vector;
+
+    fn mul(self, factor: f32) -> Vector {
+        Vector::new(self.x * factor, self.y * factor)
+    }
+}
+
+// core has no f32::floor without the standard library
+pub fn floor(value: f32) -> f32 {
+    let truncated = value as i32 as f32;
+    if truncated > value { truncated - 1.0 } else { truncated }
+}
+
+pub fn ceil(value: f32) -> f32 {
+    -floor(-value)
+}
+
+/// Anti-aliased coverage of shapes made of lines and quadratic curves (non-zero winding,
+/// overlaps saturate), like font-rs: each line adds the signed area it covers to the
+/// cells it crosses, and a running sum along the rows turns that into coverage.
+pub struct Rasterizer<'a> {
+    width: usize,
+    height: usize,
+    /// width * height, and 2 more for lines on the right edge of the last row
+    cells: &'a mut [f32],
+    /// Outline coordinates are y up, the pixel of (x, y) is origin + (x, -y) * scale
+    scale: f32,
+    origin: Vector,
+    start: Vector,
+    current: Vector,
+}
+
+impl<'a> Rasterizer<'a> {
+    /// A `width` x `height` pixels picture, None when `cells` is too small for it
+    pub fn new(cells: &'a mut [f32], width: usize, height: usize, scale: f32, origin: Vector) -> Option<Self> {
+        let cells = cells.get_mut(..width.checked_mul(height)?.checked_add(2)?)?;
+        cells.fill(0.0);
+        let zero = Vector::new(0.0, 0.0);
+        Some(Rasterizer { width, height, cells, scale, origin, start: zero, current: zero })
+    }
+
+    fn to_pixels(&self, point: Vector) -> Vector {
+        Vector::new(self.origin.x + point.x * self.scale, self.origin.y - point.y * self.scale)
+    }
+
+    /// A line in pixels. What sticks out is pushed onto the edges.
+    pub fn line(&mut self, from: Vector, to: Vector) {
+        let (width, height) = (self.width as f32, self.height as f32);
+        let clamp = |point: Vector| Vector::new(point.x.clamp(0.0, width), point.y.clamp(0.0, height));
+        let (from, to) = (clamp(from), clamp(to));
+        if from.y == to.y {
+            return;
+        }
+        // Always downwards, `direction` keeps the winding
+        let (direction, from, to) = if from.y < to.y { (1.0, from, to) } else { (-1.0, to, from) };
+        let dxdy = (to.x - from.x) / (to.y - from.y);
+        let mut x = from.x;
+        let last_row = (ceil(to.y) as usize).min(self.height);
+        for y in from.y as usize..last_row {
+            let row = y * self.width;
+            let dy = to.y.min(y as f32 + 1.0) - from.y.max(y as f32);
+            // Rounding errors must not step out of the picture
+            let x_next = (x + dxdy * dy).clamp(0.0, width);
+            let area = dy * direction;
+            let (x0, x1) = if x < x_next { (x, x_next) } else { (x_next, x) };
+            let x0_floor = floor(x0);
+            let x0_cell = x0_floor as usize;
+            let x1_cell = ceil(x1) as usize;
+            if x1_cell <= x0_cell + 1 {
+                // Within one cell: split between it and the next one by the mean x
+                let inside = 0.5 * (x + x_next) - x0_floor;
+                self.cells[row + x0_cell] += area - area * inside;
+                self.cells[row + x0_cell + 1] += area * inside;
+            } else {
+                let inverse = 1.0 / (x1 - x0);
+                let x0_fraction = x0 - x0_floor;
+                let first = 0.5 * inverse * (1.0 - x0_fraction) * (1.0 - x0_fraction);
+                let x1_fraction = x1 - x1_cell as f32 + 1.0;
+                let last = 0.5 * inverse * x1_fraction * x1_fraction;
+                self.cells[row + x0_cell] += area * first;
+                if x1_cell == x0_cell + 2 {
+                    self.cells[row + x0_cell + 1] += area * (1.0 - first - last);
+                } else {
+                    let second = inverse * (1.5 - x0_fraction);
+                    self.cells[row + x0_cell + 1] += area * (second - first);
+                    for cell in x0_cell + 2..x1_cell - 1 {
+                        self.cells[row + cell] += area * inverse;
+                    }
+                    let before_last = second + (x1_cell - x0_cell - 3) as f32 * inverse;
+                    self.cells[row + x1_cell - 1] += area * (1.0 - before_last - last);
+                }
+                self.cells[row + x1_cell] += area * last;
+            }
+            x = x_next;
+        }
+    }
+
+    /// A quadratic curve in pixels, as lines less than about a tenth of a pixel off
+    pub fn quad(&mut self, from: Vector, control: Vector, to: Vector) {
+        let deviation = from - control * 2.0 + to;
+        let deviation = deviation.x * deviation.x + deviation.y * deviation.y;
+        if deviation < 0.333 {
+            self.line(from, to);
+            return;
+        }
+        let mut segments = 1;
+        while ((segments * segments * segments * segments) as f32) < 3.0 * deviation && segments < MAX_QUAD_SEGMENTS {
+            segments += 1;
+        }
+        let mut previous = from;
+        for i in 1..=segments {
+            let t = i as f32 / segments as f32;
+            let next = from.lerp(control, t).lerp(control.lerp(to, t), t);
+            self.line(previous, next);
+            previous = next;
+        }
+    }
+
+    /// Coverage of every pixel, row after row, 0 (outside) to 255 (inside)
+    pub fn coverage(&self, mask: &mut [u8]) {
+        let mut sum = 0.0;
+        for (cell, coverage) in self.cells.iter().zip(mask.iter_mut()).take(self.width * self.height) {
+            sum += cell;
+            *coverage = (sum.abs().min(1.0) * 255.0 + 0.5) as u8;
+        }
+    }
+}
+
+impl OutlineSink for Rasterizer<'_> {
+    fn move_to(&mut self, to: Vector) {
+        self.close();
+        self.start = self.to_pixels(to);
+        self.current = self.start;
+    }
+
+    fn line_to(&mut self, to: Vector) {
+        let to = self.to_pixels(to);
+        self.line(self.current, to);
+        self.current = to;
+    }
+
+    fn quad_to(&mut self, control: Vector, to: Vector) {
+        let (control, to) = (self.to_pixels(control), self.to_pixels(to));
+        self.quad(self.current, control, to);
+        self.current = to;
+    }
+
+    fn close(&mut self) {
+        if self.current != self.start {
+            self.line(self.current, self.start);
+        }
+        self.current = self.start;
+    }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+
+    fn rasterize(width: usize, height: usize, draw: impl FnOnce(&mut Rasterizer)) -> Vec<u8> {
+        let mut cells = vec![0.0; width * height + 2];
+        let mut rasterizer = Rasterizer::new(&mut cells, width, height, 1.0, Vector::new(0.0, 0.0)).unwrap();
+        draw(&mut rasterizer);
+        let mut mask = vec![0; width * height];
+        rasterizer.coverage(&mut mask);
+        mask
+    }
+
+    fn rectangle(rasterizer: &mut Rasterizer, left: f32, top: f32, right: f32, bottom: f32) {
+        let corners = [(left, top), (right, top), (right, bottom), (left, bottom), (left, top)];
+        for pair in corners.windows(2) {
+            rasterizer.line(Vector::new(pair[0].0, pair[0].1), Vector::new(pair[1].0, pair[1].1));
+        }
+    }
+
+    #[test]
+    fn partial_pixels() {
+        let mask = rasterize(4, 2, |rasterizer| rectangle(rasterizer, 0.5, 0.0, 2.5, 1.5));
+        assert_eq!(mask, [128, 255, 128, 0, 64, 128, 64, 0]);
+    }
+
+    #[test]
+    fn winding_and_clipping() {
+        // Twice over the same pixels saturates, the part off the picture is dropped
+        let mask = rasterize(3, 1, |rasterizer| {
+            rectangle(rasterizer, 0.0, 0.0, 2.0, 1.0);
+            rectangle(rasterizer, 1.0, -5.0, 9.0, 1.0);
+        });
+        assert_eq!(mask, [255, 255, 255]);
+        // The other way round cancels out
+        let mask = rasterize(2, 1, |rasterizer| {
+            rectangle(rasterizer, 0.0, 0.0, 2.0, 1.0);
+            rectangle(rasterizer, 0.0, 1.0, 1.0, 0.0);
+        });
+        assert_eq!(mask, [0, 255]);
+    }
+
+    #[test]
+    fn diagonal() {
+        // Half of each pixel on the diagonal
+        let mask = rasterize(2, 2, |rasterizer| {
+            rasterizer.line(Vector::new(0.0, 0.0), Vector::new(2.0, 2.0));
+            rasterizer.line(Vector::new(2.0, 2.0), Vector::new(0.0, 2.0));
+            rasterizer.line(Vector::new(0.0, 2.0), Vector::new(0.0, 0.0));
+        });
+        assert_eq!(mask, [128, 0, 255, 128]);
+    }
+
+    #[test]
+    fn too_small() {
+        let mut cells = [0.0; 5];
+        assert!(Rasterizer::new(&mut cells, 2, 2, 1.0, Vector::new(0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn floor_and_ceil() {
+        assert_eq!((floor(1.5), floor(-1.5), floor(-2.0), floor(0.0)), (1.0, -2.0, -2.0, 0.0));
+        assert_eq!((ceil(1.5), ceil(-1.5), ceil(2.0)), (2.0, -1.0, 2.0));
+    }
+
+    #[test]
+    fn vertical_tangent_on_the_edge() {
+        // The left side of an 'O' touching x = 0: rounding made x a little negative
+        // around there, which moved the row's coverage one pixel right
+        let mask = rasterize(2, 12, |rasterizer| {
+            rasterizer.line(Vector::new(0.288, 5.576), Vector::new(0.0, 8.6));
+            rasterizer.line(Vector::new(0.0, 8.6), Vector::new(0.288, 11.624));
+            rasterizer.line(Vector::new(0.288, 11.624), Vector::new(2.0, 11.624));
+            rasterizer.line(Vector::new(2.0, 11.624), Vector::new(2.0, 5.576));
+            rasterizer.line(Vector::new(2.0, 5.576), Vector::new(0.288, 5.576));
+        });
+        assert!(mask[8 * 2] > 240, "{:?}", mask);
+    }
+}
@@ -0,0 +1,761 @@
+use crate::font::raster::Vector;
+
+/// sfnt versions: TrueType outlines, Apple's TrueType, CFF outlines, font collection
+const VERSION_TRUETYPE: u32 = 0x0001_0000;
+const VERSION_TRUE: u32 = u32::from_be_bytes(*b"true");
+const VERSION_CFF: u32 = u32::from_be_bytes(*b"OTTO");
+const COLLECTION: u32 = u32::from_be_bytes(*b"ttcf");
+
+// Flags of the points of simple glyphs
+const ON_CURVE: u8 = 0x01;
+const X_SHORT: u8 = 0x02;
+const Y_SHORT: u8 = 0x04;
+const REPEAT: u8 = 0x08;
+/// With X_SHORT the sign of x, without it x is the same as the previous point's
+const X_SAME_OR_POSITIVE: u8 = 0x10;
+const Y_SAME_OR_POSITIVE: u8 = 0x20;
+
+// Flags of the components of composite glyphs
+const ARGS_ARE_WORDS: u16 = 0x0001;
+const ARGS_ARE_XY_VALUES: u16 = 0x0002;
+const HAVE_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const HAVE_XY_SCALE: u16 = 0x0040;
+const HAVE_TWO_BY_TWO: u16 = 0x0080;
+/// Composite glyphs made of composite glyphs..., deeper is taken for a loop
+const MAX_COMPONENT_DEPTH: usize = 8;
+
+/// Coverage of a `kern` subtable: horizontal, not minimum values, not cross-stream
+const KERN_HORIZONTAL: u16 = 0x0001;
+const KERN_FLAGS: u16 = 0x000F;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrueTypeError {
+    /// Not an sfnt file (.ttf, .otf, .ttc)
+    NotTrueType,
+    /// An OpenType font with PostScript (CFF) outlines instead of `glyf`
+    CffOutlines,
+    MissingTable(&'static str),
+    /// Offsets or sizes out of the file
+    Malformed,
+    /// No cmap subtable for Unicode
+    NoUnicodeCmap,
+}
+
+/// Where the shape of a glyph goes: contours of lines and quadratic curves
+pub trait OutlineSink {
+    /// Start a contour
+    fn move_to(&mut self, to: Vector);
+    fn line_to(&mut self, to: Vector);
+    fn quad_to(&mut self, control: Vector, to: Vector);
+    /// End the contour, back to its start
+    fn close(&mut self);
+}
+
+/// Extent of a glyph's outline, in font units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoundingBox {
+    pub x_min: i16,
+    pub y_min: i16,
+    pub x_max: i16,
+    pub y_max: i16,
+}
+
+/// Which glyph a character has
+#[derive(Clone, Copy)]
+enum Cmap<'a> {
+    /// Segments of the Basic Multilingual Plane
+    Format4(&'a [u8]),
+    /// Groups of any plane
+    Format12(&'a [u8]),
+}
+
+/// A TrueType font (or OpenType with TrueType outlines), read in place from the file content.
+/// Only what's needed to draw text: characters to glyphs, outlines, advances and the
+/// kerning pairs of `kern` (not GPOS, no hinting).
+pub struct TrueTypeFont<'a> {
+    glyf: &'a [u8],
+    loca: &'a [u8],
+    /// u32 offsets in `loca`, or u16 halves
+    long_offsets: bool,
+    hmtx: &'a [u8],
+    /// Advances in `hmtx`, the glyphs after use the last one
+    metric_count: usize,
+    glyph_count: usize,
+    units_per_em: u16,
+    ascender: i16,
+    descender: i16,
+    line_gap: i16,
+    cmap: Cmap<'a>,
+    /// Pairs of the horizontal format 0 subtable of `kern`, sorted by glyphs
+    kerning_pairs: &'a [u8],
+}
+
+impl<'a> TrueTypeFont<'a> {
+    /// The font of `data`, the whole content of a file. The first font of a collection.
+    pub fn parse(data: &'a [u8]) -> Result<Self, TrueTypeError> {
+        let mut directory = 0;
+        let mut version = u32_at(data, 0).ok_or(TrueTypeError::NotTrueType)?;
+        if version == COLLECTION {
+            directory = u32_at(data, 12).ok_or(TrueTypeError::Malformed)? as usize;
+            version = u32_at(data, directory).ok_or(TrueTypeError::Malformed)?;
+        }
+        match version {
+            VERSION_TRUETYPE | VERSION_TRUE => {}
+            VERSION_CFF => return Err(TrueTypeError::CffOutlines),
+            _ => return Err(TrueTypeError::NotTrueType),
+        }
+        let table = |tag| find_table(data, directory, tag);
+
+        let head = table("head")?;
+        let hhea = table("hhea")?;
+        let maxp = table("maxp")?;
+        let malformed = TrueTypeError::Malformed;
+        let units_per_em = u16_at(head, 18).ok_or(malformed)?;
+        let long_offsets = i16_at(head, 50).ok_or(malformed)? == 1;
+        let glyph_count = u16_at(maxp, 4).ok_or(malformed)? as usize;
+        let metric_count = u16_at(hhea, 34).ok_or(malformed)? as usize;
+        let (ascender, descender, line_gap) = (i16_at(hhea, 4), i16_at(hhea, 6), i16_at(hhea, 8));
+        let (Some(ascender), Some(descender), Some(line_gap)) = (ascender, descender, line_gap) else {
+            return Err(malformed);
+        };
+
+        let hmtx = table("hmtx")?;
+        let loca = table("loca")?;
+        let glyf = table("glyf")?;
+        let offset_size = if long_offsets { 4 } else { 2 };
+        if units_per_em == 0 || metric_count == 0 || hmtx.len() < metric_count * 4 || loca.len() < (glyph_count + 1) * offset_size {
+            return Err(malformed);
+        }
+        let cmap = parse_cmap(table("cmap")?)?;
+        // Optional, and only the simplest (and most common) kind
+        let kerning_pairs = table("kern").ok().and_then(kerning_pairs).unwrap_or(&[]);
+
+        Ok(TrueTypeFont {
+            glyf,
+            loca,
+            long_offsets,
+            hmtx,
+            metric_count,
+            glyph_count,
+            units_per_em,
+            ascender,
+            descender,
+            line_gap,
+            cmap,
+            kerning_pairs,
+        })
+    }
+
+    pub fn glyph_count(&self) -> usize {
+        self.glyph_count
+    }
+
+    /// Size of the em square in font units, the size text is drawn at
+    pub fn units_per_em(&self) -> u16 {
+        self.units_per_em
+    }
+
+    /// Top of the tallest letters above the baseline, in font units
+    pub fn ascender(&self) -> i16 {
+        self.ascender
+    }
+
+    /// Bottom of the letters like 'p' below the baseline (negative), in font units
+    pub fn descender(&self) -> i16 {
+        self.descender
+    }
+
+    /// Space between the descender of a line and the ascender of the next one
+    pub fn line_gap(&self) -> i16 {
+        self.line_gap
+    }
+
+    pub fn has_kerning(&self) -> bool {
+        !self.kerning_pairs.is_empty()
+    }
+
+    /// The glyph of `char`, 0 (the missing character box) when the font doesn't have it
+    pub fn glyph_index(&self, char: char) -> u16 {
+        let code = char as u32;
+        let glyph = match self.cmap {
+            Cmap::Format4(subtable) => format4_glyph(subtable, code),
+            Cmap::Format12(subtable) => format12_glyph(subtable, code),
+        };
+        glyph.filter(|&glyph| (glyph as usize) < self.glyph_count).unwrap_or(0)
+    }
+
+    /// How far the next glyph goes after `glyph`, in font units
+    pub fn advance_width(&self, glyph: u16) -> u16 {
+        let metric = (glyph as usize).min(self.metric_count - 1);
+        u16_at(self.hmtx, metric * 4).unwrap_or(0)
+    }
+
+    /// What to add to the advance of `left` when `right` follows it, in font units
+    pub fn kerning(&self, left: u16, right: u16) -> i16 {
+        let key = (left as u32) << 16 | right as u32;
+        let (mut low, mut high) = (0, self.kerning_pairs.len() / 6);
+        while low < high {
+            let middle = (low + high) / 2;
+            let Some(pair) = u32_at(self.kerning_pairs, middle * 6) else {
+                return 0;
+            };
+            match pair.cmp(&key) {
+                core::cmp::Ordering::Less => low = middle + 1,
+                core::cmp::Ordering::Greater => high = middle,
+                core::cmp::Ordering::Equal => return i16_at(self.kerning_pairs, middle * 6 + 4).unwrap_or(0),
+            }
+        }
+        0
+    }
+
+    /// The outline's extent, None for glyphs without one (like the space)
+    pub fn bounding_box(&self, glyph: u16) -> Option<BoundingBox> {
+        let data = self.glyph_data(glyph)?;
+        Some(BoundingBox { x_min: i16_at(data, 2)?, y_min: i16_at(data, 4)?, x_max: i16_at(data, 6)?, y_max: i16_at(data, 8)? })
+    }
+
+    /// Send the contours of `glyph` to `sink`, in font units with y up. Returns false when
+    /// the glyph is malformed, what came before the error has been sent.
+    pub fn outline(&self, glyph: u16, sink: &mut impl OutlineSink) -> bool {
+        self.transformed_outline(glyph, &Transform::IDENTITY, sink, 0).is_some()
+    }
+
+    /// The glyph's part of `glyf`, None when it has no outline
+    fn glyph_data(&self, glyph: u16) -> Option<&'a [u8]> {
+        let glyph = glyph as usize;
+        if glyph >= self.glyph_count {
+            return None;
+        }
+        let (start, end) = if self.long_offsets {
+            (u32_at(self.loca, glyph * 4)? as usize, u32_at(self.loca, glyph * 4 + 4)? as usize)
+        } else {
+            (u16_at(self.loca, glyph * 2)? as usize * 2, u16_at(self.loca, glyph * 2 + 2)? as usize * 2)
+        };
+        if end <= start {
+            return None;
+        }
+        self.glyf.get(start..end)
+    }
+
+    fn transformed_outline(&self, glyph: u16, transform: &Transform, sink: &mut impl OutlineSink, depth: usize) -> Option<()> {
+        let Some(data) = self.glyph_data(glyph) else {
+            return Some(());
+        };
+        let contour_count = i16_at(data, 0)?;
+        if contour_count >= 0 {
+            return simple_outline(data, contour_count as usize, transform, sink);
+        }
+        if depth >= MAX_COMPONENT_DEPTH {
+            return None;
+        }
+        let mut reader = Reader::at(data, 10);
+        loop {
+            let flags = reader.u16()?;
+            let component = reader.u16()?;
+            let (dx, dy) = if flags & ARGS_ARE_WORDS != 0 {
+                (reader.i16()? as f32, reader.i16()? as f32)
+            } else {
+                (reader.i8()? as f32, reader.i8()? as f32)
+            };
+            // Otherwise they are points to match, rare enough to leave the component in place
+            let (dx, dy) = if flags & ARGS_ARE_XY_VALUES != 0 { (dx, dy) } else { (0.0, 0.0) };
+            let mut component_transform = Transform { dx, dy, ..Transform::IDENTITY };
+            if flags & HAVE_SCALE != 0 {
+                component_transform.xx = reader.f2dot14()?;
+                component_transform.yy = component_transform.xx;
+            } else if flags & HAVE_XY_SCALE != 0 {
+                component_transform.xx = reader.f2dot14()?;
+                component_transform.yy = reader.f2dot14()?;
+            } else if flags & HAVE_TWO_BY_TWO != 0 {
+                component_transform.xx = reader.f2dot14()?;
+                component_transform.yx = reader.f2dot14()?;
+                component_transform.xy = reader.f2dot14()?;
+                component_transform.yy = reader.f2dot14()?;
+            }
+            self.transformed_outline(component, &transform.after(&component_transform), sink, depth + 1)?;
+            if flags & MORE_COMPONENTS == 0 {
+                return Some(());
+            }
+        }
+    }
+}
+
+/// Contours of a glyph made of points
+fn simple_outline(data: &[u8], contour_count: usize, transform: &Transform, sink: &mut impl OutlineSink) -> Option<()> {
+    if contour_count == 0 {
+        return Some(());
+    }
+    let mut reader = Reader::at(data, 10);
+    let ends = reader.bytes(contour_count * 2)?;
+    let point_count = u16_at(ends, (contour_count - 1) * 2)? as usize + 1;
+    let instructions = reader.u16()? as usize;
+    reader.skip(instructions)?;
+
+    // The flags come first, then all the x and all the y: find where they start
+    let flags_start = reader.offset;
+    let mut flags = Flags { reader, flag: 0, repeat: 0 };
+    let mut x_size = 0;
+    for _ in 0..point_count {
+        let flag = flags.next()?;
+        x_size += match (flag & X_SHORT != 0, flag & X_SAME_OR_POSITIVE != 0) {
+            (true, _) => 1,
+            (false, true) => 0,
+            (false, false) => 2,
+        };
+    }
+    let mut xs = Reader::at(data, flags.reader.offset);
+    let mut ys = Reader::at(data, flags.reader.offset + x_size);
+    let mut flags = Flags { reader: Reader::at(data, flags_start), flag: 0, repeat: 0 };
+
+    let (mut x, mut y) = (0i32, 0i32);
+    let mut contour = Contour::default();
+    let mut contour_index = 0;
+    let mut contour_end = u16_at(ends, 0)? as usize;
+    for point in 0..point_count {
+        let flag = flags.next()?;
+        x += coordinate(&mut xs, flag, X_SHORT, X_SAME_OR_POSITIVE)?;
+        y += coordinate(&mut ys, flag, Y_SHORT, Y_SAME_OR_POSITIVE)?;
+        contour.push(transform.apply(x as f32, y as f32), flag & ON_CURVE != 0, sink);
+        if point == contour_end {
+            contour.close(sink);
+            contour_index += 1;
+            if contour_index < contour_count {
+                contour_end = u16_at(ends, contour_index * 2)? as usize;
+            }
+        }
+    }
+    // When the ends are not in order
+    contour.close(sink);
+    Some(())
+}
+
+/// The change of x or y from the previous point
+fn coordinate(reader: &mut Reader, flag: u8, short: u8, same_or_positive: u8) -> Option<i32> {
+    match (flag & short != 0, flag & same_or_positive != 0) {
+        (true, true) => Some(reader.u8()? as i32),
+        (true, false) => Some(-(reader.u8()? as i32)),
+        (false, true) => Some(0),
+        (false, false) => Some(reader.i16()? as i32),
+    }
+}
+
+/// Point flags, with the repeats expanded
+struct Flags<'a> {
+    reader: Reader<'a>,
+    flag: u8,
+    repeat: u8,
+}
+
+impl Flags<'_> {
+    fn next(&mut self) -> Option<u8> {
+        if self.repeat > 0 {
+            self.repeat -= 1;
+            return Some(self.flag);
+        }
+        self.flag = self.reader.u8()?;
+        if self.flag & REPEAT != 0 {
+            self.repeat = self.reader.u8()?;
+        }
+        Some(self.flag)
+    }
+}
+
+/// Turns the points of a contour into lines and curves. Between two off curve points
+/// there is an implied on curve point halfway, a contour may even start off curve.
+#[derive(Default)]
+struct Contour {
+    /// The on curve point the contour started at
+    start: Option<Vector>,
+    /// The first point when it is off curve, it comes back when closing
+    first_off: Option<Vector>,
+    /// Control point waiting for the end of its curve
+    last_off: Option<Vector>,
+}
+
+impl Contour {
+    fn push(&mut self, point: Vector, on_curve: bool, sink: &mut impl OutlineSink) {
+        if self.start.is_none() {
+            match (on_curve, self.first_off) {
+                (true, _) => self.start = Some(point),
+                (false, None) => {
+                    self.first_off = Some(point);
+                    return;
+                }
+                (false, Some(first_off)) => {
+                    self.start = Some(first_off.midpoint(point));
+                    self.last_off = Some(point);
+                }
+            }
+            sink.move_to(self.start.unwrap_or(point));
+            return;
+        }
+        match (self.last_off, on_curve) {
+            (None, true) => sink.line_to(point),
+            (None, false) => self.last_off = Some(point),
+            (Some(control), true) => {
+                sink.quad_to(control, point);
+                self.last_off = None;
+            }
+            (Some(control), false) => {
+                sink.quad_to(control, control.midpoint(point));
+                self.last_off = Some(point);
+            }
+        }
+    }
+
+    fn close(&mut self, sink: &mut impl OutlineSink) {
+        if let Some(start) = self.start {
+            match (self.last_off, self.first_off) {
+                (None, None) => {}
+                (None, Some(first_off)) => sink.quad_to(first_off, start),
+                (Some(control), None) => sink.quad_to(control, start),
+                (Some(control), Some(first_off)) => {
+                    sink.quad_to(control, control.midpoint(first_off));
+                    sink.quad_to(first_off, start);
+                }
+            }
+            sink.close();
+        }
+        *self = Contour::default();
+    }
+}
+
+/// Affine transform of the components of composite glyphs
+#[derive(Clone, Copy)]
+struct Transform {
+    xx: f32,
+    yx: f32,
+    xy: f32,
+    yy: f32,
+    dx: f32,
+    dy: f32,
+}
+
+impl Transform {
+    const IDENTITY: Transform = Transform { xx: 1.0, yx: 0.0, xy: 0.0, yy: 1.0, dx: 0.0, dy: 0.0 };
+
+    fn apply(&self, x: f32, y: f32) -> Vector {
+        Vector::new(self.xx * x + self.xy * y + self.dx, self.yx * x + self.yy * y + self.dy)
+    }
+
+    /// `inner`, then this one
+    fn after(&self, inner: &Transform) -> Transform {
+        Transform {
+            xx: self.xx * inner.xx + self.xy * inner.yx,
+            yx: self.yx * inner.xx + self.yy * inner.yx,
+            xy: self.xx * inner.xy + self.xy * inner.yy,
+            yy: self.yx * inner.xy + self.yy * inner.yy,
+            dx: self.xx * inner.dx + self.xy * inner.dy + self.dx,
+            dy: self.yx * inner.dx + self.yy * inner.dy + self.dy,
+        }
+    }
+}
+
+fn find_table<'a>(data: &'a [u8], directory: usize, tag: &'static str) -> Result<&'a [u8], TrueTypeError> {
+    let table_count = u16_at(data, directory + 4).ok_or(TrueTypeError::Malformed)?;
+    for i in 0..table_count as usize {
+        let record = directory + 12 + i * 16;
+        if data.get(record..record + 4) != Some(tag.as_bytes()) {
+            continue;
+        }
+        let (Some(offset), Some(length)) = (u32_at(data, record + 8), u32_at(data, record + 12)) else {
+            return Err(TrueTypeError::Malformed);
+        };
+        let (offset, length) = (offset as usize, length as usize);
+        return data.get(offset..offset.saturating_add(length)).ok_or(TrueTypeError::Malformed);
+    }
+    Err(TrueTypeError::MissingTable(tag))
+}
+
+/// A Unicode subtable of `cmap`, the one for all planes if there is one
+fn parse_cmap(cmap: &[u8]) -> Result<Cmap<'_>, TrueTypeError> {
+    let count = u16_at(cmap, 2).ok_or(TrueTypeError::Malformed)?;
+    let mut found = None;
+    for i in 0..count as usize {
+        let record = 4 + i * 8;
+        let (Some(platform), Some(encoding), Some(offset)) = (u16_at(cmap, record), u16_at(cmap, record + 2), u32_at(cmap, record + 4))
+        else {
+            return Err(TrueTypeError::Malformed);
+        };
+        // Unicode platform, or Windows with Unicode BMP or full repertoire
+        let unicode = platform == 0 || (platform == 3 && (encoding == 1 || encoding == 10));
+        let Some(subtable) = cmap.get(offset as usize..).filter(|_| unicode) else {
+            continue;
+        };
+        match u16_at(subtable, 0) {
+            Some(12) => return Ok(Cmap::Format12(subtable)),
+            Some(4) => found = found.or(Some(Cmap::Format4(subtable))),
+            _ => {}
+        }
+    }
+    found.ok_or(TrueTypeError::NoUnicodeCmap)
+}
+
+fn format4_glyph(subtable: &[u8], code: u32) -> Option<u16> {
+    let code = u16::try_from(code).ok()?;
+    let segment_count = u16_at(subtable, 6)? as usize / 2;
+    let ends = 14;
+    let starts = ends + segment_count * 2 + 2;
+    let deltas = starts + segment_count * 2;
+    let range_offsets = deltas + segment_count * 2;
+    // The first segment ending at or after the code
+    let (mut low, mut high) = (0, segment_count);
+    while low < high {
+        let middle = (low + high) / 2;
+        if u16_at(subtable, ends + middle * 2)? < code {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    let segment = low;
+    if segment == segment_count {
+        return None;
+    }
+    let start = u16_at(subtable, starts + segment * 2)?;
+    if code < start {
+        return None;
+    }
+    let delta = u16_at(subtable, deltas + segment * 2)?;
+    let range_offset = u16_at(subtable, range_offsets + segment * 2)? as usize;
+    if range_offset == 0 {
+        return Some(code.wrapping_add(delta));
+    }
+    // Relative to where the range offset itself is
+    let glyph = u16_at(subtable, range_offsets + segment * 2 + range_offset + (code - start) as usize * 2)?;
+    (glyph != 0).then(|| glyph.wrapping_add(delta))
+}
+
+fn format12_glyph(subtable: &[u8], code: u32) -> Option<u16> {
+    let group_count = u32_at(subtable, 12)? as usize;
+    let (mut low, mut high) = (0, group_count);
+    while low < high {
+        let middle = (low + high) / 2;
+        let group = 16 + middle * 12;
+        let (start, end) = (u32_at(subtable, group)?, u32_at(subtable, group + 4)?);
+        if end < code {
+            low = middle + 1;
+        } else if start > code {
+            high = middle;
+        } else {
+            return u16::try_from(u32_at(subtable, group + 8)?.checked_add(code - start)?).ok();
+        }
+    }
+    None
+}
+
+/// The pairs of the first subtable of `kern` that is horizontal kerning in format 0
+fn kerning_pairs(kern: &[u8]) -> Option<&[u8]> {
+    // Apple's version 1 table is not supported
+    if u16_at(kern, 0)? != 0 {
+        return None;
+    }
+    let mut offset = 4;
+    for _ in 0..u16_at(kern, 2)? {
+        let length = u16_at(kern, offset + 2)? as usize;
+        let coverage = u16_at(kern, offset + 4)?;
+        if coverage >> 8 == 0 && coverage & KERN_FLAGS == KERN_HORIZONTAL {
+            let count = u16_at(kern, offset + 6)? as usize;
+            return kern.get(offset + 14..offset + 14 + count * 6);
+        }
+        offset += length;
+    }
+    None
+}
+
+// The file is big-endian, and may be broken: reads out of it give None
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Reader::at(data, offset).u16()
+}
+
+fn i16_at(data: &[u8], offset: usize) -> Option<i16> {
+    Reader::at(data, offset).i16()
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Reader::at(data, offset).u32()
+}
+
+#[derive(Clone, Copy)]
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn at(data: &'a [u8], offset: usize) -> Self {
+        Reader { data, offset }
+    }
+
+    fn bytes(&mut self, count: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.offset..self.offset.checked_add(count)?)?;
+        self.offset += count;
+        Some(bytes)
+    }
+
+    fn skip(&mut self, count: usize) -> Option<()> {
+        self.bytes(count).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn i8(&mut self) -> Option<i8> {
+        Some(self.u8()? as i8)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_be_bytes(self.bytes(2)?.try_into().ok()?))
+    }
+
+    fn i16(&mut self) -> Option<i16> {
+        Some(self.u16()? as i16)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    /// Fixed point 2.14 number, for scales
+    fn f2dot14(&mut self) -> Option<f32> {
+        Some(self.i16()? as f32 / 16384.0)
+    }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+    use crate::testing::truetype_font;
+
+    /// The outline as text: "M x,y", "L x,y", "Q x,y x,y", "Z"
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl OutlineSink for Recorder {
+        fn move_to(&mut self, to: Vector) {
+            self.0.push(format!("M {},{}", to.x, to.y));
+        }
+
+        fn line_to(&mut self, to: Vector) {
+            self.0.push(format!("L {},{}", to.x, to.y));
+        }
+
+        fn quad_to(&mut self, control: Vector, to: Vector) {
+            self.0.push(format!("Q {},{} {},{}", control.x, control.y, to.x, to.y));
+        }
+
+        fn close(&mut self) {
+            self.0.push("Z".into());
+        }
+    }
+
+    fn outline(font: &TrueTypeFont, glyph: u16) -> Vec<String> {
+        let mut recorder = Recorder::default();
+        assert!(font.outline(glyph, &mut recorder));
+        recorder.0
+    }
+
+    #[test]
+    fn metrics() {
+        let data = truetype_font(false, false);
+        let font = TrueTypeFont::parse(&data).unwrap();
+        assert_eq!((font.glyph_count(), font.units_per_em()), (5, 1000));
+        assert_eq!((font.ascender(), font.descender(), font.line_gap()), (800, -200, 0));
+        assert_eq!([0, 1, 2, 3, 4].map(|glyph| font.advance_width(glyph)), [500, 400, 700, 1000, 1000]);
+        assert_eq!(font.bounding_box(3), Some(BoundingBox { x_min: 100, y_min: 0, x_max: 950, y_max: 700 }));
+        assert_eq!(font.bounding_box(4), None);
+        assert_eq!(font.bounding_box(5), None);
+    }
+
+    #[test]
+    fn characters() {
+        for format12 in [false, true] {
+            let data = truetype_font(false, format12);
+            let font = TrueTypeFont::parse(&data).unwrap();
+            let glyphs = [' ', 'H', 'I', 'O', 'é', 'J', '\u{FFFF}'].map(|char| font.glyph_index(char));
+            assert_eq!(glyphs, [4, 3, 1, 2, 2, 0, 0]);
+            // Only format 12 goes beyond U+FFFF
+            assert_eq!(font.glyph_index('😀'), if format12 { 1 } else { 0 });
+        }
+    }
+
+    #[test]
+    fn kerning() {
+        let data = truetype_font(false, false);
+        let font = TrueTypeFont::parse(&data).unwrap();
+        assert!(font.has_kerning());
+        assert_eq!(font.kerning(1, 2), -100);
+        assert_eq!(font.kerning(2, 1), -50);
+        assert_eq!(font.kerning(1, 1), 0);
+        assert_eq!(font.kerning(3, 0), 0);
+    }
+
+    #[test]
+    fn outlines() {
+        for long_offsets in [false, true] {
+            let data = truetype_font(long_offsets, false);
+            let font = TrueTypeFont::parse(&data).unwrap();
+            assert_eq!(outline(&font, 1), ["M 100,0", "L 300,0", "L 300,700", "L 100,700", "Z"]);
+            // Off curve points only: curves between the implied midpoints. The hole is lines.
+            assert_eq!(
+                outline(&font, 2),
+                [
+                    "M 300,0", "Q 600,0 600,350", "Q 600,700 300,700", "Q 0,700 0,350", "Q 0,0 300,0", "Z",
+                    "M 200,200", "L 200,500", "L 400,500", "L 400,200", "Z",
+                ]
+            );
+            assert_eq!(
+                outline(&font, 3),
+                [
+                    "M 100,0", "L 300,0", "L 300,700", "L 100,700", "Z",
+                    "M 650,0", "L 950,0", "L 950,350", "L 650,350", "Z",
+                ]
+            );
+            assert!(outline(&font, 4).is_empty());
+        }
+    }
+
+    #[test]
+    fn start_off_curve() {
+        // Off, on, off: the first point comes back last
+        let mut recorder = Recorder::default();
+        let mut contour = Contour::default();
+        for (x, y, on_curve) in [(0.0, 0.0, false), (10.0, 0.0, true), (10.0, 10.0, false)] {
+            contour.push(Vector::new(x, y), on_curve, &mut recorder);
+        }
+        contour.close(&mut recorder);
+        assert_eq!(recorder.0, ["M 10,0", "Q 10,10 5,5", "Q 0,0 10,0", "Z"]);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(TrueTypeFont::parse(&[]).err(), Some(TrueTypeError::NotTrueType));
+        assert_eq!(TrueTypeFont::parse(b"not a font").err(), Some(TrueTypeError::NotTrueType));
+        assert_eq!(TrueTypeFont::parse(b"OTTO\0\0").err(), Some(TrueTypeError::CffOutlines));
+        let no_tables = [0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(TrueTypeFont::parse(&no_tables).err(), Some(TrueTypeError::MissingTable("head")));
+        // The last tables (maxp) cut
+        let data = truetype_font(false, false);
+        assert_eq!(TrueTypeFont::parse(&data[..data.len() - 8]).err(), Some(TrueTypeError::Malformed));
+    }
+
+    #[test]
+    fn collection() {
+        // The same font after a collection header: the offsets are from the start of the file
+        let font = truetype_font(false, false);
+        let mut data = b"ttcf\x00\x01\x00\x00\x00\x00\x00\x01\x00\x00\x00\x10".to_vec();
+        data.extend(&font[..12]);
+        for record in font[12..].chunks(16).take(8) {
+            let offset = u32_at(record, 8).unwrap() + 16;
+            data.extend(&record[..8]);
+            data.extend(offset.to_be_bytes());
+            data.extend(&record[12..]);
+        }
+        data.extend(&font[12 + 8 * 16..]);
+        let font = TrueTypeFont::parse(&data).unwrap();
+        assert_eq!(font.glyph_index('O'), 2);
+        assert_eq!(outline(&font, 1).len(), 5);
+    }
+}
@@ -0,0 +1,262 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::fs::{write_u32, DirEntry, FileName, FileSystem, FileType, FsError, Stat};
+use crate::console::console;
+use crate::keyboard::{read_scancode, Key, KeyDecoder};
+use crate::process;
+use crate::serial::COM1;
+use crate::vbe::get_vbe;
+
+/// ioctl on /dev/fb0: fills 16 bytes with the width, height, pitch (bytes per
+/// scanline) and bits per pixel of the screen, as little endian u32
+pub const FB_GET_INFO: u32 = 0x4600;
+const FB_INFO_SIZE: usize = 16;
+
+const ROOT_INODE: u64 = 0;
+const NULL: u64 = 1;
+const ZERO: u64 = 2;
+const RANDOM: u64 = 3;
+const FB0: u64 = 4;
+const KBD: u64 = 5;
+const TTY_S0: u64 = 6;
+const CONSOLE: u64 = 7;
+
+const DEVICES: [(&str, u64); 7] = [
+    ("null", NULL),
+    ("zero", ZERO),
+    ("random", RANDOM),
+    ("fb0", FB0),
+    ("kbd", KBD),
+    ("ttyS0", TTY_S0),
+    ("console", CONSOLE),
+];
+
+/// Device files, usually mounted at /dev. Reads of kbd and ttyS0 do not
+/// block: they return what has arrived so far, possibly nothing.
+/// console is the screen plus the keyboard and serial input as characters,
+/// its reads wait for at least one.
+pub struct DevFs;
+
+impl DevFs {
+    fn check_device(inode: u64) -> Result<(), FsError> {
+        match inode {
+            NULL..=CONSOLE => Ok(()),
+            ROOT_INODE => Err(FsError::IsADirectory),
+            _ => Err(FsError::NotFound),
+        }
+    }
+}
+
+impl FileSystem for DevFs {
+    fn name(&self) -> &str {
+        "devfs"
+    }
+
+    fn root(&self) -> u64 {
+        ROOT_INODE
+    }
+
+    fn lookup(&self, dir: u64, name: &str) -> Result<u64, FsError> {
+        if dir != ROOT_INODE {
+            return Err(FsError::NotADirectory);
+        }
+        DEVICES.iter().find(|(device, _)| *device == name).map(|&(_, inode)| inode).ok_or(FsError::NotFound)
+    }
+
+    fn stat(&self, inode: u64) -> Result<Stat, FsError> {
+        let (file_type, size, permissions) = match inode {
+            ROOT_INODE => (FileType::Directory, 0, 0o755),
+            FB0 => (FileType::CharDevice, get_vbe().framebuffer_size() as u64, 0o660),
+            NULL..=CONSOLE => (FileType::CharDevice, 0, 0o666),
+            _ => return Err(FsError::NotFound),
+        };
+        Ok(Stat { file_type, size, inode, links: 1, permissions })
+    }
+
+    fn read(&self, inode: u64, offset: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        DevFs::check_device(inode)?;
+        match inode {
+            NULL => Ok(0),
+            ZERO => {
+                buffer.fill(0);
+                Ok(buffer.len())
+            }
+            RANDOM => {
+                for chunk in buffer.chunks_mut(8) {
+                    chunk.copy_from_slice(&random_u64().to_le_bytes()[..chunk.len()]);
+                }
+                Ok(buffer.len())
+            }
+            FB0 => {
+                let (address, count) = framebuffer_range(offset, buffer.len());
+                unsafe { core::ptr::copy_nonoverlapping(address as *const u8, buffer.as_mut_ptr(), count); }
+                Ok(count)
+            }
+            KBD => Ok(fill_from(buffer, read_scancode)),
+            TTY_S0 => Ok(fill_from(buffer, || COM1.read_byte())),
+            _ => Ok(read_console(buffer)),
+        }
+    }
+
+    fn read_dir(&self, dir: u64, cursor: &mut u64) -> Result<Option<DirEntry>, FsError> {
+        if dir != ROOT_INODE {
+            return Err(FsError::NotADirectory);
+        }
+        let Some(&(name, inode)) = DEVICES.get(*cursor as usize) else {
+            return Ok(None);
+        };
+        *cursor += 1;
+        Ok(Some(DirEntry { name: FileName::new(name), inode, file_type: FileType::CharDevice }))
+    }
+
+    fn write(&self, inode: u64, offset: u64, data: &[u8]) -> Result<usize, FsError> {
+        DevFs::check_device(inode)?;
+        match inode {
+            FB0 => {
+                let (address, count) = framebuffer_range(offset, data.len());
+                if count == 0 && !data.is_empty() {
+                    return Err(FsError::NoSpace);
+                }
+                unsafe { core::ptr::copy_nonoverlapping(data.as_ptr(), address as *mut u8, count); }
+                Ok(count)
+            }
+            TTY_S0 => {
+                for &byte in data {
+                    COM1.write_byte(byte);
+                }
+                Ok(data.len())
+            }
+            CONSOLE => {
+                if let Some(console) = console() {
+                    console.write_bytes(data);
+                }
+                Ok(data.len())
+            }
+            KBD => Err(FsError::InvalidArgument),
+            // null, zero and random swallow everything
+            _ => Ok(data.len()),
+        }
+    }
+
+    fn truncate(&self, inode: u64, _size: u64) -> Result<(), FsError> {
+        // Opening a device with TRUNCATE is harmless
+        DevFs::check_device(inode)
+    }
+
+    fn ioctl(&self, inode: u64, request: u32, data: &mut [u8]) -> Result<usize, FsError> {
+        DevFs::check_device(inode)?;
+        match (inode, request) {
+            (FB0, FB_GET_INFO) => {
+                let info = data.get_mut(..FB_INFO_SIZE).ok_or(FsError::InvalidArgument)?;
+                let vbe = get_vbe();
+                write_u32(info, 0, vbe.width() as u32);
+                write_u32(info, 4, vbe.height() as u32);
+                write_u32(info, 8, vbe.pitch() as u32);
+                write_u32(info, 12, vbe.bpp() as u32);
+                Ok(FB_INFO_SIZE)
+            }
+            _ => Err(FsError::InvalidArgument),
+        }
+    }
+
+    fn mmap(&self, inode: u64, offset: u64, length: usize) -> Result<usize, FsError> {
+        DevFs::check_device(inode)?;
+        if inode != FB0 {
+            return Err(FsError::Unsupported);
+        }
+        let (address, count) = framebuffer_range(offset, length);
+        if count != length {
+            return Err(FsError::InvalidArgument);
+        }
+        Ok(address)
+    }
+}
+
+/// Address of `offset` in the framebuffer, and how many of `length` bytes from there are in it
+fn framebuffer_range(offset: u64, length: usize) -> (usize, usize) {
+    let vbe = get_vbe();
+    let size = vbe.framebuffer_size();
+    let offset = (offset as usize).min(size);
+    (vbe.framebuffer() + offset, length.min(size - offset))
+}
+
+/// Copy bytes from `next` into `buffer` until it is full or `next` has nothing more
+fn fill_from(buffer: &mut [u8], mut next: impl FnMut() -> Option<u8>) -> usize {
+    let mut count = 0;
+    while count < buffer.len() {
+        let Some(byte) = next() else {
+            break;
+        };
+        buffer[count] = byte;
+        count += 1;
+    }
+    count
+}
+
+static mut CONSOLE_KEYS: KeyDecoder = KeyDecoder::new();
+
+/// Characters typed on the keyboard or received on the serial port, waiting until there is one
+fn read_console(buffer: &mut [u8]) -> usize {
+    if buffer.is_empty() {
+        return 0;
+    }
+    let keys = unsafe { &mut *core::ptr::addr_of_mut!(CONSOLE_KEYS) };
+    let mut next = || {
+        while let Some(scancode) = read_scancode() {
+            let byte = match keys.decode(scancode) {
+                Some(Key::Char(char)) if char.is_ascii() => char as u8,
+                Some(Key::Ctrl(char)) => char as u8 - b'a' + 1,
+                Some(Key::Enter) => b'\n',
+                Some(Key::Backspace) => 0x08,
+                Some(Key::Tab) => b'\t',
+                Some(Key::Escape) => 0x1B,
+                _ => continue,
+            };
+            return Some(byte);
+        }
+        COM1.read_byte().map(|byte| if byte == b'\r' { b'\n' } else { byte })
+    };
+    loop {
+        let count = fill_from(buffer, &mut next);
+        if count > 0 {
+            return count;
+        }
+        // Let the other processes run, then wait for the keyboard interrupt, or the
+        // timer to poll the serial port
+        process::yield_now();
+        unsafe { core::arch::asm!("hlt"); }
+    }
+}
+
+// xorshift64* state, seeded from the time stamp counter on first use
+static RANDOM_STATE: AtomicU64 = AtomicU64::new(0);
+
+/// Random number from RDRAND when the CPU has it, otherwise from a xorshift generator.
+/// Fine for games and test data, not for cryptography.
+fn random_u64() -> u64 {
+    if has_rdrand() {
+        for _ in 0..10 {
+            let value: u64;
+            let ok: u8;
+            unsafe { core::arch::asm!("rdrand {}", "setc {}", out(reg) value, out(reg_byte) ok, options(nomem, nostack)); }
+            if ok != 0 {
+                return value;
+            }
+        }
+    }
+
+    let mut state = RANDOM_STATE.load(Ordering::Relaxed);
+    if state == 0 {
+        state = unsafe { core::arch::x86_64::_rdtsc() } | 1;
+    }
+    state ^= state >> 12;
+    state ^= state << 25;
+    state ^= state >> 27;
+    RANDOM_STATE.store(state, Ordering::Relaxed);
+    state.wrapping_mul(0x2545F4914F6CDD1D)
+}
+
+fn has_rdrand() -> bool {
+    // CPUID leaf 1, ECX bit 30
+    let features = core::arch::x86_64::__cpuid(1);
+    features.ecx & (1 << 30) != 0
+}
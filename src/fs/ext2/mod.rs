@@ -0,0 +1,654 @@
+use crate::block::{BlockDevice, SECTOR_SIZE};
+use crate::fs::{read_u16, read_u32, DirEntry, FileName, FileSystem, FileType, FsError, Stat, MAX_NAME_LEN};
+
+const SUPERBLOCK_OFFSET: u64 = 1024;
+const EXT2_MAGIC: u16 = 0xEF53;
+const ROOT_INODE: u32 = 2;
+const GROUP_DESCRIPTOR_SIZE: u64 = 32;
+// Revision 0 filesystems have fixed 128-byte inodes
+const GOOD_OLD_INODE_SIZE: u32 = 128;
+
+// Incompatible features we can read: directory entries with a file type byte, flexible block groups
+const INCOMPAT_FILETYPE: u32 = 0x0002;
+const INCOMPAT_FLEX_BG: u32 = 0x0200;
+const SUPPORTED_INCOMPAT: u32 = INCOMPAT_FILETYPE | INCOMPAT_FLEX_BG;
+
+const DIRECT_BLOCKS: u32 = 12;
+const INDIRECT_BLOCK: usize = 12;
+const DOUBLE_INDIRECT_BLOCK: usize = 13;
+const TRIPLE_INDIRECT_BLOCK: usize = 14;
+
+// Type bits of the inode mode
+const MODE_TYPE_MASK: u16 = 0xF000;
+const MODE_DIRECTORY: u16 = 0x4000;
+const MODE_REGULAR: u16 = 0x8000;
+const MODE_SYMLINK: u16 = 0xA000;
+
+// Targets shorter than this are stored in the block pointers ("fast" symlinks)
+const FAST_SYMLINK_SIZE: u64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ext2FileType {
+    Regular,
+    Directory,
+    Symlink,
+    Other,
+}
+
+/// An inode read from the inode table
+#[derive(Debug, Clone, Copy)]
+pub struct Ext2Node {
+    number: u32,
+    mode: u16,
+    size: u64,
+    links: u16,
+    /// Number of 512-byte sectors used, including indirect blocks
+    sectors: u32,
+    block: [u32; 15],
+}
+
+impl Ext2Node {
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn links(&self) -> u16 {
+        self.links
+    }
+
+    /// Permission bits (rwxrwxrwx plus setuid/setgid/sticky)
+    pub fn permissions(&self) -> u16 {
+        self.mode & !MODE_TYPE_MASK
+    }
+
+    pub fn file_type(&self) -> Ext2FileType {
+        match self.mode & MODE_TYPE_MASK {
+            MODE_REGULAR => Ext2FileType::Regular,
+            MODE_DIRECTORY => Ext2FileType::Directory,
+            MODE_SYMLINK => Ext2FileType::Symlink,
+            _ => Ext2FileType::Other,
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type() == Ext2FileType::Directory
+    }
+}
+
+pub struct Ext2DirEntry {
+    pub name: FileName,
+    pub inode: u32,
+    /// From the directory entry itself, Other when the filesystem does not record it
+    pub file_type: Ext2FileType,
+}
+
+/// Read-only ext2 driver
+pub struct Ext2Fs<'a> {
+    device: &'a dyn BlockDevice,
+    block_size: u32,
+    inodes_count: u32,
+    inodes_per_group: u32,
+    inode_size: u32,
+    /// Block holding the first group descriptor
+    group_table_block: u32,
+}
+
+impl<'a> Ext2Fs<'a> {
+    pub fn mount(device: &'a dyn BlockDevice) -> Result<Self, FsError> {
+        if device.sector_size() != SECTOR_SIZE {
+            return Err(FsError::Unsupported);
+        }
+        let mut superblock = [0u8; 1024];
+        device.read_sectors(SUPERBLOCK_OFFSET / SECTOR_SIZE as u64, &mut superblock)?;
+
+        if read_u16(&superblock, 56) != EXT2_MAGIC {
+            return Err(FsError::BadSuperblock);
+        }
+        let inodes_count = read_u32(&superblock, 0);
+        let first_data_block = read_u32(&superblock, 20);
+        let log_block_size = read_u32(&superblock, 24);
+        let inodes_per_group = read_u32(&superblock, 40);
+        let revision = read_u32(&superblock, 76);
+
+        // Blocks go up to 64 KiB
+        if log_block_size > 6 {
+            return Err(FsError::Unsupported);
+        }
+        let block_size = 1024 << log_block_size;
+        let inode_size = if revision == 0 { GOOD_OLD_INODE_SIZE } else { read_u16(&superblock, 88) as u32 };
+        if inode_size < GOOD_OLD_INODE_SIZE || !inode_size.is_power_of_two() || inode_size > block_size {
+            return Err(FsError::BadSuperblock);
+        }
+        if revision > 0 && read_u32(&superblock, 96) & !SUPPORTED_INCOMPAT != 0 {
+            return Err(FsError::Unsupported);
+        }
+        if inodes_per_group == 0 {
+            return Err(FsError::BadSuperblock);
+        }
+
+        Ok(Ext2Fs {
+            device,
+            block_size,
+            inodes_count,
+            inodes_per_group,
+            inode_size,
+            group_table_block: first_data_block + 1,
+        })
+    }
+
+    /// Read inode `number` from the inode table of its block group
+    pub fn inode(&self, number: u32) -> Result<Ext2Node, FsError> {
+        if number == 0 || number > self.inodes_count {
+            return Err(FsError::NotFound);
+        }
+        let group = (number - 1) / self.inodes_per_group;
+        let index = (number - 1) % self.inodes_per_group;
+
+        let descriptor = self.group_table_block as u64 * self.block_size as u64 + group as u64 * GROUP_DESCRIPTOR_SIZE;
+        let inode_table = self.read_u32_at(descriptor + 8)?;
+
+        let position = inode_table as u64 * self.block_size as u64 + index as u64 * self.inode_size as u64;
+        let mut raw = [0u8; GOOD_OLD_INODE_SIZE as usize];
+        self.read_bytes(position, &mut raw)?;
+
+        let mode = read_u16(&raw, 0);
+        let mut size = read_u32(&raw, 4) as u64;
+        // The high half of the size is only defined for regular files (it was dir_acl before)
+        if mode & MODE_TYPE_MASK == MODE_REGULAR {
+            size |= (read_u32(&raw, 108) as u64) << 32;
+        }
+        let mut block = [0u32; 15];
+        for (i, pointer) in block.iter_mut().enumerate() {
+            *pointer = read_u32(&raw, 40 + i * 4);
+        }
+
+        Ok(Ext2Node {
+            number,
+            mode,
+            size,
+            links: read_u16(&raw, 26),
+            sectors: read_u32(&raw, 28),
+            block,
+        })
+    }
+
+    pub fn lookup(&self, dir: &Ext2Node, name: &str) -> Result<Ext2Node, FsError> {
+        let mut cursor = 0;
+        while let Some(entry) = self.read_dir(dir, &mut cursor)? {
+            if entry.name.as_str() == name {
+                return self.inode(entry.inode);
+            }
+        }
+        Err(FsError::NotFound)
+    }
+
+    /// Return the entry at byte offset `cursor` of a directory (start with 0) and advance it.
+    /// "." and ".." are returned like any other entry.
+    pub fn read_dir(&self, dir: &Ext2Node, cursor: &mut u64) -> Result<Option<Ext2DirEntry>, FsError> {
+        if !dir.is_dir() {
+            return Err(FsError::NotADirectory);
+        }
+        while *cursor + 8 <= dir.size {
+            let mut header = [0u8; 8];
+            self.read_data(dir, *cursor, &mut header)?;
+            let inode = read_u32(&header, 0);
+            let record_length = read_u16(&header, 4) as u64;
+            let name_length = header[6] as usize;
+            if record_length < 8 || *cursor + record_length > dir.size {
+                return Err(FsError::Corrupted);
+            }
+
+            let position = *cursor;
+            *cursor += record_length;
+            // Unused entries (deleted files, padding up to the end of a block) have inode 0
+            if inode == 0 || name_length == 0 {
+                continue;
+            }
+
+            let mut raw_name = [0u8; MAX_NAME_LEN];
+            let raw_name = &mut raw_name[..name_length];
+            self.read_data(dir, position + 8, raw_name)?;
+            let name = match core::str::from_utf8(raw_name) {
+                Ok(name) => FileName::new(name),
+                Err(_) => FileName::new("?"),
+            };
+            // Only meaningful with the filetype feature, it is the high byte of the name length otherwise
+            let file_type = match header[7] {
+                1 => Ext2FileType::Regular,
+                2 => Ext2FileType::Directory,
+                7 => Ext2FileType::Symlink,
+                _ => Ext2FileType::Other,
+            };
+            return Ok(Some(Ext2DirEntry { name, inode, file_type }));
+        }
+        Ok(None)
+    }
+
+    /// Read from a regular file, returns the number of bytes read (0 at the end of the file)
+    pub fn read(&self, node: &Ext2Node, offset: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        match node.file_type() {
+            Ext2FileType::Directory => Err(FsError::IsADirectory),
+            _ => self.read_data(node, offset, buffer),
+        }
+    }
+
+    /// Copy the target of a symbolic link into `buffer`, returns its length
+    pub fn read_link(&self, node: &Ext2Node, buffer: &mut [u8]) -> Result<usize, FsError> {
+        if node.file_type() != Ext2FileType::Symlink {
+            return Err(FsError::InvalidName);
+        }
+        let length = (node.size as usize).min(buffer.len());
+        if node.size < FAST_SYMLINK_SIZE && node.sectors == 0 {
+            // Fast symlink: the target is stored in place of the block pointers
+            for (i, byte) in buffer[..length].iter_mut().enumerate() {
+                *byte = node.block[i / 4].to_le_bytes()[i % 4];
+            }
+            return Ok(length);
+        }
+        self.read_data(node, 0, &mut buffer[..length])
+    }
+
+    fn read_data(&self, node: &Ext2Node, offset: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        if offset >= node.size {
+            return Ok(0);
+        }
+        let length = buffer.len().min((node.size - offset) as usize);
+        let block_size = self.block_size as u64;
+
+        let mut done = 0;
+        while done < length {
+            let position = offset + done as u64;
+            let in_block = position % block_size;
+            let count = ((block_size - in_block) as usize).min(length - done);
+            let block = self.data_block(node, (position / block_size) as u32)?;
+            if block == 0 {
+                // Hole in a sparse file
+                buffer[done..done + count].fill(0);
+            } else {
+                self.read_bytes(block as u64 * block_size + in_block, &mut buffer[done..done + count])?;
+            }
+            done += count;
+        }
+        Ok(length)
+    }
+
+    /// Map the n-th block of a file to a block number, 0 for holes
+    fn data_block(&self, node: &Ext2Node, index: u32) -> Result<u32, FsError> {
+        let per_block = self.block_size / 4;
+        if index < DIRECT_BLOCKS {
+            return Ok(node.block[index as usize]);
+        }
+
+        let mut index = index - DIRECT_BLOCKS;
+        if index < per_block {
+            return self.follow(node.block[INDIRECT_BLOCK], index, 1);
+        }
+        index -= per_block;
+        if index < per_block * per_block {
+            return self.follow(node.block[DOUBLE_INDIRECT_BLOCK], index, 2);
+        }
+        index -= per_block * per_block;
+        if (index as u64) < (per_block as u64).pow(3) {
+            return self.follow(node.block[TRIPLE_INDIRECT_BLOCK], index, 3);
+        }
+        Err(FsError::Corrupted)
+    }
+
+    /// Walk `levels` levels of indirect blocks starting at `block`
+    fn follow(&self, block: u32, index: u32, levels: u32) -> Result<u32, FsError> {
+        let per_block = self.block_size / 4;
+        let mut block = block;
+        for level in (0..levels).rev() {
+            if block == 0 {
+                return Ok(0);
+            }
+            let slot = (index / per_block.pow(level)) % per_block;
+            block = self.read_u32_at(block as u64 * self.block_size as u64 + slot as u64 * 4)?;
+        }
+        Ok(block)
+    }
+
+    fn read_u32_at(&self, position: u64) -> Result<u32, FsError> {
+        let mut bytes = [0u8; 4];
+        self.read_bytes(position, &mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    /// Read bytes at any byte position of the volume
+    fn read_bytes(&self, position: u64, buffer: &mut [u8]) -> Result<(), FsError> {
+        let mut sector = [0u8; SECTOR_SIZE];
+        let mut done = 0;
+        while done < buffer.len() {
+            let current = position + done as u64;
+            let in_sector = (current % SECTOR_SIZE as u64) as usize;
+            let count = (SECTOR_SIZE - in_sector).min(buffer.len() - done);
+            self.device.read_sectors(current / SECTOR_SIZE as u64, &mut sector)?;
+            buffer[done..done + count].copy_from_slice(&sector[in_sector..in_sector + count]);
+            done += count;
+        }
+        Ok(())
+    }
+}
+
+impl Ext2FileType {
+    fn to_vfs(self) -> FileType {
+        match self {
+            Ext2FileType::Directory => FileType::Directory,
+            Ext2FileType::Symlink => FileType::Symlink,
+            // Device nodes of an ext2 image are not wired to our drivers
+            Ext2FileType::Regular | Ext2FileType::Other => FileType::Regular,
+        }
+    }
+}
+
+impl FileSystem for Ext2Fs<'_> {
+    fn name(&self) -> &str {
+        "ext2"
+    }
+
+    fn root(&self) -> u64 {
+        ROOT_INODE as u64
+    }
+
+    fn lookup(&self, dir: u64, name: &str) -> Result<u64, FsError> {
+        let dir = self.inode(dir as u32)?;
+        Ok(Ext2Fs::lookup(self, &dir, name)?.number() as u64)
+    }
+
+    fn stat(&self, inode: u64) -> Result<Stat, FsError> {
+        let node = self.inode(inode as u32)?;
+        Ok(Stat {
+            file_type: node.file_type().to_vfs(),
+            size: node.size(),
+            inode,
+            links: node.links() as u32,
+            permissions: node.permissions() & 0o777,
+        })
+    }
+
+    fn read(&self, inode: u64, offset: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        let node = self.inode(inode as u32)?;
+        Ext2Fs::read(self, &node, offset, buffer)
+    }
+
+    fn read_dir(&self, dir: u64, cursor: &mut u64) -> Result<Option<DirEntry>, FsError> {
+        let dir = self.inode(dir as u32)?;
+        while let Some(entry) = Ext2Fs::read_dir(self, &dir, cursor)? {
+            let name = entry.name.as_str();
+            if name == "." || name == ".." {
+                continue;
+            }
+            let file_type = match entry.file_type {
+                Ext2FileType::Other => self.inode(entry.inode)?.file_type(),
+                file_type => file_type,
+            };
+            return Ok(Some(DirEntry { name: entry.name, inode: entry.inode as u64, file_type: file_type.to_vfs() }));
+        }
+        Ok(None)
+    }
+
+    fn read_link(&self, inode: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        let node = self.inode(inode as u32)?;
+        Ext2Fs::read_link(self, &node, buffer)
+    }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+    use crate::block::BlockError;
+    use crate::fs::{write_u16, write_u32};
+    use crate::testing::{list_dir, read_file, MemoryDisk};
+
+    const BLOCK: usize = 1024;
+    const INODE_TABLE: u32 = 5;
+    const INODES: u32 = 32;
+    const DIR_MODE: u16 = MODE_DIRECTORY | 0o755;
+    const FILE_MODE: u16 = MODE_REGULAR | 0o644;
+    const LINK_MODE: u16 = MODE_SYMLINK | 0o777;
+
+    /// A volume of 1 KiB blocks: superblock in block 1, group descriptor in block 2,
+    /// 32 inodes of 128 bytes in blocks 5 to 8, data from block 10
+    struct Image {
+        bytes: Vec<u8>,
+        next_block: u32,
+    }
+
+    impl Image {
+        fn new(revision: u32) -> Self {
+            let mut bytes = vec![0u8; 64 * BLOCK];
+            let superblock = &mut bytes[BLOCK..2 * BLOCK];
+            write_u32(superblock, 0, INODES);
+            write_u32(superblock, 20, 1);
+            write_u32(superblock, 40, INODES);
+            write_u16(superblock, 56, EXT2_MAGIC);
+            write_u32(superblock, 76, revision);
+            if revision > 0 {
+                write_u16(superblock, 88, GOOD_OLD_INODE_SIZE as u16);
+                write_u32(superblock, 96, INCOMPAT_FILETYPE);
+            }
+            write_u32(&mut bytes[2 * BLOCK..], 8, INODE_TABLE);
+            Image { bytes, next_block: 10 }
+        }
+
+        /// Store `data` in a new block, returns its number
+        fn block(&mut self, data: &[u8]) -> u32 {
+            let block = self.next_block;
+            self.next_block += 1;
+            if self.bytes.len() < self.next_block as usize * BLOCK {
+                self.bytes.resize(self.next_block as usize * BLOCK, 0);
+            }
+            self.bytes[block as usize * BLOCK..][..data.len()].copy_from_slice(data);
+            block
+        }
+
+        fn inode(&mut self, number: u32, mode: u16, size: u64, blocks: &[u32]) {
+            let raw = &mut self.bytes[INODE_TABLE as usize * BLOCK + (number as usize - 1) * 128..][..128];
+            write_u16(raw, 0, mode);
+            write_u32(raw, 4, size as u32);
+            write_u16(raw, 26, if mode & MODE_TYPE_MASK == MODE_DIRECTORY { 2 } else { 1 });
+            // Fast symlinks use no blocks, whatever is in the pointers
+            let fast_symlink = mode & MODE_TYPE_MASK == MODE_SYMLINK && size < FAST_SYMLINK_SIZE;
+            let used = if fast_symlink { 0 } else { blocks.iter().filter(|&&block| block != 0).count() as u32 };
+            write_u32(raw, 28, used * (BLOCK / SECTOR_SIZE) as u32);
+            for (i, &block) in blocks.iter().enumerate() {
+                write_u32(raw, 40 + i * 4, block);
+            }
+            write_u32(raw, 108, (size >> 32) as u32);
+        }
+
+        /// A directory of one block with (inode, name, type) entries, the last one spanning to its end
+        fn directory(&mut self, number: u32, entries: &[(u32, &str, u8)]) {
+            let mut data = Vec::new();
+            for (i, &(inode, name, file_type)) in entries.iter().enumerate() {
+                let length = if i + 1 == entries.len() { BLOCK - data.len() } else { (8 + name.len()).next_multiple_of(4) };
+                let start = data.len();
+                data.resize(start + length, 0);
+                write_u32(&mut data, start, inode);
+                write_u16(&mut data, start + 4, length as u16);
+                data[start + 6] = name.len() as u8;
+                data[start + 7] = file_type;
+                data[start + 8..start + 8 + name.len()].copy_from_slice(name.as_bytes());
+            }
+            let block = self.block(&data);
+            self.inode(number, DIR_MODE, BLOCK as u64, &[block]);
+        }
+
+        fn superblock(&mut self) -> &mut [u8] {
+            &mut self.bytes[BLOCK..2 * BLOCK]
+        }
+    }
+
+    /// Root with a file, a subdirectory, a fast and a slow symlink, and a deleted entry
+    fn sample(revision: u32) -> Image {
+        let mut image = Image::new(revision);
+        let hello = image.block(b"hello, ext2\n");
+        image.inode(12, FILE_MODE, 12, &[hello]);
+        image.directory(13, &[(13, ".", 2), (2, "..", 2), (16, "deep", 1)]);
+        image.inode(16, FILE_MODE, 0, &[]);
+        // The target of a fast symlink is in place of the block pointers
+        let mut target = [0u32; 3];
+        for (i, byte) in b"hello.txt".iter().enumerate() {
+            target[i / 4] |= (*byte as u32) << (8 * (i % 4));
+        }
+        image.inode(14, LINK_MODE, 9, &target);
+        let slow = "sub/".repeat(20);
+        let slow_block = image.block(slow.as_bytes());
+        image.inode(15, LINK_MODE, slow.len() as u64, &[slow_block]);
+        image.directory(2, &[(2, ".", 2), (2, "..", 2), (12, "hello.txt", 1), (0, "deleted", 1), (13, "sub", 2), (14, "link", 7), (15, "slow", 7)]);
+        image
+    }
+
+    fn resolve(fs: &dyn FileSystem, path: &str) -> Result<u64, FsError> {
+        path.split('/').try_fold(fs.root(), |dir, name| fs.lookup(dir, name))
+    }
+
+    #[test]
+    fn files_directories_and_links() {
+        let disk = MemoryDisk::new(sample(1).bytes);
+        let ext2 = Ext2Fs::mount(&disk).unwrap();
+        let fs: &dyn FileSystem = &ext2;
+        assert_eq!(ext2.block_size, 1024);
+        assert_eq!(list_dir(fs, fs.root()), ["hello.txt", "sub", "link", "slow"]);
+        assert_eq!(list_dir(fs, resolve(fs, "sub").unwrap()), ["deep"]);
+
+        let hello = resolve(fs, "hello.txt").unwrap();
+        assert_eq!(read_file(fs, hello), b"hello, ext2\n");
+        let stat = fs.stat(hello).unwrap();
+        assert_eq!((stat.file_type, stat.size, stat.links, stat.permissions), (FileType::Regular, 12, 1, 0o644));
+        assert_eq!(fs.stat(resolve(fs, "sub/deep").unwrap()).unwrap().size, 0);
+        assert_eq!(fs.stat(fs.root()).unwrap().file_type, FileType::Directory);
+
+        let mut target = [0u8; 100];
+        let link = resolve(fs, "link").unwrap();
+        assert_eq!(fs.stat(link).unwrap().file_type, FileType::Symlink);
+        assert_eq!(fs.read_link(link, &mut target), Ok(9));
+        assert_eq!(&target[..9], b"hello.txt");
+        assert_eq!(fs.read_link(resolve(fs, "slow").unwrap(), &mut target), Ok(80));
+        assert_eq!(&target[..80], "sub/".repeat(20).as_bytes());
+        assert_eq!(fs.read_link(hello, &mut target), Err(FsError::InvalidName));
+    }
+
+    #[test]
+    fn lookup_errors() {
+        let disk = MemoryDisk::new(sample(1).bytes);
+        let ext2 = Ext2Fs::mount(&disk).unwrap();
+        let fs: &dyn FileSystem = &ext2;
+        let hello = resolve(fs, "hello.txt").unwrap();
+        assert_eq!(resolve(fs, "deleted"), Err(FsError::NotFound));
+        assert_eq!(fs.lookup(hello, "x"), Err(FsError::NotADirectory));
+        assert_eq!(fs.read(fs.root(), 0, &mut [0; 4]), Err(FsError::IsADirectory));
+        assert_eq!(fs.read(hello, 100, &mut [0; 4]), Ok(0));
+        assert_eq!(fs.stat(0).err(), Some(FsError::NotFound));
+        assert_eq!(fs.stat(INODES as u64 + 1).err(), Some(FsError::NotFound));
+        assert_eq!(fs.create(fs.root(), "new", FileType::Regular), Err(FsError::ReadOnly));
+    }
+
+    #[test]
+    fn types_without_filetype_feature() {
+        // Revision 0: no file type in directory entries, it comes from the inodes
+        let mut image = sample(0);
+        image.directory(2, &[(2, ".", 0), (2, "..", 0), (12, "hello.txt", 0), (13, "sub", 0), (14, "link", 0)]);
+        let disk = MemoryDisk::new(image.bytes);
+        let ext2 = Ext2Fs::mount(&disk).unwrap();
+        let mut cursor = 0;
+        let mut types = Vec::new();
+        while let Some(entry) = FileSystem::read_dir(&ext2, ROOT_INODE as u64, &mut cursor).unwrap() {
+            types.push(entry.file_type);
+        }
+        assert_eq!(types, [FileType::Regular, FileType::Directory, FileType::Symlink]);
+    }
+
+    #[test]
+    fn indirect_blocks_and_holes() {
+        let mut image = Image::new(1);
+        let per_block = (BLOCK / 4) as u32;
+        let first = image.block(&[1; BLOCK]);
+        let single = image.block(&[2; BLOCK]);
+        let last_single = image.block(&[3; BLOCK]);
+        let double = image.block(&[4; BLOCK]);
+
+        let mut pointers = vec![0u8; BLOCK];
+        write_u32(&mut pointers, 0, single);
+        write_u32(&mut pointers, (per_block as usize - 1) * 4, last_single);
+        let indirect = image.block(&pointers);
+        let mut pointers = vec![0u8; BLOCK];
+        write_u32(&mut pointers, 0, double);
+        let double_second = image.block(&pointers);
+        let mut pointers = vec![0u8; BLOCK];
+        write_u32(&mut pointers, 0, double_second);
+        let double_first = image.block(&pointers);
+
+        let mut blocks = [0u32; 15];
+        blocks[0] = first;
+        blocks[INDIRECT_BLOCK] = indirect;
+        blocks[DOUBLE_INDIRECT_BLOCK] = double_first;
+        // Ends one block into the double indirect ones, with 64-bit size bits left at zero
+        let size = (DIRECT_BLOCKS + per_block + 1) as u64 * BLOCK as u64;
+        image.inode(12, FILE_MODE, size, &blocks);
+        image.inode(13, FILE_MODE, 5 << 32, &[]);
+        image.directory(2, &[(12, "sparse", 1), (13, "huge", 1)]);
+
+        let disk = MemoryDisk::new(image.bytes);
+        let ext2 = Ext2Fs::mount(&disk).unwrap();
+        let fs: &dyn FileSystem = &ext2;
+        let sparse = resolve(fs, "sparse").unwrap();
+        let data = read_file(fs, sparse);
+        assert_eq!(data.len() as u64, size);
+        let block_byte = |index: u32| data[index as usize * BLOCK];
+        assert_eq!(block_byte(0), 1);
+        assert_eq!(block_byte(5), 0);
+        assert_eq!(block_byte(DIRECT_BLOCKS), 2);
+        assert_eq!(block_byte(DIRECT_BLOCKS + 1), 0);
+        assert_eq!(block_byte(DIRECT_BLOCKS + per_block - 1), 3);
+        assert_eq!(block_byte(DIRECT_BLOCKS + per_block), 4);
+        assert_eq!(fs.stat(resolve(fs, "huge").unwrap()).unwrap().size, 5 << 32);
+    }
+
+    #[test]
+    fn bad_volumes() {
+        let mount = |image: Vec<u8>| Ext2Fs::mount(&MemoryDisk::new(image)).err();
+        let mut image = sample(1);
+        image.superblock()[56] = 0;
+        assert_eq!(mount(image.bytes), Some(FsError::BadSuperblock));
+        let mut image = sample(1);
+        write_u32(image.superblock(), 24, 7);
+        assert_eq!(mount(image.bytes), Some(FsError::Unsupported));
+        let mut image = sample(1);
+        write_u16(image.superblock(), 88, 100);
+        assert_eq!(mount(image.bytes), Some(FsError::BadSuperblock));
+        let mut image = sample(1);
+        // Compression
+        write_u32(image.superblock(), 96, 0x0001);
+        assert_eq!(mount(image.bytes), Some(FsError::Unsupported));
+        let mut image = sample(1);
+        write_u32(image.superblock(), 40, 0);
+        assert_eq!(mount(image.bytes), Some(FsError::BadSuperblock));
+        assert_eq!(mount(Vec::new()), Some(FsError::Block(BlockError::OutOfRange)));
+
+        // Only the superblock: the inode table is past the end
+        let truncated = MemoryDisk::new(sample(1).bytes[..2 * BLOCK].to_vec());
+        let ext2 = Ext2Fs::mount(&truncated).unwrap();
+        assert_eq!(ext2.inode(ROOT_INODE).err(), Some(FsError::Block(BlockError::OutOfRange)));
+    }
+
+    #[test]
+    fn corrupt_directories() {
+        for record_length in [4u16, 2000] {
+            let mut image = sample(1);
+            // The record length of ".." in the root
+            let root_block = read_u32(&image.bytes, INODE_TABLE as usize * BLOCK + 128 + 40) as usize;
+            write_u16(&mut image.bytes, root_block * BLOCK + 12 + 4, record_length);
+            let disk = MemoryDisk::new(image.bytes);
+            let ext2 = Ext2Fs::mount(&disk).unwrap();
+            let mut cursor = 0;
+            let root = ext2.inode(ROOT_INODE).unwrap();
+            assert!(ext2.read_dir(&root, &mut cursor).unwrap().is_some());
+            assert_eq!(ext2.read_dir(&root, &mut cursor).err(), Some(FsError::Corrupted), "length {}", record_length);
+        }
+    }
+}
@@ -0,0 +1,1384 @@
+use core::cell::Cell;
+use core::ops::ControlFlow;
+use crate::block::{BlockDevice, SECTOR_SIZE};
+use crate::fs::{read_u16, read_u32, write_u16, write_u32, DirEntry, FileName, FileSystem, FileType, FsError, Stat};
+
+const DIR_ENTRY_SIZE: usize = 32;
+const ENTRIES_PER_SECTOR: u32 = (SECTOR_SIZE / DIR_ENTRY_SIZE) as u32;
+
+// Directory entry attributes
+const ATTR_READ_ONLY: u8 = 0x01;
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_ARCHIVE: u8 = 0x20;
+const ATTR_LONG_NAME: u8 = 0x0F;
+
+// First byte of a directory entry
+const ENTRY_END: u8 = 0x00;
+const ENTRY_DELETED: u8 = 0xE5;
+// A real 0xE5 as first character is stored as 0x05
+const ENTRY_KANJI_E5: u8 = 0x05;
+
+const LFN_LAST: u8 = 0x40;
+const LFN_CHARS_PER_ENTRY: usize = 13;
+const LFN_MAX_ENTRIES: usize = 20;
+// Byte offsets of the 13 UTF-16 characters inside a long name entry
+const LFN_CHAR_OFFSETS: [usize; LFN_CHARS_PER_ENTRY] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+// Case flags of the short name (Windows NT extension)
+const CASE_LOWER_BASE: u8 = 0x08;
+const CASE_LOWER_EXT: u8 = 0x10;
+
+// 1980-01-01, we have no clock yet
+const DEFAULT_DATE: u16 = 0x0021;
+
+// FAT has no inode numbers. Ours pack the position of the short entry: directory
+// cluster (28 bits), number of long name entries (5 bits) and slot index (31 bits).
+const ROOT_INODE: u64 = u64::MAX;
+
+const FSINFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+const FSINFO_STRUCT_SIGNATURE: u32 = 0x6141_7272;
+const FSINFO_FREE_COUNT: usize = 488;
+const FSINFO_NEXT_FREE: usize = 492;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatKind {
+    Fat16,
+    Fat32,
+}
+
+/// Position of a directory entry, so it can be updated or deleted later
+#[derive(Debug, Clone, Copy)]
+struct EntryRef {
+    /// First cluster of the directory holding the entry (0 = FAT16 root region)
+    dir: u32,
+    /// Slot of the first long name entry (equals `slot` without long name)
+    first_slot: u32,
+    /// Slot of the short entry
+    slot: u32,
+    lba: u64,
+    offset: usize,
+}
+
+/// A file or directory of a FAT volume
+#[derive(Debug, Clone, Copy)]
+pub struct FatNode {
+    first_cluster: u32,
+    size: u32,
+    attributes: u8,
+    /// None for the root directory, which has no entry
+    entry: Option<EntryRef>,
+}
+
+impl FatNode {
+    pub fn is_dir(&self) -> bool {
+        self.attributes & ATTR_DIRECTORY != 0
+    }
+}
+
+pub struct FatDirEntry {
+    pub name: FileName,
+    pub node: FatNode,
+}
+
+pub struct FatFs<'a> {
+    device: &'a dyn BlockDevice,
+    kind: FatKind,
+    sectors_per_cluster: u32,
+    fat_start: u64,
+    fat_sectors: u64,
+    fat_count: u32,
+    /// Fixed root directory region (FAT16 only)
+    root_dir_start: u64,
+    root_dir_sectors: u32,
+    /// 0 on FAT16, where the root is not a cluster chain
+    root_cluster: u32,
+    data_start: u64,
+    cluster_count: u32,
+    fsinfo_sector: Option<u64>,
+    /// Where to start looking for a free cluster
+    next_free: Cell<u32>,
+    /// The FSInfo free cluster count has been invalidated
+    fsinfo_stale: Cell<bool>,
+}
+
+impl<'a> FatFs<'a> {
+    /// Read the BIOS parameter block of a FAT16 or FAT32 volume
+    pub fn mount(device: &'a dyn BlockDevice) -> Result<Self, FsError> {
+        let mut boot = [0u8; SECTOR_SIZE];
+        device.read_sectors(0, &mut boot)?;
+        if boot[510] != 0x55 || boot[511] != 0xAA {
+            return Err(FsError::BadSuperblock);
+        }
+
+        let bytes_per_sector = read_u16(&boot, 11) as usize;
+        let sectors_per_cluster = boot[13] as u32;
+        let reserved_sectors = read_u16(&boot, 14) as u64;
+        let fat_count = boot[16] as u32;
+        let root_entries = read_u16(&boot, 17) as u32;
+        let total_sectors = match read_u16(&boot, 19) {
+            0 => read_u32(&boot, 32) as u64,
+            count => count as u64,
+        };
+        let fat_sectors = match read_u16(&boot, 22) {
+            0 => read_u32(&boot, 36) as u64,
+            count => count as u64,
+        };
+
+        if bytes_per_sector != SECTOR_SIZE || bytes_per_sector != device.sector_size() {
+            return Err(FsError::Unsupported);
+        }
+        if !sectors_per_cluster.is_power_of_two() || fat_count == 0 || fat_sectors == 0 {
+            return Err(FsError::BadSuperblock);
+        }
+
+        let root_dir_sectors = (root_entries * DIR_ENTRY_SIZE as u32).div_ceil(SECTOR_SIZE as u32);
+        let root_dir_start = reserved_sectors + fat_count as u64 * fat_sectors;
+        let data_start = root_dir_start + root_dir_sectors as u64;
+        if total_sectors <= data_start {
+            return Err(FsError::BadSuperblock);
+        }
+        let cluster_count = ((total_sectors - data_start) / sectors_per_cluster as u64) as u32;
+
+        // The FAT type is defined by the number of clusters only
+        let kind = match cluster_count {
+            0..4085 => return Err(FsError::Unsupported), // FAT12
+            4085..65525 => FatKind::Fat16,
+            _ => FatKind::Fat32,
+        };
+
+        let mut fs = FatFs {
+            device,
+            kind,
+            sectors_per_cluster,
+            fat_start: reserved_sectors,
+            fat_sectors,
+            fat_count,
+            root_dir_start,
+            root_dir_sectors,
+            root_cluster: 0,
+            data_start,
+            cluster_count,
+            fsinfo_sector: None,
+            next_free: Cell::new(2),
+            fsinfo_stale: Cell::new(false),
+        };
+
+        if kind == FatKind::Fat32 {
+            fs.root_cluster = read_u32(&boot, 44) & 0x0FFF_FFFF;
+            if !fs.is_valid_cluster(fs.root_cluster) {
+                return Err(FsError::BadSuperblock);
+            }
+            let fsinfo = read_u16(&boot, 48) as u64;
+            if fsinfo != 0 && fsinfo != 0xFFFF {
+                let mut info = [0u8; SECTOR_SIZE];
+                device.read_sectors(fsinfo, &mut info)?;
+                if read_u32(&info, 0) == FSINFO_LEAD_SIGNATURE && read_u32(&info, 484) == FSINFO_STRUCT_SIGNATURE {
+                    fs.fsinfo_sector = Some(fsinfo);
+                    let hint = read_u32(&info, FSINFO_NEXT_FREE);
+                    if fs.is_valid_cluster(hint) {
+                        fs.next_free.set(hint);
+                    }
+                }
+            }
+        }
+
+        Ok(fs)
+    }
+
+    pub fn root(&self) -> FatNode {
+        FatNode {
+            first_cluster: self.root_cluster,
+            size: 0,
+            attributes: ATTR_DIRECTORY,
+            entry: None,
+        }
+    }
+
+    /// Find `name` (case-insensitive, long or short name) in a directory
+    pub fn lookup(&self, dir: &FatNode, name: &str) -> Result<FatNode, FsError> {
+        let mut cursor = 0;
+        while let Some(entry) = self.read_dir(dir, &mut cursor)? {
+            if entry.name.as_str().eq_ignore_ascii_case(name) {
+                return Ok(entry.node);
+            }
+        }
+        Err(FsError::NotFound)
+    }
+
+    /// Return the directory entry following `cursor` (start with 0) and advance it.
+    /// "." and ".." are skipped, None means the end of the directory.
+    pub fn read_dir(&self, dir: &FatNode, cursor: &mut u32) -> Result<Option<FatDirEntry>, FsError> {
+        if !dir.is_dir() {
+            return Err(FsError::NotADirectory);
+        }
+        let start = *cursor;
+        let mut long_name = LongName::new();
+        let found = self.walk_dir(dir.first_cluster, |index, lba, offset, slot| {
+            if index < start {
+                return ControlFlow::Continue(());
+            }
+            match slot[0] {
+                ENTRY_END => return ControlFlow::Break(None),
+                ENTRY_DELETED => {
+                    long_name.reset();
+                    return ControlFlow::Continue(());
+                }
+                _ => {}
+            }
+            if slot[11] == ATTR_LONG_NAME {
+                long_name.push(index, slot);
+                return ControlFlow::Continue(());
+            }
+            let (name, first_slot) = match long_name.take(slot) {
+                Some(name) => (name, long_name.first_slot.unwrap_or(index)),
+                None => (short_name_to_string(slot), index),
+            };
+            long_name.reset();
+            if slot[11] & ATTR_VOLUME_ID != 0 || slot[0] == b'.' {
+                return ControlFlow::Continue(());
+            }
+            let entry = EntryRef { dir: dir.first_cluster, first_slot, slot: index, lba, offset };
+            let node = self.node_from_entry(slot, Some(entry));
+            ControlFlow::Break(Some((index, FatDirEntry { name, node })))
+        })?;
+
+        match found.flatten() {
+            Some((index, entry)) => {
+                *cursor = index + 1;
+                Ok(Some(entry))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn node_from_entry(&self, slot: &[u8], entry: Option<EntryRef>) -> FatNode {
+        let high = if self.kind == FatKind::Fat32 { read_u16(slot, 20) as u32 } else { 0 };
+        let first_cluster = (high << 16) | read_u16(slot, 26) as u32;
+        let attributes = slot[11];
+        // ".." entries pointing at the root use cluster 0
+        if attributes & ATTR_DIRECTORY != 0 && first_cluster == 0 {
+            return self.root();
+        }
+        FatNode { first_cluster, size: read_u32(slot, 28), attributes, entry }
+    }
+
+    /// Read from a file at `offset`, returns the number of bytes read (0 at the end of the file)
+    pub fn read(&self, node: &FatNode, offset: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        if node.is_dir() {
+            return Err(FsError::IsADirectory);
+        }
+        let size = node.size as u64;
+        if offset >= size {
+            return Ok(0);
+        }
+        if !self.is_valid_cluster(node.first_cluster) {
+            return Err(FsError::Corrupted);
+        }
+        let length = buffer.len().min((size - offset) as usize);
+        let cluster_bytes = self.cluster_bytes();
+
+        let mut cluster = self.nth_cluster(node.first_cluster, offset / cluster_bytes)?;
+        let mut position = offset;
+        let mut done = 0;
+        let mut sector = [0u8; SECTOR_SIZE];
+        while done < length {
+            let in_cluster = position % cluster_bytes;
+            if in_cluster == 0 && position != offset {
+                cluster = self.next_cluster(cluster)?.ok_or(FsError::Corrupted)?;
+            }
+            let lba = self.cluster_lba(cluster) + in_cluster / SECTOR_SIZE as u64;
+            let in_sector = (position % SECTOR_SIZE as u64) as usize;
+            let count = (SECTOR_SIZE - in_sector).min(length - done);
+            if count == SECTOR_SIZE {
+                self.device.read_sectors(lba, &mut buffer[done..done + SECTOR_SIZE])?;
+            } else {
+                self.device.read_sectors(lba, &mut sector)?;
+                buffer[done..done + count].copy_from_slice(&sector[in_sector..in_sector + count]);
+            }
+            done += count;
+            position += count as u64;
+        }
+        Ok(length)
+    }
+
+    /// Write to a file at `offset`, growing it (and zero filling any gap) as needed
+    pub fn write(&self, node: &mut FatNode, offset: u64, data: &[u8]) -> Result<usize, FsError> {
+        if node.is_dir() {
+            return Err(FsError::IsADirectory);
+        }
+        let end = offset.checked_add(data.len() as u64).ok_or(FsError::NoSpace)?;
+        if end > u32::MAX as u64 {
+            return Err(FsError::NoSpace);
+        }
+        if data.is_empty() {
+            return Ok(0);
+        }
+
+        // Writing past the end: fill the hole with zeros first
+        let zeros = [0u8; SECTOR_SIZE];
+        while (node.size as u64) < offset {
+            let gap = ((offset - node.size as u64) as usize).min(SECTOR_SIZE);
+            let size = node.size as u64;
+            self.write(node, size, &zeros[..gap])?;
+        }
+
+        let cluster_bytes = self.cluster_bytes();
+        if node.first_cluster == 0 {
+            node.first_cluster = self.allocate_cluster()?;
+        }
+        let mut cluster = node.first_cluster;
+        for _ in 0..offset / cluster_bytes {
+            cluster = self.next_or_allocate(cluster)?;
+        }
+
+        let mut position = offset;
+        let mut done = 0;
+        let mut sector = [0u8; SECTOR_SIZE];
+        while done < data.len() {
+            let in_cluster = position % cluster_bytes;
+            if in_cluster == 0 && position != offset {
+                cluster = self.next_or_allocate(cluster)?;
+            }
+            let lba = self.cluster_lba(cluster) + in_cluster / SECTOR_SIZE as u64;
+            let in_sector = (position % SECTOR_SIZE as u64) as usize;
+            let count = (SECTOR_SIZE - in_sector).min(data.len() - done);
+            if count == SECTOR_SIZE {
+                self.device.write_sectors(lba, &data[done..done + SECTOR_SIZE])?;
+            } else {
+                self.device.read_sectors(lba, &mut sector)?;
+                sector[in_sector..in_sector + count].copy_from_slice(&data[done..done + count]);
+                self.device.write_sectors(lba, &sector)?;
+            }
+            done += count;
+            position += count as u64;
+        }
+
+        node.size = node.size.max(end as u32);
+        self.update_entry(node)?;
+        Ok(done)
+    }
+
+    /// Shrink or grow a file to `size` bytes, freeing the clusters no longer needed
+    pub fn truncate(&self, node: &mut FatNode, size: u32) -> Result<(), FsError> {
+        if node.is_dir() {
+            return Err(FsError::IsADirectory);
+        }
+        if size > node.size {
+            let zeros = [0u8; SECTOR_SIZE];
+            while node.size < size {
+                let gap = ((size - node.size) as usize).min(SECTOR_SIZE);
+                let end = node.size as u64;
+                self.write(node, end, &zeros[..gap])?;
+            }
+            return Ok(());
+        }
+
+        let clusters_kept = (size as u64).div_ceil(self.cluster_bytes());
+        if node.first_cluster != 0 {
+            if clusters_kept == 0 {
+                self.free_chain(node.first_cluster)?;
+                node.first_cluster = 0;
+            } else {
+                let last = self.nth_cluster(node.first_cluster, clusters_kept - 1)?;
+                if let Some(rest) = self.next_cluster(last)? {
+                    self.set_fat_entry(last, self.end_of_chain())?;
+                    self.free_chain(rest)?;
+                }
+            }
+        }
+        node.size = size;
+        self.update_entry(node)
+    }
+
+    /// Create an empty file or directory named `name` in `parent`
+    pub fn create(&self, parent: &FatNode, name: &str, directory: bool) -> Result<FatNode, FsError> {
+        if !parent.is_dir() {
+            return Err(FsError::NotADirectory);
+        }
+        let mut units = [0u16; LFN_MAX_ENTRIES * LFN_CHARS_PER_ENTRY];
+        let unit_count = encode_long_name(name, &mut units)?;
+        match self.lookup(parent, name) {
+            Ok(_) => return Err(FsError::AlreadyExists),
+            Err(FsError::NotFound) => {}
+            Err(error) => return Err(error),
+        }
+
+        let (short_name, needs_long_name) = self.short_name(parent, name)?;
+        let long_entries = if needs_long_name { unit_count.div_ceil(LFN_CHARS_PER_ENTRY) } else { 0 };
+        let first_slot = self.find_free_slots(parent.first_cluster, long_entries as u32 + 1)?;
+
+        let mut first_cluster = 0;
+        if directory {
+            first_cluster = self.allocate_cluster()?;
+            self.write_dot_entries(first_cluster, parent)?;
+        }
+
+        // Long name entries are stored last part first, right before the short entry
+        let checksum = short_name_checksum(&short_name);
+        for i in 0..long_entries {
+            let order = long_entries - i;
+            let mut slot = [0u8; DIR_ENTRY_SIZE];
+            slot[0] = order as u8 | if i == 0 { LFN_LAST } else { 0 };
+            slot[11] = ATTR_LONG_NAME;
+            slot[13] = checksum;
+            for (j, &offset) in LFN_CHAR_OFFSETS.iter().enumerate() {
+                let index = (order - 1) * LFN_CHARS_PER_ENTRY + j;
+                // Name is terminated by 0x0000 then padded with 0xFFFF
+                let unit = match index {
+                    i if i < unit_count => units[i],
+                    i if i == unit_count => 0x0000,
+                    _ => 0xFFFF,
+                };
+                write_u16(&mut slot, offset, unit);
+            }
+            self.write_slot(parent.first_cluster, first_slot + i as u32, &slot)?;
+        }
+
+        let attributes = if directory { ATTR_DIRECTORY } else { ATTR_ARCHIVE };
+        let slot_index = first_slot + long_entries as u32;
+        let slot = short_entry(&short_name, attributes, first_cluster, 0);
+        let (lba, offset) = self.write_slot(parent.first_cluster, slot_index, &slot)?;
+
+        Ok(FatNode {
+            first_cluster,
+            size: 0,
+            attributes,
+            entry: Some(EntryRef { dir: parent.first_cluster, first_slot, slot: slot_index, lba, offset }),
+        })
+    }
+
+    /// Delete a file, or an empty directory, and free its clusters
+    pub fn remove(&self, node: &FatNode) -> Result<(), FsError> {
+        let entry = node.entry.ok_or(FsError::InvalidName)?;
+        if node.is_dir() {
+            let mut cursor = 0;
+            if self.read_dir(node, &mut cursor)?.is_some() {
+                return Err(FsError::DirectoryNotEmpty);
+            }
+        }
+
+        let mut deleted = [0u8; DIR_ENTRY_SIZE];
+        for index in entry.first_slot..=entry.slot {
+            let (lba, offset) = self.slot_location(entry.dir, index)?.ok_or(FsError::Corrupted)?;
+            let mut sector = [0u8; SECTOR_SIZE];
+            self.device.read_sectors(lba, &mut sector)?;
+            deleted.copy_from_slice(&sector[offset..offset + DIR_ENTRY_SIZE]);
+            deleted[0] = ENTRY_DELETED;
+            sector[offset..offset + DIR_ENTRY_SIZE].copy_from_slice(&deleted);
+            self.device.write_sectors(lba, &sector)?;
+        }
+
+        if node.first_cluster != 0 {
+            self.free_chain(node.first_cluster)?;
+        }
+        Ok(())
+    }
+
+    /// Commit pending writes to the disk
+    pub fn sync(&self) -> Result<(), FsError> {
+        self.device.flush()?;
+        Ok(())
+    }
+
+    fn inode_of(&self, node: &FatNode) -> u64 {
+        match node.entry {
+            None => ROOT_INODE,
+            Some(entry) => {
+                (entry.dir as u64) << 36 | ((entry.slot - entry.first_slot) as u64) << 31 | entry.slot as u64
+            }
+        }
+    }
+
+    fn node_of(&self, inode: u64) -> Result<FatNode, FsError> {
+        if inode == ROOT_INODE {
+            return Ok(self.root());
+        }
+        let dir = (inode >> 36) as u32;
+        let long_entries = ((inode >> 31) & 0x1F) as u32;
+        let slot = (inode & 0x7FFF_FFFF) as u32;
+        let (lba, offset) = self.slot_location(dir, slot)?.ok_or(FsError::NotFound)?;
+
+        let mut sector = [0u8; SECTOR_SIZE];
+        self.device.read_sectors(lba, &mut sector)?;
+        let raw = &sector[offset..offset + DIR_ENTRY_SIZE];
+        if raw[0] == ENTRY_END || raw[0] == ENTRY_DELETED || raw[11] == ATTR_LONG_NAME {
+            return Err(FsError::NotFound);
+        }
+        let entry = EntryRef { dir, first_slot: slot - long_entries, slot, lba, offset };
+        Ok(self.node_from_entry(raw, Some(entry)))
+    }
+
+    fn update_entry(&self, node: &FatNode) -> Result<(), FsError> {
+        let Some(entry) = node.entry else {
+            return Ok(());
+        };
+        let mut sector = [0u8; SECTOR_SIZE];
+        self.device.read_sectors(entry.lba, &mut sector)?;
+        let slot = &mut sector[entry.offset..entry.offset + DIR_ENTRY_SIZE];
+        write_u16(slot, 20, (node.first_cluster >> 16) as u16);
+        write_u16(slot, 26, node.first_cluster as u16);
+        write_u32(slot, 28, node.size);
+        write_u16(slot, 24, DEFAULT_DATE);
+        self.device.write_sectors(entry.lba, &sector)?;
+        Ok(())
+    }
+
+    fn write_dot_entries(&self, cluster: u32, parent: &FatNode) -> Result<(), FsError> {
+        let mut sector = [0u8; SECTOR_SIZE];
+        let dot = short_entry(b".          ", ATTR_DIRECTORY, cluster, 0);
+        // ".." of a directory in the root points at cluster 0, even on FAT32
+        let parent_cluster = if parent.first_cluster == self.root_cluster { 0 } else { parent.first_cluster };
+        let dot_dot = short_entry(b"..         ", ATTR_DIRECTORY, parent_cluster, 0);
+        sector[..DIR_ENTRY_SIZE].copy_from_slice(&dot);
+        sector[DIR_ENTRY_SIZE..2 * DIR_ENTRY_SIZE].copy_from_slice(&dot_dot);
+        self.device.write_sectors(self.cluster_lba(cluster), &sector)?;
+        Ok(())
+    }
+
+    /// Build the 8.3 name of a new entry. Returns whether long name entries are needed too.
+    fn short_name(&self, dir: &FatNode, name: &str) -> Result<([u8; 11], bool), FsError> {
+        let (base, extension) = match name.rfind('.') {
+            Some(dot) if dot > 0 => (&name[..dot], &name[dot + 1..]),
+            _ => (name, ""),
+        };
+
+        let mut short = [b' '; 11];
+        let mut lossy = false;
+        let mut base_len = 0;
+        for char in base.chars() {
+            match short_name_char(char) {
+                Some(_) if base_len == 8 => lossy = true,
+                Some(byte) => {
+                    lossy |= byte as char != char;
+                    short[base_len] = byte;
+                    base_len += 1;
+                }
+                None => lossy = true,
+            }
+        }
+        let mut extension_len = 0;
+        for char in extension.chars() {
+            match short_name_char(char) {
+                Some(_) if extension_len == 3 => lossy = true,
+                Some(byte) => {
+                    lossy |= byte as char != char;
+                    short[8 + extension_len] = byte;
+                    extension_len += 1;
+                }
+                None => lossy = true,
+            }
+        }
+        if base_len == 0 {
+            short[0] = b'_';
+            base_len = 1;
+            lossy = true;
+        }
+
+        if !lossy {
+            return Ok((short, false));
+        }
+
+        // Add a "~N" numeric tail until the short name is unique in the directory
+        for number in 1..1_000_000u32 {
+            let mut digits = [0u8; 7];
+            let mut tail_len = 0;
+            let mut n = number;
+            while n > 0 {
+                digits[tail_len] = b'0' + (n % 10) as u8;
+                tail_len += 1;
+                n /= 10;
+            }
+            let keep = base_len.min(8 - tail_len - 1);
+            let mut candidate = short;
+            candidate[keep] = b'~';
+            for i in 0..tail_len {
+                candidate[keep + 1 + i] = digits[tail_len - 1 - i];
+            }
+            for byte in candidate[keep + 1 + tail_len..8].iter_mut() {
+                *byte = b' ';
+            }
+            if !self.short_name_exists(dir, &candidate)? {
+                return Ok((candidate, true));
+            }
+        }
+        Err(FsError::AlreadyExists)
+    }
+
+    fn short_name_exists(&self, dir: &FatNode, short: &[u8; 11]) -> Result<bool, FsError> {
+        let found = self.walk_dir(dir.first_cluster, |_, _, _, slot| {
+            match slot[0] {
+                ENTRY_END => ControlFlow::Break(false),
+                ENTRY_DELETED => ControlFlow::Continue(()),
+                _ if slot[11] == ATTR_LONG_NAME => ControlFlow::Continue(()),
+                _ if slot[0..11] == short[..] => ControlFlow::Break(true),
+                _ => ControlFlow::Continue(()),
+            }
+        })?;
+        Ok(found.unwrap_or(false))
+    }
+
+    /// Find `count` consecutive free slots in a directory, growing it if needed
+    fn find_free_slots(&self, dir: u32, count: u32) -> Result<u32, FsError> {
+        let mut run_start = 0;
+        let mut run_length = 0;
+        let mut total = 0;
+        let found = self.walk_dir(dir, |index, _, _, slot| {
+            total = index + 1;
+            if slot[0] == ENTRY_END || slot[0] == ENTRY_DELETED {
+                if run_length == 0 {
+                    run_start = index;
+                }
+                run_length += 1;
+                if run_length == count {
+                    return ControlFlow::Break(run_start);
+                }
+            } else {
+                run_length = 0;
+            }
+            ControlFlow::Continue(())
+        })?;
+        if let Some(first) = found {
+            return Ok(first);
+        }
+
+        // The FAT16 root directory has a fixed size
+        if dir == 0 {
+            return Err(FsError::NoSpace);
+        }
+        let first = if run_length > 0 { run_start } else { total };
+        let slots_per_cluster = self.cluster_bytes() as u32 / DIR_ENTRY_SIZE as u32;
+        let mut last = self.nth_cluster(dir, (total / slots_per_cluster).saturating_sub(1) as u64)?;
+        while total - first < count {
+            last = self.next_or_allocate(last)?;
+            total += slots_per_cluster;
+        }
+        Ok(first)
+    }
+
+    fn write_slot(&self, dir: u32, index: u32, slot: &[u8; DIR_ENTRY_SIZE]) -> Result<(u64, usize), FsError> {
+        let (lba, offset) = self.slot_location(dir, index)?.ok_or(FsError::Corrupted)?;
+        let mut sector = [0u8; SECTOR_SIZE];
+        self.device.read_sectors(lba, &mut sector)?;
+        sector[offset..offset + DIR_ENTRY_SIZE].copy_from_slice(slot);
+        self.device.write_sectors(lba, &sector)?;
+        Ok((lba, offset))
+    }
+
+    fn slot_location(&self, dir: u32, index: u32) -> Result<Option<(u64, usize)>, FsError> {
+        let mut sectors = DirSectors::new(self, dir);
+        let mut lba = None;
+        for _ in 0..=index / ENTRIES_PER_SECTOR {
+            lba = sectors.next()?;
+            if lba.is_none() {
+                return Ok(None);
+            }
+        }
+        let offset = (index % ENTRIES_PER_SECTOR) as usize * DIR_ENTRY_SIZE;
+        Ok(lba.map(|lba| (lba, offset)))
+    }
+
+    /// Call `visit(slot index, lba, offset in sector, slot)` for every 32-byte slot of a directory
+    fn walk_dir<R>(
+        &self,
+        dir: u32,
+        mut visit: impl FnMut(u32, u64, usize, &[u8]) -> ControlFlow<R>,
+    ) -> Result<Option<R>, FsError> {
+        let mut sectors = DirSectors::new(self, dir);
+        let mut index = 0;
+        let mut sector = [0u8; SECTOR_SIZE];
+        while let Some(lba) = sectors.next()? {
+            self.device.read_sectors(lba, &mut sector)?;
+            for (i, slot) in sector.chunks_exact(DIR_ENTRY_SIZE).enumerate() {
+                if let ControlFlow::Break(result) = visit(index, lba, i * DIR_ENTRY_SIZE, slot) {
+                    return Ok(Some(result));
+                }
+                index += 1;
+            }
+        }
+        Ok(None)
+    }
+
+    fn cluster_bytes(&self) -> u64 {
+        self.sectors_per_cluster as u64 * SECTOR_SIZE as u64
+    }
+
+    fn cluster_lba(&self, cluster: u32) -> u64 {
+        self.data_start + (cluster as u64 - 2) * self.sectors_per_cluster as u64
+    }
+
+    fn is_valid_cluster(&self, cluster: u32) -> bool {
+        (2..self.cluster_count + 2).contains(&cluster)
+    }
+
+    fn end_of_chain(&self) -> u32 {
+        match self.kind {
+            FatKind::Fat16 => 0xFFFF,
+            FatKind::Fat32 => 0x0FFF_FFFF,
+        }
+    }
+
+    fn fat_entry_location(&self, cluster: u32) -> (u64, usize) {
+        let entry_size = match self.kind {
+            FatKind::Fat16 => 2,
+            FatKind::Fat32 => 4,
+        };
+        let offset = cluster as u64 * entry_size;
+        (self.fat_start + offset / SECTOR_SIZE as u64, (offset % SECTOR_SIZE as u64) as usize)
+    }
+
+    fn fat_entry(&self, cluster: u32) -> Result<u32, FsError> {
+        let (lba, offset) = self.fat_entry_location(cluster);
+        let mut sector = [0u8; SECTOR_SIZE];
+        self.device.read_sectors(lba, &mut sector)?;
+        Ok(match self.kind {
+            FatKind::Fat16 => read_u16(&sector, offset) as u32,
+            FatKind::Fat32 => read_u32(&sector, offset) & 0x0FFF_FFFF,
+        })
+    }
+
+    /// Update an entry in every copy of the FAT
+    fn set_fat_entry(&self, cluster: u32, value: u32) -> Result<(), FsError> {
+        let (lba, offset) = self.fat_entry_location(cluster);
+        let mut sector = [0u8; SECTOR_SIZE];
+        for copy in 0..self.fat_count as u64 {
+            let lba = lba + copy * self.fat_sectors;
+            self.device.read_sectors(lba, &mut sector)?;
+            match self.kind {
+                FatKind::Fat16 => write_u16(&mut sector, offset, value as u16),
+                FatKind::Fat32 => {
+                    // The top 4 bits are reserved and must be preserved
+                    let reserved = read_u32(&sector, offset) & 0xF000_0000;
+                    write_u32(&mut sector, offset, reserved | (value & 0x0FFF_FFFF));
+                }
+            }
+            self.device.write_sectors(lba, &sector)?;
+        }
+        Ok(())
+    }
+
+    /// Next cluster of a chain, None at the end of the chain
+    fn next_cluster(&self, cluster: u32) -> Result<Option<u32>, FsError> {
+        let next = self.fat_entry(cluster)?;
+        let end = match self.kind {
+            FatKind::Fat16 => 0xFFF8,
+            FatKind::Fat32 => 0x0FFF_FFF8,
+        };
+        if next >= end {
+            Ok(None)
+        } else if self.is_valid_cluster(next) {
+            Ok(Some(next))
+        } else {
+            Err(FsError::Corrupted)
+        }
+    }
+
+    fn nth_cluster(&self, first: u32, n: u64) -> Result<u32, FsError> {
+        let mut cluster = first;
+        for _ in 0..n {
+            cluster = self.next_cluster(cluster)?.ok_or(FsError::Corrupted)?;
+        }
+        Ok(cluster)
+    }
+
+    fn next_or_allocate(&self, cluster: u32) -> Result<u32, FsError> {
+        match self.next_cluster(cluster)? {
+            Some(next) => Ok(next),
+            None => {
+                let next = self.allocate_cluster()?;
+                self.set_fat_entry(cluster, next)?;
+                Ok(next)
+            }
+        }
+    }
+
+    /// Take a free cluster, mark it as the end of a chain and zero its content
+    fn allocate_cluster(&self) -> Result<u32, FsError> {
+        let mut start = self.next_free.get();
+        if !self.is_valid_cluster(start) {
+            start = 2;
+        }
+        let mut cluster = start;
+        while self.fat_entry(cluster)? != 0 {
+            cluster += 1;
+            if !self.is_valid_cluster(cluster) {
+                cluster = 2;
+            }
+            if cluster == start {
+                return Err(FsError::NoSpace);
+            }
+        }
+
+        self.set_fat_entry(cluster, self.end_of_chain())?;
+        self.next_free.set(cluster + 1);
+        self.invalidate_fsinfo()?;
+
+        let zeros = [0u8; SECTOR_SIZE];
+        let lba = self.cluster_lba(cluster);
+        for i in 0..self.sectors_per_cluster as u64 {
+            self.device.write_sectors(lba + i, &zeros)?;
+        }
+        Ok(cluster)
+    }
+
+    fn free_chain(&self, first: u32) -> Result<(), FsError> {
+        let mut cluster = Some(first);
+        // A corrupted FAT could loop forever
+        let mut budget = self.cluster_count;
+        while let Some(current) = cluster {
+            if budget == 0 {
+                return Err(FsError::Corrupted);
+            }
+            budget -= 1;
+            cluster = self.next_cluster(current)?;
+            self.set_fat_entry(current, 0)?;
+        }
+        if first < self.next_free.get() {
+            self.next_free.set(first);
+        }
+        self.invalidate_fsinfo()
+    }
+
+    /// We do not keep the FSInfo free count up to date, so mark it as unknown
+    /// the first time the FAT changes (readers then recompute it).
+    fn invalidate_fsinfo(&self) -> Result<(), FsError> {
+        let Some(lba) = self.fsinfo_sector else {
+            return Ok(());
+        };
+        if self.fsinfo_stale.replace(true) {
+            return Ok(());
+        }
+        let mut info = [0u8; SECTOR_SIZE];
+        self.device.read_sectors(lba, &mut info)?;
+        write_u32(&mut info, FSINFO_FREE_COUNT, 0xFFFF_FFFF);
+        write_u32(&mut info, FSINFO_NEXT_FREE, 0xFFFF_FFFF);
+        self.device.write_sectors(lba, &info)?;
+        Ok(())
+    }
+}
+
+/// Iterate over the sectors of a directory: the fixed root region on FAT16,
+/// otherwise the sectors of its cluster chain.
+struct DirSectors<'f, 'a> {
+    fs: &'f FatFs<'a>,
+    cluster: u32,
+    sector: u32,
+}
+
+impl<'f, 'a> DirSectors<'f, 'a> {
+    fn new(fs: &'f FatFs<'a>, dir: u32) -> Self {
+        DirSectors { fs, cluster: dir, sector: 0 }
+    }
+
+    fn next(&mut self) -> Result<Option<u64>, FsError> {
+        if self.cluster == 0 {
+            if self.sector >= self.fs.root_dir_sectors {
+                return Ok(None);
+            }
+            self.sector += 1;
+            return Ok(Some(self.fs.root_dir_start + self.sector as u64 - 1));
+        }
+
+        if self.sector == self.fs.sectors_per_cluster {
+            match self.fs.next_cluster(self.cluster)? {
+                Some(next) => self.cluster = next,
+                None => return Ok(None),
+            }
+            self.sector = 0;
+        }
+        self.sector += 1;
+        Ok(Some(self.fs.cluster_lba(self.cluster) + self.sector as u64 - 1))
+    }
+}
+
+/// Long name being assembled from the entries preceding a short entry
+struct LongName {
+    units: [u16; LFN_MAX_ENTRIES * LFN_CHARS_PER_ENTRY],
+    /// Order of the last long entry seen (entries count down to 1)
+    order: u8,
+    checksum: u8,
+    first_slot: Option<u32>,
+}
+
+impl LongName {
+    fn new() -> Self {
+        LongName { units: [0; LFN_MAX_ENTRIES * LFN_CHARS_PER_ENTRY], order: 0, checksum: 0, first_slot: None }
+    }
+
+    fn reset(&mut self) {
+        self.order = 0;
+        self.first_slot = None;
+    }
+
+    fn push(&mut self, index: u32, slot: &[u8]) {
+        let order = slot[0] & 0x1F;
+        if slot[0] & LFN_LAST != 0 {
+            if order == 0 || order as usize > LFN_MAX_ENTRIES {
+                self.reset();
+                return;
+            }
+            self.units.fill(0xFFFF);
+            self.checksum = slot[13];
+            self.first_slot = Some(index);
+        } else if self.order == 0 || order != self.order - 1 || slot[13] != self.checksum {
+            // Orphan long name entry, ignore it
+            self.reset();
+            return;
+        }
+        self.order = order;
+        let base = (order as usize - 1) * LFN_CHARS_PER_ENTRY;
+        for (i, &offset) in LFN_CHAR_OFFSETS.iter().enumerate() {
+            self.units[base + i] = read_u16(slot, offset);
+        }
+    }
+
+    /// The long name of the short entry `slot`, if the assembled one belongs to it
+    fn take(&self, slot: &[u8]) -> Option<FileName> {
+        if self.order != 1 || self.checksum != short_name_checksum(&slot[0..11]) {
+            return None;
+        }
+        let mut name = FileName::empty();
+        let units = self.units.iter().copied().take_while(|&unit| unit != 0x0000 && unit != 0xFFFF);
+        for char in char::decode_utf16(units) {
+            if !name.push(char.unwrap_or(char::REPLACEMENT_CHARACTER)) {
+                break;
+            }
+        }
+        Some(name)
+    }
+}
+
+fn short_name_to_string(slot: &[u8]) -> FileName {
+    let mut name = FileName::empty();
+    let case = slot[12];
+    let push_part = |name: &mut FileName, part: &[u8], lower: bool| {
+        for (i, &byte) in part.iter().enumerate() {
+            let byte = if i == 0 && byte == ENTRY_KANJI_E5 { ENTRY_DELETED } else { byte };
+            let char = match byte {
+                0x20..0x7F if lower => byte.to_ascii_lowercase() as char,
+                0x20..0x7F => byte as char,
+                _ => '?',
+            };
+            name.push(char);
+        }
+    };
+
+    let base = trim_spaces(&slot[0..8]);
+    let extension = trim_spaces(&slot[8..11]);
+    push_part(&mut name, base, case & CASE_LOWER_BASE != 0);
+    if !extension.is_empty() {
+        name.push('.');
+        push_part(&mut name, extension, case & CASE_LOWER_EXT != 0);
+    }
+    name
+}
+
+fn trim_spaces(bytes: &[u8]) -> &[u8] {
+    let end = bytes.iter().rposition(|&byte| byte != b' ').map_or(0, |i| i + 1);
+    &bytes[..end]
+}
+
+fn short_name_checksum(short: &[u8]) -> u8 {
+    short.iter().fold(0u8, |sum, &byte| ((sum & 1) << 7).wrapping_add(sum >> 1).wrapping_add(byte))
+}
+
+/// Character as stored in a short name, None for characters that are dropped
+fn short_name_char(char: char) -> Option<u8> {
+    match char {
+        ' ' | '.' => None,
+        'a'..='z' => Some(char.to_ascii_uppercase() as u8),
+        'A'..='Z' | '0'..='9' => Some(char as u8),
+        '!' | '#' | '$' | '%' | '&' | '\'' | '(' | ')' | '-' | '@' | '^' | '_' | '`' | '{' | '}' | '~' => Some(char as u8),
+        _ => Some(b'_'),
+    }
+}
+
+/// Validate a new file name and encode it as UTF-16 for the long name entries
+fn encode_long_name(name: &str, units: &mut [u16]) -> Result<usize, FsError> {
+    if name.is_empty() || name == "." || name == ".." || name.ends_with(' ') || name.ends_with('.') {
+        return Err(FsError::InvalidName);
+    }
+    let mut count = 0;
+    for char in name.chars() {
+        if (char as u32) < 0x20 || "\"*/:<>?\\|".contains(char) {
+            return Err(FsError::InvalidName);
+        }
+        let mut buffer = [0u16; 2];
+        for &unit in char.encode_utf16(&mut buffer).iter() {
+            if count == units.len() {
+                return Err(FsError::InvalidName);
+            }
+            units[count] = unit;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+fn short_entry(short_name: &[u8; 11], attributes: u8, first_cluster: u32, size: u32) -> [u8; DIR_ENTRY_SIZE] {
+    let mut slot = [0u8; DIR_ENTRY_SIZE];
+    slot[0..11].copy_from_slice(short_name);
+    slot[11] = attributes;
+    write_u16(&mut slot, 16, DEFAULT_DATE); // creation date
+    write_u16(&mut slot, 18, DEFAULT_DATE); // last access date
+    write_u16(&mut slot, 20, (first_cluster >> 16) as u16);
+    write_u16(&mut slot, 24, DEFAULT_DATE); // last write date
+    write_u16(&mut slot, 26, first_cluster as u16);
+    write_u32(&mut slot, 28, size);
+    slot
+}
+
+impl FileSystem for FatFs<'_> {
+    fn name(&self) -> &str {
+        "fat"
+    }
+
+    fn root(&self) -> u64 {
+        ROOT_INODE
+    }
+
+    fn lookup(&self, dir: u64, name: &str) -> Result<u64, FsError> {
+        let dir = self.node_of(dir)?;
+        let node = FatFs::lookup(self, &dir, name)?;
+        Ok(self.inode_of(&node))
+    }
+
+    fn stat(&self, inode: u64) -> Result<Stat, FsError> {
+        let node = self.node_of(inode)?;
+        let (file_type, permissions) = if node.is_dir() { (FileType::Directory, 0o755) } else { (FileType::Regular, 0o644) };
+        // The read-only attribute removes the write bits
+        let permissions = if node.attributes & ATTR_READ_ONLY != 0 { permissions & 0o555 } else { permissions };
+        Ok(Stat { file_type, size: node.size as u64, inode, links: 1, permissions })
+    }
+
+    fn read(&self, inode: u64, offset: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        let node = self.node_of(inode)?;
+        FatFs::read(self, &node, offset, buffer)
+    }
+
+    fn read_dir(&self, dir: u64, cursor: &mut u64) -> Result<Option<DirEntry>, FsError> {
+        let dir = self.node_of(dir)?;
+        let mut slot = *cursor as u32;
+        let entry = FatFs::read_dir(self, &dir, &mut slot)?;
+        *cursor = slot as u64;
+        Ok(entry.map(|entry| DirEntry {
+            name: entry.name,
+            inode: self.inode_of(&entry.node),
+            file_type: if entry.node.is_dir() { FileType::Directory } else { FileType::Regular },
+        }))
+    }
+
+    fn write(&self, inode: u64, offset: u64, data: &[u8]) -> Result<usize, FsError> {
+        let mut node = self.node_of(inode)?;
+        FatFs::write(self, &mut node, offset, data)
+    }
+
+    fn create(&self, dir: u64, name: &str, file_type: FileType) -> Result<u64, FsError> {
+        let directory = match file_type {
+            FileType::Regular => false,
+            FileType::Directory => true,
+            _ => return Err(FsError::Unsupported),
+        };
+        let dir = self.node_of(dir)?;
+        let node = FatFs::create(self, &dir, name, directory)?;
+        Ok(self.inode_of(&node))
+    }
+
+    fn remove(&self, dir: u64, name: &str) -> Result<(), FsError> {
+        let dir = self.node_of(dir)?;
+        let node = FatFs::lookup(self, &dir, name)?;
+        FatFs::remove(self, &node)
+    }
+
+    fn truncate(&self, inode: u64, size: u64) -> Result<(), FsError> {
+        let size = u32::try_from(size).map_err(|_| FsError::NoSpace)?;
+        let mut node = self.node_of(inode)?;
+        FatFs::truncate(self, &mut node, size)
+    }
+
+    fn link(&self, _dir: u64, _name: &str, _inode: u64) -> Result<(), FsError> {
+        Err(FsError::Unsupported)
+    }
+
+    fn sync(&self) -> Result<(), FsError> {
+        FatFs::sync(self)
+    }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+    use crate::block::BlockError;
+    use crate::testing::{list_dir, read_file, MemoryDisk};
+
+    const KINDS: [FatKind; 2] = [FatKind::Fat16, FatKind::Fat32];
+
+    /// An empty volume. FAT16: 2 sectors per cluster and a root region of 512 entries at
+    /// LBA 35. FAT32: 1 sector per cluster, the root in cluster 2 (LBA 1058) and FSInfo at LBA 1.
+    fn format(kind: FatKind) -> Vec<u8> {
+        let (clusters, per_cluster, reserved, root_entries, entry_size) = match kind {
+            FatKind::Fat16 => (4200u32, 2u32, 1u32, 512u32, 2u32),
+            FatKind::Fat32 => (65600, 1, 32, 0, 4),
+        };
+        let fat_sectors = ((clusters + 2) * entry_size).div_ceil(SECTOR_SIZE as u32);
+        let data_start = reserved + 2 * fat_sectors + root_entries * DIR_ENTRY_SIZE as u32 / SECTOR_SIZE as u32;
+        let total = data_start + clusters * per_cluster;
+        let mut image = vec![0u8; total as usize * SECTOR_SIZE];
+
+        let boot = &mut image[..SECTOR_SIZE];
+        write_u16(boot, 11, SECTOR_SIZE as u16);
+        boot[13] = per_cluster as u8;
+        write_u16(boot, 14, reserved as u16);
+        boot[16] = 2;
+        write_u16(boot, 17, root_entries as u16);
+        match kind {
+            FatKind::Fat16 => {
+                write_u16(boot, 19, total as u16);
+                write_u16(boot, 22, fat_sectors as u16);
+            }
+            FatKind::Fat32 => {
+                write_u32(boot, 32, total);
+                write_u32(boot, 36, fat_sectors);
+                write_u32(boot, 44, 2);
+                write_u16(boot, 48, 1);
+            }
+        }
+        boot[510..512].copy_from_slice(&[0x55, 0xAA]);
+
+        // Media and end of chain markers, then the chain of the FAT32 root
+        let reserved_entries: &[u32] = match kind {
+            FatKind::Fat16 => &[0xFFF8, 0xFFFF],
+            FatKind::Fat32 => {
+                let info = &mut image[SECTOR_SIZE..2 * SECTOR_SIZE];
+                for (offset, value) in [(0, FSINFO_LEAD_SIGNATURE), (484, FSINFO_STRUCT_SIGNATURE), (488, clusters - 1), (492, 3)] {
+                    write_u32(info, offset, value);
+                }
+                &[0x0FFF_FFF8, 0x0FFF_FFFF, 0x0FFF_FFFF]
+            }
+        };
+        for copy in 0..2 {
+            let fat = &mut image[(reserved + copy * fat_sectors) as usize * SECTOR_SIZE..];
+            for (i, &value) in reserved_entries.iter().enumerate() {
+                match kind {
+                    FatKind::Fat16 => write_u16(fat, i * 2, value as u16),
+                    FatKind::Fat32 => write_u32(fat, i * 4, value),
+                }
+            }
+        }
+        image
+    }
+
+    fn root_lba(kind: FatKind) -> u64 {
+        match kind {
+            FatKind::Fat16 => 35,
+            FatKind::Fat32 => 1058,
+        }
+    }
+
+    /// The 8.3 names in the first sector of the root directory, as stored
+    fn short_names(disk: &MemoryDisk, kind: FatKind) -> Vec<String> {
+        let mut sector = [0u8; SECTOR_SIZE];
+        disk.read_sectors(root_lba(kind), &mut sector).unwrap();
+        sector.chunks_exact(DIR_ENTRY_SIZE)
+            .filter(|slot| slot[0] != ENTRY_END && slot[0] != ENTRY_DELETED && slot[11] != ATTR_LONG_NAME)
+            .map(|slot| String::from_utf8_lossy(&slot[..11]).into_owned())
+            .collect()
+    }
+
+    fn resolve(fs: &dyn FileSystem, path: &str) -> Result<u64, FsError> {
+        path.split('/').try_fold(fs.root(), |dir, name| fs.lookup(dir, name))
+    }
+
+    #[test]
+    fn mounts_both_kinds() {
+        for kind in KINDS {
+            let disk = MemoryDisk::new(format(kind));
+            let fat = FatFs::mount(&disk).unwrap();
+            let fs: &dyn FileSystem = &fat;
+            assert_eq!(fat.kind, kind);
+            assert_eq!(list_dir(fs, fs.root()), Vec::<String>::new());
+        }
+    }
+
+    #[test]
+    fn files_and_directories() {
+        let data: Vec<u8> = (0..1500u32).map(|i| (i * 7) as u8).collect();
+        for kind in KINDS {
+            let disk = MemoryDisk::new(format(kind));
+            let fat = FatFs::mount(&disk).unwrap();
+            let fs: &dyn FileSystem = &fat;
+            let root = fs.root();
+            let docs = fs.create(root, "docs", FileType::Directory).unwrap();
+            let file = fs.create(docs, "readme.txt", FileType::Regular).unwrap();
+            // Crosses clusters of both volumes
+            assert_eq!(fs.write(file, 0, &data), Ok(data.len()));
+
+            assert_eq!(resolve(fs, "DOCS/README.TXT"), Ok(file));
+            assert_eq!(read_file(fs, file), data);
+            let stat = fs.stat(file).unwrap();
+            assert_eq!((stat.file_type, stat.size), (FileType::Regular, 1500));
+            assert_eq!(fs.stat(docs).unwrap().file_type, FileType::Directory);
+            assert_eq!(list_dir(fs, docs), ["readme.txt"]);
+
+            // The volume is the same once mounted again
+            let fat = FatFs::mount(&disk).unwrap();
+            let fs: &dyn FileSystem = &fat;
+            assert_eq!(read_file(fs, resolve(fs, "docs/readme.txt").unwrap()), data);
+            assert_eq!(fs.create(docs, "README.TXT", FileType::Regular), Err(FsError::AlreadyExists));
+            assert_eq!(fs.remove(root, "docs"), Err(FsError::DirectoryNotEmpty));
+            fs.remove(docs, "readme.txt").unwrap();
+            fs.remove(root, "docs").unwrap();
+            assert_eq!(list_dir(fs, root), Vec::<String>::new());
+            assert_eq!(resolve(fs, "docs"), Err(FsError::NotFound));
+        }
+    }
+
+    #[test]
+    fn freed_clusters_are_reused() {
+        let disk = MemoryDisk::new(format(FatKind::Fat32));
+        let fat = FatFs::mount(&disk).unwrap();
+            let fs: &dyn FileSystem = &fat;
+        let mut first = fat.create(&fat.root(), "first", false).unwrap();
+        fat.write(&mut first, 0, &[1; 3 * SECTOR_SIZE]).unwrap();
+        // The FSInfo hint said 3
+        assert_eq!(first.first_cluster, 3);
+        fs.remove(fs.root(), "first").unwrap();
+
+        let mut second = fat.create(&fat.root(), "second", false).unwrap();
+        fat.write(&mut second, 0, b"x").unwrap();
+        assert_eq!(second.first_cluster, 3);
+
+        // The free count is no longer known
+        let mut info = [0u8; SECTOR_SIZE];
+        disk.read_sectors(1, &mut info).unwrap();
+        assert_eq!(read_u32(&info, FSINFO_FREE_COUNT), 0xFFFF_FFFF);
+    }
+
+    #[test]
+    fn long_and_short_names() {
+        for kind in KINDS {
+            let disk = MemoryDisk::new(format(kind));
+            let fat = FatFs::mount(&disk).unwrap();
+            let fs: &dyn FileSystem = &fat;
+            let root = fs.root();
+            for name in ["README.TXT", "Readme2.txt", "A long file name.txt", "A long file name 2.txt", "ş.txt", ".profile"] {
+                fs.create(root, name, FileType::Regular).unwrap();
+            }
+            assert_eq!(list_dir(fs, root), ["README.TXT", "Readme2.txt", "A long file name.txt", "A long file name 2.txt", "ş.txt", ".profile"]);
+            // 'ş' has no 8.3 character: "_" is not the name, so it needs a long name too
+            assert_eq!(short_names(&disk, kind), ["README  TXT", "README~1TXT", "ALONGF~1TXT", "ALONGF~2TXT", "_~1     TXT", "PROFIL~1   "]);
+            assert!(resolve(fs, "ş.txt").is_ok());
+            assert!(resolve(fs, "a LONG file NAME.TXT").is_ok());
+        }
+    }
+
+    #[test]
+    fn invalid_names() {
+        let disk = MemoryDisk::new(format(FatKind::Fat16));
+        let fat = FatFs::mount(&disk).unwrap();
+            let fs: &dyn FileSystem = &fat;
+        let root = fs.root();
+        let long = "x".repeat(LFN_MAX_ENTRIES * LFN_CHARS_PER_ENTRY + 1);
+        for name in ["", ".", "..", "trailing.", "trailing ", "a:b", "a*b", "a/b", "tab\t", &long] {
+            assert_eq!(fs.create(root, name, FileType::Regular), Err(FsError::InvalidName), "{:?}", name);
+        }
+        assert_eq!(fs.create(root, "link", FileType::Symlink), Err(FsError::Unsupported));
+        let file = fs.create(root, "file", FileType::Regular).unwrap();
+        assert_eq!(fs.create(file, "inside", FileType::Regular), Err(FsError::NotADirectory));
+        assert_eq!(fs.link(root, "other", file), Err(FsError::Unsupported));
+    }
+
+    #[test]
+    fn holes_and_truncate() {
+        for kind in KINDS {
+            let disk = MemoryDisk::new(format(kind));
+            let fat = FatFs::mount(&disk).unwrap();
+            let fs: &dyn FileSystem = &fat;
+            let file = fs.create(fs.root(), "file", FileType::Regular).unwrap();
+            fs.write(file, 3000, b"end").unwrap();
+            let data = read_file(fs, file);
+            assert_eq!(data.len(), 3003);
+            assert!(data[..3000].iter().all(|&byte| byte == 0));
+
+            fs.truncate(file, 10).unwrap();
+            assert_eq!(fs.stat(file).unwrap().size, 10);
+            fs.truncate(file, 2000).unwrap();
+            assert_eq!(read_file(fs, file), vec![0; 2000]);
+            fs.truncate(file, 0).unwrap();
+            assert_eq!(fs.read(file, 0, &mut [0; 4]), Ok(0));
+            assert_eq!(fs.truncate(file, 1 << 32), Err(FsError::NoSpace));
+        }
+    }
+
+    #[test]
+    fn fat16_root_is_full() {
+        let disk = MemoryDisk::new(format(FatKind::Fat16));
+        let fat = FatFs::mount(&disk).unwrap();
+            let fs: &dyn FileSystem = &fat;
+        let root = fs.root();
+        for i in 0..512 {
+            fs.create(root, &format!("F{}", i), FileType::Regular).unwrap();
+        }
+        assert_eq!(fs.create(root, "MORE", FileType::Regular), Err(FsError::NoSpace));
+        // Subdirectories grow
+        fs.remove(root, "F0").unwrap();
+        let dir = fs.create(root, "DIR", FileType::Directory).unwrap();
+        for i in 0..100 {
+            fs.create(dir, &format!("F{}", i), FileType::Regular).unwrap();
+        }
+        assert_eq!(list_dir(fs, dir).len(), 100);
+    }
+
+    #[test]
+    fn bad_volumes() {
+        let image = format(FatKind::Fat16);
+        let mount = |image: Vec<u8>| FatFs::mount(&MemoryDisk::new(image)).err();
+
+        let mut no_signature = image.clone();
+        no_signature[511] = 0;
+        assert_eq!(mount(no_signature), Some(FsError::BadSuperblock));
+        let mut big_sectors = image.clone();
+        write_u16(&mut big_sectors, 11, 1024);
+        assert_eq!(mount(big_sectors), Some(FsError::Unsupported));
+        let mut odd_clusters = image.clone();
+        odd_clusters[13] = 3;
+        assert_eq!(mount(odd_clusters), Some(FsError::BadSuperblock));
+        let mut no_fat = image.clone();
+        no_fat[16] = 0;
+        assert_eq!(mount(no_fat), Some(FsError::BadSuperblock));
+        // Fewer than 4085 clusters is FAT12
+        let mut fat12 = image.clone();
+        write_u16(&mut fat12, 19, 67 + 4000 * 2);
+        assert_eq!(mount(fat12), Some(FsError::Unsupported));
+        let mut no_data = image.clone();
+        write_u16(&mut no_data, 19, 67);
+        assert_eq!(mount(no_data), Some(FsError::BadSuperblock));
+        let mut bad_root = format(FatKind::Fat32);
+        write_u32(&mut bad_root, 44, 0);
+        assert_eq!(mount(bad_root), Some(FsError::BadSuperblock));
+        assert_eq!(mount(Vec::new()), Some(FsError::Block(BlockError::OutOfRange)));
+
+        // The boot sector alone mounts, but the root directory is past the end
+        let truncated = MemoryDisk::new(image[..SECTOR_SIZE].to_vec());
+        let fs = FatFs::mount(&truncated).unwrap();
+        assert_eq!(fs.read_dir(&fs.root(), &mut 0).err(), Some(FsError::Block(BlockError::OutOfRange)));
+    }
+
+    #[test]
+    fn broken_cluster_chain() {
+        let disk = MemoryDisk::new(format(FatKind::Fat16));
+        let fat = FatFs::mount(&disk).unwrap();
+            let fs: &dyn FileSystem = &fat;
+        let mut node = fat.create(&fat.root(), "file", false).unwrap();
+        fat.write(&mut node, 0, &[7; 4 * SECTOR_SIZE]).unwrap();
+        // The file's first cluster now links to cluster 1, which doesn't exist
+        fat.set_fat_entry(node.first_cluster, 1).unwrap();
+        let file = resolve(fs, "file").unwrap();
+        assert_eq!(fs.read(file, 0, &mut [0; 4 * SECTOR_SIZE]), Err(FsError::Corrupted));
+        assert_eq!(fs.read(file, 0, &mut [0; 2 * SECTOR_SIZE]), Ok(2 * SECTOR_SIZE));
+
+        // A chain that loops
+        fat.set_fat_entry(node.first_cluster, node.first_cluster).unwrap();
+        assert_eq!(fs.remove(fs.root(), "file"), Err(FsError::Corrupted));
+    }
+}
@@ -0,0 +1,212 @@
+use crate::fs::vfs::{resolve, resolve_no_follow, resolve_parent, Vnode};
+use crate::fs::{DirEntry, FileType, FsError, Stat};
+
+/// Files one task can keep open at the same time
+pub const MAX_OPEN_FILES: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenFlags(u32);
+
+impl OpenFlags {
+    pub const READ: OpenFlags = OpenFlags(1 << 0);
+    pub const WRITE: OpenFlags = OpenFlags(1 << 1);
+    pub const READ_WRITE: OpenFlags = OpenFlags(Self::READ.0 | Self::WRITE.0);
+    /// Create the file if it does not exist
+    pub const CREATE: OpenFlags = OpenFlags(1 << 2);
+    /// Empty the file when opening it
+    pub const TRUNCATE: OpenFlags = OpenFlags(1 << 3);
+    /// Every write goes to the end of the file
+    pub const APPEND: OpenFlags = OpenFlags(1 << 4);
+
+    pub const fn union(self, other: OpenFlags) -> OpenFlags {
+        OpenFlags(self.0 | other.0)
+    }
+
+    pub const fn contains(self, other: OpenFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn from_bits(bits: u32) -> OpenFlags {
+        OpenFlags(bits)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SeekFrom {
+    Start(u64),
+    Current(i64),
+    End(i64),
+}
+
+/// File descriptor: index in the open file table of a task
+pub type Fd = usize;
+
+#[derive(Clone, Copy)]
+struct OpenFile {
+    vnode: Vnode,
+    /// Byte offset for files, read_dir cursor for directories
+    offset: u64,
+    flags: OpenFlags,
+}
+
+/// The open files of one task
+pub struct FileTable {
+    files: [Option<OpenFile>; MAX_OPEN_FILES],
+}
+
+impl FileTable {
+    pub const fn new() -> Self {
+        FileTable { files: [None; MAX_OPEN_FILES] }
+    }
+
+    pub fn open(&mut self, path: &str, flags: OpenFlags) -> Result<Fd, FsError> {
+        let fd = self.files.iter().position(|file| file.is_none()).ok_or(FsError::TooManyOpenFiles)?;
+
+        let vnode = match resolve(path) {
+            Ok(vnode) => vnode,
+            Err(FsError::NotFound) if flags.contains(OpenFlags::CREATE) => {
+                let (dir, name) = resolve_parent(path)?;
+                let inode = dir.fs.create(dir.inode, name.as_str(), FileType::Regular)?;
+                Vnode { fs: dir.fs, inode }
+            }
+            Err(error) => return Err(error),
+        };
+
+        let stat = vnode.fs.stat(vnode.inode)?;
+        if stat.file_type == FileType::Directory && flags.contains(OpenFlags::WRITE) {
+            return Err(FsError::IsADirectory);
+        }
+        if flags.contains(OpenFlags::TRUNCATE) && flags.contains(OpenFlags::WRITE) {
+            vnode.fs.truncate(vnode.inode, 0)?;
+        }
+
+        self.files[fd] = Some(OpenFile { vnode, offset: 0, flags });
+        Ok(fd)
+    }
+
+    pub fn close(&mut self, fd: Fd) -> Result<(), FsError> {
+        let file = self.files.get_mut(fd).and_then(Option::take).ok_or(FsError::BadDescriptor)?;
+        if file.flags.contains(OpenFlags::WRITE) {
+            file.vnode.fs.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Close every open file, ignoring errors (when a task ends)
+    pub fn close_all(&mut self) {
+        for fd in 0..MAX_OPEN_FILES {
+            if self.files[fd].is_some() {
+                let _ = self.close(fd);
+            }
+        }
+    }
+
+    fn get(&mut self, fd: Fd) -> Result<&mut OpenFile, FsError> {
+        self.files.get_mut(fd).and_then(Option::as_mut).ok_or(FsError::BadDescriptor)
+    }
+
+    pub fn read(&mut self, fd: Fd, buffer: &mut [u8]) -> Result<usize, FsError> {
+        let file = self.get(fd)?;
+        if !file.flags.contains(OpenFlags::READ) {
+            return Err(FsError::BadDescriptor);
+        }
+        let count = file.vnode.fs.read(file.vnode.inode, file.offset, buffer)?;
+        file.offset += count as u64;
+        Ok(count)
+    }
+
+    pub fn write(&mut self, fd: Fd, data: &[u8]) -> Result<usize, FsError> {
+        let file = self.get(fd)?;
+        if !file.flags.contains(OpenFlags::WRITE) {
+            return Err(FsError::BadDescriptor);
+        }
+        if file.flags.contains(OpenFlags::APPEND) {
+            file.offset = file.vnode.fs.stat(file.vnode.inode)?.size;
+        }
+        let count = file.vnode.fs.write(file.vnode.inode, file.offset, data)?;
+        file.offset += count as u64;
+        Ok(count)
+    }
+
+    /// Move the file offset, returns the new offset
+    pub fn seek(&mut self, fd: Fd, position: SeekFrom) -> Result<u64, FsError> {
+        let file = self.get(fd)?;
+        let offset = match position {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => file.offset.checked_add_signed(delta),
+            SeekFrom::End(delta) => file.vnode.fs.stat(file.vnode.inode)?.size.checked_add_signed(delta),
+        };
+        file.offset = offset.ok_or(FsError::InvalidArgument)?;
+        Ok(file.offset)
+    }
+
+    /// Next entry of an open directory, None once all entries were returned
+    pub fn read_dir(&mut self, fd: Fd) -> Result<Option<DirEntry>, FsError> {
+        let file = self.get(fd)?;
+        file.vnode.fs.read_dir(file.vnode.inode, &mut file.offset)
+    }
+
+    /// Device specific request, see `FileSystem::ioctl`
+    pub fn ioctl(&mut self, fd: Fd, request: u32, data: &mut [u8]) -> Result<usize, FsError> {
+        let file = self.get(fd)?;
+        file.vnode.fs.ioctl(file.vnode.inode, request, data)
+    }
+
+    /// Address where `length` bytes of the file at `offset` can be accessed directly.
+    /// They are mapped for reading and writing, so the file must be open for both.
+    pub fn mmap(&mut self, fd: Fd, offset: u64, length: usize) -> Result<usize, FsError> {
+        let file = self.get(fd)?;
+        if !file.flags.contains(OpenFlags::READ_WRITE) {
+            return Err(FsError::BadDescriptor);
+        }
+        file.vnode.fs.mmap(file.vnode.inode, offset, length)
+    }
+
+    pub fn vnode(&mut self, fd: Fd) -> Result<Vnode, FsError> {
+        Ok(self.get(fd)?.vnode)
+    }
+}
+
+/// Table used by the kernel itself
+static mut KERNEL_FILES: FileTable = FileTable::new();
+/// Table of the running process, swapped on a task switch
+static mut CURRENT_FILES: *mut FileTable = core::ptr::addr_of_mut!(KERNEL_FILES);
+
+/// Open file table of the running task
+pub fn current_files() -> &'static mut FileTable {
+    unsafe { &mut *CURRENT_FILES }
+}
+
+/// Make `table` the open file table used by `current_files` (called on task switch)
+pub unsafe fn set_current_files(table: *mut FileTable) {
+    unsafe { CURRENT_FILES = table; }
+}
+
+pub fn stat(path: &str) -> Result<Stat, FsError> {
+    let vnode = resolve(path)?;
+    vnode.fs.stat(vnode.inode)
+}
+
+pub fn mkdir(path: &str) -> Result<(), FsError> {
+    let (dir, name) = resolve_parent(path)?;
+    dir.fs.create(dir.inode, name.as_str(), FileType::Directory)?;
+    dir.fs.sync()
+}
+
+/// Remove a file or an empty directory
+pub fn unlink(path: &str) -> Result<(), FsError> {
+    let (dir, name) = resolve_parent(path)?;
+    dir.fs.remove(dir.inode, name.as_str())?;
+    dir.fs.sync()
+}
+
+/// Make `new_path` another name for the file at `existing` (hard link)
+pub fn link(existing: &str, new_path: &str) -> Result<(), FsError> {
+    let target = resolve_no_follow(existing)?;
+    let (dir, name) = resolve_parent(new_path)?;
+    if !core::ptr::addr_eq(target.fs, dir.fs) {
+        return Err(FsError::CrossDevice);
+    }
+    dir.fs.link(dir.inode, name.as_str(), target.inode)?;
+    dir.fs.sync()
+}
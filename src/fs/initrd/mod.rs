@@ -0,0 +1,319 @@
+use crate::fs::{DirEntry, FileName, FileSystem, FileType, FsError, Stat};
+
+/// Files and directories an initrd can hold (directories implied by paths included)
+pub const MAX_INITRD_NODES: usize = 256;
+
+const NEWC_MAGIC: &[u8; 6] = b"070701";
+const NEWC_HEADER_SIZE: usize = 110;
+const TRAILER: &str = "TRAILER!!!";
+
+const MODE_TYPE_MASK: u32 = 0o170000;
+const MODE_DIRECTORY: u32 = 0o040000;
+const MODE_SYMLINK: u32 = 0o120000;
+
+const ROOT_INODE: u64 = 0;
+
+#[derive(Clone, Copy)]
+struct InitrdNode {
+    /// Path without leading "./" or "/" ("" for the root)
+    path: &'static str,
+    file_type: FileType,
+    permissions: u16,
+    /// File content, or link target
+    data: &'static [u8],
+}
+
+/// Read-only filesystem over a newc cpio archive kept in memory
+/// (what `find . | cpio -o -H newc` produces)
+pub struct InitrdFs {
+    nodes: [Option<InitrdNode>; MAX_INITRD_NODES],
+    count: usize,
+}
+
+impl InitrdFs {
+    /// Parse the archive in `image`. Data after the trailer (padding) is ignored.
+    pub fn parse(image: &'static [u8]) -> Result<Self, FsError> {
+        if image.len() < NEWC_HEADER_SIZE || &image[..6] != NEWC_MAGIC {
+            return Err(FsError::BadSuperblock);
+        }
+
+        let mut fs = InitrdFs { nodes: [None; MAX_INITRD_NODES], count: 0 };
+        fs.add(InitrdNode { path: "", file_type: FileType::Directory, permissions: 0o755, data: &[] })?;
+
+        let mut offset = 0;
+        loop {
+            let header = image.get(offset..offset + NEWC_HEADER_SIZE).ok_or(FsError::Corrupted)?;
+            if &header[..6] != NEWC_MAGIC {
+                return Err(FsError::Corrupted);
+            }
+            let mode = hex_field(header, 1)?;
+            let file_size = hex_field(header, 6)? as usize;
+            let name_size = hex_field(header, 11)? as usize;
+
+            // The name (with its NUL) follows the header, then the data, both 4-byte aligned
+            let name_start = offset + NEWC_HEADER_SIZE;
+            let data_start = align4(name_start + name_size);
+            let data_end = data_start + file_size;
+            let raw_name = image.get(name_start..name_start + name_size.saturating_sub(1)).ok_or(FsError::Corrupted)?;
+            let data = image.get(data_start..data_end).ok_or(FsError::Corrupted)?;
+            let name = core::str::from_utf8(raw_name).map_err(|_| FsError::Corrupted)?;
+            offset = align4(data_end);
+
+            if name == TRAILER {
+                break;
+            }
+            let path = name.trim_start_matches("./").trim_start_matches('/').trim_end_matches('/');
+            if path.is_empty() || path == "." {
+                continue;
+            }
+
+            let file_type = match mode & MODE_TYPE_MASK {
+                MODE_DIRECTORY => FileType::Directory,
+                MODE_SYMLINK => FileType::Symlink,
+                _ => FileType::Regular,
+            };
+            fs.add_parents(path)?;
+            let node = InitrdNode { path, file_type, permissions: (mode & 0o777) as u16, data };
+            match fs.find(path) {
+                // A directory already created because a file inside it came first
+                Some(index) => fs.nodes[index] = Some(node),
+                None => fs.add(node)?,
+            }
+        }
+        Ok(fs)
+    }
+
+    fn add(&mut self, node: InitrdNode) -> Result<(), FsError> {
+        if self.count == MAX_INITRD_NODES {
+            return Err(FsError::NoSpace);
+        }
+        self.nodes[self.count] = Some(node);
+        self.count += 1;
+        Ok(())
+    }
+
+    /// cpio archives may list "a/b/c" without "a" and "a/b", create them
+    fn add_parents(&mut self, path: &'static str) -> Result<(), FsError> {
+        for (i, byte) in path.bytes().enumerate() {
+            if byte == b'/' && self.find(&path[..i]).is_none() {
+                self.add(InitrdNode { path: &path[..i], file_type: FileType::Directory, permissions: 0o755, data: &[] })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn find(&self, path: &str) -> Option<usize> {
+        self.nodes[..self.count].iter().position(|node| node.is_some_and(|node| node.path == path))
+    }
+
+    fn node(&self, inode: u64) -> Result<&InitrdNode, FsError> {
+        self.nodes.get(inode as usize).and_then(Option::as_ref).ok_or(FsError::NotFound)
+    }
+}
+
+impl FileSystem for InitrdFs {
+    fn name(&self) -> &str {
+        "initrd"
+    }
+
+    fn root(&self) -> u64 {
+        ROOT_INODE
+    }
+
+    fn lookup(&self, dir: u64, name: &str) -> Result<u64, FsError> {
+        let dir = self.node(dir)?;
+        if dir.file_type != FileType::Directory {
+            return Err(FsError::NotADirectory);
+        }
+        let found = self.nodes[..self.count].iter().position(|node| {
+            node.is_some_and(|node| parent_and_name(node.path) == Some((dir.path, name)))
+        });
+        found.map(|index| index as u64).ok_or(FsError::NotFound)
+    }
+
+    fn stat(&self, inode: u64) -> Result<Stat, FsError> {
+        let node = self.node(inode)?;
+        Ok(Stat {
+            file_type: node.file_type,
+            size: node.data.len() as u64,
+            inode,
+            links: 1,
+            permissions: node.permissions,
+        })
+    }
+
+    fn read(&self, inode: u64, offset: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        let node = self.node(inode)?;
+        if node.file_type == FileType::Directory {
+            return Err(FsError::IsADirectory);
+        }
+        let data = node.data.get(offset as usize..).unwrap_or(&[]);
+        let count = data.len().min(buffer.len());
+        buffer[..count].copy_from_slice(&data[..count]);
+        Ok(count)
+    }
+
+    fn read_dir(&self, dir: u64, cursor: &mut u64) -> Result<Option<DirEntry>, FsError> {
+        let dir = self.node(dir)?;
+        if dir.file_type != FileType::Directory {
+            return Err(FsError::NotADirectory);
+        }
+        while (*cursor as usize) < self.count {
+            let index = *cursor as usize;
+            *cursor += 1;
+            let Some(node) = &self.nodes[index] else {
+                continue;
+            };
+            if let Some((parent, name)) = parent_and_name(node.path)
+                && parent == dir.path
+            {
+                return Ok(Some(DirEntry { name: FileName::new(name), inode: index as u64, file_type: node.file_type }));
+            }
+        }
+        Ok(None)
+    }
+
+    fn read_link(&self, inode: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        let node = self.node(inode)?;
+        if node.file_type != FileType::Symlink {
+            return Err(FsError::InvalidName);
+        }
+        let count = node.data.len().min(buffer.len());
+        buffer[..count].copy_from_slice(&node.data[..count]);
+        Ok(count)
+    }
+}
+
+/// Split "a/b/c" into ("a/b", "c"), "a" into ("", "a"). None for the root.
+fn parent_and_name(path: &str) -> Option<(&str, &str)> {
+    if path.is_empty() {
+        return None;
+    }
+    Some(match path.rfind('/') {
+        Some(slash) => (&path[..slash], &path[slash + 1..]),
+        None => ("", path),
+    })
+}
+
+/// Field `index` of a newc header (8 hexadecimal digits each after the magic: ino, mode, ...)
+fn hex_field(header: &[u8], index: usize) -> Result<u32, FsError> {
+    let start = 6 + index * 8;
+    let digits = core::str::from_utf8(&header[start..start + 8]).map_err(|_| FsError::Corrupted)?;
+    u32::from_str_radix(digits, 16).map_err(|_| FsError::Corrupted)
+}
+
+fn align4(value: usize) -> usize {
+    (value + 3) & !3
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+    use crate::testing::{list_dir, read_file};
+
+    /// One newc entry: its header, name and data, each padded to 4 bytes
+    fn entry(name: &str, mode: u32, data: &[u8]) -> Vec<u8> {
+        let mut bytes = NEWC_MAGIC.to_vec();
+        let fields = [0, mode, 0, 0, 1, 0, data.len() as u32, 0, 0, 0, 0, name.len() as u32 + 1, 0];
+        for field in fields {
+            bytes.extend(format!("{:08X}", field).bytes());
+        }
+        bytes.extend(name.bytes());
+        bytes.push(0);
+        bytes.resize(align4(bytes.len()), 0);
+        bytes.extend(data);
+        bytes.resize(align4(bytes.len()), 0);
+        bytes
+    }
+
+    /// An archive of the entries, then the trailer and padding like cpio's
+    fn archive(entries: &[(&str, u32, &[u8])]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = entries.iter().flat_map(|&(name, mode, data)| entry(name, mode, data)).collect();
+        bytes.extend(entry(TRAILER, 0, &[]));
+        bytes.resize(bytes.len().next_multiple_of(512), 0);
+        bytes
+    }
+
+    fn parse(image: Vec<u8>) -> Result<InitrdFs, FsError> {
+        InitrdFs::parse(image.leak())
+    }
+
+    fn resolve(fs: &InitrdFs, path: &str) -> Result<u64, FsError> {
+        path.split('/').try_fold(fs.root(), |dir, name| fs.lookup(dir, name))
+    }
+
+    #[test]
+    fn files_directories_and_links() {
+        let fs = parse(archive(&[
+            (".", MODE_DIRECTORY | 0o755, b""),
+            // Before its directory, which comes later with other permissions
+            ("./etc/motd", 0o100644, b"welcome\n"),
+            ("./etc", MODE_DIRECTORY | 0o700, b""),
+            ("bin/hello", 0o100755, b"\x7fELF"),
+            ("./etc/link", MODE_SYMLINK | 0o777, b"motd"),
+        ])).unwrap();
+        let root = fs.root();
+        assert_eq!(list_dir(&fs, root), ["etc", "bin"]);
+        assert_eq!(list_dir(&fs, resolve(&fs, "etc").unwrap()), ["motd", "link"]);
+
+        let motd = resolve(&fs, "etc/motd").unwrap();
+        assert_eq!(read_file(&fs, motd), b"welcome\n");
+        let stat = fs.stat(motd).unwrap();
+        assert_eq!((stat.file_type, stat.size, stat.permissions), (FileType::Regular, 8, 0o644));
+        assert_eq!(fs.stat(resolve(&fs, "etc").unwrap()).unwrap().permissions, 0o700);
+        // Created for bin/hello
+        assert_eq!(fs.stat(resolve(&fs, "bin").unwrap()).unwrap().file_type, FileType::Directory);
+        assert_eq!(read_file(&fs, resolve(&fs, "bin/hello").unwrap()), b"\x7fELF");
+
+        let link = resolve(&fs, "etc/link").unwrap();
+        let mut target = [0u8; 16];
+        assert_eq!(fs.read_link(link, &mut target), Ok(4));
+        assert_eq!(&target[..4], b"motd");
+        assert_eq!(fs.read_link(motd, &mut target), Err(FsError::InvalidName));
+    }
+
+    #[test]
+    fn lookup_errors() {
+        let fs = parse(archive(&[("file", 0o100644, b"data")])).unwrap();
+        let file = resolve(&fs, "file").unwrap();
+        assert_eq!(resolve(&fs, "missing"), Err(FsError::NotFound));
+        assert_eq!(fs.lookup(file, "x"), Err(FsError::NotADirectory));
+        assert_eq!(fs.read(fs.root(), 0, &mut [0; 4]), Err(FsError::IsADirectory));
+        assert_eq!(fs.read(file, 10, &mut [0; 4]), Ok(0));
+        assert_eq!(fs.stat(MAX_INITRD_NODES as u64).err(), Some(FsError::NotFound));
+        assert_eq!(fs.write(file, 0, b"x"), Err(FsError::ReadOnly));
+    }
+
+    #[test]
+    fn bad_archives() {
+        assert_eq!(parse(Vec::new()).err(), Some(FsError::BadSuperblock));
+        assert_eq!(parse(vec![b'0'; 200]).err(), Some(FsError::BadSuperblock));
+
+        let image = archive(&[("file", 0o100644, b"some data")]);
+        // Cut in the data, and right before the trailer
+        let first = entry("file", 0o100644, b"some data").len();
+        for length in [NEWC_HEADER_SIZE + 10, first - 4, first] {
+            assert_eq!(parse(image[..length].to_vec()).err(), Some(FsError::Corrupted), "{} bytes", length);
+        }
+        let mut bad_digit = image.clone();
+        bad_digit[6 + 6 * 8] = b'G';
+        assert_eq!(parse(bad_digit).err(), Some(FsError::Corrupted));
+        let mut bad_magic = image.clone();
+        bad_magic[first] = b'1';
+        assert_eq!(parse(bad_magic).err(), Some(FsError::Corrupted));
+        let mut huge = image.clone();
+        huge[6 + 6 * 8..6 + 7 * 8].copy_from_slice(b"FFFFFFFF");
+        assert_eq!(parse(huge).err(), Some(FsError::Corrupted));
+        let mut not_utf8 = image.clone();
+        not_utf8[NEWC_HEADER_SIZE] = 0xFF;
+        assert_eq!(parse(not_utf8).err(), Some(FsError::Corrupted));
+    }
+
+    #[test]
+    fn too_many_files() {
+        let names: Vec<String> = (0..MAX_INITRD_NODES).map(|i| format!("f{}", i)).collect();
+        let entries: Vec<(&str, u32, &[u8])> = names.iter().map(|name| (name.as_str(), 0o100644, &b""[..])).collect();
+        assert_eq!(parse(archive(&entries[..MAX_INITRD_NODES - 1])).map(|fs| fs.count).ok(), Some(MAX_INITRD_NODES));
+        assert_eq!(parse(archive(&entries)).err(), Some(FsError::NoSpace));
+    }
+}
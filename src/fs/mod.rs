@@ -0,0 +1,360 @@
+use core::ptr::{addr_of, addr_of_mut};
+use crate::ata::get_drive;
+use crate::block::cache::{BlockCache, CacheStorage};
+use crate::block::BlockError;
+use crate::fs::devfs::DevFs;
+use crate::fs::ext2::Ext2Fs;
+use crate::fs::fat::FatFs;
+use crate::fs::initrd::InitrdFs;
+use crate::fs::procfs::ProcFs;
+use crate::fs::ramfs::{RamFs, RamStorage};
+use crate::partition::{Guid, PartitionKind, PartitionTable};
+use crate::{BLOCK_CACHE_ADDRESS, INITRD_ADDRESS, INITRD_MAX_SIZE, RAMFS_ADDRESS};
+
+pub mod devfs;
+pub mod ext2;
+pub mod fat;
+pub mod file;
+pub mod initrd;
+pub mod procfs;
+pub mod ramfs;
+pub mod vfs;
+
+/// Filesystems of each type that can be mounted from the disk at once
+const MAX_VOLUMES: usize = 4;
+
+// Everything a mounted disk filesystem borrows has to live forever
+static mut DISK_CACHE: Option<BlockCache<'static>> = None;
+static mut DISK_PARTITIONS: Option<PartitionTable<'static>> = None;
+static mut FAT_VOLUMES: [Option<FatFs<'static>>; MAX_VOLUMES] = [const { None }; MAX_VOLUMES];
+static mut EXT2_VOLUMES: [Option<Ext2Fs<'static>>; MAX_VOLUMES] = [const { None }; MAX_VOLUMES];
+static mut INITRD: Option<InitrdFs> = None;
+static mut RAMFS: Option<RamFs<'static>> = None;
+static DEVFS: DevFs = DevFs;
+static PROCFS: ProcFs = ProcFs;
+
+/// Longest file name (in UTF-8 bytes) a filesystem can hand out
+pub const MAX_NAME_LEN: usize = 255;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsError {
+    Block(BlockError),
+    /// The volume is not formatted with the expected filesystem
+    BadSuperblock,
+    /// The on-disk structures are inconsistent
+    Corrupted,
+    /// The filesystem uses a feature we do not implement
+    Unsupported,
+    NotFound,
+    NotADirectory,
+    IsADirectory,
+    AlreadyExists,
+    DirectoryNotEmpty,
+    InvalidName,
+    InvalidArgument,
+    /// No free cluster / block left on the volume
+    NoSpace,
+    ReadOnly,
+    /// The path does not fit in a `PathBuf`, or links to itself too many times
+    PathTooLong,
+    /// The file descriptor is not open (or not open for this operation)
+    BadDescriptor,
+    /// The open file table is full
+    TooManyOpenFiles,
+    /// The mount table is full, or the mount point is already used
+    MountFailed,
+    /// A hard link to a file on another filesystem
+    CrossDevice,
+}
+
+impl From<BlockError> for FsError {
+    fn from(error: BlockError) -> Self {
+        FsError::Block(error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    Regular,
+    Directory,
+    Symlink,
+    CharDevice,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Stat {
+    pub file_type: FileType,
+    pub size: u64,
+    /// Inode number, unique within a mounted filesystem
+    pub inode: u64,
+    pub links: u32,
+    /// rwxrwxrwx permission bits
+    pub permissions: u16,
+}
+
+#[derive(Clone, Copy)]
+pub struct DirEntry {
+    pub name: FileName,
+    pub inode: u64,
+    pub file_type: FileType,
+}
+
+/// Operations every filesystem (FAT, ext2, ramfs, device files, ...) provides to the VFS.
+/// Nodes are designated by inode numbers, whose meaning is private to each filesystem.
+pub trait FileSystem {
+    /// Short name of the filesystem type ("fat", "ext2", ...)
+    fn name(&self) -> &str;
+
+    fn root(&self) -> u64;
+
+    /// Find `name` in directory `dir`
+    fn lookup(&self, dir: u64, name: &str) -> Result<u64, FsError>;
+
+    fn stat(&self, inode: u64) -> Result<Stat, FsError>;
+
+    /// Read at `offset`, returns the number of bytes read (0 at the end of the file)
+    fn read(&self, inode: u64, offset: u64, buffer: &mut [u8]) -> Result<usize, FsError>;
+
+    /// Return the entry at `cursor` (start with 0) and advance the cursor, None at the end.
+    /// "." and ".." are not returned.
+    fn read_dir(&self, dir: u64, cursor: &mut u64) -> Result<Option<DirEntry>, FsError>;
+
+    fn write(&self, _inode: u64, _offset: u64, _data: &[u8]) -> Result<usize, FsError> {
+        Err(FsError::ReadOnly)
+    }
+
+    /// Create an empty regular file or directory, returns its inode
+    fn create(&self, _dir: u64, _name: &str, _file_type: FileType) -> Result<u64, FsError> {
+        Err(FsError::ReadOnly)
+    }
+
+    /// Remove a file or an empty directory
+    fn remove(&self, _dir: u64, _name: &str) -> Result<(), FsError> {
+        Err(FsError::ReadOnly)
+    }
+
+    fn truncate(&self, _inode: u64, _size: u64) -> Result<(), FsError> {
+        Err(FsError::ReadOnly)
+    }
+
+    /// Give the existing file `inode` one more name, `name` in `dir` (hard link)
+    fn link(&self, _dir: u64, _name: &str, _inode: u64) -> Result<(), FsError> {
+        Err(FsError::ReadOnly)
+    }
+
+    /// Device specific request on a device file. `data` carries the request's
+    /// argument in and its result out, returns the number of bytes of result.
+    fn ioctl(&self, _inode: u64, _request: u32, _data: &mut [u8]) -> Result<usize, FsError> {
+        Err(FsError::Unsupported)
+    }
+
+    /// Address of the memory backing `length` bytes at `offset` of a device file,
+    /// for devices that can be accessed directly (like the framebuffer)
+    fn mmap(&self, _inode: u64, _offset: u64, _length: usize) -> Result<usize, FsError> {
+        Err(FsError::Unsupported)
+    }
+
+    /// Copy the target of a symbolic link into `buffer`, returns its length
+    fn read_link(&self, _inode: u64, _buffer: &mut [u8]) -> Result<usize, FsError> {
+        Err(FsError::InvalidName)
+    }
+
+    /// Commit pending writes to the underlying device
+    fn sync(&self) -> Result<(), FsError> {
+        Ok(())
+    }
+}
+
+/// File name stored inline, so directory listings do not need a heap
+#[derive(Clone, Copy)]
+pub struct FileName {
+    bytes: [u8; MAX_NAME_LEN],
+    len: u8,
+}
+
+impl FileName {
+    pub const fn empty() -> Self {
+        FileName { bytes: [0; MAX_NAME_LEN], len: 0 }
+    }
+
+    /// Copy `name`, cutting it at the last whole character that fits
+    pub fn new(name: &str) -> Self {
+        let mut file_name = FileName::empty();
+        for char in name.chars() {
+            if !file_name.push(char) {
+                break;
+            }
+        }
+        file_name
+    }
+
+    /// Append a character, returns false when it does not fit
+    pub fn push(&mut self, char: char) -> bool {
+        let mut utf8 = [0u8; 4];
+        let encoded = char.encode_utf8(&mut utf8).as_bytes();
+        let len = self.len as usize;
+        if len + encoded.len() > MAX_NAME_LEN {
+            return false;
+        }
+        self.bytes[len..len + encoded.len()].copy_from_slice(encoded);
+        self.len += encoded.len() as u8;
+        true
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only whole characters are ever pushed
+        core::str::from_utf8(&self.bytes[..self.len as usize]).unwrap_or("")
+    }
+}
+
+/// Split a path into its non-empty components ("/a//b/" -> "a", "b")
+pub fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|component| !component.is_empty() && *component != ".")
+}
+
+pub fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+pub fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+pub fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    read_u32(bytes, offset) as u64 | (read_u32(bytes, offset + 4) as u64) << 32
+}
+
+pub fn write_u16(bytes: &mut [u8], offset: usize, value: u16) {
+    bytes[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+pub fn write_u32(bytes: &mut [u8], offset: usize, value: u32) {
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Mount the initrd loaded by kernel_entry.asm as the root filesystem, a ramfs
+/// at "/tmp", the device files at "/dev", kernel information at "/proc", then the
+/// FAT and ext2 partitions of the first ATA disk at "/mnt/0", "/mnt/1", ...
+/// Without initrd, the ramfs becomes the root instead.
+pub fn init_fs() {
+    mount_initrd();
+    mount_ramfs();
+    let _ = vfs::mount("/dev", &DEVFS);
+    let _ = vfs::mount("/proc", &PROCFS);
+    mount_disk();
+}
+
+fn mount_initrd() {
+    let image = unsafe { core::slice::from_raw_parts(INITRD_ADDRESS as *const u8, INITRD_MAX_SIZE) };
+    let Ok(initrd) = InitrdFs::parse(image) else {
+        return;
+    };
+    let initrd = unsafe {
+        INITRD = Some(initrd);
+        (*addr_of!(INITRD)).as_ref().unwrap()
+    };
+    let _ = vfs::mount("/", initrd);
+}
+
+fn mount_ramfs() {
+    let ramfs = unsafe {
+        RAMFS = Some(RamFs::new(RamStorage::at(RAMFS_ADDRESS)));
+        (*addr_of!(RAMFS)).as_ref().unwrap()
+    };
+    let path = if vfs::resolve("/").is_ok() { "/tmp" } else { "/" };
+    let _ = vfs::mount(path, ramfs);
+}
+
+/// The ramfs mounted by init_fs
+pub fn ramfs() -> Option<&'static RamFs<'static>> {
+    unsafe { (*addr_of!(RAMFS)).as_ref() }
+}
+
+fn mount_disk() {
+    let Some(drive) = get_drive(0) else {
+        return;
+    };
+    let cache = unsafe {
+        DISK_CACHE = Some(BlockCache::new(drive, CacheStorage::at(BLOCK_CACHE_ADDRESS)));
+        (*addr_of!(DISK_CACHE)).as_ref().unwrap()
+    };
+    let Ok(table) = PartitionTable::parse(cache) else {
+        return;
+    };
+    let partitions = unsafe {
+        DISK_PARTITIONS = Some(table);
+        (*addr_of!(DISK_PARTITIONS)).as_ref().unwrap()
+    };
+
+    // The root is the initrd or the ramfs by now, disks go under /mnt
+    let mut mounted = 0;
+    let mut fat_count = 0;
+    let mut ext2_count = 0;
+    for partition in partitions.iter() {
+        let fs: &'static dyn FileSystem = match disk_format(partition.kind()) {
+            Some(DiskFormat::Fat) if fat_count < MAX_VOLUMES => {
+                let Ok(fat) = FatFs::mount(partition) else {
+                    continue;
+                };
+                unsafe {
+                    let volumes = &mut *addr_of_mut!(FAT_VOLUMES);
+                    volumes[fat_count] = Some(fat);
+                    fat_count += 1;
+                    volumes[fat_count - 1].as_ref().unwrap()
+                }
+            }
+            Some(DiskFormat::Ext2) if ext2_count < MAX_VOLUMES => {
+                let Ok(ext2) = Ext2Fs::mount(partition) else {
+                    continue;
+                };
+                unsafe {
+                    let volumes = &mut *addr_of_mut!(EXT2_VOLUMES);
+                    volumes[ext2_count] = Some(ext2);
+                    ext2_count += 1;
+                    volumes[ext2_count - 1].as_ref().unwrap()
+                }
+            }
+            _ => continue,
+        };
+
+        let mut path = vfs::PathBuf::root();
+        let mut digits = [0u8; 2];
+        if path.push("mnt").and_then(|_| path.push(format_decimal(mounted, &mut digits))).is_err() {
+            continue;
+        }
+        if vfs::mount(path.as_str(), fs).is_ok() {
+            mounted += 1;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiskFormat {
+    Fat,
+    Ext2,
+}
+
+/// The filesystem a partition type is meant for, None for the ones we don't read (swap, NTFS...)
+fn disk_format(kind: PartitionKind) -> Option<DiskFormat> {
+    match kind {
+        // FAT12, FAT16 (CHS and LBA), FAT32 (CHS and LBA)
+        PartitionKind::Mbr(0x01 | 0x04 | 0x06 | 0x0E | 0x0B | 0x0C) => Some(DiskFormat::Fat),
+        PartitionKind::Mbr(0x83) => Some(DiskFormat::Ext2),
+        PartitionKind::Gpt(Guid::EFI_SYSTEM | Guid::BASIC_DATA) => Some(DiskFormat::Fat),
+        PartitionKind::Gpt(Guid::LINUX_FILESYSTEM) => Some(DiskFormat::Ext2),
+        _ => None,
+    }
+}
+
+/// Write `value` (< 100) in decimal into `buffer`
+fn format_decimal(value: usize, buffer: &mut [u8; 2]) -> &str {
+    let len = if value >= 10 {
+        buffer[0] = b'0' + (value / 10 % 10) as u8;
+        buffer[1] = b'0' + (value % 10) as u8;
+        2
+    } else {
+        buffer[0] = b'0' + value as u8;
+        1
+    };
+    core::str::from_utf8(&buffer[..len]).unwrap_or("")
+}
@@ -0,0 +1,279 @@
+use core::fmt::{self, Write};
+use core::mem::size_of;
+use crate::block::cache::CacheStorage;
+use crate::fs::ramfs::RamStorage;
+use crate::fs::{ramfs, DirEntry, FileName, FileSystem, FileType, FsError, Stat};
+use crate::idt::interrupt_count;
+use crate::memory::{frame_counts, FRAME_SIZE};
+use crate::pci::for_each_device;
+use crate::process::{for_each_process, ProcessState};
+use crate::timer::uptime_ms;
+use crate::vbe::{get_vbe, ChannelMask};
+use crate::{cmos, BACK_BUFFER_ADDRESS, BACK_BUFFER_MAX_SIZE, BLOCK_CACHE_ADDRESS, FRAMES_START, INITRD_ADDRESS, INITRD_MAX_SIZE, RAMFS_ADDRESS};
+use crate::{FONT_ADDRESS, FONT_MAX_SIZE, GLYPH_CACHE_ADDRESS, GLYPH_CACHE_SIZE, TRUETYPE_ADDRESS, TRUETYPE_MAX_SIZE};
+use crate::{KERNEL_STACKS_ADDRESS, KERNEL_STACKS_SIZE};
+
+/// Longest text a /proc file can produce, the rest is cut
+const PROC_TEXT_SIZE: usize = 4096;
+
+const ROOT_INODE: u64 = 0;
+const UPTIME: u64 = 1;
+const MEMINFO: u64 = 2;
+const TASKS: u64 = 3;
+const INTERRUPTS: u64 = 4;
+const PCI: u64 = 5;
+const VBE: u64 = 6;
+const CPUINFO: u64 = 7;
+
+const FILES: [(&str, u64); 7] = [
+    ("uptime", UPTIME),
+    ("meminfo", MEMINFO),
+    ("tasks", TASKS),
+    ("interrupts", INTERRUPTS),
+    ("pci", PCI),
+    ("vbe", VBE),
+    ("cpuinfo", CPUINFO),
+];
+
+// Fixed memory regions, see kernel_entry.asm, boot/paging.asm and linker.ld
+const KERNEL_START: usize = 0x8000;
+const PAGE_TABLES_START: usize = 0x80000;
+const PAGE_TABLES_END: usize = 0x86000;
+const STACK_START: usize = 0x1C0000;
+const STACK_END: usize = 0x200000;
+
+unsafe extern "C" {
+    static _bss_end: u8;
+}
+
+/// Read-only text files describing the state of the kernel, usually mounted
+/// at /proc. The content is generated again on every read.
+pub struct ProcFs;
+
+/// Text built in a fixed buffer with `write!`, cut when full
+struct Text {
+    bytes: [u8; PROC_TEXT_SIZE],
+    len: usize,
+}
+
+impl Write for Text {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        let count = text.len().min(PROC_TEXT_SIZE - self.len);
+        self.bytes[self.len..self.len + count].copy_from_slice(&text.as_bytes()[..count]);
+        self.len += count;
+        if count < text.len() { Err(fmt::Error) } else { Ok(()) }
+    }
+}
+
+impl ProcFs {
+    fn generate(inode: u64, text: &mut Text) -> fmt::Result {
+        match inode {
+            UPTIME => {
+                let uptime = uptime_ms();
+                writeln!(text, "{}.{:02}", uptime / 1000, uptime % 1000 / 10)
+            }
+            MEMINFO => write_meminfo(text),
+            TASKS => {
+                writeln!(text, "{:>5}  {:>5}  {:<10}  NAME", "PID", "PPID", "STATE")?;
+                let mut result = Ok(());
+                for_each_process(|process| {
+                    let state = match process.state {
+                        ProcessState::Running => "running",
+                        ProcessState::Waiting => "waiting",
+                        ProcessState::Zombie(_) => "zombie",
+                    };
+                    result = result.and_then(|_| writeln!(
+                        text, "{:>5}  {:>5}  {:<10}  {}", process.pid, process.parent, state, process.name.as_str(),
+                    ));
+                });
+                result
+            }
+            INTERRUPTS => {
+                for vector in 0..=255u8 {
+                    let count = interrupt_count(vector);
+                    if count != 0 {
+                        writeln!(text, "{:>3}: {:>10}  {}", vector, count, vector_name(vector))?;
+                    }
+                }
+                Ok(())
+            }
+            PCI => {
+                let mut result = Ok(());
+                for_each_device(|device| {
+                    result = result.and_then(|_| writeln!(
+                        text,
+                        "{:02x}:{:02x}.{}  {:04x}:{:04x}  class {:02x}{:02x}{:02x}  rev {:02x}  {}",
+                        device.bus, device.device, device.function,
+                        device.vendor_id, device.device_id,
+                        device.class, device.subclass, device.prog_if,
+                        device.revision, device.class_name(),
+                    ));
+                });
+                result
+            }
+            VBE => write_vbe(text),
+            _ => write_cpuinfo(text),
+        }
+    }
+}
+
+fn write_meminfo(text: &mut Text) -> fmt::Result {
+    const KIB: usize = 1024;
+    let kernel_end = core::ptr::addr_of!(_bss_end) as usize;
+    let ramfs_used = ramfs().map_or(0, |ramfs| ramfs.used_bytes());
+    let (free_frames, total_frames) = frame_counts();
+    let frame_kib = FRAME_SIZE as usize / KIB;
+
+    writeln!(text, "MemTotal:      {:>8} KiB", cmos::memory_size() / KIB)?;
+    writeln!(text, "Kernel:        {:>8} KiB  at {:#x}", (kernel_end - KERNEL_START) / KIB, KERNEL_START)?;
+    writeln!(text, "PageTables:    {:>8} KiB  at {:#x}", (PAGE_TABLES_END - PAGE_TABLES_START) / KIB, PAGE_TABLES_START)?;
+    writeln!(text, "BlockCache:    {:>8} KiB  at {:#x}", size_of::<CacheStorage>() / KIB, BLOCK_CACHE_ADDRESS)?;
+    writeln!(text, "TaskStacks:    {:>8} KiB  at {:#x}", KERNEL_STACKS_SIZE / KIB, KERNEL_STACKS_ADDRESS)?;
+    writeln!(text, "KernelStack:   {:>8} KiB  at {:#x}", (STACK_END - STACK_START) / KIB, STACK_START)?;
+    writeln!(text, "ConsoleFont:   {:>8} KiB  at {:#x}", 2 * FONT_MAX_SIZE / KIB, FONT_ADDRESS)?;
+    writeln!(text, "Initrd:        {:>8} KiB  at {:#x}", INITRD_MAX_SIZE / KIB, INITRD_ADDRESS)?;
+    writeln!(text, "Ramfs:         {:>8} KiB  at {:#x}", size_of::<RamStorage>() / KIB, RAMFS_ADDRESS)?;
+    writeln!(text, "RamfsUsed:     {:>8} KiB", ramfs_used / KIB)?;
+    writeln!(text, "BackBuffer:    {:>8} KiB  at {:#x}", BACK_BUFFER_MAX_SIZE / KIB, BACK_BUFFER_ADDRESS)?;
+    writeln!(text, "TrueType:      {:>8} KiB  at {:#x}", 2 * TRUETYPE_MAX_SIZE / KIB, TRUETYPE_ADDRESS)?;
+    writeln!(text, "GlyphCache:    {:>8} KiB  at {:#x}", GLYPH_CACHE_SIZE / KIB, GLYPH_CACHE_ADDRESS)?;
+    writeln!(text, "Frames:        {:>8} KiB  at {:#x}", total_frames * frame_kib, FRAMES_START)?;
+    writeln!(text, "FramesFree:    {:>8} KiB", free_frames * frame_kib)
+}
+
+fn write_vbe(text: &mut Text) -> fmt::Result {
+    let vbe = get_vbe();
+    writeln!(text, "resolution:    {}x{}", vbe.width(), vbe.height())?;
+    writeln!(text, "pitch:         {} bytes", vbe.pitch())?;
+    writeln!(text, "bpp:           {} ({} bytes per pixel)", vbe.bpp(), vbe.pixel_format().bytes_per_pixel)?;
+    writeln!(text, "memory model:  {}", vbe.memory_model())?;
+    writeln!(text, "framebuffer:   {:#x} ({} KiB)", vbe.framebuffer(), vbe.framebuffer_size() / 1024)?;
+    // size:position of each channel, like 8:16 for red in a 32 bpp BGRX pixel
+    let channels = [("red", vbe.red()), ("green", vbe.green()), ("blue", vbe.blue()), ("reserved", vbe.reserved())];
+    for (name, ChannelMask { size, position }) in channels {
+        writeln!(text, "{:<15}{}:{}", name, size, position)?;
+    }
+    Ok(())
+}
+
+// (CPUID leaf 1 register, bit, name)
+const EDX: u8 = 0;
+const ECX: u8 = 1;
+const CPU_FEATURES: [(u8, u32, &str); 25] = [
+    (EDX, 0, "fpu"), (EDX, 4, "tsc"), (EDX, 5, "msr"), (EDX, 6, "pae"),
+    (EDX, 8, "cx8"), (EDX, 9, "apic"), (EDX, 11, "sep"), (EDX, 13, "pge"),
+    (EDX, 15, "cmov"), (EDX, 19, "clflush"), (EDX, 23, "mmx"), (EDX, 24, "fxsr"),
+    (EDX, 25, "sse"), (EDX, 26, "sse2"), (EDX, 28, "ht"), (ECX, 0, "sse3"),
+    (ECX, 9, "ssse3"), (ECX, 12, "fma"), (ECX, 17, "pcid"), (ECX, 19, "sse4_1"),
+    (ECX, 20, "sse4_2"), (ECX, 23, "popcnt"), (ECX, 26, "xsave"), (ECX, 28, "avx"),
+    (ECX, 30, "rdrand"),
+];
+
+fn write_cpuinfo(text: &mut Text) -> fmt::Result {
+    use core::arch::x86_64::__cpuid;
+
+    let leaf0 = __cpuid(0);
+    let mut vendor = [0u8; 12];
+    vendor[0..4].copy_from_slice(&leaf0.ebx.to_le_bytes());
+    vendor[4..8].copy_from_slice(&leaf0.edx.to_le_bytes());
+    vendor[8..12].copy_from_slice(&leaf0.ecx.to_le_bytes());
+    writeln!(text, "vendor:   {}", core::str::from_utf8(&vendor).unwrap_or("?"))?;
+
+    if __cpuid(0x8000_0000).eax >= 0x8000_0004 {
+        let mut brand = [0u8; 48];
+        for (i, leaf) in (0x8000_0002..=0x8000_0004).enumerate() {
+            let registers = __cpuid(leaf);
+            for (j, register) in [registers.eax, registers.ebx, registers.ecx, registers.edx].iter().enumerate() {
+                let start = i * 16 + j * 4;
+                brand[start..start + 4].copy_from_slice(&register.to_le_bytes());
+            }
+        }
+        let brand = core::str::from_utf8(&brand).unwrap_or("?").trim_end_matches('\0').trim();
+        writeln!(text, "model:    {}", brand)?;
+    }
+
+    let leaf1 = __cpuid(1);
+    let family = (leaf1.eax >> 8) & 0xF;
+    let family = if family == 0xF { family + ((leaf1.eax >> 20) & 0xFF) } else { family };
+    let model = (leaf1.eax >> 4) & 0xF | ((leaf1.eax >> 16) & 0xF) << 4;
+    writeln!(text, "family:   {}", family)?;
+    writeln!(text, "model id: {}", model)?;
+    writeln!(text, "stepping: {}", leaf1.eax & 0xF)?;
+
+    write!(text, "flags:   ")?;
+    for (register, bit, name) in CPU_FEATURES {
+        let value = if register == EDX { leaf1.edx } else { leaf1.ecx };
+        if value & (1 << bit) != 0 {
+            write!(text, " {}", name)?;
+        }
+    }
+    writeln!(text)
+}
+
+fn vector_name(vector: u8) -> &'static str {
+    match vector {
+        0 => "divide error",
+        3 => "breakpoint",
+        6 => "invalid opcode",
+        13 => "general protection",
+        14 => "page fault",
+        32 => "timer",
+        33 => "keyboard",
+        46 => "ata primary",
+        47 => "ata secondary",
+        _ => "",
+    }
+}
+
+impl FileSystem for ProcFs {
+    fn name(&self) -> &str {
+        "procfs"
+    }
+
+    fn root(&self) -> u64 {
+        ROOT_INODE
+    }
+
+    fn lookup(&self, dir: u64, name: &str) -> Result<u64, FsError> {
+        if dir != ROOT_INODE {
+            return Err(FsError::NotADirectory);
+        }
+        FILES.iter().find(|(file, _)| *file == name).map(|&(_, inode)| inode).ok_or(FsError::NotFound)
+    }
+
+    fn stat(&self, inode: u64) -> Result<Stat, FsError> {
+        let (file_type, permissions) = match inode {
+            ROOT_INODE => (FileType::Directory, 0o555),
+            UPTIME..=CPUINFO => (FileType::Regular, 0o444),
+            _ => return Err(FsError::NotFound),
+        };
+        // Sizes are unknown until the text is generated, read until the end instead
+        Ok(Stat { file_type, size: 0, inode, links: 1, permissions })
+    }
+
+    fn read(&self, inode: u64, offset: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        match inode {
+            ROOT_INODE => return Err(FsError::IsADirectory),
+            UPTIME..=CPUINFO => {}
+            _ => return Err(FsError::NotFound),
+        }
+        let mut text = Text { bytes: [0; PROC_TEXT_SIZE], len: 0 };
+        // Only fails when the text is cut, keep what fit
+        let _ = ProcFs::generate(inode, &mut text);
+        let content = text.bytes[..text.len].get(offset as usize..).unwrap_or(&[]);
+        let count = content.len().min(buffer.len());
+        buffer[..count].copy_from_slice(&content[..count]);
+        Ok(count)
+    }
+
+    fn read_dir(&self, dir: u64, cursor: &mut u64) -> Result<Option<DirEntry>, FsError> {
+        if dir != ROOT_INODE {
+            return Err(FsError::NotADirectory);
+        }
+        let Some(&(name, inode)) = FILES.get(*cursor as usize) else {
+            return Ok(None);
+        };
+        *cursor += 1;
+        Ok(Some(DirEntry { name: FileName::new(name), inode, file_type: FileType::Regular }))
+    }
+}
@@ -0,0 +1,459 @@
+use core::cell::RefCell;
+use crate::fs::{DirEntry, FileName, FileSystem, FileType, FsError, Stat, MAX_NAME_LEN};
+
+pub const RAMFS_BLOCK_SIZE: usize = 4096;
+/// Data blocks shared by all the files (4 MiB)
+pub const RAMFS_BLOCKS: usize = 1024;
+/// Files and directories, the root included
+pub const MAX_RAMFS_NODES: usize = 256;
+/// Names in all directories together (a hard link is one more name)
+pub const MAX_RAMFS_ENTRIES: usize = 512;
+/// Blocks one file can use (1 MiB)
+pub const MAX_FILE_BLOCKS: usize = 256;
+
+const MAX_FILE_SIZE: u64 = (MAX_FILE_BLOCKS * RAMFS_BLOCK_SIZE) as u64;
+// Marks a hole in a file (reads as zeros)
+const NO_BLOCK: u16 = u16::MAX;
+const ROOT_INODE: u64 = 0;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct RamNode {
+    used: bool,
+    file_type: FileType,
+    permissions: u16,
+    /// Names pointing to this node (+ "." and the ".." of subdirectories for directories)
+    links: u32,
+    size: u64,
+    blocks: [u16; MAX_FILE_BLOCKS],
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct RamEntry {
+    used: bool,
+    /// Node of the directory holding this name
+    parent: u16,
+    node: u16,
+    name: FileName,
+}
+
+/// Memory used by a ramfs. Like `CacheStorage`, it is carved out of a fixed
+/// memory region since there is no heap (see `RamStorage::at`).
+#[repr(C)]
+pub struct RamStorage {
+    nodes: [RamNode; MAX_RAMFS_NODES],
+    entries: [RamEntry; MAX_RAMFS_ENTRIES],
+    block_used: [bool; RAMFS_BLOCKS],
+    blocks: [[u8; RAMFS_BLOCK_SIZE]; RAMFS_BLOCKS],
+}
+
+impl RamStorage {
+    /// Zero the memory at `address` and use it as ramfs storage
+    pub unsafe fn at(address: usize) -> &'static mut RamStorage {
+        let storage = address as *mut RamStorage;
+        unsafe {
+            core::ptr::write_bytes(storage, 0, 1);
+            &mut *storage
+        }
+    }
+
+    fn node(&self, inode: u64) -> Result<&RamNode, FsError> {
+        self.nodes.get(inode as usize).filter(|node| node.used).ok_or(FsError::NotFound)
+    }
+
+    fn node_mut(&mut self, inode: u64) -> Result<&mut RamNode, FsError> {
+        self.nodes.get_mut(inode as usize).filter(|node| node.used).ok_or(FsError::NotFound)
+    }
+
+    fn directory(&self, inode: u64) -> Result<&RamNode, FsError> {
+        let node = self.node(inode)?;
+        if node.file_type != FileType::Directory {
+            return Err(FsError::NotADirectory);
+        }
+        Ok(node)
+    }
+
+    fn find_entry(&self, dir: u64, name: &str) -> Option<usize> {
+        self.entries.iter().position(|entry| {
+            entry.used && entry.parent as u64 == dir && entry.name.as_str() == name
+        })
+    }
+
+    fn add_entry(&mut self, dir: u64, name: &str, node: u64) -> Result<(), FsError> {
+        let slot = self.entries.iter().position(|entry| !entry.used).ok_or(FsError::NoSpace)?;
+        self.entries[slot] = RamEntry { used: true, parent: dir as u16, node: node as u16, name: FileName::new(name) };
+        Ok(())
+    }
+
+    fn allocate_block(&mut self) -> Result<u16, FsError> {
+        let block = self.block_used.iter().position(|used| !used).ok_or(FsError::NoSpace)?;
+        self.block_used[block] = true;
+        self.blocks[block].fill(0);
+        Ok(block as u16)
+    }
+
+    /// Drop the content of `inode` past `size` (zeroing the end of the last kept block)
+    fn shrink(&mut self, inode: u64, size: u64) {
+        let node = &mut self.nodes[inode as usize];
+        let kept_blocks = size.div_ceil(RAMFS_BLOCK_SIZE as u64) as usize;
+        let mut freed = [NO_BLOCK; MAX_FILE_BLOCKS];
+        for (index, block) in node.blocks.iter_mut().enumerate().skip(kept_blocks) {
+            freed[index] = *block;
+            *block = NO_BLOCK;
+        }
+        let tail = size as usize % RAMFS_BLOCK_SIZE;
+        let last = if tail != 0 { node.blocks[kept_blocks - 1] } else { NO_BLOCK };
+
+        for block in freed.into_iter().filter(|&block| block != NO_BLOCK) {
+            self.block_used[block as usize] = false;
+        }
+        if last != NO_BLOCK {
+            self.blocks[last as usize][tail..].fill(0);
+        }
+    }
+}
+
+/// Writable filesystem living entirely in memory: directories, regular files
+/// (sparse, up to 1 MiB each) and hard links. Everything is lost on reboot.
+pub struct RamFs<'a> {
+    storage: RefCell<&'a mut RamStorage>,
+}
+
+impl<'a> RamFs<'a> {
+    /// An empty filesystem with only its root directory
+    pub fn new(storage: &'a mut RamStorage) -> Self {
+        storage.nodes[ROOT_INODE as usize] = RamNode {
+            used: true,
+            file_type: FileType::Directory,
+            permissions: 0o755,
+            links: 2,
+            size: 0,
+            blocks: [NO_BLOCK; MAX_FILE_BLOCKS],
+        };
+        RamFs { storage: RefCell::new(storage) }
+    }
+
+    /// Bytes of file data stored
+    pub fn used_bytes(&self) -> usize {
+        let storage = self.storage.borrow();
+        storage.block_used.iter().filter(|&&used| used).count() * RAMFS_BLOCK_SIZE
+    }
+}
+
+impl FileSystem for RamFs<'_> {
+    fn name(&self) -> &str {
+        "ramfs"
+    }
+
+    fn root(&self) -> u64 {
+        ROOT_INODE
+    }
+
+    fn lookup(&self, dir: u64, name: &str) -> Result<u64, FsError> {
+        let storage = self.storage.borrow();
+        storage.directory(dir)?;
+        let entry = storage.find_entry(dir, name).ok_or(FsError::NotFound)?;
+        Ok(storage.entries[entry].node as u64)
+    }
+
+    fn stat(&self, inode: u64) -> Result<Stat, FsError> {
+        let storage = self.storage.borrow();
+        let node = storage.node(inode)?;
+        Ok(Stat {
+            file_type: node.file_type,
+            size: node.size,
+            inode,
+            links: node.links,
+            permissions: node.permissions,
+        })
+    }
+
+    fn read(&self, inode: u64, offset: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        let storage = self.storage.borrow();
+        let node = storage.node(inode)?;
+        if node.file_type == FileType::Directory {
+            return Err(FsError::IsADirectory);
+        }
+        if offset >= node.size {
+            return Ok(0);
+        }
+        let count = buffer.len().min((node.size - offset) as usize);
+        let mut done = 0;
+        while done < count {
+            let position = offset as usize + done;
+            let within = position % RAMFS_BLOCK_SIZE;
+            let chunk = (RAMFS_BLOCK_SIZE - within).min(count - done);
+            let target = &mut buffer[done..done + chunk];
+            match node.blocks[position / RAMFS_BLOCK_SIZE] {
+                NO_BLOCK => target.fill(0),
+                block => target.copy_from_slice(&storage.blocks[block as usize][within..within + chunk]),
+            }
+            done += chunk;
+        }
+        Ok(count)
+    }
+
+    fn read_dir(&self, dir: u64, cursor: &mut u64) -> Result<Option<DirEntry>, FsError> {
+        let storage = self.storage.borrow();
+        storage.directory(dir)?;
+        while (*cursor as usize) < MAX_RAMFS_ENTRIES {
+            let entry = &storage.entries[*cursor as usize];
+            *cursor += 1;
+            if entry.used && entry.parent as u64 == dir {
+                let file_type = storage.nodes[entry.node as usize].file_type;
+                return Ok(Some(DirEntry { name: entry.name, inode: entry.node as u64, file_type }));
+            }
+        }
+        Ok(None)
+    }
+
+    fn write(&self, inode: u64, offset: u64, data: &[u8]) -> Result<usize, FsError> {
+        let mut storage = self.storage.borrow_mut();
+        if storage.node(inode)?.file_type == FileType::Directory {
+            return Err(FsError::IsADirectory);
+        }
+        if offset.saturating_add(data.len() as u64) > MAX_FILE_SIZE {
+            return Err(FsError::NoSpace);
+        }
+
+        let mut done = 0;
+        while done < data.len() {
+            let position = offset as usize + done;
+            let index = position / RAMFS_BLOCK_SIZE;
+            let within = position % RAMFS_BLOCK_SIZE;
+            let chunk = (RAMFS_BLOCK_SIZE - within).min(data.len() - done);
+            let block = match storage.nodes[inode as usize].blocks[index] {
+                NO_BLOCK => match storage.allocate_block() {
+                    Ok(block) => {
+                        storage.nodes[inode as usize].blocks[index] = block;
+                        block
+                    }
+                    // Keep what was written so far
+                    Err(_) if done > 0 => break,
+                    Err(error) => return Err(error),
+                },
+                block => block,
+            };
+            storage.blocks[block as usize][within..within + chunk].copy_from_slice(&data[done..done + chunk]);
+            done += chunk;
+        }
+
+        let node = &mut storage.nodes[inode as usize];
+        node.size = node.size.max(offset + done as u64);
+        Ok(done)
+    }
+
+    fn create(&self, dir: u64, name: &str, file_type: FileType) -> Result<u64, FsError> {
+        let mut storage = self.storage.borrow_mut();
+        storage.directory(dir)?;
+        check_name(name)?;
+        let permissions = match file_type {
+            FileType::Regular => 0o644,
+            FileType::Directory => 0o755,
+            _ => return Err(FsError::Unsupported),
+        };
+        if storage.find_entry(dir, name).is_some() {
+            return Err(FsError::AlreadyExists);
+        }
+        if storage.entries.iter().all(|entry| entry.used) {
+            return Err(FsError::NoSpace);
+        }
+        let inode = storage.nodes.iter().position(|node| !node.used).ok_or(FsError::NoSpace)? as u64;
+
+        let links = if file_type == FileType::Directory { 2 } else { 1 };
+        storage.nodes[inode as usize] = RamNode { used: true, file_type, permissions, links, size: 0, blocks: [NO_BLOCK; MAX_FILE_BLOCKS] };
+        storage.add_entry(dir, name, inode)?;
+        if file_type == FileType::Directory {
+            // The ".." of the new directory
+            storage.node_mut(dir)?.links += 1;
+        }
+        Ok(inode)
+    }
+
+    fn remove(&self, dir: u64, name: &str) -> Result<(), FsError> {
+        let mut storage = self.storage.borrow_mut();
+        storage.directory(dir)?;
+        let entry = storage.find_entry(dir, name).ok_or(FsError::NotFound)?;
+        let inode = storage.entries[entry].node as u64;
+
+        if storage.node(inode)?.file_type == FileType::Directory {
+            if storage.entries.iter().any(|entry| entry.used && entry.parent as u64 == inode) {
+                return Err(FsError::DirectoryNotEmpty);
+            }
+            storage.node_mut(dir)?.links -= 1;
+            storage.nodes[inode as usize].used = false;
+        } else {
+            let node = storage.node_mut(inode)?;
+            node.links -= 1;
+            if node.links == 0 {
+                storage.shrink(inode, 0);
+                storage.nodes[inode as usize].used = false;
+            }
+        }
+        storage.entries[entry].used = false;
+        Ok(())
+    }
+
+    fn truncate(&self, inode: u64, size: u64) -> Result<(), FsError> {
+        let mut storage = self.storage.borrow_mut();
+        let node = storage.node(inode)?;
+        if node.file_type == FileType::Directory {
+            return Err(FsError::IsADirectory);
+        }
+        if size > MAX_FILE_SIZE {
+            return Err(FsError::NoSpace);
+        }
+        if size < node.size {
+            storage.shrink(inode, size);
+        }
+        // Growing leaves a hole, blocks are allocated on write
+        storage.nodes[inode as usize].size = size;
+        Ok(())
+    }
+
+    fn link(&self, dir: u64, name: &str, inode: u64) -> Result<(), FsError> {
+        let mut storage = self.storage.borrow_mut();
+        storage.directory(dir)?;
+        check_name(name)?;
+        if storage.node(inode)?.file_type == FileType::Directory {
+            return Err(FsError::IsADirectory);
+        }
+        if storage.find_entry(dir, name).is_some() {
+            return Err(FsError::AlreadyExists);
+        }
+        storage.add_entry(dir, name, inode)?;
+        storage.node_mut(inode)?.links += 1;
+        Ok(())
+    }
+}
+
+fn check_name(name: &str) -> Result<(), FsError> {
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') || name.len() > MAX_NAME_LEN {
+        return Err(FsError::InvalidName);
+    }
+    Ok(())
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+    use crate::testing::{list_dir, read_file};
+
+    fn storage() -> Box<RamStorage> {
+        // All zeroes is an empty filesystem, RamFs::new adds the root
+        unsafe { Box::new_zeroed().assume_init() }
+    }
+
+    #[test]
+    fn files_and_directories() {
+        let mut storage = storage();
+        let fs = RamFs::new(&mut storage);
+        let root = fs.root();
+        let dir = fs.create(root, "dir", FileType::Directory).unwrap();
+        let file = fs.create(dir, "notes.txt", FileType::Regular).unwrap();
+        assert_eq!(fs.write(file, 0, b"hello world").unwrap(), 11);
+        assert_eq!(fs.write(file, 6, b"there").unwrap(), 5);
+
+        assert_eq!(fs.lookup(root, "dir"), Ok(dir));
+        assert_eq!(fs.lookup(dir, "notes.txt"), Ok(file));
+        assert_eq!(fs.lookup(dir, "other"), Err(FsError::NotFound));
+        assert_eq!(read_file(&fs, file), b"hello there");
+        assert_eq!(list_dir(&fs, dir), ["notes.txt"]);
+        assert_eq!(fs.stat(root).unwrap().links, 3);
+        assert_eq!(fs.stat(dir).unwrap().file_type, FileType::Directory);
+        assert_eq!(fs.used_bytes(), RAMFS_BLOCK_SIZE);
+
+        assert_eq!(fs.create(dir, "notes.txt", FileType::Regular), Err(FsError::AlreadyExists));
+        assert_eq!(fs.remove(root, "dir"), Err(FsError::DirectoryNotEmpty));
+        fs.remove(dir, "notes.txt").unwrap();
+        fs.remove(root, "dir").unwrap();
+        assert_eq!(list_dir(&fs, root), Vec::<String>::new());
+        assert_eq!((fs.stat(root).unwrap().links, fs.used_bytes()), (2, 0));
+        assert_eq!(fs.stat(file).err(), Some(FsError::NotFound));
+    }
+
+    #[test]
+    fn holes_and_truncate() {
+        let mut storage = storage();
+        let fs = RamFs::new(&mut storage);
+        let file = fs.create(fs.root(), "sparse", FileType::Regular).unwrap();
+        // Across the boundary of blocks 2 and 3, blocks 0 and 1 are a hole
+        let offset = 3 * RAMFS_BLOCK_SIZE as u64 - 2;
+        fs.write(file, offset, b"abcd").unwrap();
+        assert_eq!(fs.stat(file).unwrap().size, offset + 4);
+        assert_eq!(fs.used_bytes(), 2 * RAMFS_BLOCK_SIZE);
+        let data = read_file(&fs, file);
+        assert!(data[..offset as usize].iter().all(|&byte| byte == 0));
+        assert_eq!(&data[offset as usize..], b"abcd");
+
+        // Shrinking frees the last block and zeroes the end of the kept one, growing reads zeros
+        fs.truncate(file, offset + 1).unwrap();
+        assert_eq!(fs.used_bytes(), RAMFS_BLOCK_SIZE);
+        fs.truncate(file, offset + 4).unwrap();
+        assert_eq!(&read_file(&fs, file)[offset as usize..], b"a\0\0\0");
+        assert_eq!(fs.truncate(file, MAX_FILE_SIZE + 1), Err(FsError::NoSpace));
+        assert_eq!(fs.read(file, offset + 10, &mut [0; 4]), Ok(0));
+    }
+
+    #[test]
+    fn hard_links() {
+        let mut storage = storage();
+        let fs = RamFs::new(&mut storage);
+        let root = fs.root();
+        let file = fs.create(root, "a", FileType::Regular).unwrap();
+        fs.write(file, 0, b"shared").unwrap();
+        fs.link(root, "b", file).unwrap();
+        assert_eq!((fs.lookup(root, "b"), fs.stat(file).unwrap().links), (Ok(file), 2));
+
+        fs.remove(root, "a").unwrap();
+        assert_eq!(read_file(&fs, file), b"shared");
+        fs.remove(root, "b").unwrap();
+        assert_eq!(fs.used_bytes(), 0);
+
+        let dir = fs.create(root, "dir", FileType::Directory).unwrap();
+        assert_eq!(fs.link(root, "alias", dir), Err(FsError::IsADirectory));
+    }
+
+    #[test]
+    fn errors() {
+        let mut storage = storage();
+        let fs = RamFs::new(&mut storage);
+        let root = fs.root();
+        let file = fs.create(root, "file", FileType::Regular).unwrap();
+        for name in ["", ".", "..", "a/b", &"x".repeat(MAX_NAME_LEN + 1)] {
+            assert_eq!(fs.create(root, name, FileType::Regular), Err(FsError::InvalidName), "{:?}", name);
+        }
+        assert_eq!(fs.create(root, "link", FileType::Symlink), Err(FsError::Unsupported));
+        assert_eq!(fs.create(file, "inside", FileType::Regular), Err(FsError::NotADirectory));
+        assert_eq!(fs.lookup(file, "inside"), Err(FsError::NotADirectory));
+        assert_eq!(fs.read(root, 0, &mut [0; 4]), Err(FsError::IsADirectory));
+        assert_eq!(fs.write(root, 0, b"x"), Err(FsError::IsADirectory));
+        assert_eq!(fs.write(file, MAX_FILE_SIZE - 1, b"xy"), Err(FsError::NoSpace));
+        assert_eq!(fs.remove(root, "missing"), Err(FsError::NotFound));
+    }
+
+    #[test]
+    fn runs_out_of_blocks() {
+        let mut storage = storage();
+        let fs = RamFs::new(&mut storage);
+        let root = fs.root();
+        let full = vec![0x55; MAX_FILE_SIZE as usize];
+        for i in 0..RAMFS_BLOCKS / MAX_FILE_BLOCKS - 1 {
+            let file = fs.create(root, &format!("big{}", i), FileType::Regular).unwrap();
+            assert_eq!(fs.write(file, 0, &full), Ok(full.len()));
+        }
+        let almost = fs.create(root, "almost", FileType::Regular).unwrap();
+        // Its first block is a hole
+        assert_eq!(fs.write(almost, RAMFS_BLOCK_SIZE as u64, &full[RAMFS_BLOCK_SIZE..]), Ok(full.len() - RAMFS_BLOCK_SIZE));
+
+        // A write that got some blocks keeps them
+        let last = fs.create(root, "last", FileType::Regular).unwrap();
+        assert_eq!(fs.write(last, 0, &full[..3 * RAMFS_BLOCK_SIZE]), Ok(RAMFS_BLOCK_SIZE));
+        assert_eq!(fs.used_bytes(), RAMFS_BLOCKS * RAMFS_BLOCK_SIZE);
+        assert_eq!(fs.write(last, RAMFS_BLOCK_SIZE as u64, b"x"), Err(FsError::NoSpace));
+        // Rewriting allocated blocks needs none
+        assert_eq!(fs.write(almost, 0, b"x"), Err(FsError::NoSpace));
+        assert_eq!(fs.write(almost, RAMFS_BLOCK_SIZE as u64, b"x"), Ok(1));
+    }
+}
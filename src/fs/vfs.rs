@@ -0,0 +1,208 @@
+use crate::fs::{components, FileName, FileSystem, FileType, FsError};
+
+pub const MAX_MOUNTS: usize = 8;
+/// Longest absolute path, in bytes
+pub const MAX_PATH_LEN: usize = 256;
+// Symlinks followed while resolving one path
+const MAX_SYMLINK_FOLLOWS: usize = 8;
+
+/// Normalized absolute path ("/", "/a/b"), stored inline
+#[derive(Clone, Copy)]
+pub struct PathBuf {
+    bytes: [u8; MAX_PATH_LEN],
+    len: usize,
+}
+
+impl PathBuf {
+    pub const fn root() -> Self {
+        let mut bytes = [0; MAX_PATH_LEN];
+        bytes[0] = b'/';
+        PathBuf { bytes, len: 1 }
+    }
+
+    /// Resolve `path` against the directory `base`, removing ".", ".." and repeated slashes
+    pub fn normalize(base: &str, path: &str) -> Result<Self, FsError> {
+        let mut result = PathBuf::root();
+        if !path.starts_with('/') {
+            for component in components(base) {
+                result.push(component)?;
+            }
+        }
+        for component in components(path) {
+            match component {
+                ".." => result.pop(),
+                _ => result.push(component)?,
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn push(&mut self, component: &str) -> Result<(), FsError> {
+        let separator = if self.len > 1 { 1 } else { 0 };
+        let new_len = self.len + separator + component.len();
+        if new_len > MAX_PATH_LEN {
+            return Err(FsError::PathTooLong);
+        }
+        if separator == 1 {
+            self.bytes[self.len] = b'/';
+        }
+        self.bytes[self.len + separator..new_len].copy_from_slice(component.as_bytes());
+        self.len = new_len;
+        Ok(())
+    }
+
+    /// Remove the last component ("/" stays "/")
+    pub fn pop(&mut self) {
+        let last_slash = self.bytes[..self.len].iter().rposition(|&byte| byte == b'/').unwrap_or(0);
+        self.len = last_slash.max(1);
+    }
+
+    /// Last component, "" for the root
+    pub fn file_name(&self) -> &str {
+        let path = self.as_str();
+        &path[path.rfind('/').map_or(0, |i| i + 1)..]
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only built from &str components and '/'
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or("/")
+    }
+
+    /// The part of this path below `prefix`, if `prefix` is one of its ancestors (or itself)
+    fn strip_prefix(&self, prefix: &str) -> Option<&str> {
+        let path = self.as_str();
+        if prefix == "/" {
+            return Some(path);
+        }
+        let rest = path.strip_prefix(prefix)?;
+        (rest.is_empty() || rest.starts_with('/')).then_some(rest)
+    }
+}
+
+/// A node of the global file tree: a filesystem and an inode in it
+#[derive(Clone, Copy)]
+pub struct Vnode {
+    pub fs: &'static dyn FileSystem,
+    pub inode: u64,
+}
+
+struct Mount {
+    path: PathBuf,
+    fs: &'static dyn FileSystem,
+}
+
+static mut MOUNTS: [Option<Mount>; MAX_MOUNTS] = [const { None }; MAX_MOUNTS];
+
+fn mounts() -> &'static [Option<Mount>; MAX_MOUNTS] {
+    unsafe { &*core::ptr::addr_of!(MOUNTS) }
+}
+
+/// Attach `fs` at the absolute path `path`. The mount point does not need to exist
+/// in the parent filesystem.
+pub fn mount(path: &str, fs: &'static dyn FileSystem) -> Result<(), FsError> {
+    let path = PathBuf::normalize("/", path)?;
+    if mounts().iter().flatten().any(|mount| mount.path.as_str() == path.as_str()) {
+        return Err(FsError::MountFailed);
+    }
+    let slot = mounts().iter().position(|mount| mount.is_none()).ok_or(FsError::MountFailed)?;
+    unsafe { (*core::ptr::addr_of_mut!(MOUNTS))[slot] = Some(Mount { path, fs }); }
+    Ok(())
+}
+
+pub fn unmount(path: &str) -> Result<(), FsError> {
+    let path = PathBuf::normalize("/", path)?;
+    let slot = mounts().iter()
+        .position(|mount| mount.as_ref().is_some_and(|mount| mount.path.as_str() == path.as_str()))
+        .ok_or(FsError::NotFound)?;
+    if let Some(mount) = &mounts()[slot] {
+        mount.fs.sync()?;
+    }
+    unsafe { (*core::ptr::addr_of_mut!(MOUNTS))[slot] = None; }
+    Ok(())
+}
+
+/// Call `f(mount point, filesystem)` for every mounted filesystem
+pub fn for_each_mount(mut f: impl FnMut(&str, &'static dyn FileSystem)) {
+    for mount in mounts().iter().flatten() {
+        f(mount.path.as_str(), mount.fs);
+    }
+}
+
+/// Flush every mounted filesystem
+pub fn sync_all() -> Result<(), FsError> {
+    for mount in mounts().iter().flatten() {
+        mount.fs.sync()?;
+    }
+    Ok(())
+}
+
+/// The mount with the longest mount point containing `path`, and the rest of the path
+fn find_mount(path: &PathBuf) -> Result<(&'static Mount, &str), FsError> {
+    let mut best: Option<(&Mount, &str)> = None;
+    for mount in mounts().iter().flatten() {
+        if let Some(rest) = path.strip_prefix(mount.path.as_str())
+            && best.is_none_or(|(current, _)| mount.path.len > current.path.len)
+        {
+            best = Some((mount, rest));
+        }
+    }
+    best.ok_or(FsError::NotFound)
+}
+
+/// Resolve an absolute path to a node, following symbolic links
+pub fn resolve(path: &str) -> Result<Vnode, FsError> {
+    resolve_with(path, true)
+}
+
+/// Like `resolve`, but a symlink in the last component is returned as is
+pub fn resolve_no_follow(path: &str) -> Result<Vnode, FsError> {
+    resolve_with(path, false)
+}
+
+fn resolve_with(path: &str, follow_last: bool) -> Result<Vnode, FsError> {
+    let mut path = PathBuf::normalize("/", path)?;
+    'restart: for _ in 0..=MAX_SYMLINK_FOLLOWS {
+        let (mount, rest) = find_mount(&path)?;
+        let fs = mount.fs;
+        let mut inode = fs.root();
+        let mut walked = mount.path;
+
+        let mut remaining = components(rest).peekable();
+        while let Some(component) = remaining.next() {
+            let next = fs.lookup(inode, component)?;
+            let last = remaining.peek().is_none();
+            if (!last || follow_last) && fs.stat(next)?.file_type == FileType::Symlink {
+                // Continue from the link target, followed by what is left of the path
+                let mut target = [0u8; MAX_PATH_LEN];
+                let length = fs.read_link(next, &mut target)?;
+                let target = core::str::from_utf8(&target[..length]).map_err(|_| FsError::Corrupted)?;
+                let mut new_path = PathBuf::normalize(walked.as_str(), target)?;
+                for component in remaining {
+                    match component {
+                        ".." => new_path.pop(),
+                        _ => new_path.push(component)?,
+                    }
+                }
+                path = new_path;
+                continue 'restart;
+            }
+            walked.push(component)?;
+            inode = next;
+        }
+        return Ok(Vnode { fs, inode });
+    }
+    Err(FsError::PathTooLong)
+}
+
+/// Resolve the directory containing `path`, and return it with the last component
+pub fn resolve_parent(path: &str) -> Result<(Vnode, FileName), FsError> {
+    let path = PathBuf::normalize("/", path)?;
+    let name = path.file_name();
+    if name.is_empty() {
+        return Err(FsError::InvalidName);
+    }
+    let mut parent = path;
+    parent.pop();
+    let dir = resolve(parent.as_str())?;
+    Ok((dir, FileName::new(name)))
+}
@@ -0,0 +1,94 @@
+use core::mem::size_of;
+
+// Selectors, the first three are the ones of boot/gdt.asm.
+// syscall loads CS = STAR[47:32] and SS = CS + 8, sysret loads SS = STAR[63:48] + 8
+// and CS = STAR[63:48] + 16, which fixes the order of the kernel and user segments.
+pub const KERNEL_CODE_SELECTOR: u16 = 0x18;
+pub const KERNEL_DATA_SELECTOR: u16 = 0x10;
+// 0x20 is a second kernel data segment, the SS loaded by syscall
+pub const USER_DATA_SELECTOR: u16 = 0x28 | 3;
+pub const USER_CODE_SELECTOR: u16 = 0x30 | 3;
+const TSS_SELECTOR: u16 = 0x38;
+
+/// STAR[63:48], sysret adds 8 for SS and 16 for CS
+pub const SYSRET_BASE_SELECTOR: u16 = 0x20;
+
+/// 64-bit Task State Segment: only the stack loaded on entry to ring 0 is used
+#[repr(C, packed)]
+pub struct TaskStateSegment {
+    reserved0: u32,
+    /// Stacks for privilege levels 0-2
+    rsp: [u64; 3],
+    reserved1: u64,
+    /// Interrupt stack table
+    ist: [u64; 7],
+    reserved2: u64,
+    reserved3: u16,
+    /// No I/O permission bitmap, user mode cannot use in/out
+    iomap_base: u16,
+}
+
+static mut TSS: TaskStateSegment = TaskStateSegment {
+    reserved0: 0,
+    rsp: [0; 3],
+    reserved1: 0,
+    ist: [0; 7],
+    reserved2: 0,
+    reserved3: 0,
+    iomap_base: size_of::<TaskStateSegment>() as u16,
+};
+
+/// Null, kernel code 32, kernel data, kernel code 64, kernel data (syscall SS),
+/// user data, user code 64 and the two halves of the TSS descriptor
+static mut GDT: [u64; 9] = [
+    0,
+    0x00CF_9A00_0000_FFFF,
+    0x00CF_9200_0000_FFFF,
+    0x00AF_9A00_0000_FFFF,
+    0x00CF_9200_0000_FFFF,
+    0x00CF_F200_0000_FFFF,
+    0x00AF_FA00_0000_FFFF,
+    0,
+    0,
+];
+
+#[repr(C, packed)]
+struct GdtPtr {
+    limit: u16,
+    base: u64,
+}
+
+/// Replace the GDT of boot/gdt.asm by one with user segments and a TSS.
+/// The kernel selectors keep their values, so CS does not need a far jump.
+pub fn init_gdt() {
+    unsafe {
+        let tss = core::ptr::addr_of!(TSS) as u64;
+        let limit = (size_of::<TaskStateSegment>() - 1) as u64;
+        // Present, 64-bit available TSS (type 9)
+        GDT[7] = limit & 0xFFFF | (tss & 0xFF_FFFF) << 16 | 0x89 << 40 | (limit >> 16 & 0xF) << 48 | (tss >> 24 & 0xFF) << 56;
+        GDT[8] = tss >> 32;
+
+        let ptr = GdtPtr {
+            limit: (size_of::<[u64; 9]>() - 1) as u16,
+            base: core::ptr::addr_of!(GDT) as u64,
+        };
+        core::arch::asm!(
+            "lgdt [{ptr}]",
+            "mov ds, {data:x}",
+            "mov es, {data:x}",
+            "mov ss, {data:x}",
+            "ltr {tss:x}",
+            ptr = in(reg) &ptr,
+            data = in(reg) KERNEL_DATA_SELECTOR,
+            tss = in(reg) TSS_SELECTOR,
+            options(nostack, preserves_flags),
+        );
+    }
+}
+
+/// Stack the CPU switches to when an interrupt or `int 0x80` arrives in ring 3
+pub fn set_kernel_stack(stack_top: u64) {
+    unsafe {
+        (*core::ptr::addr_of_mut!(TSS)).rsp[0] = stack_top;
+    }
+}
@@ -0,0 +1,42 @@
+use crate::color::Color;
+use crate::graphics::canvas::Canvas;
+
+/// A canvas in memory, one Color per pixel row by row, for off-screen drawing
+/// (windows, cached glyphs) that is then copied with `Canvas::draw_bitmap`
+pub struct Bitmap<'a> {
+    pixels: &'a mut [Color],
+    width: usize,
+    height: usize,
+}
+
+impl<'a> Bitmap<'a> {
+    /// A width x height bitmap on `pixels`, None when it is too small
+    pub fn new(pixels: &'a mut [Color], width: usize, height: usize) -> Option<Self> {
+        let pixels = pixels.get_mut(..width.checked_mul(height)?)?;
+        Some(Bitmap { pixels, width, height })
+    }
+}
+
+impl Canvas for Bitmap<'_> {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> Option<Color> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.pixels[y * self.width + x])
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if x < self.width && y < self.height {
+            let pixel = &mut self.pixels[y * self.width + x];
+            *pixel = color.over(*pixel);
+        }
+    }
+}
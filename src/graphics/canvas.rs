@@ -0,0 +1,72 @@
+use crate::color::Color;
+use crate::font;
+use crate::graphics::bitmap::Bitmap;
+use crate::graphics::rect::Rect;
+
+/// Something to draw on: the screen, a bitmap in memory or a part of another canvas.
+/// Implementors provide the pixel access, text and shapes (in `graphics`) work on any.
+///
+/// Drawing is clipped: any coordinates are fine, what falls outside of `clip` is not drawn.
+pub trait Canvas {
+    fn width(&self) -> usize;
+
+    fn height(&self) -> usize;
+
+    fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width(), self.height())
+    }
+
+    /// Area drawing is limited to, inside the bounds
+    fn clip(&self) -> Rect {
+        self.bounds()
+    }
+
+    /// Color at (x, y), None outside the clip rectangle
+    fn pixel(&self, x: usize, y: usize) -> Option<Color>;
+
+    /// Draw `color` over the pixel at (x, y), blended when it is translucent
+    fn draw_pixel(&mut self, x: usize, y: usize, color: Color);
+
+    /// What `pixel` returns after drawing the opaque `color`, which differs from it
+    /// when the canvas has fewer bits per channel
+    fn stored(&self, color: Color) -> Color {
+        color
+    }
+
+    /// Fill the part of `rect` in the clip rectangle
+    fn fill_rect(&mut self, rect: Rect, color: Color) {
+        let rect = rect.intersection(&self.clip());
+        for y in rect.y..rect.bottom() {
+            for x in rect.x..rect.right() {
+                self.draw_pixel(x, y, color);
+            }
+        }
+    }
+
+    /// Fill the clip rectangle
+    fn clear_background(&mut self, color: Color) {
+        self.fill_rect(self.clip(), color);
+    }
+
+    /// Draw a square at (x, y) with size square_size
+    fn draw_square(&mut self, x: usize, y: usize, square_size: usize, color: Color) {
+        self.fill_rect(Rect::new(x, y, square_size, square_size), color);
+    }
+
+    /// Copy `bitmap` with its top left corner at (x, y), blending its translucent pixels
+    fn draw_bitmap(&mut self, x: usize, y: usize, bitmap: &Bitmap) {
+        let area = Rect::new(x, y, bitmap.width(), bitmap.height()).intersection(&self.clip());
+        for dy in area.y..area.bottom() {
+            for dx in area.x..area.right() {
+                if let Some(color) = bitmap.pixel(dx - x, dy - y) {
+                    self.draw_pixel(dx, dy, color);
+                }
+            }
+        }
+    }
+
+    /// `text` on one line from (x, y), in the current font
+    fn draw_text(&mut self, x: usize, y: usize, text: &str, color: Color) {
+        font::draw_text(self, font::font(), x, y, text, color);
+    }
+}
@@ -0,0 +1,108 @@
+use crate::color::Color;
+use crate::graphics::{draw_span, Point};
+use crate::graphics::canvas::Canvas;
+use crate::graphics::rect::Rect;
+
+/// Most corners of a polygon fill_polygon takes, the others are ignored
+pub const MAX_POLYGON_POINTS: usize = 64;
+/// Spans flood_fill can have waiting (4 bytes each, on the kernel stack)
+const FLOOD_STACK_SIZE: usize = 1024;
+
+/// Fill the inside of the polygon (even-odd rule), a scanline at a time. A pixel is
+/// inside when its center is, so polygons sharing an edge don't overlap.
+pub fn fill_polygon<C: Canvas + ?Sized>(canvas: &mut C, points: &[Point], color: Color) {
+    let points = &points[..points.len().min(MAX_POLYGON_POINTS)];
+    if points.len() < 3 {
+        return;
+    }
+    let clip = canvas.clip();
+    let top = points.iter().map(|point| point.y).min().unwrap_or(0).max(clip.y as i32);
+    let bottom = points.iter().map(|point| point.y).max().unwrap_or(0).min(clip.bottom() as i32);
+
+    // x where the edges cross the middle of the row, in 16.16 fixed point
+    let mut crossings = [0i64; MAX_POLYGON_POINTS];
+    for y in top..bottom {
+        let mut count = 0;
+        for (i, start) in points.iter().enumerate() {
+            let end = points[(i + 1) % points.len()];
+            let (upper, lower) = if start.y < end.y { (*start, end) } else { (end, *start) };
+            if y < upper.y || y >= lower.y {
+                continue;
+            }
+            // i128: the points may be far off screen
+            let (dx, dy) = (lower.x as i128 - upper.x as i128, lower.y as i128 - upper.y as i128);
+            let progress = 2 * (y as i128 - upper.y as i128) + 1;
+            crossings[count] = ((upper.x as i64) << 16) + (((dx * progress) << 16) / (2 * dy)) as i64;
+            count += 1;
+        }
+        crossings[..count].sort_unstable();
+        for pair in crossings[..count].chunks_exact(2) {
+            // Pixels whose center is in [pair[0], pair[1])
+            let first = (pair[0] + 0x7FFF) >> 16;
+            let last = ((pair[1] + 0x7FFF) >> 16) - 1;
+            if first <= last {
+                draw_span(canvas, first as i32, last as i32, y, color);
+            }
+        }
+    }
+}
+
+pub fn fill_triangle<C: Canvas + ?Sized>(canvas: &mut C, a: Point, b: Point, c: Point, color: Color) {
+    fill_polygon(canvas, &[a, b, c], color);
+}
+
+/// Paint `color` over the area of same colored pixels around (x, y), inside the clip
+/// rectangle. Returns false when the area was too complex to fill completely.
+pub fn flood_fill<C: Canvas + ?Sized>(canvas: &mut C, x: i32, y: i32, color: Color) -> bool {
+    if x < 0 || y < 0 {
+        return true;
+    }
+    let Some(target) = canvas.pixel(x as usize, y as usize) else {
+        return true;
+    };
+    // Filled pixels must not look like the target anymore, or this would never end
+    if canvas.stored(color.over(target)) == target {
+        return true;
+    }
+    let is_target = |canvas: &C, x: usize, y: usize| canvas.pixel(x, y) == Some(target);
+
+    // Seeds of spans to fill, canvases are smaller than 65536 pixels
+    let mut stack = [(0u16, 0u16); FLOOD_STACK_SIZE];
+    stack[0] = (x as u16, y as u16);
+    let mut count = 1;
+    let mut complete = true;
+    while count > 0 {
+        count -= 1;
+        let (x, y) = (stack[count].0 as usize, stack[count].1 as usize);
+        if !is_target(canvas, x, y) {
+            continue;
+        }
+        let (mut left, mut right) = (x, x);
+        while left > 0 && is_target(canvas, left - 1, y) {
+            left -= 1;
+        }
+        while is_target(canvas, right + 1, y) {
+            right += 1;
+        }
+        canvas.fill_rect(Rect::new(left, y, right - left + 1, 1), color);
+
+        // One seed for each run of target pixels just above and below the span
+        for row in [y.wrapping_sub(1), y + 1] {
+            let mut in_run = false;
+            for column in left..=right {
+                if !is_target(canvas, column, row) {
+                    in_run = false;
+                } else if !in_run {
+                    in_run = true;
+                    if count == FLOOD_STACK_SIZE {
+                        complete = false;
+                        continue;
+                    }
+                    stack[count] = (column as u16, row as u16);
+                    count += 1;
+                }
+            }
+        }
+    }
+    complete
+}
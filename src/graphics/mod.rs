@@ -0,0 +1,435 @@
+//! Drawing on any Canvas. The shapes take signed coordinates so they can stick out
+//! of the canvas on any side, everything is clipped by the canvas.
+
+use crate::color::Color;
+use crate::graphics::canvas::Canvas;
+use crate::graphics::rect::Rect;
+
+pub mod bitmap;
+pub mod canvas;
+mod fill;
+pub mod rect;
+pub mod view;
+
+pub use fill::{fill_polygon, fill_triangle, flood_fill};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Point {
+    pub const fn new(x: i32, y: i32) -> Self {
+        Point { x, y }
+    }
+}
+
+/// The part of the rectangle at (x, y) a canvas can show, None when it is all off the top or left
+fn visible_rect(x: i32, y: i32, width: i32, height: i32) -> Option<Rect> {
+    let (right, bottom) = (x.saturating_add(width), y.saturating_add(height));
+    let (x, y) = (x.max(0), y.max(0));
+    if right <= x || bottom <= y {
+        return None;
+    }
+    Some(Rect::new(x as usize, y as usize, (right - x) as usize, (bottom - y) as usize))
+}
+
+pub fn plot<C: Canvas + ?Sized>(canvas: &mut C, x: i32, y: i32, color: Color) {
+    if x >= 0 && y >= 0 {
+        canvas.draw_pixel(x as usize, y as usize, color);
+    }
+}
+
+/// Pixels from x0 to x1 (both included, in any order) on row y
+pub fn draw_span<C: Canvas + ?Sized>(canvas: &mut C, x0: i32, x1: i32, y: i32, color: Color) {
+    let (left, right) = (x0.min(x1), x0.max(x1));
+    if let Some(rect) = visible_rect(left, y, right - left + 1, 1) {
+        canvas.fill_rect(rect, color);
+    }
+}
+
+/// Bresenham line from (x0, y0) to (x1, y1), both ends included
+pub fn draw_line<C: Canvas + ?Sized>(canvas: &mut C, x0: i32, y0: i32, x1: i32, y1: i32, color: Color) {
+    if y0 == y1 {
+        return draw_span(canvas, x0, x1, y0, color);
+    }
+    let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+    let (step_x, step_y) = (if x0 < x1 { 1 } else { -1 }, if y0 < y1 { 1 } else { -1 });
+    let (mut x, mut y) = (x0, y0);
+    let mut error = dx + dy;
+    loop {
+        plot(canvas, x, y, color);
+        if x == x1 && y == y1 {
+            break;
+        }
+        let twice = 2 * error;
+        if twice >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if twice <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+/// Lines between consecutive points, and back to the first one when `closed`
+pub fn draw_polyline<C: Canvas + ?Sized>(canvas: &mut C, points: &[Point], closed: bool, color: Color) {
+    for pair in points.windows(2) {
+        draw_line(canvas, pair[0].x, pair[0].y, pair[1].x, pair[1].y, color);
+    }
+    if let (true, Some(first), Some(last)) = (closed && points.len() > 2, points.first(), points.last()) {
+        draw_line(canvas, last.x, last.y, first.x, first.y, color);
+    }
+}
+
+pub fn draw_rect<C: Canvas + ?Sized>(canvas: &mut C, x: i32, y: i32, width: i32, height: i32, color: Color) {
+    if width <= 0 || height <= 0 {
+        return;
+    }
+    let (right, bottom) = (x + width - 1, y + height - 1);
+    draw_span(canvas, x, right, y, color);
+    draw_span(canvas, x, right, bottom, color);
+    // The sides without the corners, so no pixel is drawn twice
+    for side in [x, right] {
+        if let Some(rect) = visible_rect(side, y + 1, 1, height - 2) {
+            canvas.fill_rect(rect, color);
+        }
+        if width == 1 {
+            break;
+        }
+    }
+}
+
+pub fn fill_rect<C: Canvas + ?Sized>(canvas: &mut C, x: i32, y: i32, width: i32, height: i32, color: Color) {
+    if let Some(rect) = visible_rect(x, y, width, height) {
+        canvas.fill_rect(rect, color);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GradientDirection {
+    /// From the left side to the right one
+    Horizontal,
+    /// From the top to the bottom
+    Vertical,
+}
+
+/// Rectangle going from the first of `colors` to the second, one color per row or column
+pub fn fill_gradient<C: Canvas + ?Sized>(
+    canvas: &mut C,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    colors: (Color, Color),
+    direction: GradientDirection,
+) {
+    let (from, to) = colors;
+    let steps = match direction {
+        GradientDirection::Horizontal => width,
+        GradientDirection::Vertical => height,
+    };
+    for i in 0..steps.max(0) {
+        let t = if steps > 1 { (i as i64 * 255 / (steps as i64 - 1)) as u8 } else { 0 };
+        let color = from.lerp(to, t);
+        match direction {
+            GradientDirection::Horizontal => fill_rect(canvas, x + i, y, 1, height, color),
+            GradientDirection::Vertical => fill_rect(canvas, x, y + i, width, 1, color),
+        }
+    }
+}
+
+/// Midpoint ellipse: calls `f(dx, dy)` for the points of the quarter with dx, dy >= 0,
+/// from the top (0, ry) to the right (rx, 0). dx never decreases and dy never increases.
+fn ellipse_quarter(rx: i32, ry: i32, mut f: impl FnMut(i32, i32)) {
+    let (rx2, ry2) = (rx as i64 * rx as i64, ry as i64 * ry as i64);
+    let (mut x, mut y) = (0i64, ry as i64);
+    let (mut px, mut py) = (0, 2 * rx2 * y);
+
+    // Where the slope is above -1: a step right each time
+    let mut p = ry2 - rx2 * ry as i64 + rx2 / 4;
+    while px < py {
+        f(x as i32, y as i32);
+        x += 1;
+        px += 2 * ry2;
+        if p < 0 {
+            p += ry2 + px;
+        } else {
+            y -= 1;
+            py -= 2 * rx2;
+            p += ry2 + px - py;
+        }
+    }
+    // Then a step down each time
+    p = (ry2 * (2 * x + 1) * (2 * x + 1)) / 4 + rx2 * (y - 1) * (y - 1) - rx2 * ry2;
+    while y >= 0 {
+        f(x as i32, y as i32);
+        y -= 1;
+        py -= 2 * rx2;
+        if p > 0 {
+            p += rx2 - py;
+        } else {
+            x += 1;
+            px += 2 * ry2;
+            p += rx2 - py + px;
+        }
+    }
+}
+
+/// Like ellipse_quarter, but once per row with the widest dx of that row
+fn ellipse_rows(rx: i32, ry: i32, mut f: impl FnMut(i32, i32)) {
+    let mut row: Option<(i32, i32)> = None;
+    ellipse_quarter(rx, ry, |dx, dy| {
+        if let Some((last_dx, last_dy)) = row
+            && last_dy != dy
+        {
+            f(last_dx, last_dy);
+        }
+        row = Some((dx, dy));
+    });
+    if let Some((dx, dy)) = row {
+        f(dx, dy);
+    }
+}
+
+/// Ellipse centered on (cx, cy) with radii rx and ry
+pub fn draw_ellipse<C: Canvas + ?Sized>(canvas: &mut C, cx: i32, cy: i32, rx: i32, ry: i32, color: Color) {
+    if rx < 0 || ry < 0 {
+        return;
+    }
+    if rx == 0 || ry == 0 {
+        return draw_line(canvas, cx - rx, cy - ry, cx + rx, cy + ry, color);
+    }
+    ellipse_quarter(rx, ry, |dx, dy| {
+        plot(canvas, cx + dx, cy + dy, color);
+        if dx != 0 {
+            plot(canvas, cx - dx, cy + dy, color);
+        }
+        if dy != 0 {
+            plot(canvas, cx + dx, cy - dy, color);
+            if dx != 0 {
+                plot(canvas, cx - dx, cy - dy, color);
+            }
+        }
+    });
+}
+
+pub fn fill_ellipse<C: Canvas + ?Sized>(canvas: &mut C, cx: i32, cy: i32, rx: i32, ry: i32, color: Color) {
+    if rx < 0 || ry < 0 {
+        return;
+    }
+    if rx == 0 || ry == 0 {
+        return draw_line(canvas, cx - rx, cy - ry, cx + rx, cy + ry, color);
+    }
+    ellipse_rows(rx, ry, |dx, dy| {
+        draw_span(canvas, cx - dx, cx + dx, cy - dy, color);
+        if dy != 0 {
+            draw_span(canvas, cx - dx, cx + dx, cy + dy, color);
+        }
+    });
+}
+
+pub fn draw_circle<C: Canvas + ?Sized>(canvas: &mut C, cx: i32, cy: i32, radius: i32, color: Color) {
+    draw_ellipse(canvas, cx, cy, radius, radius, color);
+}
+
+pub fn fill_circle<C: Canvas + ?Sized>(canvas: &mut C, cx: i32, cy: i32, radius: i32, color: Color) {
+    fill_ellipse(canvas, cx, cy, radius, radius, color);
+}
+
+/// Radius of the corners of a width x height rectangle, at most half of its smaller side
+fn corner_radius(width: i32, height: i32, radius: i32) -> i32 {
+    radius.clamp(0, (width.min(height) - 1) / 2)
+}
+
+/// Rectangle with quarter circle corners of `radius`
+pub fn draw_rounded_rect<C: Canvas + ?Sized>(canvas: &mut C, x: i32, y: i32, width: i32, height: i32, radius: i32, color: Color) {
+    if width <= 0 || height <= 0 {
+        return;
+    }
+    let radius = corner_radius(width, height, radius);
+    if radius == 0 {
+        return draw_rect(canvas, x, y, width, height, color);
+    }
+    // Centers of the corner circles
+    let (left, top) = (x + radius, y + radius);
+    let (right, bottom) = (x + width - 1 - radius, y + height - 1 - radius);
+    if left < right - 1 {
+        draw_span(canvas, left + 1, right - 1, y, color);
+        draw_span(canvas, left + 1, right - 1, y + height - 1, color);
+    }
+    fill_rect(canvas, x, top + 1, 1, bottom - top - 1, color);
+    fill_rect(canvas, x + width - 1, top + 1, 1, bottom - top - 1, color);
+    ellipse_quarter(radius, radius, |dx, dy| {
+        plot(canvas, right + dx, top - dy, color);
+        plot(canvas, left - dx, top - dy, color);
+        plot(canvas, right + dx, bottom + dy, color);
+        plot(canvas, left - dx, bottom + dy, color);
+    });
+}
+
+pub fn fill_rounded_rect<C: Canvas + ?Sized>(canvas: &mut C, x: i32, y: i32, width: i32, height: i32, radius: i32, color: Color) {
+    if width <= 0 || height <= 0 {
+        return;
+    }
+    let radius = corner_radius(width, height, radius);
+    let (left, top) = (x + radius, y + radius);
+    let (right, bottom) = (x + width - 1 - radius, y + height - 1 - radius);
+    fill_rect(canvas, x, top, width, bottom - top + 1, color);
+    if radius == 0 {
+        return;
+    }
+    ellipse_rows(radius, radius, |dx, dy| {
+        if dy != 0 {
+            draw_span(canvas, left - dx, right + dx, top - dy, color);
+            draw_span(canvas, left - dx, right + dx, bottom + dy, color);
+        }
+    });
+}
+
+pub fn draw_triangle<C: Canvas + ?Sized>(canvas: &mut C, a: Point, b: Point, c: Point, color: Color) {
+    draw_polyline(canvas, &[a, b, c], true, color);
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+    use crate::graphics::bitmap::Bitmap;
+    use crate::graphics::view::View;
+    use crate::testing::{assert_golden, to_text};
+
+    const PALETTE: [(Color, char); 4] = [(Color::BLACK, '.'), (Color::WHITE, '#'), (Color::RED, 'r'), (Color::BLUE, 'b')];
+
+    /// Run `draw` on a black width x height bitmap and return it as text
+    fn draw(width: usize, height: usize, draw: impl FnOnce(&mut Bitmap)) -> String {
+        let mut pixels = vec![Color::BLACK; width * height];
+        let mut bitmap = Bitmap::new(&mut pixels, width, height).unwrap();
+        draw(&mut bitmap);
+        to_text(&bitmap, &PALETTE)
+    }
+
+    #[test]
+    fn lines() {
+        let text = draw(16, 8, |canvas| {
+            draw_line(canvas, 0, 0, 15, 7, Color::WHITE);
+            draw_line(canvas, 15, 0, 8, 7, Color::RED);
+            // Mostly off the canvas
+            draw_line(canvas, -20, 6, 100, 6, Color::BLUE);
+        });
+        assert_golden("lines", &text);
+    }
+
+    #[test]
+    fn rectangles() {
+        let text = draw(20, 12, |canvas| {
+            draw_rect(canvas, 1, 1, 8, 5, Color::WHITE);
+            fill_rect(canvas, 3, 3, 4, 1, Color::RED);
+            fill_rounded_rect(canvas, 10, 1, 9, 7, 3, Color::BLUE);
+            draw_rounded_rect(canvas, -2, 7, 10, 8, 2, Color::WHITE);
+        });
+        assert_golden("rectangles", &text);
+    }
+
+    #[test]
+    fn ellipses() {
+        let text = draw(25, 13, |canvas| {
+            draw_circle(canvas, 6, 6, 5, Color::WHITE);
+            fill_ellipse(canvas, 18, 6, 6, 3, Color::RED);
+            draw_ellipse(canvas, 18, 6, 6, 6, Color::BLUE);
+        });
+        assert_golden("ellipses", &text);
+    }
+
+    #[test]
+    fn ellipses_are_symmetric() {
+        for (rx, ry) in [(1, 1), (3, 7), (10, 2), (9, 9)] {
+            let (width, height) = (2 * rx as usize + 1, 2 * ry as usize + 1);
+            let mut pixels = vec![Color::BLACK; width * height];
+            let mut bitmap = Bitmap::new(&mut pixels, width, height).unwrap();
+            draw_ellipse(&mut bitmap, rx, ry, rx, ry, Color::WHITE);
+            for y in 0..height {
+                for x in 0..width {
+                    let pixel = bitmap.pixel(x, y);
+                    assert_eq!(pixel, bitmap.pixel(width - 1 - x, y), "{}x{} at ({}, {})", rx, ry, x, y);
+                    assert_eq!(pixel, bitmap.pixel(x, height - 1 - y), "{}x{} at ({}, {})", rx, ry, x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn triangles() {
+        let text = draw(16, 10, |canvas| {
+            fill_triangle(canvas, Point::new(1, 1), Point::new(14, 3), Point::new(4, 9), Color::RED);
+            draw_triangle(canvas, Point::new(1, 1), Point::new(14, 3), Point::new(4, 9), Color::WHITE);
+        });
+        assert_golden("triangles", &text);
+    }
+
+    #[test]
+    fn polygons_sharing_an_edge_do_not_overlap() {
+        let mut pixels = vec![Color::TRANSPARENT; 40 * 30];
+        let mut bitmap = Bitmap::new(&mut pixels, 40, 30).unwrap();
+        let half = Color::RED.with_alpha(0x80);
+        let (a, b, c, d) = (Point::new(2, 1), Point::new(37, 5), Point::new(30, 28), Point::new(4, 20));
+        fill_triangle(&mut bitmap, a, b, c, half);
+        fill_triangle(&mut bitmap, a, c, d, half);
+        // A pixel drawn twice would be more opaque, and the diagonal leaves no gap
+        assert!(pixels.iter().all(|pixel| pixel.alpha == 0 || *pixel == half));
+        for y in 6..20 {
+            let row = &pixels[y * 40..(y + 1) * 40];
+            let first = row.iter().position(|pixel| *pixel == half).unwrap();
+            let last = row.iter().rposition(|pixel| *pixel == half).unwrap();
+            assert!(row[first..=last].iter().all(|pixel| *pixel == half), "gap in row {}", y);
+        }
+    }
+
+    #[test]
+    fn flood_fill_stays_inside() {
+        let text = draw(14, 9, |canvas| {
+            draw_circle(canvas, 4, 4, 4, Color::WHITE);
+            draw_rect(canvas, 9, 0, 5, 9, Color::WHITE);
+            assert!(flood_fill(canvas, 4, 4, Color::RED));
+            assert!(flood_fill(canvas, 11, 4, Color::BLUE));
+            // Filling with the same color does nothing
+            assert!(flood_fill(canvas, 11, 4, Color::BLUE));
+        });
+        assert_golden("flood_fill", &text);
+    }
+
+    #[test]
+    fn gradient() {
+        let mut pixels = vec![Color::BLACK; 5];
+        let mut bitmap = Bitmap::new(&mut pixels, 5, 1).unwrap();
+        fill_gradient(&mut bitmap, 0, 0, 5, 1, (Color::BLACK, Color::WHITE), GradientDirection::Horizontal);
+        let reds: Vec<u8> = pixels.iter().map(|pixel| pixel.red).collect();
+        assert_eq!(reds, [0x00, 0x3F, 0x7F, 0xBF, 0xFF]);
+
+        let mut bitmap = Bitmap::new(&mut pixels, 1, 5).unwrap();
+        fill_gradient(&mut bitmap, 0, 0, 1, 5, (Color::WHITE, Color::BLACK), GradientDirection::Vertical);
+        let reds: Vec<u8> = pixels.iter().map(|pixel| pixel.red).collect();
+        assert_eq!(reds, [0xFF, 0xC0, 0x80, 0x40, 0x00]);
+    }
+
+    #[test]
+    fn text() {
+        // The built-in font has no 'é', it becomes '?'
+        let text = draw(34, 18, |canvas| canvas.draw_text(1, 1, "Hi?é", Color::WHITE));
+        assert_golden("text", &text);
+    }
+
+    #[test]
+    fn view_translates_and_clips() {
+        let text = draw(12, 8, |canvas| {
+            let mut view = View::new(canvas, Rect::new(3, 2, 6, 4));
+            assert_eq!((view.width(), view.height()), (6, 4));
+            view.clear_background(Color::BLUE);
+            draw_line(&mut view, -5, 0, 20, 0, Color::WHITE);
+            fill_circle(&mut view, 5, 3, 3, Color::RED);
+        });
+        assert_golden("view", &text);
+    }
+}
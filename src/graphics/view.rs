@@ -0,0 +1,59 @@
+use crate::color::Color;
+use crate::graphics::canvas::Canvas;
+use crate::graphics::rect::Rect;
+
+/// A part of another canvas with its own coordinates: (0, 0) is the top left of the
+/// area, and nothing is drawn outside of it. A window can draw its content in one.
+pub struct View<'a, C: Canvas + ?Sized> {
+    canvas: &'a mut C,
+    area: Rect,
+}
+
+impl<'a, C: Canvas + ?Sized> View<'a, C> {
+    /// `area` of `canvas`, cut to its bounds
+    pub fn new(canvas: &'a mut C, area: Rect) -> Self {
+        let area = area.intersection(&canvas.bounds());
+        View { canvas, area }
+    }
+}
+
+impl<C: Canvas + ?Sized> Canvas for View<'_, C> {
+    fn width(&self) -> usize {
+        self.area.width
+    }
+
+    fn height(&self) -> usize {
+        self.area.height
+    }
+
+    /// The clip rectangle of the canvas in the view, in view coordinates
+    fn clip(&self) -> Rect {
+        let clip = self.canvas.clip().intersection(&self.area);
+        Rect::new(clip.x.saturating_sub(self.area.x), clip.y.saturating_sub(self.area.y), clip.width, clip.height)
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> Option<Color> {
+        if x >= self.area.width || y >= self.area.height {
+            return None;
+        }
+        self.canvas.pixel(self.area.x + x, self.area.y + y)
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if x < self.area.width && y < self.area.height {
+            self.canvas.draw_pixel(self.area.x + x, self.area.y + y, color);
+        }
+    }
+
+    fn stored(&self, color: Color) -> Color {
+        self.canvas.stored(color)
+    }
+
+    /// Passed on to the canvas, for its faster fill
+    fn fill_rect(&mut self, rect: Rect, color: Color) {
+        let rect = rect.intersection(&self.bounds());
+        if !rect.is_empty() {
+            self.canvas.fill_rect(Rect::new(self.area.x + rect.x, self.area.y + rect.y, rect.width, rect.height), color);
+        }
+    }
+}
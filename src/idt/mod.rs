@@ -1,7 +1,11 @@
 use core::mem::size_of;
+use core::sync::atomic::{AtomicU64, Ordering};
 use crate::color::Color;
-use crate::{io, pic};
-use crate::vbe::get_vbe;
+use crate::gdt::KERNEL_CODE_SELECTOR;
+use crate::graphics::canvas::Canvas;
+use crate::syscall::{int80_entry, return_to_kernel};
+use crate::vbe::screen;
+use crate::{ata, io, keyboard, pic, println, process, timer};
 
 #[derive(Debug)]
 #[repr(C)]
@@ -34,6 +38,18 @@ pub struct IdtPtr {
 
 static mut IDT: [IdtEntry; 256] = [IdtEntry::new(); 256];
 
+/// Interrupts received so far, per vector
+static INTERRUPT_COUNTS: [AtomicU64; 256] = [const { AtomicU64::new(0) }; 256];
+
+fn count_interrupt(vector: u8) {
+    INTERRUPT_COUNTS[vector as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+/// How many times the handler of `vector` ran
+pub fn interrupt_count(vector: u8) -> u64 {
+    INTERRUPT_COUNTS[vector as usize].load(Ordering::Relaxed)
+}
+
 impl IdtEntry {
     pub const fn new() -> Self {
         IdtEntry {
@@ -50,17 +66,30 @@ impl IdtEntry {
     pub fn set_handler(&mut self, handler: u64) {
         self.offset_low = handler as u16;
         // CRITICAL: This must match the CODE_SEG_64 in your gdt.asm (0x18)
-        self.selector = 0x18;
+        self.selector = KERNEL_CODE_SELECTOR;
         self.ist = 0;
         // 0x8E = Present, Ring 0, Interrupt Gate
         self.type_attr = 0x8E;
         self.offset_middle = (handler >> 16) as u16;
         self.offset_high = (handler >> 32) as u32;
     }
+
+    /// Like set_handler, but user mode may also raise it with `int`
+    pub fn set_user_handler(&mut self, handler: u64) {
+        self.set_handler(handler);
+        // 0xEE = Present, Ring 3, Interrupt Gate
+        self.type_attr = 0xEE;
+    }
 }
 
 pub fn init_idt() {
     unsafe {
+        // CPU exceptions a user program can cause, they kill it
+        IDT[0].set_handler(divide_error_handler as *const () as u64);
+        IDT[6].set_handler(invalid_opcode_handler as *const () as u64);
+        IDT[13].set_handler(general_protection_handler as *const () as u64);
+        IDT[14].set_handler(page_fault_handler as *const () as u64);
+
         // Set Breakpoint Handler (Vector 3)
         // This is useful for testing interrupts without crashing
         IDT[3].set_handler(breakpoint_handler as *const () as u64);
@@ -73,9 +102,19 @@ pub fn init_idt() {
 
         core::arch::asm!("lidt [{}]", in(reg) &ptr, options(readonly, nostack, preserves_flags));
 
+        // Index 32 = PIC Offset (32) + IRQ 0 (PIT timer)
+        IDT[32].set_handler(timer_handler as *const () as u64);
+
         // Index 33 = PIC Offset (32) + IRQ 1 (Keyboard)
         IDT[33].set_handler(keyboard_handler as *const () as u64);
 
+        // Index 46/47 = Slave PIC Offset (40) + IRQ 14/15 (ATA primary/secondary channel)
+        IDT[46].set_handler(ata_primary_handler as *const () as u64);
+        IDT[47].set_handler(ata_secondary_handler as *const () as u64);
+
+        // System calls from ring 3, for programs not using `syscall`
+        IDT[0x80].set_user_handler(int80_entry as *const () as u64);
+
         let ptr = IdtPtr {
             limit: (size_of::<[IdtEntry; 256]>() - 1) as u16,
             base: core::ptr::addr_of!(IDT) as u64,
@@ -86,28 +125,134 @@ pub fn init_idt() {
     }
 }
 
+/// An exception in a user program ends it with exit code 128 + vector,
+/// one in the kernel is a bug
+fn handle_exception(vector: u8, name: &str, stack_frame: &InterruptStackFrame, detail: u64) {
+    count_interrupt(vector);
+    if stack_frame.code_segment & 3 == 3 {
+        println!("{} at {:#x} ({:#x}), program killed", name, stack_frame.instruction_pointer, detail);
+        return_to_kernel(128 + vector as i64);
+    }
+    panic!("{} at {:#x} ({:#x})", name, stack_frame.instruction_pointer, detail);
+}
+
+extern "x86-interrupt" fn divide_error_handler(stack_frame: InterruptStackFrame) {
+    handle_exception(0, "divide error", &stack_frame, 0);
+}
+
+extern "x86-interrupt" fn invalid_opcode_handler(stack_frame: InterruptStackFrame) {
+    handle_exception(6, "invalid opcode", &stack_frame, 0);
+}
+
+extern "x86-interrupt" fn general_protection_handler(stack_frame: InterruptStackFrame, error_code: u64) {
+    handle_exception(13, "general protection fault", &stack_frame, error_code);
+}
+
+extern "x86-interrupt" fn page_fault_handler(stack_frame: InterruptStackFrame, _error_code: u64) {
+    // CR2 holds the address that could not be accessed
+    let address: u64;
+    unsafe { core::arch::asm!("mov {}, cr2", out(reg) address, options(nomem, nostack, preserves_flags)); }
+    handle_exception(14, "page fault", &stack_frame, address);
+}
+
 extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
-    let vbe_info = get_vbe();
-    vbe_info.draw_square(200, 200, 100, Color{red: 0xFF, green: 0xFF, blue: 0xFF});
+    count_interrupt(3);
+    if let Some(screen) = screen() {
+        screen.draw_square(200, 200, 100, Color::WHITE);
+        screen.flush();
+    }
+}
+
+extern "x86-interrupt" fn timer_handler(stack_frame: InterruptStackFrame) {
+    count_interrupt(32);
+    timer::handle_tick();
+    unsafe { pic::notify_eoi(32); }
+    // Only user programs are preempted, the kernel switches tasks where it waits
+    if stack_frame.code_segment & 3 == 3 {
+        process::preempt();
+    }
 }
 
 extern "x86-interrupt" fn keyboard_handler(_stack_frame: InterruptStackFrame) {
+    count_interrupt(33);
     unsafe {
         // 1. Read the scancode from the keyboard data port
         let scancode = io::inb(0x60);
 
-        // 2. Visual feedback
-        let vbe = get_vbe();
-        // If the top bit is 0, it's a Key Press. If 1, it's a Release.
-        if scancode < 128 {
-            // Key Down -> Green Square
-            vbe.draw_square(100, 100, 50, Color { red: 0, green: 255, blue: 0 });
-        } else {
-            // Key Up -> Red Square
-            vbe.draw_square(100, 100, 50, Color { red: 255, green: 0, blue: 0 });
-        }
+        // 2. Queue it for the shell and /dev/kbd
+        keyboard::handle_scancode(scancode);
 
         // 3. Acknowledge the interrupt
         pic::notify_eoi(33);
     }
 }
+
+extern "x86-interrupt" fn ata_primary_handler(_stack_frame: InterruptStackFrame) {
+    count_interrupt(46);
+    ata::handle_irq(&ata::PRIMARY);
+    unsafe { pic::notify_eoi(46); }
+}
+
+extern "x86-interrupt" fn ata_secondary_handler(_stack_frame: InterruptStackFrame) {
+    count_interrupt(47);
+    ata::handle_irq(&ata::SECONDARY);
+    unsafe { pic::notify_eoi(47); }
+}
+
+#[cfg(all(test, target_os = "none"))]
+mod tests {
+    use super::*;
+    use crate::test_runner::ShouldPanic;
+
+    #[test_case]
+    fn int3_reaches_breakpoint_handler() {
+        let before = interrupt_count(3);
+        unsafe { core::arch::asm!("int3"); }
+        assert_eq!(interrupt_count(3), before + 1);
+    }
+
+    #[test_case]
+    fn timer_interrupts_arrive() {
+        let before = interrupt_count(32);
+        // 100 Hz: a few halts are enough
+        for _ in 0..10 {
+            unsafe { core::arch::asm!("hlt"); }
+        }
+        assert!(interrupt_count(32) > before);
+    }
+
+    #[test_case]
+    const PAGE_FAULT_PANICS: ShouldPanic = ShouldPanic {
+        name: "idt::tests::page_fault_panics",
+        message: "page fault",
+        // Above the 4 GiB identity map
+        test: || unsafe {
+            core::ptr::read_volatile(0x10_0000_0000 as *const u64);
+        },
+    };
+
+    #[test_case]
+    const DIVIDE_ERROR_PANICS: ShouldPanic = ShouldPanic {
+        name: "idt::tests::divide_error_panics",
+        message: "divide error",
+        test: || unsafe {
+            core::arch::asm!("xor edx, edx", "div {0:e}", in(reg) 0u32, inout("eax") 1u32 => _, out("edx") _);
+        },
+    };
+
+    #[test_case]
+    const INVALID_OPCODE_PANICS: ShouldPanic = ShouldPanic {
+        name: "idt::tests::invalid_opcode_panics",
+        message: "invalid opcode",
+        test: || unsafe {
+            core::arch::asm!("ud2");
+        },
+    };
+
+    #[test_case]
+    const PANICS_ARE_CAUGHT: ShouldPanic = ShouldPanic {
+        name: "idt::tests::panics_are_caught",
+        message: "on purpose",
+        test: || panic!("on purpose"),
+    };
+}
@@ -2,17 +2,60 @@ use core::arch::asm;
 
 /// Write a byte to a port
 pub unsafe fn outb(port: u16, value: u8) {
-    asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags));
+    unsafe { asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags)); }
 }
 
 /// Read a byte from a port
 pub unsafe fn inb(port: u16) -> u8 {
     let value: u8;
-    asm!("in al, dx", out("al") value, in("dx") port, options(nomem, nostack, preserves_flags));
+    unsafe { asm!("in al, dx", out("al") value, in("dx") port, options(nomem, nostack, preserves_flags)); }
     value
 }
 
+/// Write a word (16 bits) to a port
+pub unsafe fn outw(port: u16, value: u16) {
+    unsafe { asm!("out dx, ax", in("dx") port, in("ax") value, options(nomem, nostack, preserves_flags)); }
+}
+
+/// Write a double word (32 bits) to a port
+pub unsafe fn outl(port: u16, value: u32) {
+    unsafe { asm!("out dx, eax", in("dx") port, in("eax") value, options(nomem, nostack, preserves_flags)); }
+}
+
+/// Read a double word (32 bits) from a port
+pub unsafe fn inl(port: u16) -> u32 {
+    let value: u32;
+    unsafe { asm!("in eax, dx", out("eax") value, in("dx") port, options(nomem, nostack, preserves_flags)); }
+    value
+}
+
+/// Read `buffer.len() / 2` words from a port into `buffer`, each one little-endian (rep insw)
+pub unsafe fn insw(port: u16, buffer: &mut [u8]) {
+    unsafe {
+        asm!(
+            "rep insw",
+            in("dx") port,
+            inout("rdi") buffer.as_mut_ptr() => _,
+            inout("rcx") buffer.len() / 2 => _,
+            options(nostack, preserves_flags)
+        );
+    }
+}
+
+/// Write `buffer` to a port as `buffer.len() / 2` little-endian words (rep outsw)
+pub unsafe fn outsw(port: u16, buffer: &[u8]) {
+    unsafe {
+        asm!(
+            "rep outsw",
+            in("dx") port,
+            inout("rsi") buffer.as_ptr() => _,
+            inout("rcx") buffer.len() / 2 => _,
+            options(readonly, nostack, preserves_flags)
+        );
+    }
+}
+
 /// Wait a very small amount of time (used for synchronizing with slow hardware)
 pub unsafe fn wait() {
-    outb(0x80, 0);
-}
\ No newline at end of file
+    unsafe { outb(0x80, 0); }
+}
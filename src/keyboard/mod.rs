@@ -0,0 +1,207 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Scancodes kept until someone reads them, new ones are dropped when it is full
+pub const KEY_BUFFER_SIZE: usize = 256;
+
+// Ring buffer filled by the IRQ1 handler. Only the handler moves `KEY_HEAD`
+// and only readers move `KEY_TAIL`, so no lock is needed.
+static mut KEY_BUFFER: [u8; KEY_BUFFER_SIZE] = [0; KEY_BUFFER_SIZE];
+static KEY_HEAD: AtomicUsize = AtomicUsize::new(0);
+static KEY_TAIL: AtomicUsize = AtomicUsize::new(0);
+
+/// Called from the keyboard interrupt handler with the byte read from port 0x60
+pub fn handle_scancode(scancode: u8) {
+    let head = KEY_HEAD.load(Ordering::Relaxed);
+    let next = (head + 1) % KEY_BUFFER_SIZE;
+    if next == KEY_TAIL.load(Ordering::Acquire) {
+        return;
+    }
+    unsafe { (*core::ptr::addr_of_mut!(KEY_BUFFER))[head] = scancode; }
+    KEY_HEAD.store(next, Ordering::Release);
+}
+
+/// Oldest raw scancode (set 1, with its 0xE0 prefixes) not read yet
+pub fn read_scancode() -> Option<u8> {
+    let tail = KEY_TAIL.load(Ordering::Relaxed);
+    if tail == KEY_HEAD.load(Ordering::Acquire) {
+        return None;
+    }
+    let scancode = unsafe { (*core::ptr::addr_of!(KEY_BUFFER))[tail] };
+    KEY_TAIL.store((tail + 1) % KEY_BUFFER_SIZE, Ordering::Release);
+    Some(scancode)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    /// A letter typed with Ctrl held, always lowercase
+    Ctrl(char),
+    Enter,
+    Backspace,
+    Delete,
+    Tab,
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+}
+
+const RELEASED: u8 = 0x80;
+const EXTENDED_PREFIX: u8 = 0xE0;
+const LEFT_SHIFT: u8 = 0x2A;
+const RIGHT_SHIFT: u8 = 0x36;
+const CTRL: u8 = 0x1D;
+const CAPS_LOCK: u8 = 0x3A;
+
+// Scancode set 1 make codes to characters on a US QWERTY layout, without and with shift
+const US_LAYOUT: [(u8, u8); 0x3A] = [
+    (0, 0), (0, 0), (b'1', b'!'), (b'2', b'@'), (b'3', b'#'), (b'4', b'$'), (b'5', b'%'), (b'6', b'^'),
+    (b'7', b'&'), (b'8', b'*'), (b'9', b'('), (b'0', b')'), (b'-', b'_'), (b'=', b'+'), (0, 0), (0, 0),
+    (b'q', b'Q'), (b'w', b'W'), (b'e', b'E'), (b'r', b'R'), (b't', b'T'), (b'y', b'Y'), (b'u', b'U'), (b'i', b'I'),
+    (b'o', b'O'), (b'p', b'P'), (b'[', b'{'), (b']', b'}'), (0, 0), (0, 0), (b'a', b'A'), (b's', b'S'),
+    (b'd', b'D'), (b'f', b'F'), (b'g', b'G'), (b'h', b'H'), (b'j', b'J'), (b'k', b'K'), (b'l', b'L'), (b';', b':'),
+    (b'\'', b'"'), (b'`', b'~'), (0, 0), (b'\\', b'|'), (b'z', b'Z'), (b'x', b'X'), (b'c', b'C'), (b'v', b'V'),
+    (b'b', b'B'), (b'n', b'N'), (b'm', b'M'), (b',', b'<'), (b'.', b'>'), (b'/', b'?'), (0, 0), (b'*', b'*'),
+    (0, 0), (b' ', b' '),
+];
+
+/// Turns scancodes into keys, keeping track of the modifier keys
+pub struct KeyDecoder {
+    shift: bool,
+    ctrl: bool,
+    caps_lock: bool,
+    extended: bool,
+}
+
+impl KeyDecoder {
+    pub const fn new() -> Self {
+        KeyDecoder { shift: false, ctrl: false, caps_lock: false, extended: false }
+    }
+
+    /// Feed one scancode, returns the key it completes (releases and modifiers give None)
+    pub fn decode(&mut self, scancode: u8) -> Option<Key> {
+        if scancode == EXTENDED_PREFIX {
+            self.extended = true;
+            return None;
+        }
+        let extended = core::mem::replace(&mut self.extended, false);
+        let released = scancode & RELEASED != 0;
+        let code = scancode & !RELEASED;
+
+        match code {
+            LEFT_SHIFT | RIGHT_SHIFT if !extended => self.shift = !released,
+            // Right Ctrl is the extended version of the same code
+            CTRL => self.ctrl = !released,
+            CAPS_LOCK if !released => self.caps_lock = !self.caps_lock,
+            _ => {}
+        }
+        if released {
+            return None;
+        }
+
+        if extended {
+            return match code {
+                0x48 => Some(Key::Up),
+                0x50 => Some(Key::Down),
+                0x4B => Some(Key::Left),
+                0x4D => Some(Key::Right),
+                0x47 => Some(Key::Home),
+                0x4F => Some(Key::End),
+                0x53 => Some(Key::Delete),
+                0x1C => Some(Key::Enter),
+                0x35 => Some(Key::Char('/')),
+                _ => None,
+            };
+        }
+
+        match code {
+            0x01 => return Some(Key::Escape),
+            0x0E => return Some(Key::Backspace),
+            0x0F => return Some(Key::Tab),
+            0x1C => return Some(Key::Enter),
+            _ => {}
+        }
+        let &(normal, shifted) = US_LAYOUT.get(code as usize)?;
+        if normal == 0 {
+            return None;
+        }
+        let char = if normal.is_ascii_lowercase() {
+            if self.ctrl {
+                return Some(Key::Ctrl(normal as char));
+            }
+            if self.shift != self.caps_lock { shifted } else { normal }
+        } else if self.shift {
+            shifted
+        } else {
+            normal
+        };
+        Some(Key::Char(char as char))
+    }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+
+    /// The keys the scancodes decode to, in order
+    fn keys(scancodes: &[u8]) -> Vec<Key> {
+        let mut decoder = KeyDecoder::new();
+        scancodes.iter().filter_map(|&scancode| decoder.decode(scancode)).collect()
+    }
+
+    #[test]
+    fn letters_and_releases() {
+        // h pressed, released, i pressed, released
+        assert_eq!(keys(&[0x23, 0xA3, 0x17, 0x97]), [Key::Char('h'), Key::Char('i')]);
+    }
+
+    #[test]
+    fn shift() {
+        // Left shift held for "A!", released before "1"
+        assert_eq!(keys(&[0x2A, 0x1E, 0x02, 0xAA, 0x02]), [Key::Char('A'), Key::Char('!'), Key::Char('1')]);
+        // Right shift
+        assert_eq!(keys(&[0x36, 0x27, 0xB6, 0x27]), [Key::Char(':'), Key::Char(';')]);
+    }
+
+    #[test]
+    fn caps_lock_only_changes_letters() {
+        // Caps lock, "a1", shift + "a", caps lock again, "a"
+        assert_eq!(
+            keys(&[0x3A, 0xBA, 0x1E, 0x02, 0x2A, 0x1E, 0xAA, 0x3A, 0xBA, 0x1E]),
+            [Key::Char('A'), Key::Char('1'), Key::Char('a'), Key::Char('a')]
+        );
+    }
+
+    #[test]
+    fn ctrl() {
+        // Left ctrl + c, then right ctrl (extended) + shift + d
+        assert_eq!(keys(&[0x1D, 0x2E, 0x9D, 0x2E]), [Key::Ctrl('c'), Key::Char('c')]);
+        assert_eq!(keys(&[0xE0, 0x1D, 0x2A, 0x20, 0xE0, 0x9D]), [Key::Ctrl('d')]);
+    }
+
+    #[test]
+    fn extended_keys() {
+        assert_eq!(
+            keys(&[0xE0, 0x48, 0xE0, 0xC8, 0xE0, 0x4B, 0xE0, 0x53, 0xE0, 0x1C, 0xE0, 0x35, 0x1C]),
+            [Key::Up, Key::Left, Key::Delete, Key::Enter, Key::Char('/'), Key::Enter]
+        );
+        // The extended prefix only applies to the next scancode: 0x48 alone is keypad 8
+        assert_eq!(keys(&[0xE0, 0x50, 0x48]), [Key::Down]);
+    }
+
+    #[test]
+    fn fake_shift_of_extended_keys() {
+        // Some keyboards send E0 2A before the arrows, it is not a shift
+        assert_eq!(keys(&[0xE0, 0x2A, 0xE0, 0x47, 0x1E]), [Key::Home, Key::Char('a')]);
+    }
+
+    #[test]
+    fn special_keys() {
+        assert_eq!(keys(&[0x01, 0x0E, 0x0F, 0x39]), [Key::Escape, Key::Backspace, Key::Tab, Key::Char(' ')]);
+        // F1 and keys past the layout give nothing
+        assert!(keys(&[0x3B, 0x58]).is_empty());
+    }
+}
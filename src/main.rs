@@ -1,44 +1,124 @@
 #![feature(abi_x86_interrupt)]
-#![no_std]
-#![no_main]
+// Tests are built two ways: on the host (`cargo test-host`) with std and #[test], and for
+// the kernel target (`sh build-run.sh test`) with #[test_case], run in QEMU by test_runner
+#![cfg_attr(any(not(test), target_os = "none"), no_std)]
+#![cfg_attr(any(not(test), target_os = "none"), no_main)]
+#![cfg_attr(all(test, target_os = "none"), feature(custom_test_frameworks))]
+#![cfg_attr(all(test, target_os = "none"), test_runner(crate::test_runner::run_tests))]
+#![cfg_attr(all(test, target_os = "none"), reexport_test_harness_main = "test_main")]
 
+#[cfg(any(not(test), target_os = "none"))]
 use core::panic::PanicInfo;
 use idt::init_idt;
-use crate::color::Color;
+use crate::ata::init_ata;
+use crate::console::init_console;
+use crate::font::init_font;
+use crate::fs::init_fs;
+use crate::gdt::init_gdt;
+use crate::memory::init_memory;
 use crate::pic::init_pic;
-use crate::vbe::{get_vbe};
+use crate::process::init_process;
+use crate::serial::init_serial;
+use crate::syscall::init_syscall;
+use crate::timer::init_timer;
+use crate::vbe::init_vbe;
 
+mod ata;
+mod block;
+mod cmos;
 mod color;
+mod console;
+mod cpu;
+mod exec;
+mod font;
+mod fs;
+mod gdt;
+mod graphics;
 mod idt;
 mod io;
+mod keyboard;
+mod memory;
+mod partition;
+mod pci;
 mod pic;
+mod power;
+mod process;
+mod serial;
+mod shell;
+mod syscall;
+mod task;
+#[cfg(all(test, target_os = "none"))]
+mod test_runner;
+#[cfg(all(test, not(target_os = "none")))]
+mod testing;
+mod timer;
 mod vbe;
 
 const VBE_MODE_INFO_ADDRESS: u16 = 0x5000;
+// Sector cache of the boot disk (~140 KiB, identity mapped RAM above 1 MiB)
+const BLOCK_CACHE_ADDRESS: usize = 0x100000;
+// Kernel stacks of the processes, 32 KiB for each slot of the process table (after the block cache)
+const KERNEL_STACKS_ADDRESS: usize = 0x140000;
+const KERNEL_STACKS_SIZE: usize = 0x80000;
+// 0x1C0000-0x200000: stack of the boot code and init, set by kernel_entry.asm
+// Content of the loaded .psf font file, twice: a new font is read next to the one in use
+const FONT_ADDRESS: usize = 0x200000;
+const FONT_MAX_SIZE: usize = 0x100000;
+// Initrd copied by kernel_entry.asm (must match INITRD_ADDRESS and INITRD_SECTORS there)
+const INITRD_ADDRESS: usize = 0x400000;
+const INITRD_MAX_SIZE: usize = 4096 * 512;
+// Storage of the ramfs mounted at /tmp (~4.3 MiB, right after the initrd)
+const RAMFS_ADDRESS: usize = 0x600000;
+// Back buffer of the screen, big enough for 1280x1024 at 32 bpp (right after the ramfs)
+const BACK_BUFFER_ADDRESS: usize = 0xB00000;
+const BACK_BUFFER_MAX_SIZE: usize = 0x500000;
+// Content of the loaded .ttf font file, twice like the .psf one
+const TRUETYPE_ADDRESS: usize = 0x1000000;
+const TRUETYPE_MAX_SIZE: usize = 0x200000;
+// Glyphs of the .ttf font rendered so far, and the rasterizer's work area
+const GLYPH_CACHE_ADDRESS: usize = 0x1400000;
+const GLYPH_CACHE_SIZE: usize = 0x400000;
+// Everything from 24 MiB to the end of RAM is handed out in 4 KiB frames (user programs, page tables)
+const FRAMES_START: usize = 0x1800000;
 
+// Also built by the host tests, which never call it: what the kernel uses isn't dead code there
 #[unsafe(no_mangle)]
 pub extern "C" fn kernel_main() -> ! {
-    let vbe_info = get_vbe();
-
-    vbe_info.clear_background(Color{ red: 0x00, green: 0x11, blue: 0x33});
+    init_serial();
+    init_vbe();
+    init_console();
 
+    init_gdt();
     init_idt();
+    init_syscall();
+    init_memory();
     init_pic();
+    init_timer();
 
     unsafe { core::arch::asm!("sti"); } // enable CPU Interrupts
 
-    vbe_info.draw_text(100, 100, "Welcome to JackcatOS", Color{
-        red: 255,
-        green: 255,
-        blue: 255,
-    });
+    init_ata();
+    init_fs();
+    init_font();
+    init_process();
+
+    #[cfg(all(test, target_os = "none"))]
+    test_main();
+
+    shell::run()
+}
 
+#[cfg(not(test))]
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    println!("kernel panic: {}", info);
     loop {
-        unsafe { core::arch::asm!("hlt"); }
+        unsafe { core::arch::asm!("cli", "hlt"); }
     }
 }
 
+#[cfg(all(test, target_os = "none"))]
 #[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
-    loop {}
-}
\ No newline at end of file
+fn panic(info: &PanicInfo) -> ! {
+    test_runner::handle_panic(info)
+}
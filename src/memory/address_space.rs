@@ -0,0 +1,137 @@
+use core::arch::asm;
+use core::arch::x86_64::__cpuid;
+use crate::memory::paging::{
+    active_pml4, free_user_space, map_device_page, unmap_device_page, user_page_flags, DEVICE_END, DEVICE_START,
+    KERNEL_PML4, USER_START,
+};
+use crate::memory::{allocate_frame, free_frame, MemoryError, FRAME_SIZE};
+
+/// Process context identifiers tag TLB entries, so switching CR3 does not flush them
+const MAX_PCIDS: usize = 4096;
+/// CR3 bit 63: keep the TLB entries of the new PCID
+const CR3_NO_FLUSH: u64 = 1 << 63;
+const CR4_PCIDE: u64 = 1 << 17;
+
+static mut PCID_ENABLED: bool = false;
+/// One bit per PCID, 0 is the kernel's
+static mut PCIDS_USED: [u64; MAX_PCIDS / 64] = [0; MAX_PCIDS / 64];
+
+/// Turn PCIDs on when CPUID reports them (leaf 1, ECX bit 17)
+pub fn init_pcid() {
+    if __cpuid(1).ecx & (1 << 17) == 0 {
+        return;
+    }
+    unsafe {
+        // Only allowed while CR3 uses PCID 0, which the boot page tables do
+        asm!("mov {0}, cr4", "or {0}, {1}", "mov cr4, {0}", out(reg) _, in(reg) CR4_PCIDE, options(nostack, preserves_flags));
+        PCID_ENABLED = true;
+        PCIDS_USED[0] = 1;
+    }
+}
+
+pub fn pcid_enabled() -> bool {
+    unsafe { PCID_ENABLED }
+}
+
+fn allocate_pcid() -> u16 {
+    if !pcid_enabled() {
+        return 0;
+    }
+    let used = unsafe { &mut *core::ptr::addr_of_mut!(PCIDS_USED) };
+    for (i, word) in used.iter_mut().enumerate() {
+        if *word != u64::MAX {
+            let bit = word.trailing_ones() as usize;
+            *word |= 1 << bit;
+            return (i * 64 + bit) as u16;
+        }
+    }
+    // All taken: share the kernel's, flushed on every switch
+    0
+}
+
+fn free_pcid(pcid: u16) {
+    if pcid != 0 {
+        unsafe { PCIDS_USED[pcid as usize / 64] &= !(1 << (pcid % 64)); }
+    }
+}
+
+/// Page tables of one process: its own PML4 with a private user half, and the kernel
+/// half (the identity map below USER_START) shared with everyone. Dropping it frees
+/// every frame of the user half and the tables.
+pub struct AddressSpace {
+    pml4: u64,
+    pcid: u16,
+    /// TLB entries of a recycled PCID may be stale until the first switch flushes them
+    flushed: bool,
+    /// Where the next device mapping goes
+    next_device: u64,
+}
+
+impl AddressSpace {
+    pub fn new() -> Result<Self, MemoryError> {
+        let pml4 = allocate_frame()?;
+        let kernel_entries = (USER_START >> 39) as usize;
+        unsafe {
+            core::ptr::copy_nonoverlapping(KERNEL_PML4 as *const u64, pml4 as *mut u64, kernel_entries);
+        }
+        Ok(AddressSpace { pml4, pcid: allocate_pcid(), flushed: false, next_device: DEVICE_START })
+    }
+
+    pub fn pml4(&self) -> u64 {
+        self.pml4
+    }
+
+    /// Map `length` bytes of device memory at `physical` (like the framebuffer) in the
+    /// user half, readable and writable. Returns the user address of `physical`.
+    pub fn map_device(&mut self, physical: u64, length: usize) -> Result<u64, MemoryError> {
+        let first = physical & !(FRAME_SIZE - 1);
+        let end = physical
+            .checked_add(length as u64)
+            .and_then(|end| end.checked_next_multiple_of(FRAME_SIZE))
+            .ok_or(MemoryError::BadAddress)?;
+        let start = self.next_device;
+        if end - first > DEVICE_END - start {
+            return Err(MemoryError::OutOfMemory);
+        }
+        for offset in (0..end - first).step_by(FRAME_SIZE as usize) {
+            if let Err(error) = map_device_page(self.pml4, start + offset, first + offset, user_page_flags(true, false)) {
+                for mapped in (0..offset).step_by(FRAME_SIZE as usize) {
+                    unmap_device_page(self.pml4, start + mapped);
+                }
+                return Err(error);
+            }
+        }
+        self.next_device = start + (end - first);
+        Ok(start + physical % FRAME_SIZE)
+    }
+
+    /// Switch CR3 to this address space
+    pub fn activate(&mut self) {
+        let mut cr3 = self.pml4 | self.pcid as u64;
+        if self.flushed && self.pcid != 0 {
+            cr3 |= CR3_NO_FLUSH;
+        }
+        self.flushed = true;
+        unsafe { write_cr3(cr3); }
+    }
+}
+
+impl Drop for AddressSpace {
+    fn drop(&mut self) {
+        if active_pml4() == self.pml4 {
+            activate_kernel();
+        }
+        free_user_space(self.pml4);
+        free_frame(self.pml4);
+        free_pcid(self.pcid);
+    }
+}
+
+/// Switch to the boot page tables, which have no user half
+pub fn activate_kernel() {
+    unsafe { write_cr3(KERNEL_PML4); }
+}
+
+unsafe fn write_cr3(cr3: u64) {
+    unsafe { asm!("mov cr3, {}", in(reg) cr3, options(nostack, preserves_flags)); }
+}
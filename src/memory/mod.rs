@@ -0,0 +1,102 @@
+use crate::cmos;
+use crate::FRAMES_START;
+
+pub mod address_space;
+pub mod paging;
+
+pub const FRAME_SIZE: u64 = 4096;
+/// Frames above this are not identity mapped by boot/paging.asm (or are the PCI hole)
+const FRAMES_END_MAX: u64 = 0xC000_0000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryError {
+    OutOfMemory,
+    /// The address is not in the user part of the address space
+    BadAddress,
+}
+
+/// Free physical frames, linked through their first 8 bytes (all RAM is identity mapped)
+struct FrameAllocator {
+    /// Physical address of the first free frame, 0 when there is none
+    free_list: u64,
+    free: usize,
+    total: usize,
+}
+
+static mut FRAMES: FrameAllocator = FrameAllocator { free_list: 0, free: 0, total: 0 };
+
+fn frames() -> &'static mut FrameAllocator {
+    unsafe { &mut *core::ptr::addr_of_mut!(FRAMES) }
+}
+
+/// Hand every frame from FRAMES_START to the end of RAM to the frame allocator
+pub fn init_memory() {
+    let end = (cmos::memory_size() as u64).min(FRAMES_END_MAX) & !(FRAME_SIZE - 1);
+    let mut frame = end;
+    while frame > FRAMES_START as u64 {
+        frame -= FRAME_SIZE;
+        free_frame(frame);
+        frames().total += 1;
+    }
+    paging::init_paging();
+    address_space::init_pcid();
+}
+
+/// A zeroed physical frame
+pub fn allocate_frame() -> Result<u64, MemoryError> {
+    let allocator = frames();
+    let frame = allocator.free_list;
+    if frame == 0 {
+        return Err(MemoryError::OutOfMemory);
+    }
+    unsafe {
+        allocator.free_list = *(frame as *const u64);
+        core::ptr::write_bytes(frame as *mut u8, 0, FRAME_SIZE as usize);
+    }
+    allocator.free -= 1;
+    Ok(frame)
+}
+
+pub fn free_frame(frame: u64) {
+    let allocator = frames();
+    unsafe { *(frame as *mut u64) = allocator.free_list; }
+    allocator.free_list = frame;
+    allocator.free += 1;
+}
+
+/// (free, total) frames
+pub fn frame_counts() -> (usize, usize) {
+    (frames().free, frames().total)
+}
+
+#[cfg(all(test, target_os = "none"))]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn frames_are_aligned_and_zeroed() {
+        let frame = allocate_frame().unwrap();
+        assert_eq!(frame % FRAME_SIZE, 0);
+        assert!(frame >= FRAMES_START as u64);
+        let bytes = unsafe { core::slice::from_raw_parts(frame as *const u8, FRAME_SIZE as usize) };
+        assert!(bytes.iter().all(|&byte| byte == 0));
+        free_frame(frame);
+    }
+
+    #[test_case]
+    fn freed_frames_are_reused() {
+        let (free, total) = frame_counts();
+        let first = allocate_frame().unwrap();
+        let second = allocate_frame().unwrap();
+        assert_ne!(first, second);
+        assert_eq!(frame_counts(), (free - 2, total));
+        // Dirty it: the next allocation must still be zeroed
+        unsafe { *(second as *mut u64).add(1) = 0xDEAD_BEEF; }
+        free_frame(second);
+        assert_eq!(allocate_frame(), Ok(second));
+        assert_eq!(unsafe { *(second as *const u64).add(1) }, 0);
+        free_frame(second);
+        free_frame(first);
+        assert_eq!(frame_counts(), (free, total));
+    }
+}
@@ -0,0 +1,295 @@
+use core::arch::asm;
+use core::arch::x86_64::__cpuid;
+use crate::cpu::{rdmsr, wrmsr, IA32_EFER};
+use crate::memory::{allocate_frame, free_frame, MemoryError, FRAME_SIZE};
+
+pub const PRESENT: u64 = 1 << 0;
+pub const WRITABLE: u64 = 1 << 1;
+pub const USER: u64 = 1 << 2;
+/// 2 MiB / 1 GiB page instead of a pointer to the next table
+const HUGE: u64 = 1 << 7;
+/// Free for software: the page is device memory, which is not freed with the address space
+const DEVICE: u64 = 1 << 9;
+pub const NO_EXECUTE: u64 = 1 << 63;
+const ADDRESS_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+/// Page tables of boot/paging.asm, identity mapping the first 4 GiB with PML4 entry 0
+pub const KERNEL_PML4: u64 = 0x80000;
+
+// User programs live above the first 512 GiB (PML4 entry 0, the identity mapped
+// kernel) and below the last page of the lower canonical half. A syscall at the end
+// of that page would return to a non-canonical RIP, and on Intel CPUs sysretq
+// faults on it in ring 0, with the user stack already loaded.
+pub const USER_START: u64 = 0x0000_0080_0000_0000;
+pub const USER_END: u64 = 0x0000_7FFF_FFFF_F000;
+/// Device memory mapped by mmap goes from here up to DEVICE_END, far above the programs
+pub const DEVICE_START: u64 = 0x0000_7000_0000_0000;
+/// The stack is above, in the last 64 KiB of the user half
+pub const DEVICE_END: u64 = 0x0000_7F00_0000_0000;
+
+static mut NO_EXECUTE_ENABLED: bool = false;
+
+/// Enable the no-execute bit when the CPU has it
+pub fn init_paging() {
+    if __cpuid(0x8000_0000).eax >= 0x8000_0001 && __cpuid(0x8000_0001).edx & (1 << 20) != 0 {
+        unsafe {
+            wrmsr(IA32_EFER, rdmsr(IA32_EFER) | 1 << 11); // NXE
+            NO_EXECUTE_ENABLED = true;
+        }
+    }
+}
+
+/// Leaf flags of a user page
+pub fn user_page_flags(writable: bool, executable: bool) -> u64 {
+    let mut flags = PRESENT | USER;
+    if writable {
+        flags |= WRITABLE;
+    }
+    if !executable && unsafe { NO_EXECUTE_ENABLED } {
+        flags |= NO_EXECUTE;
+    }
+    flags
+}
+
+fn table(address: u64) -> &'static mut [u64; 512] {
+    unsafe { &mut *(address as *mut [u64; 512]) }
+}
+
+/// Index of `address` in a table of `level` (0 = page table, 3 = PML4)
+fn index(address: u64, level: u32) -> usize {
+    (address >> (12 + 9 * level)) as usize & 0x1FF
+}
+
+fn is_user_address(address: u64) -> bool {
+    (USER_START..USER_END).contains(&address)
+}
+
+/// Page table entry of `address`, creating the missing tables on the way.
+/// Only for user addresses, which never use huge pages.
+fn entry_for(pml4: u64, address: u64) -> Result<&'static mut u64, MemoryError> {
+    let mut current = pml4;
+    for level in (1..=3).rev() {
+        let entry = &mut table(current)[index(address, level)];
+        if *entry & PRESENT == 0 {
+            // Permissions are decided by the last level
+            *entry = allocate_frame()? | PRESENT | WRITABLE | USER;
+        }
+        current = *entry & ADDRESS_MASK;
+    }
+    Ok(&mut table(current)[index(address, 0)])
+}
+
+/// Map the user page containing `address` to a new zeroed frame, or when it is already
+/// mapped, allow what `flags` allows on top. Returns the frame.
+pub fn map_user_page(pml4: u64, address: u64, flags: u64) -> Result<u64, MemoryError> {
+    if !is_user_address(address) {
+        return Err(MemoryError::BadAddress);
+    }
+    let entry = entry_for(pml4, address)?;
+    if *entry & PRESENT != 0 {
+        // Two segments sharing a page: writable or executable if either is
+        let no_execute = *entry & flags & NO_EXECUTE;
+        *entry = (*entry | flags) & !NO_EXECUTE | no_execute;
+    } else {
+        *entry = allocate_frame()? | flags | PRESENT | USER;
+    }
+    unsafe { asm!("invlpg [{}]", in(reg) address, options(nostack, preserves_flags)); }
+    Ok(*entry & ADDRESS_MASK)
+}
+
+/// Map the user page containing `address` to the device memory at `physical`
+pub fn map_device_page(pml4: u64, address: u64, physical: u64, flags: u64) -> Result<(), MemoryError> {
+    if !is_user_address(address) {
+        return Err(MemoryError::BadAddress);
+    }
+    let entry = entry_for(pml4, address)?;
+    if *entry & PRESENT != 0 {
+        return Err(MemoryError::BadAddress);
+    }
+    *entry = physical & ADDRESS_MASK | flags | PRESENT | USER | DEVICE;
+    unsafe { asm!("invlpg [{}]", in(reg) address, options(nostack, preserves_flags)); }
+    Ok(())
+}
+
+/// Remove the mapping of map_device_page at `address`. The tables on the way stay
+/// until the address space is dropped.
+pub fn unmap_device_page(pml4: u64, address: u64) {
+    let mut current = pml4;
+    for level in (1..=3).rev() {
+        let entry = table(current)[index(address, level)];
+        if entry & PRESENT == 0 {
+            return;
+        }
+        current = entry & ADDRESS_MASK;
+    }
+    let entry = &mut table(current)[index(address, 0)];
+    if *entry & DEVICE != 0 {
+        *entry = 0;
+        unsafe { asm!("invlpg [{}]", in(reg) address, options(nostack, preserves_flags)); }
+    }
+}
+
+/// Physical address of `address` and the WRITABLE / USER / NO_EXECUTE flags that
+/// apply to it, None when it is not mapped
+pub fn translate(pml4: u64, address: u64) -> Option<(u64, u64)> {
+    let mut current = pml4;
+    let mut flags = WRITABLE | USER;
+    let mut no_execute = 0;
+    for level in (0..=3).rev() {
+        let entry = table(current)[index(address, level)];
+        if entry & PRESENT == 0 {
+            return None;
+        }
+        flags &= entry;
+        no_execute |= entry & NO_EXECUTE;
+        if level > 0 && entry & HUGE != 0 {
+            let size = 1u64 << (12 + 9 * level);
+            return Some(((entry & ADDRESS_MASK & !(size - 1)) + (address & (size - 1)), flags | no_execute));
+        }
+        current = entry & ADDRESS_MASK;
+    }
+    Some((current + (address & (FRAME_SIZE - 1)), flags | no_execute))
+}
+
+/// Copy `data` to the user address `address` of `pml4`, through the identity
+/// mapping of the frames, so `pml4` does not need to be the active one
+pub fn write_user(pml4: u64, address: u64, data: &[u8]) -> Result<(), MemoryError> {
+    let mut done = 0;
+    while done < data.len() {
+        let current = address + done as u64;
+        if !is_user_address(current) {
+            return Err(MemoryError::BadAddress);
+        }
+        let (physical, _) = translate(pml4, current).ok_or(MemoryError::BadAddress)?;
+        let count = ((FRAME_SIZE - current % FRAME_SIZE) as usize).min(data.len() - done);
+        unsafe {
+            core::ptr::copy_nonoverlapping(data[done..].as_ptr(), physical as *mut u8, count);
+        }
+        done += count;
+    }
+    Ok(())
+}
+
+/// Remove every user mapping of `pml4` and free the frames and tables behind them
+pub fn free_user_space(pml4: u64) {
+    for pml4_index in index(USER_START, 3)..index(USER_END - 1, 3) + 1 {
+        let entry = &mut table(pml4)[pml4_index];
+        if *entry & PRESENT != 0 {
+            free_table(*entry & ADDRESS_MASK, 2);
+            *entry = 0;
+        }
+    }
+    if active_pml4() == pml4 {
+        unsafe { asm!("mov {0}, cr3", "mov cr3, {0}", out(reg) _, options(nostack, preserves_flags)); }
+    }
+}
+
+/// Free a table of `level` (0 = page table) with everything it maps
+fn free_table(address: u64, level: u32) {
+    for &entry in table(address).iter() {
+        if entry & PRESENT == 0 {
+            continue;
+        }
+        if level == 0 {
+            if entry & DEVICE == 0 {
+                free_frame(entry & ADDRESS_MASK);
+            }
+        } else {
+            free_table(entry & ADDRESS_MASK, level - 1);
+        }
+    }
+    free_frame(address);
+}
+
+/// PML4 of the running code (CR3 without the flags)
+pub fn active_pml4() -> u64 {
+    let cr3: u64;
+    unsafe { asm!("mov {}, cr3", out(reg) cr3, options(nomem, nostack, preserves_flags)); }
+    cr3 & ADDRESS_MASK
+}
+
+#[cfg(all(test, target_os = "none"))]
+mod tests {
+    use super::*;
+    use crate::memory::address_space::AddressSpace;
+    use crate::memory::frame_counts;
+
+    #[test_case]
+    fn kernel_is_identity_mapped() {
+        for address in [0x8000, 0x12_3456, 0x1000_0000, 0xFFFF_F000] {
+            let (physical, flags) = translate(KERNEL_PML4, address).unwrap();
+            assert_eq!(physical, address);
+            assert_eq!(flags & USER, 0);
+        }
+        assert_eq!(translate(KERNEL_PML4, 0x10_0000_0000), None);
+        assert_eq!(translate(KERNEL_PML4, USER_START), None);
+    }
+
+    #[test_case]
+    fn user_pages() {
+        let address = USER_START + 0x1234_5000;
+        let mut space = AddressSpace::new().unwrap();
+        let frame = map_user_page(space.pml4(), address, user_page_flags(false, false)).unwrap();
+        assert_eq!(translate(space.pml4(), address + 0x10), Some((frame + 0x10, USER | (NO_EXECUTE & user_page_flags(false, false)))));
+        // Mapping it again adds the permissions
+        map_user_page(space.pml4(), address, user_page_flags(true, true)).unwrap();
+        assert_eq!(translate(space.pml4(), address), Some((frame, USER | WRITABLE)));
+
+        write_user(space.pml4(), address + 0xFFE, b"hi").unwrap();
+        assert_eq!(unsafe { *((frame + 0xFFE) as *const [u8; 2]) }, *b"hi");
+        assert_eq!(write_user(space.pml4(), address + 0xFFF, b"hi"), Err(MemoryError::BadAddress));
+        assert_eq!(map_user_page(space.pml4(), 0x8000, user_page_flags(true, false)), Err(MemoryError::BadAddress));
+
+        // Through the page tables this time
+        space.activate();
+        let value = unsafe { core::ptr::read_volatile((address + 0xFFE) as *const [u8; 2]) };
+        crate::memory::address_space::activate_kernel();
+        assert_eq!(value, *b"hi");
+    }
+
+    #[test_case]
+    fn address_spaces_give_their_frames_back() {
+        let before = frame_counts();
+        {
+            let space = AddressSpace::new().unwrap();
+            for page in 0..20 {
+                map_user_page(space.pml4(), USER_START + page * 0x20_0000, user_page_flags(true, false)).unwrap();
+            }
+            assert!(frame_counts().0 < before.0);
+        }
+        assert_eq!(frame_counts(), before);
+    }
+
+    #[test_case]
+    fn device_pages_are_not_freed() {
+        let device = allocate_frame().unwrap();
+        let before = frame_counts();
+        {
+            let mut space = AddressSpace::new().unwrap();
+            let address = space.map_device(device + 0x10, 0x2000).unwrap();
+            assert_eq!(address % FRAME_SIZE, 0x10);
+            let flags = USER | WRITABLE | (NO_EXECUTE & user_page_flags(true, false));
+            assert_eq!(translate(space.pml4(), address + 0x1000), Some((device + 0x1010, flags)));
+            // The next mapping comes after the 3 pages of this one
+            assert_eq!(space.map_device(device, 1).unwrap(), address - 0x10 + 3 * FRAME_SIZE);
+        }
+        assert_eq!(frame_counts(), before);
+        free_frame(device);
+    }
+
+    #[test_case]
+    fn failed_device_mappings_leave_nothing_behind() {
+        let device = allocate_frame().unwrap();
+        let mut space = AddressSpace::new().unwrap();
+        let too_big = (DEVICE_END - DEVICE_START) as usize;
+        assert_eq!(space.map_device(device, too_big + 1), Err(MemoryError::OutOfMemory));
+        // The second page is taken, so the first one is unmapped again
+        map_user_page(space.pml4(), DEVICE_START + FRAME_SIZE, user_page_flags(true, false)).unwrap();
+        assert_eq!(space.map_device(device, 0x2000), Err(MemoryError::BadAddress));
+        assert_eq!(translate(space.pml4(), DEVICE_START), None);
+        // Neither moved the next mapping
+        assert_eq!(space.map_device(device, 1), Ok(DEVICE_START));
+        drop(space);
+        free_frame(device);
+    }
+}
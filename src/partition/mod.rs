@@ -0,0 +1,474 @@
+use crate::block::{check_range, BlockDevice, BlockError, SECTOR_SIZE};
+
+/// Most partitions exposed for one disk (4 primary + logical ones, or GPT entries)
+pub const MAX_PARTITIONS: usize = 16;
+
+// MBR layout
+const MBR_TABLE_OFFSET: usize = 446;
+const MBR_ENTRY_SIZE: usize = 16;
+const MBR_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+const MBR_TYPE_EMPTY: u8 = 0x00;
+const MBR_TYPE_EXTENDED_CHS: u8 = 0x05;
+const MBR_TYPE_EXTENDED_LBA: u8 = 0x0F;
+const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xEE;
+// Stop following a broken (looping) chain of extended boot records
+const MAX_LOGICAL_PARTITIONS: usize = 64;
+
+// GPT layout
+const GPT_HEADER_LBA: u64 = 1;
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+const GPT_MIN_ENTRY_SIZE: usize = 128;
+// The UEFI minimum for the entry array is 16 KiB, anything far bigger is a corrupt header
+const GPT_MAX_ENTRIES_SIZE: usize = 128 * 1024;
+
+/// GUID stored as on disk (the first three fields are little-endian)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Guid(pub [u8; 16]);
+
+impl Guid {
+    pub const UNUSED: Guid = Guid([0; 16]);
+    /// C12A7328-F81F-11D2-BA4B-00A0C93EC93B
+    pub const EFI_SYSTEM: Guid = Guid([
+        0x28, 0x73, 0x2A, 0xC1, 0x1F, 0xF8, 0xD2, 0x11, 0xBA, 0x4B, 0x00, 0xA0, 0xC9, 0x3E, 0xC9, 0x3B,
+    ]);
+    /// EBD0A0A2-B9E5-4433-87C0-68B6B72699C7
+    pub const BASIC_DATA: Guid = Guid([
+        0xA2, 0xA0, 0xD0, 0xEB, 0xE5, 0xB9, 0x33, 0x44, 0x87, 0xC0, 0x68, 0xB6, 0xB7, 0x26, 0x99, 0xC7,
+    ]);
+    /// 0FC63DAF-8483-4772-8E79-3D69D8477DE4
+    pub const LINUX_FILESYSTEM: Guid = Guid([
+        0xAF, 0x3D, 0xC6, 0x0F, 0x83, 0x84, 0x72, 0x47, 0x8E, 0x79, 0x3D, 0x69, 0xD8, 0x47, 0x7D, 0xE4,
+    ]);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionKind {
+    /// MBR system id byte (0x0C = FAT32 LBA, 0x83 = Linux, ...)
+    Mbr(u8),
+    /// GPT partition type GUID
+    Gpt(Guid),
+}
+
+/// A slice of a block device, itself usable as a block device
+#[derive(Clone, Copy)]
+pub struct Partition<'a> {
+    device: &'a dyn BlockDevice,
+    start: u64,
+    sectors: u64,
+    kind: PartitionKind,
+}
+
+impl<'a> Partition<'a> {
+    pub fn new(device: &'a dyn BlockDevice, start: u64, sectors: u64, kind: PartitionKind) -> Self {
+        Partition { device, start, sectors, kind }
+    }
+
+    pub fn kind(&self) -> PartitionKind {
+        self.kind
+    }
+}
+
+impl BlockDevice for Partition<'_> {
+    fn read_sectors(&self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError> {
+        check_range(self, lba, buffer.len())?;
+        self.device.read_sectors(self.start + lba, buffer)
+    }
+
+    fn write_sectors(&self, lba: u64, buffer: &[u8]) -> Result<(), BlockError> {
+        check_range(self, lba, buffer.len())?;
+        self.device.write_sectors(self.start + lba, buffer)
+    }
+
+    fn flush(&self) -> Result<(), BlockError> {
+        self.device.flush()
+    }
+
+    fn capacity(&self) -> u64 {
+        self.sectors
+    }
+
+    fn sector_size(&self) -> usize {
+        self.device.sector_size()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionError {
+    Block(BlockError),
+    /// Sector 0 does not end with 0x55AA
+    NoPartitionTable,
+    /// The protective MBR announces a GPT but its header is invalid
+    BadGpt,
+}
+
+impl From<BlockError> for PartitionError {
+    fn from(error: BlockError) -> Self {
+        PartitionError::Block(error)
+    }
+}
+
+/// Partitions found on a disk, in table order
+pub struct PartitionTable<'a> {
+    partitions: [Option<Partition<'a>>; MAX_PARTITIONS],
+    count: usize,
+}
+
+impl<'a> PartitionTable<'a> {
+    /// Read the MBR (and the GPT it points to, if any) of `device`
+    pub fn parse(device: &'a dyn BlockDevice) -> Result<Self, PartitionError> {
+        let mut table = PartitionTable { partitions: [None; MAX_PARTITIONS], count: 0 };
+
+        let mut mbr = [0u8; SECTOR_SIZE];
+        device.read_sectors(0, &mut mbr)?;
+        if mbr[510..512] != MBR_SIGNATURE {
+            return Err(PartitionError::NoPartitionTable);
+        }
+
+        let entries = mbr_entries(&mbr);
+        if entries.iter().any(|entry| entry.kind == MBR_TYPE_GPT_PROTECTIVE) {
+            table.parse_gpt(device)?;
+            return Ok(table);
+        }
+
+        for entry in entries {
+            match entry.kind {
+                MBR_TYPE_EMPTY => {}
+                MBR_TYPE_EXTENDED_CHS | MBR_TYPE_EXTENDED_LBA => table.parse_extended(device, entry.start)?,
+                kind => table.push(device, entry.start, entry.sectors, PartitionKind::Mbr(kind)),
+            }
+        }
+        Ok(table)
+    }
+
+    /// Follow the chain of extended boot records. Each EBR holds one logical partition
+    /// (relative to the EBR itself) and a link to the next EBR (relative to the extended partition).
+    fn parse_extended(&mut self, device: &'a dyn BlockDevice, extended_start: u64) -> Result<(), PartitionError> {
+        let mut ebr_lba = extended_start;
+        let mut ebr = [0u8; SECTOR_SIZE];
+        for _ in 0..MAX_LOGICAL_PARTITIONS {
+            device.read_sectors(ebr_lba, &mut ebr)?;
+            if ebr[510..512] != MBR_SIGNATURE {
+                break;
+            }
+            let [logical, next, _, _] = mbr_entries(&ebr);
+            if logical.kind != MBR_TYPE_EMPTY {
+                self.push(device, ebr_lba + logical.start, logical.sectors, PartitionKind::Mbr(logical.kind));
+            }
+            if next.kind == MBR_TYPE_EMPTY || next.start == 0 {
+                break;
+            }
+            ebr_lba = extended_start + next.start;
+        }
+        Ok(())
+    }
+
+    fn parse_gpt(&mut self, device: &'a dyn BlockDevice) -> Result<(), PartitionError> {
+        let mut header = [0u8; SECTOR_SIZE];
+        device.read_sectors(GPT_HEADER_LBA, &mut header)?;
+        if &header[0..8] != GPT_SIGNATURE {
+            return Err(PartitionError::BadGpt);
+        }
+
+        let header_size = read_u32(&header, 12) as usize;
+        if !(92..=SECTOR_SIZE).contains(&header_size) {
+            return Err(PartitionError::BadGpt);
+        }
+        // The header CRC is computed with its own field zeroed
+        let header_crc = read_u32(&header, 16);
+        header[16..20].fill(0);
+        if crc32(0, &header[..header_size]) != header_crc {
+            return Err(PartitionError::BadGpt);
+        }
+
+        let entries_lba = read_u64(&header, 72);
+        let entry_count = read_u32(&header, 80) as usize;
+        let entry_size = read_u32(&header, 84) as usize;
+        let entries_crc = read_u32(&header, 88);
+        if entry_size < GPT_MIN_ENTRY_SIZE || !SECTOR_SIZE.is_multiple_of(entry_size)
+            || entry_count > GPT_MAX_ENTRIES_SIZE / entry_size
+        {
+            return Err(PartitionError::BadGpt);
+        }
+
+        // Walk the entry array one sector at a time, checksumming as we go
+        let entries_per_sector = SECTOR_SIZE / entry_size;
+        let sectors = entry_count.div_ceil(entries_per_sector);
+        let mut crc = 0;
+        let mut sector = [0u8; SECTOR_SIZE];
+        for i in 0..sectors {
+            device.read_sectors(entries_lba + i as u64, &mut sector)?;
+            let entries_left = entry_count - i * entries_per_sector;
+            let used = entries_left.min(entries_per_sector) * entry_size;
+            crc = crc32(crc, &sector[..used]);
+
+            for entry in sector[..used].chunks_exact(entry_size) {
+                let mut kind = [0u8; 16];
+                kind.copy_from_slice(&entry[0..16]);
+                let kind = Guid(kind);
+                if kind == Guid::UNUSED {
+                    continue;
+                }
+                let first = read_u64(entry, 32);
+                let last = read_u64(entry, 40);
+                if last < first {
+                    continue;
+                }
+                // Saturates only for last = u64::MAX, which is past the disk anyway
+                self.push(device, first, last.saturating_add(1) - first, PartitionKind::Gpt(kind));
+            }
+        }
+
+        if crc != entries_crc {
+            *self = PartitionTable { partitions: [None; MAX_PARTITIONS], count: 0 };
+            return Err(PartitionError::BadGpt);
+        }
+        Ok(())
+    }
+
+    /// Add a partition, unless it goes past the end of the disk
+    fn push(&mut self, device: &'a dyn BlockDevice, start: u64, sectors: u64, kind: PartitionKind) {
+        let on_disk = start.checked_add(sectors).is_some_and(|end| end <= device.capacity());
+        if on_disk && self.count < MAX_PARTITIONS {
+            self.partitions[self.count] = Some(Partition::new(device, start, sectors, kind));
+            self.count += 1;
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Partition<'a>> {
+        self.partitions[..self.count].iter().flatten()
+    }
+}
+
+#[derive(Clone, Copy)]
+struct MbrEntry {
+    kind: u8,
+    start: u64,
+    sectors: u64,
+}
+
+fn mbr_entries(sector: &[u8; SECTOR_SIZE]) -> [MbrEntry; 4] {
+    core::array::from_fn(|i| {
+        let offset = MBR_TABLE_OFFSET + i * MBR_ENTRY_SIZE;
+        MbrEntry {
+            kind: sector[offset + 4],
+            start: read_u32(sector, offset + 8) as u64,
+            sectors: read_u32(sector, offset + 12) as u64,
+        }
+    })
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    read_u32(bytes, offset) as u64 | (read_u32(bytes, offset + 4) as u64) << 32
+}
+
+/// CRC-32 (IEEE 802.3) as used by GPT. Pass the previous result to continue a checksum.
+fn crc32(previous: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !previous;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryDisk;
+
+    /// A sector with the boot signature and (kind, start, sectors) entries
+    fn mbr(entries: &[(u8, u32, u32)]) -> [u8; SECTOR_SIZE] {
+        let mut sector = [0u8; SECTOR_SIZE];
+        for (i, &(kind, start, sectors)) in entries.iter().enumerate() {
+            let offset = MBR_TABLE_OFFSET + i * MBR_ENTRY_SIZE;
+            sector[offset + 4] = kind;
+            sector[offset + 8..offset + 12].copy_from_slice(&start.to_le_bytes());
+            sector[offset + 12..offset + 16].copy_from_slice(&sectors.to_le_bytes());
+        }
+        sector[510..512].copy_from_slice(&MBR_SIGNATURE);
+        sector
+    }
+
+    fn disk(sectors: usize, content: &[(usize, [u8; SECTOR_SIZE])]) -> MemoryDisk {
+        let mut image = vec![0; sectors * SECTOR_SIZE];
+        for (lba, sector) in content {
+            image[lba * SECTOR_SIZE..(lba + 1) * SECTOR_SIZE].copy_from_slice(sector);
+        }
+        MemoryDisk::new(image)
+    }
+
+    fn layout(table: &PartitionTable) -> Vec<(u64, u64, PartitionKind)> {
+        table.iter().map(|partition| (partition.start, partition.capacity(), partition.kind())).collect()
+    }
+
+    /// A protective MBR, a GPT header at LBA 1 and its 4 entries of 128 bytes at LBA 2
+    fn gpt_disk(entries: &[(Guid, u64, u64)]) -> Vec<u8> {
+        let mut array = [0u8; SECTOR_SIZE];
+        for (entry, (kind, first, last)) in array.chunks_exact_mut(128).zip(entries) {
+            entry[0..16].copy_from_slice(&kind.0);
+            entry[32..40].copy_from_slice(&first.to_le_bytes());
+            entry[40..48].copy_from_slice(&last.to_le_bytes());
+        }
+        let mut header = [0u8; SECTOR_SIZE];
+        header[0..8].copy_from_slice(GPT_SIGNATURE);
+        for (offset, value) in [(8, 0x0001_0000), (12, 92), (80, 4), (84, 128), (88, crc32(0, &array))] {
+            header[offset..offset + 4].copy_from_slice(&u32::to_le_bytes(value));
+        }
+        header[72..80].copy_from_slice(&2u64.to_le_bytes());
+        let crc = crc32(0, &header[..92]);
+        header[16..20].copy_from_slice(&crc.to_le_bytes());
+
+        let mut image = vec![0; 64 * SECTOR_SIZE];
+        image[..SECTOR_SIZE].copy_from_slice(&mbr(&[(MBR_TYPE_GPT_PROTECTIVE, 1, 63)]));
+        image[SECTOR_SIZE..2 * SECTOR_SIZE].copy_from_slice(&header);
+        image[2 * SECTOR_SIZE..3 * SECTOR_SIZE].copy_from_slice(&array);
+        image
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(0, b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(crc32(0, b"1234"), b"56789"), 0xCBF4_3926);
+        assert_eq!(crc32(0, b""), 0);
+    }
+
+    #[test]
+    fn primary_and_logical_partitions() {
+        let disk = disk(64, &[
+            (0, mbr(&[(0x83, 1, 10), (MBR_TYPE_EXTENDED_LBA, 20, 40)])),
+            // Logical partitions are relative to their EBR, the links to the extended partition
+            (20, mbr(&[(0x0C, 2, 5), (MBR_TYPE_EXTENDED_CHS, 10, 20)])),
+            (30, mbr(&[(0x83, 1, 4)])),
+        ]);
+        let table = PartitionTable::parse(&disk).unwrap();
+        assert_eq!(layout(&table), [
+            (1, 10, PartitionKind::Mbr(0x83)),
+            (22, 5, PartitionKind::Mbr(0x0C)),
+            (31, 4, PartitionKind::Mbr(0x83)),
+        ]);
+    }
+
+    #[test]
+    fn partitions_past_the_disk_are_skipped() {
+        let disk = disk(64, &[
+            (0, mbr(&[(0x83, 60, 4), (0x83, 60, 5), (0x0C, u32::MAX, u32::MAX), (MBR_TYPE_EXTENDED_LBA, 20, 40)])),
+            (20, mbr(&[(0x0C, 40, 5)])),
+        ]);
+        let table = PartitionTable::parse(&disk).unwrap();
+        assert_eq!(layout(&table), [(60, 4, PartitionKind::Mbr(0x83))]);
+    }
+
+    #[test]
+    fn partition_is_a_slice_of_the_disk() {
+        let mut data = [0u8; SECTOR_SIZE];
+        data[..5].copy_from_slice(b"hello");
+        let disk = disk(16, &[(0, mbr(&[(0x83, 4, 2)])), (5, data)]);
+        let table = PartitionTable::parse(&disk).unwrap();
+        let partition = table.iter().next().unwrap();
+
+        let mut sector = [0u8; SECTOR_SIZE];
+        partition.read_sectors(1, &mut sector).unwrap();
+        assert_eq!(&sector[..5], b"hello");
+        sector[..5].copy_from_slice(b"world");
+        partition.write_sectors(0, &sector).unwrap();
+        assert_eq!(&disk.bytes()[4 * SECTOR_SIZE..4 * SECTOR_SIZE + 5], b"world");
+
+        assert_eq!(partition.read_sectors(2, &mut sector), Err(BlockError::OutOfRange));
+        assert_eq!(partition.read_sectors(1, &mut [0; 2 * SECTOR_SIZE]), Err(BlockError::OutOfRange));
+        assert_eq!(partition.read_sectors(0, &mut [0; 100]), Err(BlockError::BadBuffer));
+    }
+
+    #[test]
+    fn looping_ebr_chain_stops() {
+        // The second EBR links to itself
+        let disk = disk(64, &[
+            (0, mbr(&[(MBR_TYPE_EXTENDED_LBA, 20, 40)])),
+            (20, mbr(&[(0x83, 1, 2), (MBR_TYPE_EXTENDED_LBA, 10, 10)])),
+            (30, mbr(&[(0x83, 1, 2), (MBR_TYPE_EXTENDED_LBA, 10, 10)])),
+        ]);
+        let table = PartitionTable::parse(&disk).unwrap();
+        assert_eq!(table.iter().count(), MAX_PARTITIONS);
+    }
+
+    #[test]
+    fn mbr_errors() {
+        let no_signature = disk(4, &[]);
+        assert_eq!(PartitionTable::parse(&no_signature).err(), Some(PartitionError::NoPartitionTable));
+        let empty = MemoryDisk::new(Vec::new());
+        assert_eq!(PartitionTable::parse(&empty).err(), Some(PartitionError::Block(BlockError::OutOfRange)));
+        // The extended partition is past the end of the disk
+        let truncated = disk(8, &[(0, mbr(&[(0x83, 1, 2), (MBR_TYPE_EXTENDED_LBA, 20, 40)]))]);
+        assert_eq!(PartitionTable::parse(&truncated).err(), Some(PartitionError::Block(BlockError::OutOfRange)));
+        // An EBR without signature ends the chain
+        let unsigned_ebr = disk(32, &[(0, mbr(&[(0x83, 1, 2), (MBR_TYPE_EXTENDED_LBA, 20, 10)]))]);
+        assert_eq!(PartitionTable::parse(&unsigned_ebr).unwrap().iter().count(), 1);
+    }
+
+    #[test]
+    fn gpt_partitions() {
+        let disk = MemoryDisk::new(gpt_disk(&[
+            (Guid::EFI_SYSTEM, 34, 43),
+            (Guid::UNUSED, 44, 50),
+            (Guid::LINUX_FILESYSTEM, 44, 63),
+            // Ends before it starts
+            (Guid::BASIC_DATA, 20, 10),
+        ]));
+        let table = PartitionTable::parse(&disk).unwrap();
+        assert_eq!(layout(&table), [
+            (34, 10, PartitionKind::Gpt(Guid::EFI_SYSTEM)),
+            (44, 20, PartitionKind::Gpt(Guid::LINUX_FILESYSTEM)),
+        ]);
+
+        // Past the end of the 64 sectors, or as big as can be
+        let disk = MemoryDisk::new(gpt_disk(&[
+            (Guid::BASIC_DATA, 34, 64),
+            (Guid::BASIC_DATA, 0, u64::MAX),
+            (Guid::LINUX_FILESYSTEM, 40, 63),
+        ]));
+        let table = PartitionTable::parse(&disk).unwrap();
+        assert_eq!(layout(&table), [(40, 24, PartitionKind::Gpt(Guid::LINUX_FILESYSTEM))]);
+    }
+
+    #[test]
+    fn corrupt_gpt() {
+        let image = gpt_disk(&[(Guid::BASIC_DATA, 34, 63)]);
+        let bad_gpt = Some(PartitionError::BadGpt);
+
+        // Header checksum, entries checksum, signature
+        for (offset, value) in [(SECTOR_SIZE + 40, 1), (2 * SECTOR_SIZE + 32, 35), (SECTOR_SIZE, b'X')] {
+            let mut corrupt = image.clone();
+            corrupt[offset] = value;
+            assert_eq!(PartitionTable::parse(&MemoryDisk::new(corrupt)).err(), bad_gpt, "byte {}", offset);
+        }
+        // Entries of 100 bytes, in a header with the right checksum
+        let mut odd_entries = image.clone();
+        let header = &mut odd_entries[SECTOR_SIZE..SECTOR_SIZE + 92];
+        header[84] = 100;
+        header[16..20].fill(0);
+        let crc = crc32(0, header);
+        header[16..20].copy_from_slice(&crc.to_le_bytes());
+        assert_eq!(PartitionTable::parse(&MemoryDisk::new(odd_entries)).err(), bad_gpt);
+
+        // 2^32 - 1 entries are rejected before reading any of them
+        let mut huge = image.clone();
+        let header = &mut huge[SECTOR_SIZE..SECTOR_SIZE + 92];
+        header[80..84].fill(0xFF);
+        header[16..20].fill(0);
+        let crc = crc32(0, header);
+        header[16..20].copy_from_slice(&crc.to_le_bytes());
+        let huge = MemoryDisk::new(huge);
+        assert_eq!(PartitionTable::parse(&huge).err(), bad_gpt);
+        assert_eq!(huge.reads.get(), 2);
+
+        // Only the protective MBR
+        let truncated = MemoryDisk::new(image[..SECTOR_SIZE].to_vec());
+        assert_eq!(PartitionTable::parse(&truncated).err(), Some(PartitionError::Block(BlockError::OutOfRange)));
+    }
+}
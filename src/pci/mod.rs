@@ -0,0 +1,105 @@
+use crate::io::{inl, outl};
+
+const CONFIG_ADDRESS: u16 = 0xCF8;
+const CONFIG_DATA: u16 = 0xCFC;
+
+// Offsets in the configuration space header
+const REG_VENDOR_DEVICE: u8 = 0x00;
+const REG_CLASS: u8 = 0x08;
+const REG_HEADER_TYPE: u8 = 0x0C;
+
+const NO_DEVICE: u16 = 0xFFFF;
+const MULTI_FUNCTION: u8 = 0x80;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PciDevice {
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+    pub vendor_id: u16,
+    pub device_id: u16,
+    pub class: u8,
+    pub subclass: u8,
+    pub prog_if: u8,
+    pub revision: u8,
+}
+
+impl PciDevice {
+    /// Readable name of the class / subclass
+    pub fn class_name(&self) -> &'static str {
+        match (self.class, self.subclass) {
+            (0x01, 0x01) => "IDE controller",
+            (0x01, 0x06) => "SATA controller",
+            (0x01, 0x08) => "NVMe controller",
+            (0x01, _) => "Storage controller",
+            (0x02, 0x00) => "Ethernet controller",
+            (0x02, _) => "Network controller",
+            (0x03, 0x00) => "VGA controller",
+            (0x03, _) => "Display controller",
+            (0x04, _) => "Multimedia controller",
+            (0x05, _) => "Memory controller",
+            (0x06, 0x00) => "Host bridge",
+            (0x06, 0x01) => "ISA bridge",
+            (0x06, 0x04) => "PCI bridge",
+            (0x06, 0x80) => "Other bridge",
+            (0x06, _) => "Bridge",
+            (0x0C, 0x03) => "USB controller",
+            (0x0C, 0x05) => "SMBus controller",
+            (0x0C, _) => "Serial bus controller",
+            _ => "Unknown device",
+        }
+    }
+}
+
+/// Read a 32 bit register of a function's configuration space (configuration mechanism #1)
+pub fn read_config(bus: u8, device: u8, function: u8, offset: u8) -> u32 {
+    let address = 1 << 31
+        | (bus as u32) << 16
+        | (device as u32) << 11
+        | (function as u32) << 8
+        | (offset & 0xFC) as u32;
+    unsafe {
+        outl(CONFIG_ADDRESS, address);
+        inl(CONFIG_DATA)
+    }
+}
+
+fn probe(bus: u8, device: u8, function: u8) -> Option<PciDevice> {
+    let ids = read_config(bus, device, function, REG_VENDOR_DEVICE);
+    if ids as u16 == NO_DEVICE {
+        return None;
+    }
+    let class = read_config(bus, device, function, REG_CLASS);
+    Some(PciDevice {
+        bus,
+        device,
+        function,
+        vendor_id: ids as u16,
+        device_id: (ids >> 16) as u16,
+        class: (class >> 24) as u8,
+        subclass: (class >> 16) as u8,
+        prog_if: (class >> 8) as u8,
+        revision: class as u8,
+    })
+}
+
+/// Call `f` for every function present on every bus (brute force scan)
+pub fn for_each_device(mut f: impl FnMut(&PciDevice)) {
+    for bus in 0..=255 {
+        for device in 0..32 {
+            let Some(first) = probe(bus, device, 0) else {
+                continue;
+            };
+            f(&first);
+            let header_type = (read_config(bus, device, 0, REG_HEADER_TYPE) >> 16) as u8;
+            if header_type & MULTI_FUNCTION == 0 {
+                continue;
+            }
+            for function in 1..8 {
+                if let Some(other) = probe(bus, device, function) {
+                    f(&other);
+                }
+            }
+        }
+    }
+}
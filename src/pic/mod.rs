@@ -45,17 +45,48 @@ pub fn init_pic() {
 
         // Unmask interrupts
         // 0 = Enable, 1 = Disable
-        // For now, let's enable only the Keyboard (IRQ 1, bit 1)
-        // 1111 1101 = 0xFD
-        outb(PIC1_DATA, 0b11111101);
-        outb(PIC2_DATA, 0b11111111);
+        // Master: PIT timer (IRQ 0), Keyboard (IRQ 1) and the cascade to the Slave (IRQ 2)
+        // 1111 1000 = 0xF8
+        outb(PIC1_DATA, 0b11111000);
+        // Slave: ATA primary (IRQ 14, bit 6) and secondary (IRQ 15, bit 7) channels
+        // 0011 1111 = 0x3F
+        outb(PIC2_DATA, 0b00111111);
     }
 }
 
 /// Signal "End of Interrupt" to the PIC so it can send the next one
 pub unsafe fn notify_eoi(interrupt_id: u8) {
-    if interrupt_id >= PIC_2_OFFSET {
-        outb(PIC2_COMMAND, 0x20);
+    unsafe {
+        if interrupt_id >= PIC_2_OFFSET {
+            outb(PIC2_COMMAND, 0x20);
+        }
+        outb(PIC1_COMMAND, 0x20);
+    }
+}
+
+#[cfg(all(test, target_os = "none"))]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn masks_after_init() {
+        unsafe {
+            assert_eq!(inb(PIC1_DATA), 0b11111000);
+            assert_eq!(inb(PIC2_DATA), 0b00111111);
+        }
+    }
+
+    #[test_case]
+    fn no_interrupt_left_in_service() {
+        // OCW3: read the in-service register, a missing EOI leaves its bit set
+        unsafe {
+            outb(PIC1_COMMAND, 0x0B);
+            outb(PIC2_COMMAND, 0x0B);
+            let in_service = (inb(PIC1_COMMAND), inb(PIC2_COMMAND));
+            // Back to reading the request register, the default
+            outb(PIC1_COMMAND, 0x0A);
+            outb(PIC2_COMMAND, 0x0A);
+            assert_eq!(in_service, (0, 0));
+        }
     }
-    outb(PIC1_COMMAND, 0x20);
-}
\ No newline at end of file
+}
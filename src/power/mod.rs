@@ -0,0 +1,35 @@
+use crate::io::{outb, outw};
+
+const KEYBOARD_CONTROLLER_COMMAND: u16 = 0x64;
+const PULSE_RESET_LINE: u8 = 0xFE;
+
+// ACPI power off ports of common emulators (port, value)
+const POWER_OFF_PORTS: [(u16, u16); 3] = [
+    (0x604, 0x2000),  // QEMU
+    (0xB004, 0x2000), // Bochs and older QEMU
+    (0x4004, 0x3400), // VirtualBox
+];
+
+fn halt_forever() -> ! {
+    loop {
+        unsafe { core::arch::asm!("cli", "hlt"); }
+    }
+}
+
+/// Restart the machine through the keyboard controller, or with a triple fault if that fails
+pub fn reboot() -> ! {
+    unsafe {
+        outb(KEYBOARD_CONTROLLER_COMMAND, PULSE_RESET_LINE);
+        // An empty IDT turns the next interrupt into a triple fault, which resets the CPU
+        let empty_idt = [0u16; 5];
+        core::arch::asm!("lidt [{}]", "int3", in(reg) &empty_idt, options(noreturn));
+    }
+}
+
+/// Power off the machine on emulators, otherwise just stop the CPU
+pub fn shutdown() -> ! {
+    for (port, value) in POWER_OFF_PORTS {
+        unsafe { outw(port, value); }
+    }
+    halt_forever()
+}
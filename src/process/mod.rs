@@ -0,0 +1,252 @@
+use crate::exec::{load_program, ExecError};
+use crate::fs::file::{set_current_files, FileTable, OpenFlags};
+use crate::fs::vfs::PathBuf;
+use crate::fs::FileName;
+use crate::memory::address_space::{activate_kernel, AddressSpace};
+use crate::syscall::enter_user_mode;
+use crate::task::{kernel_stack_top, switch, Task};
+
+pub type Pid = u32;
+
+/// The shell, started by kernel_main. Adopts the children of processes that exit.
+pub const INIT_PID: Pid = 1;
+pub const MAX_PROCESSES: usize = 16;
+/// Environment of every program
+pub const DEFAULT_ENV: [&str; 1] = ["PATH=/bin"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessState {
+    /// Running, or ready to run
+    Running,
+    /// Inside waitpid, until a child exits
+    Waiting,
+    /// Exited with this code, kept until the parent collects it with waitpid
+    Zombie(i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessError {
+    /// The process table is full
+    TooManyProcesses,
+    /// waitpid found no such child
+    NoChild,
+    Exec(ExecError),
+}
+
+impl From<ExecError> for ProcessError {
+    fn from(error: ExecError) -> Self {
+        ProcessError::Exec(error)
+    }
+}
+
+pub struct Process {
+    pub pid: Pid,
+    pub parent: Pid,
+    pub name: FileName,
+    pub state: ProcessState,
+    /// Base of relative paths
+    pub cwd: PathBuf,
+    files: FileTable,
+    /// None for init, which runs in the kernel
+    space: Option<AddressSpace>,
+    task: Task,
+    /// Entry point and stack pointer of the program, until its task starts it
+    start: (u64, u64),
+}
+
+// Processes are switched when one waits for a child, and when the timer interrupts one in
+// user mode. Kernel code is never preempted, so it needs no locks.
+static mut PROCESSES: [Option<Process>; MAX_PROCESSES] = [const { None }; MAX_PROCESSES];
+static mut CURRENT: Pid = 0;
+static mut NEXT_PID: Pid = INIT_PID;
+
+fn processes() -> &'static mut [Option<Process>; MAX_PROCESSES] {
+    unsafe { &mut *core::ptr::addr_of_mut!(PROCESSES) }
+}
+
+fn find(pid: Pid) -> Option<&'static mut Process> {
+    processes().iter_mut().flatten().find(|process| process.pid == pid)
+}
+
+fn current() -> &'static mut Process {
+    find(current_pid()).expect("no current process")
+}
+
+pub fn current_pid() -> Pid {
+    unsafe { CURRENT }
+}
+
+/// Address space of the running process, None for init (which runs in the kernel)
+pub fn current_space() -> Option<&'static mut AddressSpace> {
+    current().space.as_mut()
+}
+
+/// Working directory of the running process
+pub fn current_cwd() -> PathBuf {
+    current().cwd
+}
+
+/// Continue the process `pid` with its address space and open files. Returns when
+/// a switch back to the running one happens.
+fn switch_to(pid: Pid) {
+    let from = &raw mut current().task;
+    let Some(next) = find(pid) else {
+        return;
+    };
+    unsafe {
+        CURRENT = pid;
+        set_current_files(&mut next.files);
+    }
+    match next.space.as_mut() {
+        Some(space) => space.activate(),
+        None => activate_kernel(),
+    }
+    unsafe { switch(from, &next.task); }
+}
+
+/// Switch to the next process that can run, in table order after the running one.
+/// Returns at once when there is no other, waits for one when the running one can't go on.
+fn schedule() {
+    loop {
+        let current = processes().iter().position(|slot| slot.as_ref().is_some_and(|process| process.pid == current_pid()));
+        let start = current.map_or(0, |index| index + 1);
+        let next = (0..MAX_PROCESSES)
+            .map(|offset| (start + offset) % MAX_PROCESSES)
+            .find_map(|index| processes()[index].as_ref().filter(|process| process.state == ProcessState::Running))
+            .map(|process| process.pid);
+        match next {
+            Some(pid) if pid == current_pid() => return,
+            Some(pid) => return switch_to(pid),
+            // Everyone waits: only an interrupt can change that
+            None => unsafe { core::arch::asm!("sti", "hlt"); },
+        }
+    }
+}
+
+/// Let the other processes that can run go first
+pub fn yield_now() {
+    schedule();
+}
+
+/// Called by the timer interrupt when it interrupted a user program
+pub fn preempt() {
+    yield_now();
+}
+
+/// Create init, the process the kernel (and its shell) runs as
+pub fn init_process() {
+    processes()[0] = Some(Process {
+        pid: INIT_PID,
+        parent: 0,
+        name: FileName::new("init"),
+        state: ProcessState::Running,
+        cwd: PathBuf::root(),
+        files: FileTable::new(),
+        space: None,
+        // Runs on the boot stack, saved by the first switch
+        task: Task::running(),
+        start: (0, 0),
+    });
+    unsafe {
+        NEXT_PID = INIT_PID + 1;
+        CURRENT = INIT_PID;
+    }
+    if let Some(init) = find(INIT_PID) {
+        unsafe { set_current_files(&mut init.files); }
+    }
+}
+
+/// Start the program at `path` as a child of the running process, in the directory
+/// `cwd`, with /dev/console as standard input, output and error. It runs in its own
+/// task from the next switch, when it exits it is a zombie until waitpid collects its
+/// exit code.
+pub fn spawn(path: &str, args: &[&str], env: &[&str], cwd: PathBuf) -> Result<Pid, ProcessError> {
+    let slot = processes().iter().position(Option::is_none).ok_or(ProcessError::TooManyProcesses)?;
+    let program = load_program(path, args, env)?;
+    let mut files = FileTable::new();
+    for _ in 0..3 {
+        files.open("/dev/console", OpenFlags::READ.union(OpenFlags::WRITE)).map_err(ExecError::Fs)?;
+    }
+
+    let parent = current_pid();
+    let pid = unsafe { NEXT_PID };
+    unsafe { NEXT_PID += 1; }
+    processes()[slot] = Some(Process {
+        pid,
+        parent,
+        name: FileName::new(path.rsplit('/').next().unwrap_or(path)),
+        state: ProcessState::Running,
+        cwd,
+        files,
+        space: Some(program.space),
+        // The slot's stack is free: the zombie that used it before was collected
+        task: Task::new(kernel_stack_top(slot), run_program),
+        start: (program.entry, program.stack),
+    });
+    Ok(pid)
+}
+
+/// First code of the task of a process: run its program, then end the process
+extern "C" fn run_program() -> ! {
+    let (entry, stack) = current().start;
+    let code = unsafe { enter_user_mode(entry, stack) };
+    exit(code as i32)
+}
+
+/// End the running process: free its memory and files, and keep the exit code
+/// until the parent collects it
+fn exit(code: i32) -> ! {
+    let pid = current_pid();
+    let process = current();
+    process.state = ProcessState::Zombie(code);
+    process.files.close_all();
+    // Frees all the memory of the program, and goes back to the kernel's page tables
+    drop(process.space.take());
+    let parent = process.parent;
+    for orphan in processes().iter_mut().flatten().filter(|process| process.parent == pid) {
+        orphan.parent = INIT_PID;
+    }
+    // Wake up the parent, and init if it got zombies
+    for waiting in processes().iter_mut().flatten().filter(|process| process.pid == parent || process.pid == INIT_PID) {
+        if waiting.state == ProcessState::Waiting {
+            waiting.state = ProcessState::Running;
+        }
+    }
+    schedule();
+    unreachable!("a zombie was scheduled")
+}
+
+/// Wait for a child of the running process (`pid` or any when None) to exit, collect
+/// its exit code and free its slot. Returns the child's pid and exit code.
+pub fn waitpid(pid: Option<Pid>) -> Result<(Pid, i32), ProcessError> {
+    let parent = current_pid();
+    loop {
+        let mut found = false;
+        for slot in processes().iter_mut() {
+            let Some(process) = slot else {
+                continue;
+            };
+            if process.parent != parent || pid.is_some_and(|pid| pid != process.pid) {
+                continue;
+            }
+            if let ProcessState::Zombie(code) = process.state {
+                let pid = process.pid;
+                *slot = None;
+                return Ok((pid, code));
+            }
+            found = true;
+        }
+        if !found {
+            return Err(ProcessError::NoChild);
+        }
+        current().state = ProcessState::Waiting;
+        schedule();
+    }
+}
+
+/// Call `f` with every process, in table order
+pub fn for_each_process(mut f: impl FnMut(&Process)) {
+    for process in processes().iter().flatten() {
+        f(process);
+    }
+}
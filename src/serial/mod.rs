@@ -0,0 +1,75 @@
+use core::fmt;
+use crate::io::{inb, outb};
+
+// Offsets from the base port of a 16550 UART
+const REG_DATA: u16 = 0;
+const REG_INTERRUPT_ENABLE: u16 = 1;
+const REG_FIFO_CONTROL: u16 = 2;
+const REG_LINE_CONTROL: u16 = 3;
+const REG_MODEM_CONTROL: u16 = 4;
+const REG_LINE_STATUS: u16 = 5;
+
+const LINE_STATUS_DATA_READY: u8 = 0x01;
+const LINE_STATUS_TRANSMIT_EMPTY: u8 = 0x20;
+
+// 115200 / 3 = 38400 baud
+const BAUD_DIVISOR: u16 = 3;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SerialPort {
+    base: u16,
+}
+
+/// First serial port, what QEMU's `-serial` option is connected to
+pub const COM1: SerialPort = SerialPort { base: 0x3F8 };
+
+impl SerialPort {
+    /// 38400 baud, 8 data bits, no parity, one stop bit, FIFOs on, no interrupts
+    pub fn init(&self) {
+        unsafe {
+            outb(self.base + REG_INTERRUPT_ENABLE, 0x00);
+            // DLAB on to set the baud rate divisor
+            outb(self.base + REG_LINE_CONTROL, 0x80);
+            outb(self.base + REG_DATA, BAUD_DIVISOR as u8);
+            outb(self.base + REG_INTERRUPT_ENABLE, (BAUD_DIVISOR >> 8) as u8);
+            // 8N1, DLAB off
+            outb(self.base + REG_LINE_CONTROL, 0x03);
+            // Enable and clear the FIFOs, interrupt at 14 bytes
+            outb(self.base + REG_FIFO_CONTROL, 0xC7);
+            // DTR + RTS
+            outb(self.base + REG_MODEM_CONTROL, 0x03);
+        }
+    }
+
+    pub fn write_byte(&self, byte: u8) {
+        unsafe {
+            while inb(self.base + REG_LINE_STATUS) & LINE_STATUS_TRANSMIT_EMPTY == 0 {
+                core::hint::spin_loop();
+            }
+            outb(self.base + REG_DATA, byte);
+        }
+    }
+
+    /// Next received byte, None if nothing is waiting
+    pub fn read_byte(&self) -> Option<u8> {
+        unsafe {
+            if inb(self.base + REG_LINE_STATUS) & LINE_STATUS_DATA_READY == 0 {
+                return None;
+            }
+            Some(inb(self.base + REG_DATA))
+        }
+    }
+}
+
+impl fmt::Write for SerialPort {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        for byte in text.bytes() {
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
+
+pub fn init_serial() {
+    COM1.init();
+}
@@ -0,0 +1,411 @@
+use crate::cmos::read_rtc;
+use crate::color::Color;
+use crate::console::{console, FOREGROUND};
+use crate::exec::MAX_ARGS;
+use crate::font::{self, draw_scaled_text, load_font, load_truetype, scaled_line_height, scaled_text_width, FontError};
+use crate::fs::file::{self, current_files, stat, OpenFlags};
+use crate::fs::vfs::{for_each_mount, resolve, sync_all, unmount, PathBuf};
+use crate::fs::{FileType, FsError};
+use crate::graphics::bitmap::Bitmap;
+use crate::graphics::canvas::Canvas;
+use crate::graphics::rect::Rect;
+use crate::graphics::view::View;
+use crate::graphics::{self, GradientDirection, Point};
+use crate::pci::for_each_device;
+use crate::power::{reboot, shutdown};
+use crate::process::{spawn, waitpid, DEFAULT_ENV};
+use crate::shell::{for_each_entry, print_error, set_color, Shell, DIRECTORY_COLOR};
+use crate::timer::uptime_ms;
+use crate::vbe::screen;
+use crate::{print, println};
+
+pub struct Command {
+    pub name: &'static str,
+    /// Arguments, shown by help
+    usage: &'static str,
+    description: &'static str,
+    run: fn(&mut Shell, &mut dyn Iterator<Item = &str>) -> Result<(), FsError>,
+}
+
+pub const COMMANDS: [Command; 23] = [
+    Command { name: "help", usage: "", description: "List the commands", run: help },
+    Command { name: "clear", usage: "", description: "Clear the screen", run: clear },
+    Command { name: "echo", usage: "[text...]", description: "Print the arguments", run: echo },
+    Command { name: "mem", usage: "", description: "Show memory usage", run: mem },
+    Command { name: "uptime", usage: "", description: "Time since boot", run: uptime },
+    Command { name: "date", usage: "", description: "Current date and time (UTC)", run: date },
+    Command { name: "lspci", usage: "", description: "List the PCI devices", run: lspci },
+    Command { name: "ls", usage: "[path]", description: "List a directory", run: ls },
+    Command { name: "cat", usage: "<file...>", description: "Print files", run: cat },
+    Command { name: "mkdir", usage: "<dir...>", description: "Make directories", run: mkdir },
+    Command { name: "rm", usage: "<path...>", description: "Remove files or empty directories", run: rm },
+    Command { name: "ln", usage: "<file> <name>", description: "Give a file another name (hard link)", run: ln },
+    Command { name: "stat", usage: "<path...>", description: "Show the type, size, inode and mode of files", run: file_status },
+    Command { name: "mount", usage: "", description: "List the mounted filesystems", run: mounts },
+    Command { name: "umount", usage: "<dir>", description: "Flush and detach a filesystem", run: umount },
+    Command { name: "cd", usage: "[path]", description: "Change the current directory", run: cd },
+    Command { name: "pwd", usage: "", description: "Print the current directory", run: pwd },
+    Command { name: "ps", usage: "", description: "List the processes", run: ps },
+    Command { name: "font", usage: "[file.psf]", description: "Load a console font, or describe it", run: font },
+    Command { name: "ttf", usage: "[file.ttf]", description: "Load a TrueType font, show it (clear removes it)", run: ttf },
+    Command { name: "shapes", usage: "[#rrggbb]", description: "Draw a test pattern (clear removes it)", run: shapes },
+    Command { name: "reboot", usage: "", description: "Restart the machine", run: restart },
+    Command { name: "shutdown", usage: "", description: "Power off the machine", run: power_off },
+];
+
+/// Run one command line
+pub fn execute(shell: &mut Shell, line: &str) {
+    let mut words = line.split_whitespace();
+    let Some(name) = words.next() else {
+        return;
+    };
+    let Some(command) = COMMANDS.iter().find(|command| command.name == name) else {
+        run_program(shell, name, &mut words);
+        return;
+    };
+    if let Err(error) = (command.run)(shell, &mut words) {
+        print_error(name, format_args!("{:?}", error));
+    }
+}
+
+/// Run the program `name` from /bin (or at that path when it has a slash),
+/// wait for it and report a non-zero exit code
+fn run_program(shell: &Shell, name: &str, args: &mut dyn Iterator<Item = &str>) {
+    let path = if name.contains('/') { shell.absolute(name) } else { PathBuf::normalize("/bin", name) };
+    let Ok(path) = path.and_then(|path| stat(path.as_str()).map(|_| path)) else {
+        print_error(name, format_args!("command not found, try `help`"));
+        return;
+    };
+    let mut argv = [""; MAX_ARGS];
+    argv[0] = name;
+    let mut argc = 1;
+    for arg in args {
+        if argc == MAX_ARGS {
+            print_error(name, format_args!("more than {} arguments", MAX_ARGS - 1));
+            return;
+        }
+        argv[argc] = arg;
+        argc += 1;
+    }
+
+    match spawn(path.as_str(), &argv[..argc], &DEFAULT_ENV, shell.cwd).and_then(|pid| waitpid(Some(pid))) {
+        Ok((_, 0)) => {}
+        Ok((_, code)) => print_error(name, format_args!("exited with code {}", code)),
+        Err(error) => print_error(name, format_args!("{:?}", error)),
+    }
+    // The shell is init: collect the children of programs that did not wait for them,
+    // once they exited too
+    while waitpid(None).is_ok() {}
+}
+
+fn help(_shell: &mut Shell, _args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    for command in COMMANDS.iter() {
+        println!("  {:<9}{:<15}{}", command.name, command.usage, command.description);
+    }
+    println!("Programs in /bin run by name, others by path (./prog, /mnt/0/prog)");
+    println!("Keys: arrows to edit and browse the history, Tab to complete, Ctrl+C to cancel, Ctrl+L to clear");
+    Ok(())
+}
+
+fn clear(_shell: &mut Shell, _args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    if let Some(console) = console() {
+        console.clear();
+    }
+    Ok(())
+}
+
+fn echo(_shell: &mut Shell, args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    if let Some(first) = args.next() {
+        print!("{}", first);
+        for arg in args {
+            print!(" {}", arg);
+        }
+    }
+    println!();
+    Ok(())
+}
+
+fn mem(_shell: &mut Shell, _args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    print_file("/proc/meminfo")
+}
+
+fn uptime(_shell: &mut Shell, _args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    let seconds = uptime_ms() / 1000;
+    println!("up {}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60);
+    Ok(())
+}
+
+fn date(_shell: &mut Shell, _args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    let now = read_rtc();
+    println!("{}-{:02}-{:02} {:02}:{:02}:{:02} UTC", now.year, now.month, now.day, now.hour, now.minute, now.second);
+    Ok(())
+}
+
+fn lspci(_shell: &mut Shell, _args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    for_each_device(|device| {
+        println!(
+            "{:02x}:{:02x}.{} {} [{:04x}:{:04x}]",
+            device.bus, device.device, device.function, device.class_name(), device.vendor_id, device.device_id,
+        );
+    });
+    Ok(())
+}
+
+fn ls(shell: &mut Shell, args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    let path = shell.absolute(args.next().unwrap_or("."))?;
+    let dir = resolve(path.as_str())?;
+    let stat = dir.fs.stat(dir.inode)?;
+    if stat.file_type != FileType::Directory {
+        println!("{:>10}  {}", stat.size, path.file_name());
+        return Ok(());
+    }
+    for_each_entry(&path, |name, file_type, inode| {
+        if file_type == FileType::Directory {
+            print!("{:>10}  ", "<dir>");
+            set_color(DIRECTORY_COLOR);
+            println!("{}/", name);
+            set_color(FOREGROUND);
+        } else {
+            let size = inode.and_then(|inode| dir.fs.stat(inode).ok()).map_or(0, |stat| stat.size);
+            println!("{:>10}  {}", size, name);
+        }
+    })
+}
+
+fn cat(shell: &mut Shell, args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    for arg in args {
+        let path = shell.absolute(arg)?;
+        print_file(path.as_str())?;
+    }
+    Ok(())
+}
+
+fn mkdir(shell: &mut Shell, args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    for arg in args {
+        file::mkdir(shell.absolute(arg)?.as_str())?;
+    }
+    Ok(())
+}
+
+fn rm(shell: &mut Shell, args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    for arg in args {
+        file::unlink(shell.absolute(arg)?.as_str())?;
+    }
+    Ok(())
+}
+
+fn ln(shell: &mut Shell, args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    let (Some(existing), Some(new_path)) = (args.next(), args.next()) else {
+        return Err(FsError::InvalidArgument);
+    };
+    file::link(shell.absolute(existing)?.as_str(), shell.absolute(new_path)?.as_str())
+}
+
+fn file_status(shell: &mut Shell, args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    for arg in args {
+        let path = shell.absolute(arg)?;
+        let stat = stat(path.as_str())?;
+        println!(
+            "{}: {:?}, {} bytes, inode {}, {} links, mode {:04o}",
+            path.as_str(), stat.file_type, stat.size, stat.inode, stat.links, stat.permissions,
+        );
+    }
+    Ok(())
+}
+
+fn mounts(_shell: &mut Shell, _args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    for_each_mount(|mount_point, fs| println!("{} on {}", fs.name(), mount_point));
+    Ok(())
+}
+
+fn umount(shell: &mut Shell, args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    let Some(arg) = args.next() else {
+        return Err(FsError::InvalidArgument);
+    };
+    unmount(shell.absolute(arg)?.as_str())
+}
+
+fn cd(shell: &mut Shell, args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    let path = shell.absolute(args.next().unwrap_or("/"))?;
+    let dir = resolve(path.as_str())?;
+    if dir.fs.stat(dir.inode)?.file_type != FileType::Directory {
+        return Err(FsError::NotADirectory);
+    }
+    shell.cwd = path;
+    Ok(())
+}
+
+fn pwd(shell: &mut Shell, _args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    println!("{}", shell.cwd.as_str());
+    Ok(())
+}
+
+fn ps(_shell: &mut Shell, _args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    print_file("/proc/tasks")
+}
+
+fn font(shell: &mut Shell, args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    let Some(arg) = args.next() else {
+        let font = font::font();
+        let table = if font.has_unicode_table() { "a Unicode table" } else { "no Unicode table" };
+        println!("{}x{} pixels, {} glyphs, {}", font.width(), font.height(), font.glyph_count(), table);
+        return Ok(());
+    };
+    let path = shell.absolute(arg)?;
+    match load_font(path.as_str()) {
+        Ok(()) => {
+            if let Some(console) = console() {
+                console.font_changed();
+            }
+            Ok(())
+        }
+        Err(FontError::Fs(error)) => Err(error),
+        Err(error) => {
+            print_error("font", format_args!("{:?}", error));
+            Ok(())
+        }
+    }
+}
+
+/// The scalable font at several sizes, in a panel at the bottom of the screen
+fn ttf(shell: &mut Shell, args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    if let Some(arg) = args.next() {
+        let path = shell.absolute(arg)?;
+        match load_truetype(path.as_str()) {
+            Ok(()) => {}
+            Err(FontError::Fs(error)) => return Err(error),
+            Err(error) => {
+                print_error("ttf", format_args!("{:?}", error));
+                return Ok(());
+            }
+        }
+    }
+    let Some(font) = font::truetype() else {
+        print_error("ttf", format_args!("no font loaded, give a .ttf file"));
+        return Ok(());
+    };
+    let kerning = if font.has_kerning() { "kerning pairs" } else { "no kerning pairs" };
+    println!("{} glyphs, {} units per em, {}", font.glyph_count(), font.units_per_em(), kerning);
+    let Some(screen) = screen() else {
+        return Ok(());
+    };
+    const SIZES: [(f32, &str); 7] = [(8.0, "8 pt"), (10.0, "10 pt"), (12.0, "12 pt"), (16.0, "16 pt"), (24.0, "24 pt"), (36.0, "36 pt"), (48.0, "48 pt")];
+    const SAMPLE: &str = "The quick brown fox jumps over the lazy dog. Déjà vu, 0123456789 €";
+    const PANEL: Color = Color::rgb(0x20, 0x20, 0x30);
+    const LABEL: Color = Color::rgb(0x90, 0x90, 0xA0);
+    let height = SIZES.iter().map(|&(points, _)| scaled_line_height(font, points)).sum::<i32>() + 20;
+    let (x, width) = (10, screen.width() as i32 - 20);
+    let y = (screen.height() as i32 - height - 10).max(0);
+    graphics::fill_rounded_rect(screen, x, y, width, height, 12, PANEL);
+
+    let mut panel = View::new(&mut *screen, Rect::new(x as usize, y as usize, width as usize, height as usize));
+    let cache = font::glyph_cache();
+    let mut top = 10;
+    for (points, label) in SIZES {
+        let ascent = font.ascender() as f32 * font::points_to_pixels(points) / font.units_per_em() as f32;
+        let baseline = top + (ascent + 0.5) as i32;
+        // Right aligned before the sample
+        let label_x = 60 - scaled_text_width(font, label, 10.0);
+        draw_scaled_text(&mut panel, font, cache, label_x, baseline, label, 10.0, LABEL);
+        draw_scaled_text(&mut panel, font, cache, 70, baseline, SAMPLE, points, FOREGROUND);
+        top += scaled_line_height(font, points);
+    }
+    screen.flush();
+    Ok(())
+}
+
+/// Every 2D primitive once, in a 400x300 panel at the top right of the screen.
+/// The sprite at the bottom right corner is cut by the panel.
+fn shapes(_shell: &mut Shell, args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    const SHADOW: Color = Color::BLACK.with_alpha(0x60);
+    let panel_color = match args.next() {
+        Some(arg) => match Color::from_hex(arg) {
+            Some(color) => color,
+            None => {
+                print_error("shapes", format_args!("{}: not a color", arg));
+                return Ok(());
+            }
+        },
+        None => Color::from_rgb_u32(0x303040),
+    };
+    let Some(screen) = screen() else {
+        return Ok(());
+    };
+    let (x, y) = (screen.width() as i32 - 420, 10);
+    graphics::fill_rounded_rect(screen, x + 8, y + 8, 400, 300, 12, SHADOW);
+    graphics::fill_rounded_rect(screen, x, y, 400, 300, 12, panel_color);
+    graphics::draw_rounded_rect(screen, x, y, 400, 300, 12, FOREGROUND);
+
+    // The rest in the panel's own coordinates
+    let mut panel = View::new(&mut *screen, Rect::new(x as usize, y as usize, 400, 300));
+    graphics::fill_gradient(&mut panel, 12, 136, 376, 16, (Color::from_hsv(200, 200, 255), panel_color), GradientDirection::Horizontal);
+    // Text in the console font, on the gradient
+    panel.draw_text(16, 136, "gradient", FOREGROUND);
+    for i in 0..8 {
+        graphics::draw_line(&mut panel, 20, 20, 20 + i * 15, 125, Color::YELLOW);
+    }
+    graphics::draw_rect(&mut panel, 150, 20, 90, 60, Color::GREEN);
+    graphics::fill_rect(&mut panel, 160, 30, 35, 40, Color::BLUE);
+    graphics::fill_gradient(&mut panel, 195, 30, 35, 40, (Color::CYAN, Color::MAGENTA), GradientDirection::Vertical);
+    graphics::fill_circle(&mut panel, 310, 70, 50, Color::RED);
+    graphics::draw_circle(&mut panel, 310, 70, 55, Color::GRAY);
+    graphics::draw_ellipse(&mut panel, 195, 120, 45, 20, Color::YELLOW);
+    let triangle = [Point::new(20, 280), Point::new(80, 160), Point::new(140, 280)];
+    graphics::fill_triangle(&mut panel, triangle[0], triangle[1], triangle[2], Color::GREEN);
+    graphics::draw_triangle(&mut panel, triangle[0], triangle[1], triangle[2], FOREGROUND);
+    let star = [
+        Point::new(230, 160), Point::new(248, 215), Point::new(300, 215), Point::new(258, 245), Point::new(275, 290),
+        Point::new(230, 262), Point::new(185, 290), Point::new(202, 245), Point::new(160, 215), Point::new(212, 215),
+    ];
+    graphics::fill_polygon(&mut panel, &star, Color::YELLOW);
+    // An outline, then the flood fill paints its inside
+    graphics::draw_rounded_rect(&mut panel, 320, 170, 60, 110, 20, FOREGROUND);
+    graphics::flood_fill(&mut panel, 350, 225, Color::BLUE);
+    // Translucent on top of the rest
+    graphics::fill_circle(&mut panel, 150, 150, 40, Color::GREEN.with_alpha(0x80));
+
+    // A sprite drawn once in memory, then copied twice
+    let mut pixels = [Color::TRANSPARENT; 24 * 24];
+    if let Some(mut sprite) = Bitmap::new(&mut pixels, 24, 24) {
+        for row in 0..24 {
+            graphics::draw_span(&mut sprite, 0, 23, row, Color::from_hsv(row as u16 * 15, 255, 255).with_alpha(0xC0));
+        }
+        graphics::fill_circle(&mut sprite, 12, 12, 6, Color::WHITE);
+        panel.draw_bitmap(250, 100, &sprite);
+        panel.draw_bitmap(390, 280, &sprite);
+    }
+    screen.flush();
+    Ok(())
+}
+
+fn restart(_shell: &mut Shell, _args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    let _ = sync_all();
+    println!("Rebooting...");
+    reboot()
+}
+
+fn power_off(_shell: &mut Shell, _args: &mut dyn Iterator<Item = &str>) -> Result<(), FsError> {
+    let _ = sync_all();
+    println!("It is now safe to turn off the computer.");
+    shutdown()
+}
+
+/// Copy a whole file to the console
+fn print_file(path: &str) -> Result<(), FsError> {
+    let files = current_files();
+    let fd = files.open(path, OpenFlags::READ)?;
+    let mut buffer = [0u8; 512];
+    let result = loop {
+        match files.read(fd, &mut buffer) {
+            Ok(0) => break Ok(()),
+            Ok(count) => {
+                if let Some(console) = console() {
+                    console.write_bytes(&buffer[..count]);
+                }
+            }
+            Err(error) => break Err(error),
+        }
+    };
+    files.close(fd)?;
+    result
+}
@@ -0,0 +1,258 @@
+use crate::console::console;
+use crate::fs::FileName;
+use crate::keyboard::Key;
+use crate::print;
+
+/// Longest command line, in bytes (only ASCII is typed)
+pub const MAX_LINE: usize = 256;
+/// Lines remembered for the up / down arrows
+const HISTORY_SIZE: usize = 16;
+/// Completion candidates kept for one tab press
+pub const MAX_COMPLETIONS: usize = 32;
+
+#[derive(Clone, Copy)]
+struct Line {
+    bytes: [u8; MAX_LINE],
+    len: usize,
+}
+
+impl Line {
+    const fn empty() -> Self {
+        Line { bytes: [0; MAX_LINE], len: 0 }
+    }
+
+    fn as_str(&self) -> &str {
+        // Only ASCII is inserted
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or("")
+    }
+}
+
+/// Possible completions of the word under the cursor
+pub struct Completions {
+    names: [FileName; MAX_COMPLETIONS],
+    count: usize,
+    /// Start of the completed word in the line
+    pub word_start: usize,
+}
+
+impl Completions {
+    /// Add a candidate, ignored once MAX_COMPLETIONS are kept
+    pub fn add(&mut self, name: &str) {
+        if self.count < MAX_COMPLETIONS {
+            self.names[self.count] = FileName::new(name);
+            self.count += 1;
+        }
+    }
+
+    fn names(&self) -> impl Iterator<Item = &str> {
+        self.names[..self.count].iter().map(FileName::as_str)
+    }
+
+    /// Longest prefix all candidates share
+    fn common_prefix(&self) -> &str {
+        let Some(first) = self.names().next() else {
+            return "";
+        };
+        let mut len = first.len();
+        for name in self.names() {
+            len = first.bytes().zip(name.bytes()).take(len).take_while(|(a, b)| a == b).count();
+        }
+        &first[..len]
+    }
+}
+
+/// Reads a command line from keys, with cursor movement, history and tab completion.
+/// Everything is echoed to the console.
+pub struct Editor {
+    line: Line,
+    cursor: usize,
+    history: [Line; HISTORY_SIZE],
+    /// Lines in `history`, the newest one at `(history_next - 1) % HISTORY_SIZE`
+    history_count: usize,
+    history_next: usize,
+    /// How far back the up arrow went (0 = the line being typed)
+    browsing: usize,
+    /// The line being typed, kept while browsing the history
+    draft: Line,
+}
+
+impl Editor {
+    pub const fn new() -> Self {
+        Editor {
+            line: Line::empty(),
+            cursor: 0,
+            history: [Line::empty(); HISTORY_SIZE],
+            history_count: 0,
+            history_next: 0,
+            browsing: 0,
+            draft: Line::empty(),
+        }
+    }
+
+    /// Start a new line after `prompt` has been printed
+    pub fn start(&mut self) {
+        self.line = Line::empty();
+        self.cursor = 0;
+        self.browsing = 0;
+    }
+
+    pub fn line(&self) -> &str {
+        self.line.as_str()
+    }
+
+    /// Handle one key, returns true when the line is complete (Enter)
+    pub fn handle_key(&mut self, key: Key, prompt: &str, complete: impl FnOnce(&str, &mut Completions)) -> bool {
+        match key {
+            Key::Enter => {
+                print!("\n");
+                self.remember();
+                return true;
+            }
+            Key::Ctrl('c') => {
+                print!("^C\n");
+                self.line = Line::empty();
+                return true;
+            }
+            Key::Ctrl('l') => {
+                if let Some(console) = console() {
+                    console.clear();
+                }
+                print!("{}{}", prompt, self.line.as_str());
+                self.move_back(self.line.len - self.cursor);
+            }
+            Key::Char(char) if char.is_ascii() && !char.is_ascii_control() => self.insert(&[char as u8]),
+            Key::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                print!("\x08");
+                self.remove_at_cursor();
+            }
+            Key::Delete if self.cursor < self.line.len => self.remove_at_cursor(),
+            Key::Left if self.cursor > 0 => {
+                self.cursor -= 1;
+                print!("\x08");
+            }
+            Key::Right if self.cursor < self.line.len => {
+                print!("{}", self.line.bytes[self.cursor] as char);
+                self.cursor += 1;
+            }
+            Key::Home | Key::Ctrl('a') => {
+                self.move_back(self.cursor);
+                self.cursor = 0;
+            }
+            Key::End | Key::Ctrl('e') => {
+                print!("{}", &self.line.as_str()[self.cursor..]);
+                self.cursor = self.line.len;
+            }
+            Key::Up => self.browse(self.browsing + 1),
+            Key::Down if self.browsing > 0 => self.browse(self.browsing - 1),
+            Key::Tab => self.complete(prompt, complete),
+            _ => {}
+        }
+        false
+    }
+
+    fn move_back(&self, count: usize) {
+        for _ in 0..count {
+            print!("\x08");
+        }
+    }
+
+    /// Print the line from the cursor to its end, plus `erase` blanks for removed
+    /// characters, then put the cursor back
+    fn redraw_tail(&self, erase: usize) {
+        print!("{}", &self.line.as_str()[self.cursor..]);
+        for _ in 0..erase {
+            print!(" ");
+        }
+        self.move_back(self.line.len - self.cursor + erase);
+    }
+
+    fn insert(&mut self, text: &[u8]) {
+        let count = text.len().min(MAX_LINE - self.line.len);
+        if count == 0 {
+            return;
+        }
+        self.line.bytes.copy_within(self.cursor..self.line.len, self.cursor + count);
+        self.line.bytes[self.cursor..self.cursor + count].copy_from_slice(&text[..count]);
+        self.line.len += count;
+        print!("{}", &self.line.as_str()[self.cursor..self.cursor + count]);
+        self.cursor += count;
+        self.redraw_tail(0);
+    }
+
+    fn remove_at_cursor(&mut self) {
+        self.line.bytes.copy_within(self.cursor + 1..self.line.len, self.cursor);
+        self.line.len -= 1;
+        self.redraw_tail(1);
+    }
+
+    /// Replace the whole line on screen and in the buffer
+    fn replace_line(&mut self, line: Line) {
+        self.move_back(self.cursor);
+        let erase = self.line.len.saturating_sub(line.len);
+        self.line = line;
+        self.cursor = self.line.len;
+        print!("{}", self.line.as_str());
+        for _ in 0..erase {
+            print!(" ");
+        }
+        self.move_back(erase);
+    }
+
+    fn remember(&mut self) {
+        let line = self.line.as_str();
+        let last = (self.history_next + HISTORY_SIZE - 1) % HISTORY_SIZE;
+        if line.trim().is_empty() || (self.history_count > 0 && self.history[last].as_str() == line) {
+            return;
+        }
+        self.history[self.history_next] = self.line;
+        self.history_next = (self.history_next + 1) % HISTORY_SIZE;
+        self.history_count = (self.history_count + 1).min(HISTORY_SIZE);
+    }
+
+    /// Show the history entry `back` lines ago (0 = the line being typed)
+    fn browse(&mut self, back: usize) {
+        if back > self.history_count {
+            return;
+        }
+        if self.browsing == 0 {
+            self.draft = self.line;
+        }
+        self.browsing = back;
+        let line = match back {
+            0 => self.draft,
+            _ => self.history[(self.history_next + HISTORY_SIZE - back) % HISTORY_SIZE],
+        };
+        self.replace_line(line);
+    }
+
+    fn complete(&mut self, prompt: &str, complete: impl FnOnce(&str, &mut Completions)) {
+        let mut completions = Completions {
+            names: [FileName::empty(); MAX_COMPLETIONS],
+            count: 0,
+            word_start: self.cursor,
+        };
+        complete(&self.line.as_str()[..self.cursor], &mut completions);
+        let typed = self.cursor - completions.word_start.min(self.cursor);
+
+        let prefix = completions.common_prefix();
+        if prefix.len() > typed {
+            let mut addition = [0u8; MAX_LINE];
+            let extra = &prefix.as_bytes()[typed..];
+            let count = extra.len().min(MAX_LINE);
+            addition[..count].copy_from_slice(&extra[..count]);
+            self.insert(&addition[..count]);
+            // A single file is complete, directories can be continued
+            if completions.count == 1 && !prefix.ends_with('/') {
+                self.insert(b" ");
+            }
+        } else if completions.count > 1 {
+            print!("\n");
+            for name in completions.names() {
+                print!("{}  ", name);
+            }
+            print!("\n{}{}", prompt, self.line.as_str());
+            self.move_back(self.line.len - self.cursor);
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use crate::color::Color;
+use crate::console::{console, FOREGROUND};
+use crate::fs::file::{current_files, OpenFlags};
+use crate::fs::vfs::{for_each_mount, PathBuf, MAX_PATH_LEN};
+use crate::fs::{FileName, FileType, FsError};
+use crate::keyboard::{read_scancode, Key, KeyDecoder};
+use crate::serial::COM1;
+use crate::shell::commands::COMMANDS;
+use crate::shell::editor::{Completions, Editor};
+use crate::{print, println};
+
+mod commands;
+mod editor;
+
+const PROMPT_COLOR: Color = Color::rgb(0x66, 0xCC, 0x66);
+const DIRECTORY_COLOR: Color = Color::rgb(0x66, 0x99, 0xFF);
+const ERROR_COLOR: Color = Color::rgb(0xFF, 0x66, 0x66);
+
+/// Turns the bytes a terminal sends on the serial line (VT100 escape sequences included) into keys
+struct SerialDecoder {
+    state: SerialState,
+    parameter: u8,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SerialState {
+    Normal,
+    /// After ESC
+    Escape,
+    /// After ESC [
+    Sequence,
+}
+
+impl SerialDecoder {
+    const fn new() -> Self {
+        SerialDecoder { state: SerialState::Normal, parameter: 0 }
+    }
+
+    fn decode(&mut self, byte: u8) -> Option<Key> {
+        match self.state {
+            SerialState::Normal => match byte {
+                0x1B => {
+                    self.state = SerialState::Escape;
+                    None
+                }
+                b'\r' | b'\n' => Some(Key::Enter),
+                0x08 | 0x7F => Some(Key::Backspace),
+                b'\t' => Some(Key::Tab),
+                0x01..=0x1A => Some(Key::Ctrl((b'a' + byte - 1) as char)),
+                _ if byte.is_ascii() => Some(Key::Char(byte as char)),
+                _ => None,
+            },
+            SerialState::Escape => {
+                self.state = if byte == b'[' { SerialState::Sequence } else { SerialState::Normal };
+                self.parameter = 0;
+                None
+            }
+            SerialState::Sequence => {
+                if byte.is_ascii_digit() {
+                    self.parameter = self.parameter.wrapping_mul(10).wrapping_add(byte - b'0');
+                    return None;
+                }
+                self.state = SerialState::Normal;
+                match (byte, self.parameter) {
+                    (b'A', _) => Some(Key::Up),
+                    (b'B', _) => Some(Key::Down),
+                    (b'C', _) => Some(Key::Right),
+                    (b'D', _) => Some(Key::Left),
+                    (b'H', _) | (b'~', 1 | 7) => Some(Key::Home),
+                    (b'F', _) | (b'~', 4 | 8) => Some(Key::End),
+                    (b'~', 3) => Some(Key::Delete),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+/// Keys from the PS/2 keyboard and from a terminal on the serial port
+struct Input {
+    keyboard: KeyDecoder,
+    serial: SerialDecoder,
+}
+
+impl Input {
+    /// Wait for the next key from either source, sleeping between interrupts
+    fn next_key(&mut self) -> Key {
+        loop {
+            while let Some(scancode) = read_scancode() {
+                if let Some(key) = self.keyboard.decode(scancode) {
+                    return key;
+                }
+            }
+            while let Some(byte) = COM1.read_byte() {
+                if let Some(key) = self.serial.decode(byte) {
+                    return key;
+                }
+            }
+            // The timer wakes us up at least every 10 ms to poll the serial port
+            unsafe { core::arch::asm!("hlt"); }
+        }
+    }
+}
+
+/// State the built-in commands work on
+pub struct Shell {
+    cwd: PathBuf,
+}
+
+impl Shell {
+    fn print_prompt(&self) {
+        set_color(PROMPT_COLOR);
+        print!("{}", self.cwd.as_str());
+        set_color(FOREGROUND);
+        print!("> ");
+    }
+
+    /// Absolute form of a path typed by the user
+    fn absolute(&self, path: &str) -> Result<PathBuf, FsError> {
+        PathBuf::normalize(self.cwd.as_str(), path)
+    }
+}
+
+/// "cwd> " as plain text, to reprint the prompt while editing
+struct PromptText {
+    bytes: [u8; MAX_PATH_LEN + 2],
+    len: usize,
+}
+
+impl PromptText {
+    fn new(cwd: &str) -> Self {
+        let mut prompt = PromptText { bytes: [0; MAX_PATH_LEN + 2], len: cwd.len() + 2 };
+        prompt.bytes[..cwd.len()].copy_from_slice(cwd.as_bytes());
+        prompt.bytes[cwd.len()..cwd.len() + 2].copy_from_slice(b"> ");
+        prompt
+    }
+
+    fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or("> ")
+    }
+}
+
+fn set_color(color: Color) {
+    if let Some(console) = console() {
+        console.set_foreground(color);
+    }
+}
+
+/// Print an error in red
+fn print_error(command: &str, message: core::fmt::Arguments) {
+    set_color(ERROR_COLOR);
+    println!("{}: {}", command, message);
+    set_color(FOREGROUND);
+}
+
+/// Call `f(name, file type, inode)` for every entry of the directory `path`, including
+/// filesystems mounted right under it. The inode is in the filesystem of `path`, None
+/// for mount points.
+fn for_each_entry(path: &PathBuf, mut f: impl FnMut(&str, FileType, Option<u64>)) -> Result<(), FsError> {
+    let files = current_files();
+    let fd = files.open(path.as_str(), OpenFlags::READ)?;
+    let dir = files.vnode(fd)?;
+    let listed = loop {
+        match files.read_dir(fd) {
+            Ok(Some(entry)) => f(entry.name.as_str(), entry.file_type, Some(entry.inode)),
+            Ok(None) => break Ok(()),
+            Err(error) => break Err(error),
+        }
+    };
+    files.close(fd)?;
+    listed?;
+    for_each_mount(|mount_point, _| {
+        let (parent, name) = match mount_point.rfind('/') {
+            Some(0) => ("/", &mount_point[1..]),
+            Some(slash) => (&mount_point[..slash], &mount_point[slash + 1..]),
+            None => return,
+        };
+        // Mount points also present in the parent filesystem were listed already
+        if !name.is_empty() && parent == path.as_str() && dir.fs.lookup(dir.inode, name).is_err() {
+            f(name, FileType::Directory, None);
+        }
+    });
+    Ok(())
+}
+
+/// Complete the word before the cursor: a command name for the first word, a path otherwise
+fn complete(cwd: &PathBuf, line: &str, completions: &mut Completions) {
+    let word_start = line.rfind(' ').map_or(0, |space| space + 1);
+    let word = &line[word_start..];
+    completions.word_start = word_start;
+
+    if line[..word_start].trim().is_empty() && !word.contains('/') {
+        for command in COMMANDS.iter().filter(|command| command.name.starts_with(word)) {
+            completions.add(command.name);
+        }
+        // Programs run by name
+        if let Ok(bin) = PathBuf::normalize("/", "/bin") {
+            let _ = for_each_entry(&bin, |name, file_type, _| {
+                if file_type == FileType::Regular && name.starts_with(word) {
+                    completions.add(name);
+                }
+            });
+        }
+        return;
+    }
+
+    // "dir/pre" completes names starting with "pre" in "dir", keeping "dir/" in front
+    let (dir_part, prefix) = match word.rfind('/') {
+        Some(slash) => (&word[..slash + 1], &word[slash + 1..]),
+        None => ("", word),
+    };
+    let Ok(dir) = PathBuf::normalize(cwd.as_str(), if dir_part.is_empty() { "." } else { dir_part }) else {
+        return;
+    };
+    let _ = for_each_entry(&dir, |name, file_type, _| {
+        if !name.starts_with(prefix) {
+            return;
+        }
+        let mut candidate = FileName::new(dir_part);
+        for char in name.chars() {
+            candidate.push(char);
+        }
+        if file_type == FileType::Directory {
+            candidate.push('/');
+        }
+        completions.add(candidate.as_str());
+    });
+}
+
+/// Run the shell forever
+pub fn run() -> ! {
+    let mut shell = Shell { cwd: PathBuf::root() };
+    let mut editor = Editor::new();
+    let mut input = Input { keyboard: KeyDecoder::new(), serial: SerialDecoder::new() };
+
+    println!("Welcome to JackcatOS");
+    println!("Type `help` to list the commands.");
+    println!();
+    loop {
+        shell.print_prompt();
+        editor.start();
+        let prompt = PromptText::new(shell.cwd.as_str());
+        while !editor.handle_key(input.next_key(), prompt.as_str(), |line, completions| complete(&shell.cwd, line, completions)) {}
+        commands::execute(&mut shell, editor.line());
+    }
+}
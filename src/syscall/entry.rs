@@ -0,0 +1,221 @@
+use core::arch::global_asm;
+use crate::gdt::{set_kernel_stack, KERNEL_DATA_SELECTOR, USER_CODE_SELECTOR, USER_DATA_SELECTOR};
+use crate::syscall::dispatch;
+
+/// Registers of a system call, pushed by the entry stubs below (lowest address first)
+#[repr(C)]
+pub struct SyscallFrame {
+    pub r9: u64,
+    pub r8: u64,
+    pub r10: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    /// System call number
+    pub rax: u64,
+}
+
+/// Kernel stack used by `syscall` (which, unlike interrupts, does not switch stacks)
+static mut SYSCALL_KERNEL_RSP: u64 = 0;
+/// User stack pointer, kept while switching to the kernel stack
+static mut SYSCALL_USER_RSP: u64 = 0;
+/// Kernel stack pointer saved by enter_user_mode, restored by user_return
+static mut USER_RETURN_RSP: u64 = 0;
+
+/// Where ring 3 enters the kernel and where the innermost enter_user_mode returns to.
+/// Each task has its own, swapped on a task switch.
+#[derive(Clone, Copy)]
+pub struct UserContext {
+    return_rsp: u64,
+    kernel_rsp: u64,
+}
+
+impl UserContext {
+    /// A task that did not enter user mode yet
+    pub const fn new() -> Self {
+        UserContext { return_rsp: 0, kernel_rsp: 0 }
+    }
+
+    /// The context of the running task
+    pub fn save() -> Self {
+        unsafe { UserContext { return_rsp: USER_RETURN_RSP, kernel_rsp: SYSCALL_KERNEL_RSP } }
+    }
+
+    /// Make this the context of the running task
+    pub fn restore(&self) {
+        unsafe { USER_RETURN_RSP = self.return_rsp; }
+        set_entry_stack(self.kernel_rsp);
+    }
+}
+
+unsafe extern "C" {
+    /// Target of LSTAR
+    pub fn syscall_entry();
+    /// Handler of the `int 0x80` gate
+    pub fn int80_entry();
+    fn enter_user(entry: u64, stack: u64) -> i64;
+    fn user_return(code: i64) -> !;
+}
+
+/// Ring 3 entries from inside a user program use the kernel stack right below
+/// the frame of the enter_user_mode that started it
+extern "C" fn set_entry_stack(stack_top: u64) {
+    set_kernel_stack(stack_top);
+    unsafe { SYSCALL_KERNEL_RSP = stack_top; }
+}
+
+/// Run user code at `entry` with the stack pointer `stack` in ring 3, until it exits.
+/// Returns the exit code. Calls can nest: a system call of the program may start another one.
+pub unsafe fn enter_user_mode(entry: u64, stack: u64) -> i64 {
+    unsafe { enter_user(entry, stack) }
+}
+
+/// Leave the running user program: continue after its enter_user_mode, which returns `code`
+pub fn return_to_kernel(code: i64) -> ! {
+    unsafe { user_return(code) }
+}
+
+// syscall: rax = number, arguments in rdi, rsi, rdx, r10, r8, r9, the CPU put the
+// user RIP in rcx and RFLAGS in r11. Everything but rax, rcx and r11 is preserved.
+// Interrupts are masked by FMASK until we are on the kernel stack.
+global_asm!(
+    ".global syscall_entry",
+    "syscall_entry:",
+    "mov [rip + {user_rsp}], rsp",
+    "mov rsp, [rip + {kernel_rsp}]",
+    "push qword ptr [rip + {user_rsp}]",
+    "push rcx",
+    "push r11",
+    "push rax",
+    "push rdi",
+    "push rsi",
+    "push rdx",
+    "push r10",
+    "push r8",
+    "push r9",
+    "mov rdi, rsp",
+    "sti",
+    "call {dispatch}",
+    "cli",
+    "pop r9",
+    "pop r8",
+    "pop r10",
+    "pop rdx",
+    "pop rsi",
+    "pop rdi",
+    "add rsp, 8",
+    "pop r11",
+    "pop rcx",
+    "pop rsp",
+    "sysretq",
+    user_rsp = sym SYSCALL_USER_RSP,
+    kernel_rsp = sym SYSCALL_KERNEL_RSP,
+    dispatch = sym dispatch,
+);
+
+// int 0x80: same registers as syscall, the CPU already switched to TSS.RSP0.
+// rcx and r11 are saved too since nothing clobbers them here.
+global_asm!(
+    ".global int80_entry",
+    "int80_entry:",
+    "push rcx",
+    "push r11",
+    "push rax",
+    "push rdi",
+    "push rsi",
+    "push rdx",
+    "push r10",
+    "push r8",
+    "push r9",
+    "mov rdi, rsp",
+    "sti",
+    "call {dispatch}",
+    "cli",
+    "pop r9",
+    "pop r8",
+    "pop r10",
+    "pop rdx",
+    "pop rsi",
+    "pop rdi",
+    "add rsp, 8",
+    "pop r11",
+    "pop rcx",
+    "iretq",
+    dispatch = sym dispatch,
+);
+
+// enter_user(entry, stack): save the callee-saved registers and the state of an outer
+// user program, then iretq to ring 3 with interrupts enabled and clean registers.
+// user_return(code) unwinds that frame from anywhere in the kernel.
+global_asm!(
+    ".global enter_user",
+    "enter_user:",
+    "push rbx",
+    "push rbp",
+    "push r12",
+    "push r13",
+    "push r14",
+    "push r15",
+    "push qword ptr [rip + {return_rsp}]",
+    "push qword ptr [rip + {kernel_rsp}]",
+    "mov [rip + {return_rsp}], rsp",
+    "mov r12, rdi",
+    "mov r13, rsi",
+    "and rsp, -16",
+    "mov rdi, rsp",
+    "call {set_entry_stack}",
+    "push {user_data}",
+    "push r13",
+    "push 0x202",
+    "push {user_code}",
+    "push r12",
+    "mov ax, {user_data}",
+    "mov ds, ax",
+    "mov es, ax",
+    "xor eax, eax",
+    "xor ebx, ebx",
+    "xor ecx, ecx",
+    "xor edx, edx",
+    "xor esi, esi",
+    "xor edi, edi",
+    "xor ebp, ebp",
+    "xor r8d, r8d",
+    "xor r9d, r9d",
+    "xor r10d, r10d",
+    "xor r11d, r11d",
+    "xor r12d, r12d",
+    "xor r13d, r13d",
+    "xor r14d, r14d",
+    "xor r15d, r15d",
+    "iretq",
+    "",
+    ".global user_return",
+    "user_return:",
+    "cli",
+    "mov rsp, [rip + {return_rsp}]",
+    "pop qword ptr [rip + {kernel_rsp}]",
+    "pop qword ptr [rip + {return_rsp}]",
+    "mov r12, rdi",
+    "mov rdi, [rip + {kernel_rsp}]",
+    "sub rsp, 8",
+    "call {set_entry_stack}",
+    "add rsp, 8",
+    "mov ax, {kernel_data}",
+    "mov ds, ax",
+    "mov es, ax",
+    "mov rax, r12",
+    "pop r15",
+    "pop r14",
+    "pop r13",
+    "pop r12",
+    "pop rbp",
+    "pop rbx",
+    "sti",
+    "ret",
+    return_rsp = sym USER_RETURN_RSP,
+    kernel_rsp = sym SYSCALL_KERNEL_RSP,
+    set_entry_stack = sym set_entry_stack,
+    user_data = const USER_DATA_SELECTOR,
+    user_code = const USER_CODE_SELECTOR,
+    kernel_data = const KERNEL_DATA_SELECTOR,
+);
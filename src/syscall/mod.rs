@@ -0,0 +1,300 @@
+use crate::cpu::{rdmsr, wrmsr, IA32_EFER, IA32_FMASK, IA32_LSTAR, IA32_STAR};
+use crate::exec::{ExecError, MAX_ARGS};
+use crate::fs::file::{current_files, OpenFlags, SeekFrom};
+use crate::fs::vfs::{PathBuf, MAX_PATH_LEN};
+use crate::fs::{read_u64, FsError};
+use crate::gdt::{KERNEL_CODE_SELECTOR, SYSRET_BASE_SELECTOR};
+use crate::memory::MemoryError;
+use crate::process::{current_cwd, current_pid, current_space, spawn, waitpid, ProcessError, DEFAULT_ENV};
+use crate::syscall::entry::{syscall_entry, SyscallFrame};
+use crate::syscall::user::{check_user_range, copy_from_user, copy_to_user, user_str};
+use crate::timer::uptime_ms;
+
+pub use entry::{enter_user_mode, int80_entry, return_to_kernel, UserContext};
+
+mod entry;
+pub mod user;
+
+// System call numbers: rax when executing `syscall` or `int 0x80`
+pub const SYS_EXIT: u64 = 0;
+pub const SYS_READ: u64 = 1;
+pub const SYS_WRITE: u64 = 2;
+pub const SYS_OPEN: u64 = 3;
+pub const SYS_CLOSE: u64 = 4;
+pub const SYS_SEEK: u64 = 5;
+pub const SYS_IOCTL: u64 = 6;
+pub const SYS_UPTIME: u64 = 7;
+pub const SYS_SPAWN: u64 = 8;
+pub const SYS_WAITPID: u64 = 9;
+pub const SYS_GETPID: u64 = 10;
+pub const SYS_MMAP: u64 = 11;
+
+/// Bytes moved between user memory and a file per step of read / write
+const CHUNK_SIZE: usize = 512;
+/// Bytes of the argument strings of one spawn
+const SPAWN_ARGS_SIZE: usize = 1024;
+
+/// Error of a system call, returned to user mode as `-(errno)` (the Linux numbers)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Errno {
+    NoEntry = 2,
+    Io = 5,
+    ArgumentListTooLong = 7,
+    NoExec = 8,
+    BadDescriptor = 9,
+    NoChild = 10,
+    TryAgain = 11,
+    NoMemory = 12,
+    /// A pointer outside the user address space
+    Fault = 14,
+    Busy = 16,
+    Exists = 17,
+    CrossDevice = 18,
+    NotADirectory = 20,
+    IsADirectory = 21,
+    InvalidArgument = 22,
+    TooManyFiles = 24,
+    NoSpace = 28,
+    ReadOnly = 30,
+    NameTooLong = 36,
+    NoSyscall = 38,
+    NotEmpty = 39,
+    NotSupported = 95,
+}
+
+impl From<FsError> for Errno {
+    fn from(error: FsError) -> Self {
+        match error {
+            FsError::Block(_) | FsError::BadSuperblock | FsError::Corrupted => Errno::Io,
+            FsError::Unsupported => Errno::NotSupported,
+            FsError::NotFound => Errno::NoEntry,
+            FsError::NotADirectory => Errno::NotADirectory,
+            FsError::IsADirectory => Errno::IsADirectory,
+            FsError::AlreadyExists => Errno::Exists,
+            FsError::DirectoryNotEmpty => Errno::NotEmpty,
+            FsError::InvalidName | FsError::InvalidArgument => Errno::InvalidArgument,
+            FsError::NoSpace => Errno::NoSpace,
+            FsError::ReadOnly => Errno::ReadOnly,
+            FsError::PathTooLong => Errno::NameTooLong,
+            FsError::BadDescriptor => Errno::BadDescriptor,
+            FsError::TooManyOpenFiles => Errno::TooManyFiles,
+            FsError::MountFailed => Errno::Busy,
+            FsError::CrossDevice => Errno::CrossDevice,
+        }
+    }
+}
+
+impl From<ExecError> for Errno {
+    fn from(error: ExecError) -> Self {
+        match error {
+            ExecError::NotElf | ExecError::Unsupported | ExecError::Malformed => Errno::NoExec,
+            ExecError::ArgumentsTooLong => Errno::ArgumentListTooLong,
+            ExecError::Fs(error) => error.into(),
+            ExecError::Memory(error) => error.into(),
+        }
+    }
+}
+
+impl From<MemoryError> for Errno {
+    fn from(error: MemoryError) -> Self {
+        match error {
+            MemoryError::OutOfMemory => Errno::NoMemory,
+            MemoryError::BadAddress => Errno::Fault,
+        }
+    }
+}
+
+impl From<ProcessError> for Errno {
+    fn from(error: ProcessError) -> Self {
+        match error {
+            ProcessError::TooManyProcesses => Errno::TryAgain,
+            ProcessError::NoChild => Errno::NoChild,
+            ProcessError::Exec(error) => error.into(),
+        }
+    }
+}
+
+/// Arguments rdi, rsi, rdx, r10, r8 and r9
+type Args = [u64; 6];
+
+/// Enable `syscall` / `sysret` (the `int 0x80` gate is set by init_idt)
+pub fn init_syscall() {
+    unsafe {
+        wrmsr(IA32_EFER, rdmsr(IA32_EFER) | 1); // SCE
+        wrmsr(IA32_STAR, (SYSRET_BASE_SELECTOR as u64) << 48 | (KERNEL_CODE_SELECTOR as u64) << 32);
+        wrmsr(IA32_LSTAR, syscall_entry as *const () as u64);
+        // Clear IF, TF, DF and AC on entry
+        wrmsr(IA32_FMASK, 1 << 9 | 1 << 8 | 1 << 10 | 1 << 18);
+    }
+}
+
+/// Called by both entry stubs, returns the value for rax
+extern "C" fn dispatch(frame: &mut SyscallFrame) -> i64 {
+    let args = [frame.rdi, frame.rsi, frame.rdx, frame.r10, frame.r8, frame.r9];
+    let result = match frame.rax {
+        SYS_EXIT => sys_exit(args),
+        SYS_READ => sys_read(args),
+        SYS_WRITE => sys_write(args),
+        SYS_OPEN => sys_open(args),
+        SYS_CLOSE => sys_close(args),
+        SYS_SEEK => sys_seek(args),
+        SYS_IOCTL => sys_ioctl(args),
+        SYS_UPTIME => sys_uptime(args),
+        SYS_SPAWN => sys_spawn(args),
+        SYS_WAITPID => sys_waitpid(args),
+        SYS_GETPID => sys_getpid(args),
+        SYS_MMAP => sys_mmap(args),
+        _ => Err(Errno::NoSyscall),
+    };
+    match result {
+        Ok(value) => value as i64,
+        Err(errno) => -(errno as i64),
+    }
+}
+
+/// exit(code)
+fn sys_exit(args: Args) -> Result<u64, Errno> {
+    return_to_kernel(args[0] as i32 as i64)
+}
+
+/// read(fd, buffer, length) -> bytes read, 0 at the end of the file
+fn sys_read(args: Args) -> Result<u64, Errno> {
+    let [fd, address, length, ..] = args;
+    check_user_range(address, length as usize, true)?;
+    let files = current_files();
+    let mut chunk = [0u8; CHUNK_SIZE];
+    let mut total = 0;
+    while total < length {
+        let wanted = (length - total).min(CHUNK_SIZE as u64) as usize;
+        let count = files.read(fd as usize, &mut chunk[..wanted])?;
+        copy_to_user(address + total, &chunk[..count])?;
+        total += count as u64;
+        if count < wanted {
+            break;
+        }
+    }
+    Ok(total)
+}
+
+/// write(fd, buffer, length) -> bytes written
+fn sys_write(args: Args) -> Result<u64, Errno> {
+    let [fd, address, length, ..] = args;
+    check_user_range(address, length as usize, false)?;
+    let files = current_files();
+    let mut chunk = [0u8; CHUNK_SIZE];
+    let mut total = 0;
+    while total < length {
+        let count = (length - total).min(CHUNK_SIZE as u64) as usize;
+        copy_from_user(&mut chunk[..count], address + total)?;
+        let written = files.write(fd as usize, &chunk[..count])?;
+        total += written as u64;
+        if written < count {
+            break;
+        }
+    }
+    Ok(total)
+}
+
+/// open(path, path length, flags) -> fd, flags are the bits of OpenFlags
+fn sys_open(args: Args) -> Result<u64, Errno> {
+    let [address, length, flags, ..] = args;
+    let mut buffer = [0u8; MAX_PATH_LEN];
+    let path = PathBuf::normalize(current_cwd().as_str(), user_str(address, length as usize, &mut buffer)?)?;
+    let fd = current_files().open(path.as_str(), OpenFlags::from_bits(flags as u32))?;
+    Ok(fd as u64)
+}
+
+/// close(fd)
+fn sys_close(args: Args) -> Result<u64, Errno> {
+    current_files().close(args[0] as usize)?;
+    Ok(0)
+}
+
+/// seek(fd, offset, whence) -> new offset, whence is 0 (start), 1 (current) or 2 (end)
+fn sys_seek(args: Args) -> Result<u64, Errno> {
+    let [fd, offset, whence, ..] = args;
+    let position = match whence {
+        0 => SeekFrom::Start(offset),
+        1 => SeekFrom::Current(offset as i64),
+        2 => SeekFrom::End(offset as i64),
+        _ => return Err(Errno::InvalidArgument),
+    };
+    Ok(current_files().seek(fd as usize, position)?)
+}
+
+/// ioctl(fd, request, buffer, length) -> bytes of the buffer used, see `FileSystem::ioctl`
+fn sys_ioctl(args: Args) -> Result<u64, Errno> {
+    let [fd, request, address, length, ..] = args;
+    let mut data = [0u8; CHUNK_SIZE];
+    let data = data.get_mut(..length as usize).ok_or(Errno::InvalidArgument)?;
+    copy_from_user(data, address)?;
+    let count = current_files().ioctl(fd as usize, request as u32, data)?;
+    copy_to_user(address, data)?;
+    Ok(count as u64)
+}
+
+/// uptime() -> milliseconds since boot
+fn sys_uptime(_args: Args) -> Result<u64, Errno> {
+    Ok(uptime_ms())
+}
+
+/// spawn(path, path length, args, arg count) -> pid of the child, which starts at the next switch.
+/// `args` points to (address, length) pairs of u64, one per argument string.
+fn sys_spawn(args: Args) -> Result<u64, Errno> {
+    let [path_address, path_length, args_address, count, ..] = args;
+    let count = count as usize;
+    if count > MAX_ARGS {
+        return Err(Errno::ArgumentListTooLong);
+    }
+    let cwd = current_cwd();
+    let mut path_buffer = [0u8; MAX_PATH_LEN];
+    let path = PathBuf::normalize(cwd.as_str(), user_str(path_address, path_length as usize, &mut path_buffer)?)?;
+
+    let mut pairs = [0u8; MAX_ARGS * 16];
+    copy_from_user(&mut pairs[..count * 16], args_address)?;
+    let mut strings = [0u8; SPAWN_ARGS_SIZE];
+    let mut free: &mut [u8] = &mut strings;
+    let mut argv = [""; MAX_ARGS];
+    for (i, arg) in argv[..count].iter_mut().enumerate() {
+        let (address, length) = (read_u64(&pairs, i * 16), read_u64(&pairs, i * 16 + 8) as usize);
+        if length > free.len() {
+            return Err(Errno::ArgumentListTooLong);
+        }
+        let (string, rest) = core::mem::take(&mut free).split_at_mut(length);
+        *arg = user_str(address, length, string)?;
+        free = rest;
+    }
+    Ok(spawn(path.as_str(), &argv[..count], &DEFAULT_ENV, cwd)? as u64)
+}
+
+/// waitpid(pid or -1 for any child, address of an i32 for the exit code or 0) -> pid
+fn sys_waitpid(args: Args) -> Result<u64, Errno> {
+    let [pid, status_address, ..] = args;
+    let pid = if pid as i64 == -1 { None } else { Some(pid as u32) };
+    if status_address != 0 {
+        check_user_range(status_address, 4, true)?;
+    }
+    let (pid, code) = waitpid(pid)?;
+    if status_address != 0 {
+        copy_to_user(status_address, &code.to_le_bytes())?;
+    }
+    Ok(pid as u64)
+}
+
+/// getpid() -> pid of the running process
+fn sys_getpid(_args: Args) -> Result<u64, Errno> {
+    Ok(current_pid() as u64)
+}
+
+/// mmap(fd, length, offset) -> user address of `length` bytes at `offset` of a device
+/// file (/dev/fb0) open for reading and writing, mapped until the process exits
+fn sys_mmap(args: Args) -> Result<u64, Errno> {
+    let [fd, length, offset, ..] = args;
+    if length == 0 {
+        return Err(Errno::InvalidArgument);
+    }
+    let physical = current_files().mmap(fd as usize, offset, length as usize)?;
+    // init runs in the kernel, which reaches the framebuffer directly
+    let space = current_space().ok_or(Errno::NotSupported)?;
+    Ok(space.map_device(physical as u64, length as usize)?)
+}
@@ -0,0 +1,47 @@
+use crate::memory::paging::{active_pml4, translate, USER, USER_END, USER_START, WRITABLE};
+use crate::memory::FRAME_SIZE;
+use crate::syscall::Errno;
+
+/// Fail unless `length` bytes at `address` are user memory mapped in the running
+/// program (and writable when `write` is set), so the kernel never faults on them
+pub fn check_user_range(address: u64, length: usize, write: bool) -> Result<(), Errno> {
+    let end = address.checked_add(length as u64).ok_or(Errno::Fault)?;
+    if address < USER_START || end > USER_END {
+        return Err(Errno::Fault);
+    }
+    let required = if write { USER | WRITABLE } else { USER };
+    let pml4 = active_pml4();
+    let mut page = address & !(FRAME_SIZE - 1);
+    while page < end {
+        match translate(pml4, page) {
+            Some((_, flags)) if flags & required == required => page += FRAME_SIZE,
+            _ => return Err(Errno::Fault),
+        }
+    }
+    Ok(())
+}
+
+/// Fill `buffer` from user memory at `address`
+pub fn copy_from_user(buffer: &mut [u8], address: u64) -> Result<(), Errno> {
+    check_user_range(address, buffer.len(), false)?;
+    unsafe {
+        core::ptr::copy_nonoverlapping(address as *const u8, buffer.as_mut_ptr(), buffer.len());
+    }
+    Ok(())
+}
+
+/// Copy `data` to user memory at `address`
+pub fn copy_to_user(address: u64, data: &[u8]) -> Result<(), Errno> {
+    check_user_range(address, data.len(), true)?;
+    unsafe {
+        core::ptr::copy_nonoverlapping(data.as_ptr(), address as *mut u8, data.len());
+    }
+    Ok(())
+}
+
+/// Copy a string of `length` bytes from user memory into `buffer`
+pub fn user_str(address: u64, length: usize, buffer: &mut [u8]) -> Result<&str, Errno> {
+    let buffer = buffer.get_mut(..length).ok_or(Errno::NameTooLong)?;
+    copy_from_user(buffer, address)?;
+    core::str::from_utf8(buffer).map_err(|_| Errno::InvalidArgument)
+}
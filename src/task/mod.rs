@@ -0,0 +1,111 @@
+use core::arch::global_asm;
+use crate::syscall::UserContext;
+use crate::{KERNEL_STACKS_ADDRESS, KERNEL_STACKS_SIZE};
+
+/// Stack of each task in kernel mode, but the first one, which keeps the boot stack
+pub const KERNEL_STACK_SIZE: usize = 32 * 1024;
+/// RFLAGS of a new task: interrupts stay off until it enters user mode
+const INITIAL_FLAGS: u64 = 0x2;
+
+/// A thread of kernel execution that can be switched out and continued later.
+/// The code of a task does not see the switches: `switch` returns once another task
+/// switched back to it.
+pub struct Task {
+    /// Stack pointer while switched out, the registers to restore are on the stack
+    rsp: u64,
+    user: UserContext,
+}
+
+impl Task {
+    /// The code running now, saved by its first switch
+    pub const fn running() -> Self {
+        Task { rsp: 0, user: UserContext::new() }
+    }
+
+    /// A task that starts with `start` on the stack ending at `stack_top`
+    pub fn new(stack_top: u64, start: extern "C" fn() -> !) -> Self {
+        // What switch_stacks pops, then the return address, as if `start` had been called
+        let frame = [0, 0, 0, 0, 0, 0, INITIAL_FLAGS, start as usize as u64, 0];
+        let rsp = (stack_top & !15) - (frame.len() * 8) as u64;
+        unsafe { core::ptr::copy_nonoverlapping(frame.as_ptr(), rsp as *mut u64, frame.len()); }
+        Task { rsp, user: UserContext::new() }
+    }
+}
+
+/// Top of kernel stack number `index`, there is one per slot of the process table
+pub fn kernel_stack_top(index: usize) -> u64 {
+    assert!(index < KERNEL_STACKS_SIZE / KERNEL_STACK_SIZE, "no kernel stack {}", index);
+    (KERNEL_STACKS_ADDRESS + (index + 1) * KERNEL_STACK_SIZE) as u64
+}
+
+/// Save the running task in `from` and continue `to` where it was switched out, or at
+/// its start. Returns when another task switches back to `from`.
+pub unsafe fn switch(from: *mut Task, to: *const Task) {
+    unsafe {
+        (*from).user = UserContext::save();
+        (*to).user.restore();
+        switch_stacks(&raw mut (*from).rsp, (*to).rsp);
+    }
+}
+
+unsafe extern "C" {
+    fn switch_stacks(save: *mut u64, rsp: u64);
+}
+
+// switch_stacks(save, rsp): push the callee-saved registers and RFLAGS, store rsp in
+// *save, then pop those of the other task from `rsp` and return where it called from.
+// Each task gets its own interrupt flag back.
+global_asm!(
+    ".global switch_stacks",
+    "switch_stacks:",
+    "pushfq",
+    "cli",
+    "push rbx",
+    "push rbp",
+    "push r12",
+    "push r13",
+    "push r14",
+    "push r15",
+    "mov [rdi], rsp",
+    "mov rsp, rsi",
+    "pop r15",
+    "pop r14",
+    "pop r13",
+    "pop r12",
+    "pop rbp",
+    "pop rbx",
+    "popfq",
+    "ret",
+);
+
+#[cfg(all(test, target_os = "none"))]
+mod tests {
+    use super::*;
+    use core::ptr::{addr_of, addr_of_mut};
+
+    static mut MAIN: Task = Task::running();
+    static mut OTHER: Task = Task::running();
+    static mut STEPS: u32 = 0;
+
+    extern "C" fn other() -> ! {
+        unsafe {
+            STEPS += 1;
+            switch(addr_of_mut!(OTHER), addr_of!(MAIN));
+            STEPS += 10;
+            switch(addr_of_mut!(OTHER), addr_of!(MAIN));
+        }
+        unreachable!("switched back to a finished task")
+    }
+
+    #[test_case]
+    fn switches_back_and_forth() {
+        unsafe {
+            // The last stack, no process uses it while the tests run
+            OTHER = Task::new(kernel_stack_top(KERNEL_STACKS_SIZE / KERNEL_STACK_SIZE - 1), other);
+            switch(addr_of_mut!(MAIN), addr_of!(OTHER));
+            assert_eq!(*addr_of!(STEPS), 1);
+            switch(addr_of_mut!(MAIN), addr_of!(OTHER));
+            assert_eq!(*addr_of!(STEPS), 11);
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! Runs the `#[test_case]` tests inside the kernel, after the normal boot, for what
+//! can only be tested on the machine (interrupts, PIC, paging, allocators).
+//!
+//! `sh build-run.sh test` builds them and starts QEMU without a window. Results go to
+//! the serial port, and the isa-debug-exit device ends QEMU with the overall result.
+//!
+//! There is no unwinding: when a test panics (kernel exceptions panic too), the panic
+//! handler restarts the runner on a fresh stack at the next test.
+
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+use crate::io::outl;
+use crate::serial::COM1;
+
+/// Port of QEMU's `-device isa-debug-exit,iobase=0xf4,iosize=0x04`
+const DEBUG_EXIT_PORT: u16 = 0xF4;
+/// QEMU exits with (code << 1) | 1, so 33 and 35
+const EXIT_SUCCESS: u32 = 0x10;
+const EXIT_FAILURE: u32 = 0x11;
+/// Bytes of a panic message compared with the expected one
+const MAX_MESSAGE: usize = 256;
+
+pub trait Testable {
+    fn name(&self) -> &str;
+
+    /// Part of the panic message when the test must panic
+    fn expected_panic(&self) -> Option<&str> {
+        None
+    }
+
+    fn run(&self);
+}
+
+/// `#[test_case] fn name() { ... }`
+impl<T: Fn()> Testable for T {
+    /// The path of the function, without the crate name
+    fn name(&self) -> &str {
+        let path = core::any::type_name::<T>();
+        path.split_once("::").map_or(path, |(_, path)| path)
+    }
+
+    fn run(&self) {
+        self()
+    }
+}
+
+/// A test that passes when it panics with `message` in the panic message, e.g.
+/// `#[test_case] const NAME: ShouldPanic = ShouldPanic { name: "name", message: "page fault", test: || ... };`
+pub struct ShouldPanic {
+    pub name: &'static str,
+    pub message: &'static str,
+    pub test: fn(),
+}
+
+impl Testable for ShouldPanic {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn expected_panic(&self) -> Option<&str> {
+        Some(self.message)
+    }
+
+    fn run(&self) {
+        (self.test)()
+    }
+}
+
+struct TestRun {
+    tests: &'static [&'static dyn Testable],
+    /// Index of the running test
+    current: usize,
+    failed: usize,
+    /// Where the runner's stack was, to restart from after a panic
+    stack: u64,
+}
+
+static mut RUN: Option<TestRun> = None;
+
+fn run() -> Option<&'static mut TestRun> {
+    unsafe { (*core::ptr::addr_of_mut!(RUN)).as_mut() }
+}
+
+fn report(args: fmt::Arguments) {
+    let mut port = COM1;
+    let _ = port.write_fmt(args);
+}
+
+/// The test harness calls this with every `#[test_case]`
+pub fn run_tests(tests: &[&dyn Testable]) {
+    report(format_args!("\r\nrunning {} tests\r\n", tests.len()));
+    let stack: u64;
+    unsafe { core::arch::asm!("mov {}, rsp", out(reg) stack, options(nomem, nostack, preserves_flags)); }
+    // The slice is in the frame of test_main, above `stack`, which is never left
+    let tests = unsafe { core::mem::transmute::<&[&dyn Testable], &'static [&'static dyn Testable]>(tests) };
+    unsafe { RUN = Some(TestRun { tests, current: 0, failed: 0, stack }); }
+    run_from(0)
+}
+
+fn run_from(first: usize) -> ! {
+    let Some(run) = run() else {
+        exit_qemu(EXIT_FAILURE);
+    };
+    for index in first..run.tests.len() {
+        let test = run.tests[index];
+        run.current = index;
+        report(format_args!("{} ... ", test.name()));
+        test.run();
+        if let Some(message) = test.expected_panic() {
+            report(format_args!("[failed]\r\n    did not panic with \"{}\"\r\n", message));
+            run.failed += 1;
+        } else {
+            report(format_args!("[ok]\r\n"));
+        }
+    }
+    let passed = run.tests.len() - run.failed;
+    report(format_args!("\r\ntest result: {} passed; {} failed\r\n", passed, run.failed));
+    exit_qemu(if run.failed == 0 { EXIT_SUCCESS } else { EXIT_FAILURE })
+}
+
+/// Called by the panic handler: the running test passed or failed, go on with the next one
+pub fn handle_panic(info: &PanicInfo) -> ! {
+    let Some(run) = run() else {
+        report(format_args!("\r\npanic outside of a test: {}\r\n", info));
+        exit_qemu(EXIT_FAILURE);
+    };
+    let mut message = MessageBuffer { bytes: [0; MAX_MESSAGE], length: 0 };
+    let _ = write!(message, "{}", info.message());
+    match run.tests[run.current].expected_panic() {
+        Some(expected) if message.as_str().contains(expected) => report(format_args!("[ok]\r\n")),
+        _ => {
+            report(format_args!("[failed]\r\n    {}\r\n", info));
+            run.failed += 1;
+        }
+    }
+    unsafe {
+        // Drop everything below the runner's frame. A panic in an exception handler
+        // came with interrupts off, the tests run with them on.
+        core::arch::asm!(
+            "mov rsp, {stack}",
+            "and rsp, -16",
+            "sti",
+            "call {resume}",
+            stack = in(reg) run.stack,
+            resume = sym resume_tests,
+            options(noreturn),
+        );
+    }
+}
+
+extern "C" fn resume_tests() -> ! {
+    let next = run().map_or(0, |run| run.current + 1);
+    run_from(next)
+}
+
+/// The start of a panic message, enough to look for the expected text
+struct MessageBuffer {
+    bytes: [u8; MAX_MESSAGE],
+    length: usize,
+}
+
+impl MessageBuffer {
+    fn as_str(&self) -> &str {
+        // A multi-byte character may have been cut at the end
+        match core::str::from_utf8(&self.bytes[..self.length]) {
+            Ok(text) => text,
+            Err(error) => core::str::from_utf8(&self.bytes[..error.valid_up_to()]).unwrap_or(""),
+        }
+    }
+}
+
+impl Write for MessageBuffer {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        let count = text.len().min(MAX_MESSAGE - self.length);
+        self.bytes[self.length..self.length + count].copy_from_slice(&text.as_bytes()[..count]);
+        self.length += count;
+        Ok(())
+    }
+}
+
+fn exit_qemu(code: u32) -> ! {
+    unsafe { outl(DEBUG_EXIT_PORT, code); }
+    // Not in QEMU, or without the device
+    loop {
+        unsafe { core::arch::asm!("cli", "hlt"); }
+    }
+}
@@ -0,0 +1,381 @@
+//! Helpers for the host tests: a framebuffer and a disk in plain memory, golden images.
+//!
+//! Golden images are text files in `src/testing/golden/`, one character per pixel.
+//! Run the tests with `UPDATE_GOLDEN=1` to write them from what is drawn now.
+
+use core::cell::{Cell, RefCell};
+use crate::block::{check_range, BlockDevice, BlockError, SECTOR_SIZE};
+use crate::color::Color;
+use crate::fs::FileSystem;
+use crate::graphics::canvas::Canvas;
+use crate::vbe::back_buffer::BackBuffer;
+use crate::vbe::pixel::PixelFormat;
+use crate::vbe::ChannelMask;
+
+/// A BackBuffer like the kernel's, on memory of the test instead of the BIOS framebuffer
+pub struct SoftwareFramebuffer {
+    back_buffer: BackBuffer,
+    pitch: usize,
+    format: PixelFormat,
+    // Owned here, the back buffer points into them (moving a Vec doesn't move its contents)
+    _pixels: Vec<u8>,
+    video: Vec<u8>,
+}
+
+impl SoftwareFramebuffer {
+    /// A `bpp` bits per pixel framebuffer with the usual channel layout of that depth
+    pub fn new(width: usize, height: usize, bpp: u8) -> Self {
+        let none = ChannelMask { size: 0, position: 0 };
+        let format = PixelFormat::new(bpp, none, none, none);
+        // Padded scanlines, like many real modes
+        let pitch = width * format.bytes_per_pixel + 8;
+        let mut pixels = vec![0; pitch * height];
+        let mut video = vec![0; pitch * height];
+        let back_buffer = unsafe { BackBuffer::from_memory(pixels.as_mut_ptr(), video.as_mut_ptr(), width, height, pitch, format) };
+        SoftwareFramebuffer { back_buffer, pitch, format, _pixels: pixels, video }
+    }
+
+    pub fn canvas(&mut self) -> &mut BackBuffer {
+        &mut self.back_buffer
+    }
+
+    /// What the "video memory" shows, i.e. what has been flushed
+    pub fn screen_pixel(&self, x: usize, y: usize) -> Color {
+        let offset = y * self.pitch + x * self.format.bytes_per_pixel;
+        self.format.decode(unsafe { self.format.read(self.video[offset..].as_ptr()) })
+    }
+
+    /// The flushed picture as text, see `to_text`
+    pub fn screen_text(&self, palette: &[(Color, char)]) -> String {
+        let (width, height) = (self.back_buffer.width(), self.back_buffer.height());
+        picture_text(width, height, palette, |x, y| self.screen_pixel(x, y))
+    }
+}
+
+/// A disk in plain memory, counting the requests it gets
+pub struct MemoryDisk {
+    bytes: RefCell<Vec<u8>>,
+    pub reads: Cell<usize>,
+    pub writes: Cell<usize>,
+}
+
+impl MemoryDisk {
+    /// A disk with `image` on it, zero-padded to whole sectors
+    pub fn new(mut image: Vec<u8>) -> Self {
+        image.resize(image.len().next_multiple_of(SECTOR_SIZE), 0);
+        MemoryDisk { bytes: RefCell::new(image), reads: Cell::new(0), writes: Cell::new(0) }
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        self.bytes.borrow().clone()
+    }
+}
+
+impl BlockDevice for MemoryDisk {
+    fn read_sectors(&self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError> {
+        check_range(self, lba, buffer.len())?;
+        self.reads.set(self.reads.get() + 1);
+        let start = lba as usize * SECTOR_SIZE;
+        buffer.copy_from_slice(&self.bytes.borrow()[start..start + buffer.len()]);
+        Ok(())
+    }
+
+    fn write_sectors(&self, lba: u64, buffer: &[u8]) -> Result<(), BlockError> {
+        check_range(self, lba, buffer.len())?;
+        self.writes.set(self.writes.get() + 1);
+        let start = lba as usize * SECTOR_SIZE;
+        self.bytes.borrow_mut()[start..start + buffer.len()].copy_from_slice(buffer);
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), BlockError> {
+        Ok(())
+    }
+
+    fn capacity(&self) -> u64 {
+        (self.bytes.borrow().len() / SECTOR_SIZE) as u64
+    }
+}
+
+/// Names in the directory `dir`, in the filesystem's order
+pub fn list_dir(fs: &dyn FileSystem, dir: u64) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut cursor = 0;
+    while let Some(entry) = fs.read_dir(dir, &mut cursor).unwrap() {
+        names.push(entry.name.as_str().to_string());
+    }
+    names
+}
+
+/// The whole content of the file `inode`, read in small pieces
+pub fn read_file(fs: &dyn FileSystem, inode: u64) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut buffer = [0u8; 100];
+    loop {
+        let count = fs.read(inode, data.len() as u64, &mut buffer).unwrap();
+        if count == 0 {
+            return data;
+        }
+        data.extend(&buffer[..count]);
+    }
+}
+
+/// The canvas as text, a line per row: each pixel is the character of its color in
+/// `palette`, '?' for colors that are not in it
+pub fn to_text<C: Canvas + ?Sized>(canvas: &C, palette: &[(Color, char)]) -> String {
+    picture_text(canvas.width(), canvas.height(), palette, |x, y| canvas.pixel(x, y).unwrap_or(Color::TRANSPARENT))
+}
+
+fn picture_text(width: usize, height: usize, palette: &[(Color, char)], pixel: impl Fn(usize, usize) -> Color) -> String {
+    let mut text = String::new();
+    for y in 0..height {
+        for x in 0..width {
+            let color = pixel(x, y);
+            text.push(palette.iter().find(|(other, _)| *other == color).map_or('?', |(_, char)| *char));
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Compare `text` with the golden image `name`
+#[track_caller]
+pub fn assert_golden(name: &str, text: &str) {
+    let path = format!("{}/src/testing/golden/{}.txt", env!("CARGO_MANIFEST_DIR"), name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, text).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|error| panic!("{}: {}", path, error));
+    assert!(expected == text, "{} differs from the golden image, drawn:\n{}", name, text);
+}
+
+/// A small TrueType font, the tables built here:
+/// - glyph 0, the missing character: a box
+/// - glyph 1, 'I': a rectangle, with its outline on curve
+/// - glyph 2, 'O' and 'é': a rounded square with a square hole, its outline all off curve
+/// - glyph 3, 'H': two 'I', the second one moved and scaled 1.5 x 0.5
+/// - glyph 4, ' ': no outline, and the advance of glyph 3 (hmtx stops there)
+///
+/// Kerning pairs: 'I' 'O' -100, 'O' 'I' -50. 1000 units per em.
+pub fn truetype_font(long_offsets: bool, cmap_format12: bool) -> Vec<u8> {
+    let glyphs = [
+        simple_glyph(&[&[(50, 0, true), (450, 0, true), (450, 700, true), (50, 700, true)]]),
+        simple_glyph(&[&[(100, 0, true), (300, 0, true), (300, 700, true), (100, 700, true)]]),
+        simple_glyph(&[
+            &[(0, 0, false), (600, 0, false), (600, 700, false), (0, 700, false)],
+            &[(200, 200, true), (200, 500, true), (400, 500, true), (400, 200, true)],
+        ]),
+        composite_glyph(),
+        Vec::new(),
+    ];
+    let advances = [500, 400, 700, 1000];
+
+    let mut glyf = Vec::new();
+    let mut loca = Vec::new();
+    for glyph in glyphs.iter().map(Some).chain([None]) {
+        if long_offsets {
+            push_u32(&mut loca, glyf.len() as u32);
+        } else {
+            push_u16(&mut loca, (glyf.len() / 2) as u16);
+        }
+        glyf.extend(glyph.into_iter().flatten());
+    }
+
+    let mut head = vec![0; 54];
+    head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+    head[50..52].copy_from_slice(&(long_offsets as u16).to_be_bytes());
+    let mut hhea = vec![0; 36];
+    hhea[4..6].copy_from_slice(&800i16.to_be_bytes());
+    hhea[6..8].copy_from_slice(&(-200i16).to_be_bytes());
+    hhea[34..36].copy_from_slice(&(advances.len() as u16).to_be_bytes());
+    let mut maxp = vec![0x00, 0x00, 0x50, 0x00];
+    push_u16(&mut maxp, glyphs.len() as u16);
+    let mut hmtx = Vec::new();
+    for advance in advances {
+        push_u16(&mut hmtx, advance);
+        push_u16(&mut hmtx, 0);
+    }
+    push_u16(&mut hmtx, 0);
+
+    let mut kern = Vec::new();
+    for value in [0, 1, 0, 14 + 2 * 6, 0x0001, 2, 12, 1, 0] {
+        push_u16(&mut kern, value);
+    }
+    for (left, right, value) in [(1, 2, -100i16), (2, 1, -50)] {
+        push_u16(&mut kern, left);
+        push_u16(&mut kern, right);
+        push_u16(&mut kern, value as u16);
+    }
+
+    let cmap = if cmap_format12 { cmap_format12_table() } else { cmap_format4_table() };
+    sfnt(&[
+        (b"cmap", cmap),
+        (b"glyf", glyf),
+        (b"head", head),
+        (b"hhea", hhea),
+        (b"hmtx", hmtx),
+        (b"kern", kern),
+        (b"loca", loca),
+        (b"maxp", maxp),
+    ])
+}
+
+fn push_u16(data: &mut Vec<u8>, value: u16) {
+    data.extend(value.to_be_bytes());
+}
+
+fn push_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend(value.to_be_bytes());
+}
+
+/// The table directory and the tables, each on 4 bytes
+fn sfnt(tables: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let mut data = Vec::new();
+    push_u32(&mut data, 0x0001_0000);
+    for value in [tables.len() as u16, 0, 0, 0] {
+        push_u16(&mut data, value);
+    }
+    let mut offset = 12 + tables.len() * 16;
+    for (tag, table) in tables {
+        data.extend(*tag);
+        push_u32(&mut data, 0);
+        push_u32(&mut data, offset as u32);
+        push_u32(&mut data, table.len() as u32);
+        offset += table.len().next_multiple_of(4);
+    }
+    for (_, table) in tables {
+        data.extend(table);
+        data.resize(data.len().next_multiple_of(4), 0);
+    }
+    data
+}
+
+/// Contours of (x, y, on curve) points, with the short, same and repeat encodings
+fn simple_glyph(contours: &[&[(i16, i16, bool)]]) -> Vec<u8> {
+    let points = contours.concat();
+    let mut data = Vec::new();
+    push_u16(&mut data, contours.len() as u16);
+    let xs = points.iter().map(|point| point.0);
+    let ys = points.iter().map(|point| point.1);
+    for value in [xs.clone().min(), ys.clone().min(), xs.max(), ys.max()] {
+        push_u16(&mut data, value.unwrap_or(0) as u16);
+    }
+    let mut end = 0;
+    for contour in contours {
+        end += contour.len();
+        push_u16(&mut data, end as u16 - 1);
+    }
+    push_u16(&mut data, 0);
+
+    let (mut flags, mut xs, mut ys) = (Vec::new(), Vec::new(), Vec::new());
+    let mut previous = (0, 0);
+    for &(x, y, on_curve) in &points {
+        let mut flag = on_curve as u8;
+        for (delta, coordinates, short) in [(x - previous.0, &mut xs, 0x02), (y - previous.1, &mut ys, 0x04)] {
+            let same_or_positive = short << 3;
+            if delta == 0 {
+                flag |= same_or_positive;
+            } else if delta.unsigned_abs() < 256 {
+                flag |= short | if delta > 0 { same_or_positive } else { 0 };
+                coordinates.push(delta.unsigned_abs() as u8);
+            } else {
+                coordinates.extend(delta.to_be_bytes());
+            }
+        }
+        flags.push(flag);
+        previous = (x, y);
+    }
+    // Runs of the same flag
+    let mut index = 0;
+    while index < flags.len() {
+        let run = flags[index..].iter().take_while(|&&flag| flag == flags[index]).count();
+        if run > 1 {
+            data.extend([flags[index] | 0x08, run as u8 - 1]);
+        } else {
+            data.push(flags[index]);
+        }
+        index += run;
+    }
+    data.extend(xs);
+    data.extend(ys);
+    data.resize(data.len().next_multiple_of(2), 0);
+    data
+}
+
+/// Glyph 1, and glyph 1 at x + 500 scaled 1.5 x 0.5
+fn composite_glyph() -> Vec<u8> {
+    let mut data = Vec::new();
+    for value in [-1i16, 100, 0, 950, 700] {
+        push_u16(&mut data, value as u16);
+    }
+    // Byte arguments, more components
+    data.extend([0x00, 0x22, 0x00, 0x01, 0, 0]);
+    // Word arguments, x and y scales
+    data.extend([0x00, 0x43, 0x00, 0x01]);
+    for value in [500, 0, 0x6000, 0x2000] {
+        push_u16(&mut data, value);
+    }
+    data
+}
+
+/// Characters to glyphs, in the Basic Multilingual Plane
+fn cmap_format4_table() -> Vec<u8> {
+    // (start, end, delta, glyphs when not by delta)
+    let segments: [(u16, u16, i16, &[u16]); 5] = [
+        (0x20, 0x20, 4 - 0x20, &[]),
+        (0x48, 0x49, 0, &[3, 1]),
+        (0x4F, 0x4F, 2 - 0x4F, &[]),
+        (0xE9, 0xE9, 2 - 0xE9, &[]),
+        (0xFFFF, 0xFFFF, 1, &[]),
+    ];
+    let count = segments.len() as u16;
+    let mut subtable = Vec::new();
+    for value in [4, 0, 0, count * 2, 4, 1, count * 2 - 4] {
+        push_u16(&mut subtable, value);
+    }
+    segments.iter().for_each(|segment| push_u16(&mut subtable, segment.1));
+    push_u16(&mut subtable, 0);
+    segments.iter().for_each(|segment| push_u16(&mut subtable, segment.0));
+    segments.iter().for_each(|segment| push_u16(&mut subtable, segment.2 as u16));
+    let mut glyph_ids = Vec::new();
+    for (i, segment) in segments.iter().enumerate() {
+        // From this offset to the segment's first glyph id
+        let offset = if segment.3.is_empty() { 0 } else { (count as usize - i + glyph_ids.len()) * 2 };
+        push_u16(&mut subtable, offset as u16);
+        glyph_ids.extend(segment.3);
+    }
+    glyph_ids.iter().for_each(|&glyph| push_u16(&mut subtable, glyph));
+    let length = subtable.len() as u16;
+    subtable[2..4].copy_from_slice(&length.to_be_bytes());
+    cmap_table(3, 1, subtable)
+}
+
+/// Characters to glyphs, in all planes: U+1F600 is 'I' too
+fn cmap_format12_table() -> Vec<u8> {
+    let groups = [(0x20, 0x20, 4), (0x48, 0x48, 3), (0x49, 0x49, 1), (0x4F, 0x4F, 2), (0xE9, 0xE9, 2), (0x1F600, 0x1F600, 1)];
+    let mut subtable = vec![0x00, 0x0C, 0x00, 0x00];
+    push_u32(&mut subtable, 16 + groups.len() as u32 * 12);
+    push_u32(&mut subtable, 0);
+    push_u32(&mut subtable, groups.len() as u32);
+    for (start, end, glyph) in groups {
+        push_u32(&mut subtable, start);
+        push_u32(&mut subtable, end);
+        push_u32(&mut subtable, glyph);
+    }
+    cmap_table(3, 10, subtable)
+}
+
+/// A cmap with `subtable`, after a Macintosh one that must be skipped
+fn cmap_table(platform: u16, encoding: u16, subtable: Vec<u8>) -> Vec<u8> {
+    let mut cmap = Vec::new();
+    for value in [0, 2, 1, 0, 0, 20, platform, encoding, 0, 20 + 6] {
+        push_u16(&mut cmap, value);
+    }
+    // Format 6 with no characters
+    for value in [6, 10, 0] {
+        push_u16(&mut cmap, value);
+    }
+    cmap.extend(subtable);
+    cmap
+}
@@ -0,0 +1,39 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::io::outb;
+
+/// Timer interrupts per second
+pub const TIMER_FREQUENCY: u32 = 100;
+
+// The PIT counts down from a divisor of its 1.193182 MHz input clock
+const PIT_FREQUENCY: u32 = 1_193_182;
+const PIT_CHANNEL0: u16 = 0x40;
+const PIT_COMMAND: u16 = 0x43;
+// Channel 0, low byte then high byte, mode 3 (square wave)
+const PIT_MODE: u8 = 0x36;
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// Make the PIT fire IRQ0 TIMER_FREQUENCY times per second
+pub fn init_timer() {
+    let divisor = (PIT_FREQUENCY / TIMER_FREQUENCY) as u16;
+    unsafe {
+        outb(PIT_COMMAND, PIT_MODE);
+        outb(PIT_CHANNEL0, divisor as u8);
+        outb(PIT_CHANNEL0, (divisor >> 8) as u8);
+    }
+}
+
+/// Called from the IRQ0 handler
+pub fn handle_tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Timer interrupts since init_timer
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Milliseconds since init_timer, with a 1000 / TIMER_FREQUENCY ms resolution
+pub fn uptime_ms() -> u64 {
+    ticks() * 1000 / TIMER_FREQUENCY as u64
+}
@@ -0,0 +1,267 @@
+use crate::color::Color;
+use crate::graphics::canvas::Canvas;
+use crate::graphics::rect::Rect;
+use crate::vbe::pixel::PixelFormat;
+use crate::vbe::VbeModeInfo;
+
+/// Dirty rectangles tracked before they are merged into one
+const MAX_DIRTY: usize = 8;
+
+/// Off-screen copy of the framebuffer in RAM that all drawing goes to. `flush` copies
+/// what changed since the last flush to video memory, which is uncached and slow to
+/// write pixel by pixel.
+pub struct BackBuffer {
+    pixels: *mut u8,
+    screen: *mut u8,
+    width: usize,
+    height: usize,
+    /// Bytes per scanline, the same as video memory so a pixel has the same offset in both
+    pitch: usize,
+    format: PixelFormat,
+    clip: Rect,
+    dirty: [Rect; MAX_DIRTY],
+    dirty_count: usize,
+}
+
+impl BackBuffer {
+    /// Back buffer of the mode `vbe` at `address`. When the mode needs more than
+    /// `max_size` bytes, drawing goes straight to video memory instead.
+    pub fn new(vbe: &VbeModeInfo, address: usize, max_size: usize) -> Self {
+        let screen = vbe.framebuffer() as *mut u8;
+        let pixels = if vbe.framebuffer_size() <= max_size { address as *mut u8 } else { screen };
+        unsafe { BackBuffer::from_memory(pixels, screen, vbe.width(), vbe.height(), vbe.pitch(), vbe.pixel_format()) }
+    }
+
+    /// Back buffer at `pixels` for a framebuffer at `screen` (both can be the same,
+    /// flush does nothing then), e.g. plain memory standing in for video memory
+    ///
+    /// # Safety
+    /// `pixels` and `screen` must be `pitch * height` writable bytes, for as long as the back buffer is used.
+    pub unsafe fn from_memory(pixels: *mut u8, screen: *mut u8, width: usize, height: usize, pitch: usize, format: PixelFormat) -> Self {
+        BackBuffer {
+            pixels,
+            screen,
+            width,
+            height,
+            pitch,
+            format,
+            clip: Rect::new(0, 0, width, height),
+            dirty: [Rect::new(0, 0, 0, 0); MAX_DIRTY],
+            dirty_count: 0,
+        }
+    }
+
+    /// Limit drawing to `rect` (and the screen)
+    pub fn set_clip(&mut self, rect: Rect) {
+        self.clip = rect.intersection(&self.bounds());
+    }
+
+    /// Allow drawing on the whole screen again
+    pub fn reset_clip(&mut self) {
+        self.clip = self.bounds();
+    }
+
+    /// Remember that `rect` must be copied to the screen by the next flush
+    pub fn mark_dirty(&mut self, rect: Rect) {
+        let rect = rect.intersection(&self.bounds());
+        if rect.is_empty() || self.pixels == self.screen {
+            return;
+        }
+        let dirty = &mut self.dirty[..self.dirty_count];
+        if let Some(other) = dirty.iter_mut().find(|other| other.touches(&rect)) {
+            *other = other.union(&rect);
+        } else if self.dirty_count < MAX_DIRTY {
+            self.dirty[self.dirty_count] = rect;
+            self.dirty_count += 1;
+        } else {
+            // Out of slots: one rectangle around everything
+            let all = dirty.iter().fold(rect, |all, other| all.union(other));
+            self.dirty[0] = all;
+            self.dirty_count = 1;
+        }
+    }
+
+    /// Copy the dirty rectangles to video memory, one scanline at a time
+    pub fn flush(&mut self) {
+        let bytes_per_pixel = self.format.bytes_per_pixel;
+        for rect in &self.dirty[..self.dirty_count] {
+            let length = rect.width * bytes_per_pixel;
+            for y in rect.y..rect.bottom() {
+                let offset = y * self.pitch + rect.x * bytes_per_pixel;
+                // Both sides are plain memory: this is a rep movs, not a loop of pixel writes
+                unsafe { core::ptr::copy_nonoverlapping(self.pixels.add(offset), self.screen.add(offset), length); }
+            }
+        }
+        self.dirty_count = 0;
+    }
+
+    /// Move the whole picture `rows` pixels up and fill the bottom with `color`.
+    /// The clip rectangle doesn't apply.
+    pub fn scroll_up(&mut self, rows: usize, color: Color) {
+        let rows = rows.min(self.height);
+        let kept = (self.height - rows) * self.pitch;
+        unsafe { core::ptr::copy(self.pixels.add(rows * self.pitch), self.pixels, kept); }
+        let clip = self.clip;
+        self.reset_clip();
+        self.fill_rect(Rect::new(0, self.height - rows, self.width, rows), color);
+        self.set_clip(clip);
+        self.mark_dirty(self.bounds());
+    }
+
+    /// Draw `color` over the pixel without marking it dirty, false when it is clipped.
+    /// Every pixel drawn goes through here or fill_rect.
+    fn write_pixel(&mut self, x: usize, y: usize, color: Color) -> bool {
+        if !self.clip.contains_point(x, y) || color.alpha == 0 {
+            return false;
+        }
+        let address = unsafe { self.pixels.add(y * self.pitch + x * self.format.bytes_per_pixel) };
+        let color = if color.is_opaque() {
+            color
+        } else {
+            color.over(self.format.decode(unsafe { self.format.read(address) }))
+        };
+        unsafe { self.format.write(address, self.format.encode(color)); }
+        true
+    }
+}
+
+impl Canvas for BackBuffer {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn clip(&self) -> Rect {
+        self.clip
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> Option<Color> {
+        if !self.clip.contains_point(x, y) {
+            return None;
+        }
+        let offset = y * self.pitch + x * self.format.bytes_per_pixel;
+        Some(self.format.decode(unsafe { self.format.read(self.pixels.add(offset)) }))
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if self.write_pixel(x, y, color) {
+            self.mark_dirty(Rect::new(x, y, 1, 1));
+        }
+    }
+
+    fn stored(&self, color: Color) -> Color {
+        self.format.decode(self.format.encode(color))
+    }
+
+    /// A scanline at a time (pixel by pixel when `color` is translucent)
+    fn fill_rect(&mut self, rect: Rect, color: Color) {
+        let rect = rect.intersection(&self.clip);
+        if rect.is_empty() || color.alpha == 0 {
+            return;
+        }
+        if color.is_opaque() {
+            let pixel = self.format.encode(color);
+            for y in rect.y..rect.bottom() {
+                let offset = y * self.pitch + rect.x * self.format.bytes_per_pixel;
+                unsafe { self.format.fill(self.pixels.add(offset), pixel, rect.width); }
+            }
+        } else {
+            for y in rect.y..rect.bottom() {
+                for x in rect.x..rect.right() {
+                    self.write_pixel(x, y, color);
+                }
+            }
+        }
+        self.mark_dirty(rect);
+    }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+    use crate::testing::{assert_golden, SoftwareFramebuffer};
+
+    const PALETTE: [(Color, char); 3] = [(Color::BLACK, '.'), (Color::WHITE, '#'), (Color::RED, 'r')];
+
+    #[test]
+    fn only_flush_shows_the_drawing() {
+        let mut framebuffer = SoftwareFramebuffer::new(8, 4, 32);
+        framebuffer.canvas().fill_rect(Rect::new(1, 1, 3, 2), Color::WHITE);
+        assert_eq!(framebuffer.screen_pixel(1, 1), Color::BLACK);
+        framebuffer.canvas().flush();
+        assert_eq!(framebuffer.screen_text(&PALETTE), "........\n.###....\n.###....\n........\n");
+    }
+
+    #[test]
+    fn flush_copies_the_dirty_rectangles_only() {
+        let mut framebuffer = SoftwareFramebuffer::new(8, 3, 16);
+        let canvas = framebuffer.canvas();
+        canvas.fill_rect(Rect::new(0, 0, 8, 3), Color::WHITE);
+        canvas.flush();
+        // Changed behind the back buffer's back: not dirty, so not copied
+        canvas.dirty_count = 0;
+        canvas.fill_rect(Rect::new(0, 0, 8, 3), Color::BLACK);
+        canvas.dirty_count = 0;
+        canvas.draw_pixel(2, 1, Color::RED);
+        canvas.draw_pixel(6, 1, Color::RED);
+        canvas.flush();
+        assert_eq!(framebuffer.screen_text(&PALETTE), "########\n##r###r#\n########\n");
+    }
+
+    #[test]
+    fn dirty_rectangles_merge() {
+        let mut framebuffer = SoftwareFramebuffer::new(100, 100, 32);
+        let canvas = framebuffer.canvas();
+        canvas.mark_dirty(Rect::new(0, 0, 10, 10));
+        canvas.mark_dirty(Rect::new(10, 0, 10, 10));
+        assert_eq!(canvas.dirty[..canvas.dirty_count], [Rect::new(0, 0, 20, 10)]);
+        for i in 0..MAX_DIRTY {
+            canvas.mark_dirty(Rect::new(i * 10 + 5, 50, 2, 2));
+        }
+        // One slot too many: everything becomes one rectangle
+        assert_eq!(canvas.dirty[..canvas.dirty_count], [Rect::new(0, 0, 77, 52)]);
+    }
+
+    #[test]
+    fn clipping() {
+        let mut framebuffer = SoftwareFramebuffer::new(6, 4, 24);
+        let canvas = framebuffer.canvas();
+        canvas.set_clip(Rect::new(2, 1, 10, 2));
+        canvas.clear_background(Color::WHITE);
+        canvas.draw_pixel(0, 0, Color::RED);
+        assert_eq!(canvas.pixel(0, 0), None);
+        canvas.reset_clip();
+        canvas.draw_pixel(1, 1, Color::RED);
+        canvas.flush();
+        assert_eq!(framebuffer.screen_text(&PALETTE), "......\n.r####\n..####\n......\n");
+    }
+
+    #[test]
+    fn translucent_colors_are_blended() {
+        for bpp in [15, 16, 24, 32] {
+            let mut framebuffer = SoftwareFramebuffer::new(2, 1, bpp);
+            let canvas = framebuffer.canvas();
+            canvas.clear_background(Color::WHITE);
+            canvas.draw_pixel(0, 0, Color::BLACK.with_alpha(0x80));
+            canvas.fill_rect(Rect::new(1, 0, 1, 1), Color::RED.with_alpha(0));
+            let gray = canvas.pixel(0, 0).unwrap();
+            assert_eq!(gray, canvas.stored(Color::rgb(0x7F, 0x7F, 0x7F)), "{} bpp", bpp);
+            assert_eq!(canvas.pixel(1, 0), Some(Color::WHITE), "{} bpp", bpp);
+        }
+    }
+
+    #[test]
+    fn scroll() {
+        let mut framebuffer = SoftwareFramebuffer::new(4, 4, 32);
+        let canvas = framebuffer.canvas();
+        for y in 0..4 {
+            canvas.draw_pixel(y, y, Color::WHITE);
+        }
+        canvas.scroll_up(2, Color::RED);
+        canvas.flush();
+        assert_golden("scroll", &framebuffer.screen_text(&PALETTE));
+    }
+}
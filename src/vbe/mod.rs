@@ -1,8 +1,16 @@
-use crate::VBE_MODE_INFO_ADDRESS;
-use crate::color::Color;
-use crate::vbe::no_font::{NO_FONT_HEIGHT, NO_FONT_WIDTH};
+use crate::vbe::back_buffer::BackBuffer;
+use crate::vbe::pixel::PixelFormat;
+use crate::{BACK_BUFFER_ADDRESS, BACK_BUFFER_MAX_SIZE, VBE_MODE_INFO_ADDRESS};
 
-mod no_font;
+pub mod back_buffer;
+pub mod pixel;
+
+/// Where one color channel sits in a pixel: `size` bits starting at bit `position`
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelMask {
+    pub size: u8,
+    pub position: u8,
+}
 
 #[repr(packed)]
 pub struct VbeModeInfo {
@@ -40,87 +48,74 @@ pub struct VbeModeInfo {
 }
 
 impl VbeModeInfo {
-    pub fn clear_background(&self, color: Color) {
-        let width = self.width as usize;
-        let height = self.height as usize;
-        for y in 0..height {
-            for x in 0..width {
-                self.draw_pixel(x, y, color);
-            }
-        }
+    pub fn width(&self) -> usize {
+        self.width as usize
+    }
+
+    pub fn height(&self) -> usize {
+        self.height as usize
+    }
+
+    /// Bytes per scanline
+    pub fn pitch(&self) -> usize {
+        self.pitch as usize
+    }
+
+    pub fn bpp(&self) -> u8 {
+        self.bpp
+    }
+
+    /// 4 = packed pixel, 6 = direct color
+    pub fn memory_model(&self) -> u8 {
+        self.memory_model
+    }
+
+    pub fn red(&self) -> ChannelMask {
+        ChannelMask { size: self.red_mask, position: self.red_position }
+    }
+
+    pub fn green(&self) -> ChannelMask {
+        ChannelMask { size: self.green_mask, position: self.green_position }
     }
 
-    /// Draw a square at (x, y) with size square_size
-    pub fn draw_square(&self, x: usize, y: usize, square_size: usize, color: Color) {
-        for y in y..(y + square_size) {
-            for x in x..(x + square_size) {
-                self.draw_pixel(x, y, color);
-            }
-        }
+    pub fn blue(&self) -> ChannelMask {
+        ChannelMask { size: self.blue_mask, position: self.blue_position }
     }
 
-    pub fn draw_pixel(&self, x: usize, y: usize, color: Color) {
-        let framebuffer = self.framebuffer as *mut u8;
-        let pitch = self.pitch as usize;
-        let bpp = self.bpp as usize;
-        let bytes_per_pixel = bpp / 8;
-        let offset = y * pitch + x * bytes_per_pixel;
-        unsafe {
-            *framebuffer.add(offset) = color.blue;
-            *framebuffer.add(offset + 1) = color.green;
-            *framebuffer.add(offset + 2) = color.red;
-        }
+    pub fn reserved(&self) -> ChannelMask {
+        ChannelMask { size: self.reserved_mask, position: self.reserved_position }
     }
 
-    pub fn draw_text(&self, x: usize, y: usize, text: &str, color: Color) {
-        let mut x_offset = x;
-        for char in text.chars() {
-            match char {
-                'a' | 'A' => self.draw_no_font_sprite(x_offset, y, no_font::A, color),
-                'b' | 'B' => self.draw_no_font_sprite(x_offset, y, no_font::B, color),
-                'c' | 'C' => self.draw_no_font_sprite(x_offset, y, no_font::C, color),
-                'd' | 'D' => self.draw_no_font_sprite(x_offset, y, no_font::D, color),
-                'e' | 'E' => self.draw_no_font_sprite(x_offset, y, no_font::E, color),
-                'f' | 'F' => self.draw_no_font_sprite(x_offset, y, no_font::F, color),
-                'g' | 'G' => self.draw_no_font_sprite(x_offset, y, no_font::G, color),
-                'h' | 'H' => self.draw_no_font_sprite(x_offset, y, no_font::H, color),
-                'i' | 'I' => self.draw_no_font_sprite(x_offset, y, no_font::I, color),
-                'j' | 'J' => self.draw_no_font_sprite(x_offset, y, no_font::J, color),
-                'k' | 'K' => self.draw_no_font_sprite(x_offset, y, no_font::K, color),
-                'l' | 'L' => self.draw_no_font_sprite(x_offset, y, no_font::L, color),
-                'm' | 'M' => self.draw_no_font_sprite(x_offset, y, no_font::M, color),
-                'n' | 'N' => self.draw_no_font_sprite(x_offset, y, no_font::N, color),
-                'o' | 'O' => self.draw_no_font_sprite(x_offset, y, no_font::O, color),
-                'p' | 'P' => self.draw_no_font_sprite(x_offset, y, no_font::P, color),
-                'q' | 'Q' => self.draw_no_font_sprite(x_offset, y, no_font::Q, color),
-                'r' | 'R' => self.draw_no_font_sprite(x_offset, y, no_font::R, color),
-                's' | 'S' => self.draw_no_font_sprite(x_offset, y, no_font::S, color),
-                't' | 'T' => self.draw_no_font_sprite(x_offset, y, no_font::T, color),
-                'u' | 'U' => self.draw_no_font_sprite(x_offset, y, no_font::U, color),
-                'v' | 'V' => self.draw_no_font_sprite(x_offset, y, no_font::V, color),
-                'w' | 'W' => self.draw_no_font_sprite(x_offset, y, no_font::W, color),
-                'x' | 'X' => self.draw_no_font_sprite(x_offset, y, no_font::X, color),
-                'y' | 'Y' => self.draw_no_font_sprite(x_offset, y, no_font::Y, color),
-                'z' | 'Z' => self.draw_no_font_sprite(x_offset, y, no_font::Z, color),
-                ' ' => (),
-                _ => self.draw_square(x_offset, y, 10, color),
-            }
-            x_offset += 13;
-        }
+    /// How colors are encoded, from bpp and the channel masks
+    pub fn pixel_format(&self) -> PixelFormat {
+        PixelFormat::new(self.bpp, self.red(), self.green(), self.blue())
     }
 
-    pub fn draw_no_font_sprite(&self, x: usize, y: usize, font_sprite: [[i32; NO_FONT_WIDTH]; NO_FONT_HEIGHT], color: Color) {
-        for ny in y..y + NO_FONT_HEIGHT {
-            for nx in x..x + NO_FONT_WIDTH {
-                if font_sprite[ny - y][nx - x] == 0 {
-                    continue;
-                }
-                self.draw_pixel(nx, ny, color);
-            }
-        }
+    /// Physical address of the video memory
+    pub fn framebuffer(&self) -> usize {
+        self.framebuffer as usize
+    }
+
+    /// Size of the video memory in bytes
+    pub fn framebuffer_size(&self) -> usize {
+        self.pitch() * self.height()
     }
 }
 
 pub fn get_vbe<'a>() -> &'a VbeModeInfo {
     unsafe { &*(VBE_MODE_INFO_ADDRESS as *const VbeModeInfo) }
 }
+
+static mut SCREEN: Option<BackBuffer> = None;
+
+/// Set up the back buffer of the VBE mode picked by kernel_entry.asm
+pub fn init_vbe() {
+    unsafe {
+        SCREEN = Some(BackBuffer::new(get_vbe(), BACK_BUFFER_ADDRESS, BACK_BUFFER_MAX_SIZE));
+    }
+}
+
+/// What everything draws on, shown by `flush`
+pub fn screen() -> Option<&'static mut BackBuffer> {
+    unsafe { (*core::ptr::addr_of_mut!(SCREEN)).as_mut() }
+}
@@ -0,0 +1,190 @@
+use crate::color::Color;
+use crate::vbe::ChannelMask;
+
+/// How a Color is stored in video memory, from the channel masks of the mode info
+#[derive(Debug, Clone, Copy)]
+pub struct PixelFormat {
+    /// 2 for 15 and 16 bpp, 3 for 24 bpp, 4 for 32 bpp
+    pub bytes_per_pixel: usize,
+    pub red: ChannelMask,
+    pub green: ChannelMask,
+    pub blue: ChannelMask,
+}
+
+impl PixelFormat {
+    /// Format of a `bpp` bits per pixel mode. VBE 1.x BIOSes leave the masks at 0,
+    /// the usual layout of the depth is used then.
+    pub fn new(bpp: u8, red: ChannelMask, green: ChannelMask, blue: ChannelMask) -> Self {
+        let bytes_per_pixel = (bpp as usize).div_ceil(8);
+        if red.size != 0 && green.size != 0 && blue.size != 0 {
+            return PixelFormat { bytes_per_pixel, red, green, blue };
+        }
+        let mask = |size, position| ChannelMask { size, position };
+        let (red, green, blue) = match bpp {
+            15 => (mask(5, 10), mask(5, 5), mask(5, 0)),
+            16 => (mask(5, 11), mask(6, 5), mask(5, 0)),
+            _ => (mask(8, 16), mask(8, 8), mask(8, 0)),
+        };
+        PixelFormat { bytes_per_pixel, red, green, blue }
+    }
+
+    /// The bits to write for `color`, in the low `bytes_per_pixel` bytes
+    pub fn encode(&self, color: Color) -> u32 {
+        encode_channel(color.red, self.red) | encode_channel(color.green, self.green) | encode_channel(color.blue, self.blue)
+    }
+
+    /// The opaque color of `pixel`, each channel scaled back to 8 bits
+    pub fn decode(&self, pixel: u32) -> Color {
+        Color::rgb(decode_channel(pixel, self.red), decode_channel(pixel, self.green), decode_channel(pixel, self.blue))
+    }
+
+    /// Write `pixel` (from encode) at `address`, with one store for every depth but 24 bpp
+    ///
+    /// # Safety
+    /// `address` must be `bytes_per_pixel` writable bytes.
+    pub unsafe fn write(&self, address: *mut u8, pixel: u32) {
+        unsafe {
+            match self.bytes_per_pixel {
+                4 => (address as *mut u32).write_unaligned(pixel),
+                3 => {
+                    let bytes = pixel.to_le_bytes();
+                    core::ptr::copy_nonoverlapping(bytes.as_ptr(), address, 3);
+                }
+                2 => (address as *mut u16).write_unaligned(pixel as u16),
+                _ => *address = pixel as u8,
+            }
+        }
+    }
+
+    /// The pixel at `address`, as encode returns it
+    ///
+    /// # Safety
+    /// `address` must be `bytes_per_pixel` readable bytes.
+    pub unsafe fn read(&self, address: *const u8) -> u32 {
+        unsafe {
+            match self.bytes_per_pixel {
+                4 => (address as *const u32).read_unaligned(),
+                3 => u32::from_le_bytes([*address, *address.add(1), *address.add(2), 0]),
+                2 => (address as *const u16).read_unaligned() as u32,
+                _ => *address as u32,
+            }
+        }
+    }
+
+    /// Write `pixel` `count` times from `address`
+    ///
+    /// # Safety
+    /// `address` must be `count * bytes_per_pixel` writable bytes.
+    pub unsafe fn fill(&self, address: *mut u8, pixel: u32, count: usize) {
+        unsafe {
+            match self.bytes_per_pixel {
+                4 => {
+                    let pixels = address as *mut u32;
+                    for i in 0..count {
+                        pixels.add(i).write_unaligned(pixel);
+                    }
+                }
+                2 => {
+                    let pixels = address as *mut u16;
+                    for i in 0..count {
+                        pixels.add(i).write_unaligned(pixel as u16);
+                    }
+                }
+                _ => {
+                    for i in 0..count {
+                        self.write(address.add(i * self.bytes_per_pixel), pixel);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The top `mask.size` bits of `value`, moved to `mask.position`
+fn encode_channel(value: u8, mask: ChannelMask) -> u32 {
+    let value = value as u32;
+    let bits = match mask.size {
+        0 => return 0,
+        size @ 1..=8 => value >> (8 - size),
+        // Wider than 8 bits (10 bpc modes): repeat the top bits in the new low ones
+        size => {
+            let shift = (size - 8) as u32;
+            (value << shift) | (value >> (8 - shift.min(8)))
+        }
+    };
+    bits << mask.position
+}
+
+fn decode_channel(pixel: u32, mask: ChannelMask) -> u8 {
+    if mask.size == 0 {
+        return 0;
+    }
+    let max = (1u64 << mask.size) - 1;
+    let bits = (pixel as u64 >> mask.position) & max;
+    (bits * 255 / max) as u8
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+
+    const NONE: ChannelMask = ChannelMask { size: 0, position: 0 };
+
+    #[test]
+    fn default_masks() {
+        let format = PixelFormat::new(16, NONE, NONE, NONE);
+        assert_eq!(format.bytes_per_pixel, 2);
+        assert_eq!(format.encode(Color::RED), 0xF800);
+        assert_eq!(format.encode(Color::GREEN), 0x07E0);
+        assert_eq!(format.encode(Color::BLUE), 0x001F);
+        assert_eq!(PixelFormat::new(15, NONE, NONE, NONE).encode(Color::WHITE), 0x7FFF);
+        assert_eq!(PixelFormat::new(24, NONE, NONE, NONE).bytes_per_pixel, 3);
+        assert_eq!(PixelFormat::new(32, NONE, NONE, NONE).encode(Color::rgb(0x12, 0x34, 0x56)), 0x123456);
+    }
+
+    #[test]
+    fn masks_from_the_mode() {
+        // BGR order, as some BIOSes report it
+        let mask = |size, position| ChannelMask { size, position };
+        let format = PixelFormat::new(32, mask(8, 0), mask(8, 8), mask(8, 16));
+        assert_eq!(format.encode(Color::rgb(0x12, 0x34, 0x56)), 0x563412);
+        assert_eq!(format.decode(0x563412), Color::rgb(0x12, 0x34, 0x56));
+    }
+
+    #[test]
+    fn decode_scales_to_8_bits() {
+        let format = PixelFormat::new(16, NONE, NONE, NONE);
+        assert_eq!(format.decode(0xFFFF), Color::WHITE);
+        assert_eq!(format.decode(0), Color::BLACK);
+        let gray = format.decode(format.encode(Color::GRAY));
+        assert!(gray.red.abs_diff(0x80) < 8 && gray.green.abs_diff(0x80) < 4);
+    }
+
+    #[test]
+    fn wide_channels() {
+        // 10 bits per channel
+        let mask = |position| ChannelMask { size: 10, position };
+        let format = PixelFormat::new(32, mask(20), mask(10), mask(0));
+        assert_eq!(format.encode(Color::rgb(0xFF, 0, 0)), 0x3FF << 20);
+        assert_eq!(format.encode(Color::rgb(0x80, 0, 0x01)), 0x202 << 20 | 0x004);
+        assert_eq!(format.decode(0x3FF << 20), Color::RED);
+    }
+
+    #[test]
+    fn write_and_read() {
+        for bpp in [15, 16, 24, 32] {
+            let format = PixelFormat::new(bpp, NONE, NONE, NONE);
+            // One byte of margin on each side, which must not be touched
+            let mut memory = [0xAAu8; 4 * 4 + 2];
+            let pixel = format.encode(Color::rgb(0xF8, 0x10, 0x88));
+            unsafe {
+                format.fill(memory.as_mut_ptr().add(1), pixel, 4);
+                for i in 0..4 {
+                    assert_eq!(format.read(memory.as_ptr().add(1 + i * format.bytes_per_pixel)), pixel, "{} bpp", bpp);
+                }
+            }
+            assert_eq!(memory[0], 0xAA);
+            assert_eq!(memory[1 + 4 * format.bytes_per_pixel], 0xAA);
+        }
+    }
+}
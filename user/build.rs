@@ -0,0 +1,6 @@
+fn main() {
+    // Programs are linked at the address the kernel expects user code at
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    println!("cargo:rustc-link-arg=-T{}/linker.ld", manifest_dir);
+    println!("cargo:rerun-if-changed=linker.ld");
+}
@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+
+use jackcat::{ioctl, mmap, open, println, Args, FB_GET_INFO, O_READ, O_WRITE};
+
+/// fb: draw a color gradient in the top left corner of the screen, straight into the framebuffer
+#[unsafe(no_mangle)]
+fn main(_args: Args) -> i32 {
+    let Ok(fd) = open("/dev/fb0", O_READ | O_WRITE) else {
+        println!("fb: cannot open /dev/fb0");
+        return 1;
+    };
+    let mut info = [0u8; 16];
+    if ioctl(fd, FB_GET_INFO, &mut info).is_err() {
+        println!("fb: no framebuffer");
+        return 1;
+    }
+    let field = |index: usize| u32::from_le_bytes(info[index * 4..index * 4 + 4].try_into().unwrap()) as usize;
+    let (width, height, pitch, bpp) = (field(0), field(1), field(2), field(3));
+    if bpp != 32 {
+        println!("fb: {} bits per pixel, only 32 are drawn", bpp);
+        return 1;
+    }
+    let pixels = match mmap(fd, pitch * height, 0) {
+        Ok(pixels) => pixels,
+        Err(error) => {
+            println!("fb: mmap failed: errno {}", error.0);
+            return 1;
+        }
+    };
+    let size = 256.min(width).min(height);
+    for y in 0..size {
+        let row = unsafe { pixels.add(y * pitch) as *mut u32 };
+        for x in 0..size {
+            let color = (x as u32) << 16 | (y as u32) << 8 | 0x80;
+            unsafe { row.add(x).write_volatile(color); }
+        }
+    }
+    println!("fb: {}x{} pixels at {:p}", width, height, pixels);
+    0
+}
@@ -0,0 +1,14 @@
+#![no_std]
+#![no_main]
+
+use jackcat::{getpid, println, var, Args};
+
+#[unsafe(no_mangle)]
+fn main(args: Args) -> i32 {
+    println!("Hello from ring 3, I am process {}", getpid());
+    for (i, arg) in args.enumerate() {
+        println!("argv[{}] = {}", i, arg);
+    }
+    println!("PATH = {}", var("PATH").unwrap_or("(not set)"));
+    0
+}
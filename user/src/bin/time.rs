@@ -0,0 +1,34 @@
+#![no_std]
+#![no_main]
+
+use jackcat::{println, spawn, uptime_ms, waitpid, Args, MAX_ARGS};
+
+/// time <program> [args...]: run a program, then print how long it took and its exit code
+#[unsafe(no_mangle)]
+fn main(mut args: Args) -> i32 {
+    let _ = args.next();
+    let mut argv = [""; MAX_ARGS];
+    let mut argc = 0;
+    for arg in args.take(MAX_ARGS) {
+        argv[argc] = arg;
+        argc += 1;
+    }
+    if argc == 0 {
+        println!("usage: time <program> [args...]");
+        return 2;
+    }
+
+    let start = uptime_ms();
+    let result = spawn(argv[0], &argv[..argc]).and_then(|pid| waitpid(Some(pid)));
+    let elapsed = uptime_ms() - start;
+    match result {
+        Ok((pid, code)) => {
+            println!("pid {} exited with code {} after {} ms", pid, code, elapsed);
+            0
+        }
+        Err(error) => {
+            println!("time: cannot run {}: errno {}", argv[0], error.0);
+            1
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! Runtime of JackcatOS user programs: the entry point, system calls and printing.
+//! A program is a `#![no_std]` `#![no_main]` binary defining
+//! `#[unsafe(no_mangle)] fn main(args: Args) -> i32`.
+#![no_std]
+
+use core::arch::{asm, global_asm};
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+
+// System call numbers, must match src/syscall/mod.rs in the kernel
+pub const SYS_EXIT: u64 = 0;
+pub const SYS_READ: u64 = 1;
+pub const SYS_WRITE: u64 = 2;
+pub const SYS_OPEN: u64 = 3;
+pub const SYS_CLOSE: u64 = 4;
+pub const SYS_SEEK: u64 = 5;
+pub const SYS_IOCTL: u64 = 6;
+pub const SYS_UPTIME: u64 = 7;
+pub const SYS_SPAWN: u64 = 8;
+pub const SYS_WAITPID: u64 = 9;
+pub const SYS_GETPID: u64 = 10;
+pub const SYS_MMAP: u64 = 11;
+
+/// Most arguments a program can be spawned with
+pub const MAX_ARGS: usize = 16;
+
+pub const STDIN: usize = 0;
+pub const STDOUT: usize = 1;
+pub const STDERR: usize = 2;
+
+// Flags of `open`, the bits of the kernel's OpenFlags
+pub const O_READ: u32 = 1 << 0;
+pub const O_WRITE: u32 = 1 << 1;
+pub const O_CREATE: u32 = 1 << 2;
+pub const O_TRUNCATE: u32 = 1 << 3;
+pub const O_APPEND: u32 = 1 << 4;
+
+/// ioctl on /dev/fb0: width, height, pitch and bits per pixel as little endian u32
+pub const FB_GET_INFO: u32 = 0x4600;
+
+/// A failed system call, with its (Linux compatible) errno
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Error(pub i64);
+
+/// Raw system call, returns rax: the result, or -errno
+pub unsafe fn syscall(number: u64, args: [u64; 6]) -> i64 {
+    let result: i64;
+    unsafe {
+        // The kernel keeps the general purpose registers but not the SSE ones
+        asm!(
+            "syscall",
+            inlateout("rax") number as i64 => result,
+            in("rdi") args[0], in("rsi") args[1], in("rdx") args[2],
+            in("r10") args[3], in("r8") args[4], in("r9") args[5],
+            clobber_abi("sysv64"),
+            options(nostack),
+        );
+    }
+    result
+}
+
+fn check(result: i64) -> Result<u64, Error> {
+    if result < 0 { Err(Error(-result)) } else { Ok(result as u64) }
+}
+
+pub fn exit(code: i32) -> ! {
+    unsafe { syscall(SYS_EXIT, [code as u64, 0, 0, 0, 0, 0]); }
+    unreachable!()
+}
+
+pub fn read(fd: usize, buffer: &mut [u8]) -> Result<usize, Error> {
+    let result = unsafe { syscall(SYS_READ, [fd as u64, buffer.as_mut_ptr() as u64, buffer.len() as u64, 0, 0, 0]) };
+    check(result).map(|count| count as usize)
+}
+
+pub fn write(fd: usize, data: &[u8]) -> Result<usize, Error> {
+    let result = unsafe { syscall(SYS_WRITE, [fd as u64, data.as_ptr() as u64, data.len() as u64, 0, 0, 0]) };
+    check(result).map(|count| count as usize)
+}
+
+pub fn open(path: &str, flags: u32) -> Result<usize, Error> {
+    let result = unsafe { syscall(SYS_OPEN, [path.as_ptr() as u64, path.len() as u64, flags as u64, 0, 0, 0]) };
+    check(result).map(|fd| fd as usize)
+}
+
+pub fn close(fd: usize) -> Result<(), Error> {
+    check(unsafe { syscall(SYS_CLOSE, [fd as u64, 0, 0, 0, 0, 0]) }).map(|_| ())
+}
+
+/// Move the offset of `fd`, `whence` is 0 (start), 1 (current) or 2 (end)
+pub fn seek(fd: usize, offset: i64, whence: u32) -> Result<u64, Error> {
+    check(unsafe { syscall(SYS_SEEK, [fd as u64, offset as u64, whence as u64, 0, 0, 0]) })
+}
+
+pub fn ioctl(fd: usize, request: u32, data: &mut [u8]) -> Result<usize, Error> {
+    let result = unsafe {
+        syscall(SYS_IOCTL, [fd as u64, request as u64, data.as_mut_ptr() as u64, data.len() as u64, 0, 0])
+    };
+    check(result).map(|count| count as usize)
+}
+
+/// Map `length` bytes at `offset` of a device file open for reading and writing
+/// (/dev/fb0) into memory, until the program exits
+pub fn mmap(fd: usize, length: usize, offset: u64) -> Result<*mut u8, Error> {
+    let result = unsafe { syscall(SYS_MMAP, [fd as u64, length as u64, offset, 0, 0, 0]) };
+    check(result).map(|address| address as *mut u8)
+}
+
+/// Milliseconds since boot
+pub fn uptime_ms() -> u64 {
+    unsafe { syscall(SYS_UPTIME, [0; 6]) as u64 }
+}
+
+/// Run the program at `path` with `args` (args[0] being its name by convention).
+/// The child runs next to the caller, `waitpid` waits for it to exit and collects its exit code.
+pub fn spawn(path: &str, args: &[&str]) -> Result<u32, Error> {
+    let mut pairs = [[0u64; 2]; MAX_ARGS];
+    for (pair, arg) in pairs.iter_mut().zip(args) {
+        *pair = [arg.as_ptr() as u64, arg.len() as u64];
+    }
+    let count = args.len().min(MAX_ARGS + 1) as u64;
+    let result = unsafe {
+        syscall(SYS_SPAWN, [path.as_ptr() as u64, path.len() as u64, pairs.as_ptr() as u64, count, 0, 0])
+    };
+    check(result).map(|pid| pid as u32)
+}
+
+/// Wait for the child `pid` (any child when None), returns its pid and exit code
+pub fn waitpid(pid: Option<u32>) -> Result<(u32, i32), Error> {
+    let mut code = 0i32;
+    let pid = pid.map_or(-1, |pid| pid as i64);
+    let result = unsafe { syscall(SYS_WAITPID, [pid as u64, &mut code as *mut i32 as u64, 0, 0, 0, 0]) };
+    check(result).map(|pid| (pid as u32, code))
+}
+
+pub fn getpid() -> u32 {
+    unsafe { syscall(SYS_GETPID, [0; 6]) as u32 }
+}
+
+/// Strings of argv or envp
+#[derive(Clone, Copy)]
+pub struct Args {
+    next: *const *const u8,
+}
+
+impl Iterator for Args {
+    type Item = &'static str;
+
+    fn next(&mut self) -> Option<&'static str> {
+        let string = unsafe { *self.next };
+        if string.is_null() {
+            return None;
+        }
+        self.next = unsafe { self.next.add(1) };
+        let bytes = unsafe { core::ffi::CStr::from_ptr(string.cast()) }.to_bytes();
+        Some(core::str::from_utf8(bytes).unwrap_or(""))
+    }
+}
+
+static mut ENVIRONMENT: Args = Args { next: core::ptr::null() };
+
+/// The environment variables ("NAME=value")
+pub fn env() -> Args {
+    unsafe { ENVIRONMENT }
+}
+
+/// Value of the environment variable `name`
+pub fn var(name: &str) -> Option<&'static str> {
+    env().find_map(|variable| variable.strip_prefix(name)?.strip_prefix('='))
+}
+
+unsafe extern "Rust" {
+    fn main(args: Args) -> i32;
+}
+
+// The kernel starts us with rsp pointing at argc, followed by argv, envp and the auxiliary vector
+global_asm!(
+    ".global _start",
+    "_start:",
+    "mov rdi, rsp",
+    "call {start}",
+    "ud2",
+    start = sym start,
+);
+
+extern "C" fn start(stack: *const u64) -> ! {
+    unsafe {
+        let argc = *stack as usize;
+        let argv = stack.add(1) as *const *const u8;
+        ENVIRONMENT = Args { next: argv.add(argc + 1) };
+        exit(main(Args { next: argv }))
+    }
+}
+
+/// Standard output, for `write!`
+pub struct Stdout;
+
+impl Write for Stdout {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        write(STDOUT, text.as_bytes()).map(|_| ()).map_err(|_| fmt::Error)
+    }
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    let _ = Stdout.write_fmt(args);
+}
+
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => ($crate::_print(format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! println {
+    () => ($crate::print!("\n"));
+    ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    println!("panic: {}", info);
+    exit(101)
+}